@@ -1,37 +1,69 @@
 use std::collections::HashMap;
-use std::sync::Mutex;
 
 use once_cell::sync::Lazy;
 
-use crate::typecell::{get_raw_type, Type};
+use crate::{globals::Mutex, typecell::TypeCell};
 
 const UNSUPPORTED_REG_SIZE: &str = "unsupported register size";
-const I32I8: &str = "\tmovsbl eax, al";
 
-pub static ASMCODE: Lazy<Mutex<String>> =
-    Lazy::new(|| Mutex::new("\t.intel_syntax noprefix\n\t.text\n.LText0:\n".to_string()));
+const ASM_HEADER: &str = "\t.intel_syntax noprefix\n\t.text\n.LText0:\n";
+
+pub static ASMCODE: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(ASM_HEADER.to_string()));
 
 pub static ARGS_REGISTERS: Lazy<Mutex<HashMap<usize, Vec<&str>>>> = Lazy::new(|| {
     let mut map = HashMap::new();
     let _ = map.insert(1, vec!["dil", "sil", "dl", "cl", "r8b", "r9b"]);
-    let _ = map.insert(4, vec!["edi", "esi", "edx", "rcx", "r8d", "r9d"]);
+    let _ = map.insert(2, vec!["di", "si", "dx", "cx", "r8w", "r9w"]);
+    let _ = map.insert(4, vec!["edi", "esi", "edx", "ecx", "r8d", "r9d"]);
     let _ = map.insert(8, vec!["rdi", "rsi", "rdx", "rcx", "r8", "r9"]);
     Mutex::new(map)
 });
 
+/// 汎用レジスタの、大きさ(1, 2, 4, 8 バイト)ごとの名前
+static GP_REGISTERS: [[&str; 4]; 16] = [
+    ["al", "ax", "eax", "rax"],
+    ["bl", "bx", "ebx", "rbx"],
+    ["cl", "cx", "ecx", "rcx"],
+    ["dl", "dx", "edx", "rdx"],
+    ["sil", "si", "esi", "rsi"],
+    ["dil", "di", "edi", "rdi"],
+    ["bpl", "bp", "ebp", "rbp"],
+    ["spl", "sp", "esp", "rsp"],
+    ["r8b", "r8w", "r8d", "r8"],
+    ["r9b", "r9w", "r9d", "r9"],
+    ["r10b", "r10w", "r10d", "r10"],
+    ["r11b", "r11w", "r11d", "r11"],
+    ["r12b", "r12w", "r12d", "r12"],
+    ["r13b", "r13w", "r13d", "r13"],
+    ["r14b", "r14w", "r14d", "r14"],
+    ["r15b", "r15w", "r15d", "r15"],
+];
+
+/// 任意の大きさのレジスタ名から、対応する64ビットのレジスタ名を求める
+pub fn gp_register(name: &str) -> Option<&'static str> {
+    GP_REGISTERS
+        .iter()
+        .find(|names| names.contains(&name))
+        .map(|names| names[3])
+}
+
+/// 64ビットのレジスタ名から、指定した大きさのレジスタ名を求める
+pub fn sized_register(reg: &str, size: usize) -> &'static str {
+    let names = GP_REGISTERS.iter().find(|names| names[3] == reg).unwrap();
+    match size {
+        1 => names[0],
+        2 => names[1],
+        4 => names[2],
+        8 => names[3],
+        _ => {
+            panic!("{}", UNSUPPORTED_REG_SIZE);
+        }
+    }
+}
+
 static CTRL_COUNT: Lazy<Mutex<u32>> = Lazy::new(|| Mutex::new(0));
 static FUNC_COUNT: Lazy<Mutex<u32>> = Lazy::new(|| Mutex::new(0));
 
-/// キャストが生じる場合の操作をクエリするためのテーブル
-pub static CAST_TABLE: Lazy<Mutex<Vec<Vec<&str>>>> = Lazy::new(|| {
-    Mutex::new(vec![
-        //	I8		I32		U64
-        vec!["", I32I8, I32I8], // I8
-        vec![I32I8, "", ""],    // I32
-        vec![I32I8, "", ""],    // U64
-    ])
-});
-
 pub fn get_ctrl_count() -> u32 {
     let mut count = CTRL_COUNT.try_lock().unwrap();
     let c = *count;
@@ -46,6 +78,13 @@ pub fn get_func_count() -> u32 {
     c
 }
 
+/// コンパイル間でアセンブリの出力とラベルの番号をリセットする
+pub fn clear_state() {
+    *ASMCODE.try_lock().unwrap() = ASM_HEADER.to_string();
+    *CTRL_COUNT.try_lock().unwrap() = 0;
+    *FUNC_COUNT.try_lock().unwrap() = 0;
+}
+
 #[inline]
 pub fn reg_ax(size: usize) -> &'static str {
     match size {
@@ -85,26 +124,44 @@ pub fn word_ptr(size: usize) -> &'static str {
     }
 }
 
-pub fn cast(from: Type, to: Type) {
-    let t1 = get_raw_type(from) as usize;
-    let t2 = get_raw_type(to) as usize;
-    let cast_access = CAST_TABLE.try_lock().unwrap();
-    let cast_asm = cast_access[t1][t2];
-    if !cast_asm.is_empty() {
-        use crate::asm_write;
-        asm_write!("{}", cast_asm);
+// rax には常に、値の型に従って64ビット全体に符号拡張(unsigned ならゼロ拡張)した値を保持する
+// そのため、型の大きさより上位のビットを捨てる必要がある場合はこの関数で拡張し直す
+pub fn extend(typ: &TypeCell) {
+    use crate::asm_write;
+    match (typ.value_bytes(), typ.is_unsigned) {
+        (1, false) => asm_write!("\tmovsx rax, al"),
+        (1, true) => asm_write!("\tmovzx eax, al"),
+        (2, false) => asm_write!("\tmovsx rax, ax"),
+        (2, true) => asm_write!("\tmovzx eax, ax"),
+        (4, false) => asm_write!("\tcdqe"),
+        (4, true) => asm_write!("\tmov eax, eax"),
+        _ => {}
     }
 }
 
-// 現在は unsigned なデータ型を扱わないので movsx のみで OK
-#[macro_export]
-macro_rules! mov_op {
-    ($size:expr) => {
-        match $size {
-            1 => "movsx",
-            _ => "mov",
-        }
+/// rax に入っている from 型の値を to 型に変換する
+pub fn cast(from: &TypeCell, to: &TypeCell) {
+    let (from_bytes, to_bytes) = (from.value_bytes(), to.value_bytes());
+    // 大きさも符号も同じ場合や、より小さい型からの拡張で値が変わらない場合は何もしなくて良い
+    let is_same = from_bytes == to_bytes && from.is_unsigned == to.is_unsigned;
+    let is_widening = from_bytes < to_bytes && (from.is_unsigned || !to.is_unsigned);
+    if !is_same && !is_widening {
+        extend(to);
+    }
+}
+
+/// 型に従って src から rax へ値を読み込む(読み込んだ値は extend と同様に拡張された状態になる)
+pub fn load(typ: &TypeCell, src: &str) {
+    use crate::asm_write;
+    let bytes = typ.value_bytes();
+    let (op, reg) = match (bytes, typ.is_unsigned) {
+        (8, _) => ("mov", "rax"),
+        (4, true) => ("mov", "eax"),
+        (4, false) => ("movsxd", "rax"),
+        (_, true) => ("movzx", "eax"),
+        (_, false) => ("movsx", "rax"),
     };
+    asm_write!("\t{} {}, {} {}", op, reg, word_ptr(bytes), src);
 }
 
 #[macro_export]
@@ -146,28 +203,6 @@ macro_rules! mov_to {
     };
 }
 
-#[macro_export]
-macro_rules! mov_from {
-    ($size:expr, $operand1:expr, $operand2:expr) => {
-        let _word = word_ptr($size);
-        let _mov = mov_op!($size);
-        asm_write!("\t{} {}, {} [{}]", _mov, $operand1, _word, $operand2)
-    };
-
-    ($size:expr, $operand1:expr, $operand2:expr, $offset:expr) => {
-        let _word = word_ptr($size);
-        let _mov = mov_op!($size);
-        asm_write!(
-            "\t{} {}, {} [{}-{}]",
-            _mov,
-            $operand1,
-            _word,
-            $operand2,
-            $offset
-        )
-    };
-}
-
 #[macro_export]
 macro_rules! mov_glb_addr {
     ($operand:expr, $name:expr) => {
@@ -175,14 +210,6 @@ macro_rules! mov_glb_addr {
     };
 }
 
-#[macro_export]
-macro_rules! mov_from_glb {
-    ($size:expr, $operand:expr, $name:expr) => {
-        let _word = word_ptr($size);
-        asm_write!("\tmov {}, {} {}[rip]", $operand, _word, $name)
-    };
-}
-
 #[macro_export]
 macro_rules! mov_to_glb {
     ($size:expr, $operand:expr, $name:expr) => {
@@ -219,24 +246,55 @@ macro_rules! lea {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::globals::tests::test_lock;
+    use crate::typecell::Type;
 
     #[test]
     fn cast_test() {
-        ASMCODE.try_lock().unwrap().clear();
-        cast(Type::Int, Type::Int);
-        assert_eq!(*ASMCODE.try_lock().unwrap(), String::new());
-
-        ASMCODE.try_lock().unwrap().clear();
-        cast(Type::Int, Type::Ptr);
-        assert_eq!(*ASMCODE.try_lock().unwrap(), String::new());
+        let _lock = test_lock();
+        let int = TypeCell::new(Type::Int);
+        let mut uint = TypeCell::new(Type::Int);
+        uint.is_unsigned = true;
+        let mut uchar = TypeCell::new(Type::Char);
+        uchar.is_unsigned = true;
+        let cases = [
+            (&int, &int, ""),
+            (&int, &TypeCell::new(Type::Long), ""),
+            (&uchar, &int, ""),
+            (&TypeCell::new(Type::Char), &uint, "\tmov eax, eax\n"),
+            (&int, &uint, "\tmov eax, eax\n"),
+            (&TypeCell::new(Type::Long), &int, "\tcdqe\n"),
+            (&int, &TypeCell::new(Type::Short), "\tmovsx rax, ax\n"),
+            (&int, &uchar, "\tmovzx eax, al\n"),
+        ];
+        for (from, to, expected) in cases {
+            ASMCODE.try_lock().unwrap().clear();
+            cast(from, to);
+            assert_eq!(*ASMCODE.try_lock().unwrap(), expected);
+        }
+    }
 
-        ASMCODE.try_lock().unwrap().clear();
-        cast(Type::Char, Type::Ptr);
-        assert_eq!(*ASMCODE.try_lock().unwrap(), format!("{}\n", I32I8));
+    #[test]
+    fn load_test() {
+        let _lock = test_lock();
+        let mut ushort = TypeCell::new(Type::Short);
+        ushort.is_unsigned = true;
+        let cases = [
+            (TypeCell::new(Type::Char), "\tmovsx rax, BYTE PTR [rax]\n"),
+            (ushort, "\tmovzx eax, WORD PTR [rax]\n"),
+            (TypeCell::new(Type::Int), "\tmovsxd rax, DWORD PTR [rax]\n"),
+            (TypeCell::new(Type::Long), "\tmov rax, QWORD PTR [rax]\n"),
+        ];
+        for (typ, expected) in cases {
+            ASMCODE.try_lock().unwrap().clear();
+            load(&typ, "[rax]");
+            assert_eq!(*ASMCODE.try_lock().unwrap(), expected);
+        }
     }
 
     #[test]
     fn get_count_test() {
+        let _lock = test_lock();
         for i in 0..1000 {
             assert_eq!(get_ctrl_count(), i);
             assert_eq!(get_func_count(), i);
@@ -0,0 +1,623 @@
+// コンパイル中に発生したエラーや警告を表す構造体と、それを集めるシンク
+use std::fmt::{self, Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+
+use once_cell::sync::Lazy;
+
+use crate::{
+    globals::{Mutex, FILE_NAMES, SRC},
+    utils::{GREEN, LIGHTBLUE, RED, YELLOW},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let s: &str = match self {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// ソースコード上の範囲(表示のために該当する行の内容も保持しておく)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub file: String,
+    pub line: usize,
+    pub column: usize, // 行内の文字単位のオフセット(0始まり)
+    pub len: usize,
+    pub text: String, // 該当する行(末尾の "\n" を含む)
+}
+
+impl Span {
+    /// file_num 番目のファイルの位置から Span を作る
+    /// SRC や FILE_NAMES の lock をとった状態で呼ばないこと
+    pub fn new(file_num: usize, line: usize, column: usize, len: usize) -> Span {
+        let file = FILE_NAMES
+            .try_lock()
+            .unwrap()
+            .get(file_num)
+            .cloned()
+            .unwrap_or_default();
+        let text = SRC
+            .try_lock()
+            .unwrap()
+            .get(file_num)
+            .and_then(|code| code.get(line))
+            .cloned()
+            .unwrap_or_default();
+        Span {
+            file,
+            line,
+            column,
+            len,
+            text,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// エラーや警告1つ分の情報
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str, // 分類(エラーは発生箇所ごとに決めた名前、警告は gcc の -W に倣った名前)
+    pub message: String,
+    pub span: Option<Span>,          // 主な位置
+    pub labels: Vec<(Span, String)>, // 関連する位置とその説明
+    pub notes: Vec<String>,
+    pub fixits: Vec<(Span, String)>, // 置き換える範囲と置き換え後の文字列
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code,
+            message: message.into(),
+            span: None,
+            labels: vec![],
+            notes: vec![],
+            fixits: vec![],
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_span(mut self, span: Span) -> Diagnostic {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, label: impl Into<String>) -> Diagnostic {
+        self.labels.push((span, label.into()));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_fixit(mut self, span: Span, replacement: impl Into<String>) -> Diagnostic {
+        self.fixits.push((span, replacement.into()));
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// 端末向けの表示を作る(color が false ならエスケープシーケンスを含めない)
+    pub fn render(&self, color: bool) -> String {
+        let paint = |code: usize, s: &str| {
+            if color {
+                format!("\x1b[{}m{}\x1b[m", code, s)
+            } else {
+                s.to_string()
+            }
+        };
+        let color_code = match self.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+
+        let mut out = String::new();
+        match &self.span {
+            Some(span) => {
+                let title = match self.severity {
+                    Severity::Error => "rscc: Compile Error",
+                    Severity::Warning => "rscc: Warning",
+                };
+                out.push_str(&format!("{} [{}]\n", paint(color_code, title), self.code));
+                out.push_str(&format!("{}\n", paint(LIGHTBLUE, &span.to_string())));
+                out.push_str(&span.text);
+                out.push_str(&format!(
+                    "{}{} {}\n",
+                    padding(&span.text, span.column),
+                    paint(color_code, "^"),
+                    self.message
+                ));
+            }
+            None => {
+                out.push_str(&format!(
+                    "{}{} [{}] - {}\n",
+                    paint(LIGHTBLUE, "rscc: "),
+                    paint(color_code, &self.severity.to_string()),
+                    self.code,
+                    self.message
+                ));
+            }
+        }
+
+        for (span, label) in &self.labels {
+            out.push_str(&format!("{}\n", paint(LIGHTBLUE, &span.to_string())));
+            out.push_str(&span.text);
+            out.push_str(&format!(
+                "{}{} {}\n",
+                padding(&span.text, span.column),
+                paint(LIGHTBLUE, "-"),
+                label
+            ));
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("{} {}\n", paint(LIGHTBLUE, "note:"), note));
+        }
+
+        for (span, replacement) in &self.fixits {
+            let chars = span.text.chars().collect::<Vec<char>>();
+            let end = (span.column + span.len).min(chars.len());
+            let fixed = format!(
+                "{}{}{}",
+                chars[..span.column].iter().collect::<String>(),
+                replacement,
+                chars[end..].iter().collect::<String>()
+            );
+            out.push_str(&format!(
+                "{} {}に\"{}\"を{}\n",
+                paint(GREEN, "修正候補:"),
+                span,
+                replacement,
+                fixit_verb(span)
+            ));
+            out.push_str(&fixed);
+            out.push_str(&format!(
+                "{}{}\n",
+                padding(&span.text, span.column),
+                paint(GREEN, &"+".repeat(replacement.chars().count()))
+            ));
+        }
+        out
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
+
+/// 診断の並びを JSON の配列として出力する(列番号は1始まりで、 end_column は範囲の直後の列)
+pub fn to_json(diags: &[Diagnostic]) -> String {
+    let location = |span: &Span| {
+        format!(
+            "{{\"file\":{},\"line\":{},\"column\":{},\"end_column\":{}}}",
+            json_str(&span.file),
+            span.line,
+            span.column + 1,
+            span.column + span.len + 1
+        )
+    };
+    let items = diags
+        .iter()
+        .map(|diag| {
+            let related = diag
+                .labels
+                .iter()
+                .map(|(span, label)| {
+                    format!(
+                        "{{\"location\":{},\"message\":{}}}",
+                        location(span),
+                        json_str(label)
+                    )
+                })
+                .collect::<Vec<String>>();
+            let notes = diag
+                .notes
+                .iter()
+                .map(|note| json_str(note))
+                .collect::<Vec<String>>();
+            let fixits = diag
+                .fixits
+                .iter()
+                .map(|(span, replacement)| {
+                    format!(
+                        "{{\"location\":{},\"replacement\":{}}}",
+                        location(span),
+                        json_str(replacement)
+                    )
+                })
+                .collect::<Vec<String>>();
+            format!(
+                "{{\"kind\":{},\"rule\":{},\"message\":{},\"location\":{},\"related\":[{}],\"notes\":[{}],\"fixits\":[{}]}}",
+                json_str(&diag.severity.to_string().to_lowercase()),
+                json_str(diag.code),
+                json_str(&diag.message),
+                diag.span.as_ref().map_or("null".to_string(), location),
+                related.join(","),
+                notes.join(","),
+                fixits.join(",")
+            )
+        })
+        .collect::<Vec<String>>();
+    format!("[{}]\n", items.join(","))
+}
+
+/// 診断の並びを SARIF 2.1.0 の形式で出力する(補足は各結果の properties に入れる)
+pub fn to_sarif(diags: &[Diagnostic]) -> String {
+    let physical = |span: &Span| {
+        format!(
+            "{{\"artifactLocation\":{{\"uri\":{}}},\"region\":{}}}",
+            json_str(&span.file),
+            sarif_region(span)
+        )
+    };
+    let mut rules: Vec<&str> = vec![];
+    let results = diags
+        .iter()
+        .map(|diag| {
+            let rule_index = rules
+                .iter()
+                .position(|rule| *rule == diag.code)
+                .unwrap_or_else(|| {
+                    rules.push(diag.code);
+                    rules.len() - 1
+                });
+            let level = match diag.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            let locations = diag
+                .span
+                .iter()
+                .map(|span| format!("{{\"physicalLocation\":{}}}", physical(span)))
+                .collect::<Vec<String>>();
+            let related = diag
+                .labels
+                .iter()
+                .enumerate()
+                .map(|(id, (span, label))| {
+                    format!(
+                        "{{\"id\":{},\"physicalLocation\":{},\"message\":{{\"text\":{}}}}}",
+                        id,
+                        physical(span),
+                        json_str(label)
+                    )
+                })
+                .collect::<Vec<String>>();
+            let fixes = diag
+                .fixits
+                .iter()
+                .map(|(span, replacement)| {
+                    format!(
+                        "{{\"description\":{{\"text\":{}}},\"artifactChanges\":[{{\"artifactLocation\":{{\"uri\":{}}},\"replacements\":[{{\"deletedRegion\":{},\"insertedContent\":{{\"text\":{}}}}}]}}]}}",
+                        json_str(&format!("\"{}\"を{}", replacement, fixit_verb(span))),
+                        json_str(&span.file),
+                        sarif_region(span),
+                        json_str(replacement)
+                    )
+                })
+                .collect::<Vec<String>>();
+            let notes = diag
+                .notes
+                .iter()
+                .map(|note| json_str(note))
+                .collect::<Vec<String>>();
+            format!(
+                "{{\"ruleId\":{},\"ruleIndex\":{},\"level\":\"{}\",\"message\":{{\"text\":{}}},\"locations\":[{}],\"relatedLocations\":[{}],\"fixes\":[{}],\"properties\":{{\"notes\":[{}]}}}}",
+                json_str(diag.code),
+                rule_index,
+                level,
+                json_str(&diag.message),
+                locations.join(","),
+                related.join(","),
+                fixes.join(","),
+                notes.join(",")
+            )
+        })
+        .collect::<Vec<String>>();
+    let rules = rules
+        .iter()
+        .map(|rule| format!("{{\"id\":{}}}", json_str(rule)))
+        .collect::<Vec<String>>();
+    format!(
+        "{{\"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\"version\":\"2.1.0\",\"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"rscc\",\"rules\":[{}]}}}},\"columnKind\":\"unicodeCodePoints\",\"results\":[{}]}}]}}\n",
+        rules.join(","),
+        results.join(",")
+    )
+}
+
+/// SARIF の region(列番号は1始まりで、 endColumn は範囲の直後の列)
+fn sarif_region(span: &Span) -> String {
+    format!(
+        "{{\"startLine\":{},\"startColumn\":{},\"endLine\":{},\"endColumn\":{}}}",
+        span.line,
+        span.column + 1,
+        span.line,
+        span.column + span.len + 1
+    )
+}
+
+/// JSON の文字列リテラルにする
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// 修正候補が挿入か置換か
+fn fixit_verb(span: &Span) -> &'static str {
+    if span.len == 0 {
+        "挿入"
+    } else {
+        "置換"
+    }
+}
+
+/// 行の先頭から column 文字分の空白(タブはそのまま残して表示位置を揃える)
+fn padding(text: &str, column: usize) -> String {
+    text.chars()
+        .chain(std::iter::repeat(' '))
+        .take(column)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+/// コンパイルを中断する際に unwind の payload として使う
+struct FatalError;
+
+/// 各フェーズで発生した診断を溜めておくシンク
+static DIAGNOSTICS: Lazy<Mutex<Vec<Diagnostic>>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// 診断をシンクに追加する(コンパイルは続行する)
+pub fn report(diag: Diagnostic) {
+    DIAGNOSTICS.try_lock().unwrap().push(diag);
+}
+
+/// 診断をシンクに追加し、コンパイルを中断する
+/// 直前のエラーと同じ位置のエラーは、エラーからの復帰で同じ箇所を読み直したものとみなして追加しない
+pub fn abort(diag: Diagnostic) -> ! {
+    let is_duplicate = DIAGNOSTICS
+        .try_lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|d| d.is_error())
+        .is_some_and(|d| d.span.is_some() && d.span == diag.span);
+    if !is_duplicate {
+        report(diag);
+    }
+    bail();
+}
+
+/// 診断を追加せずにコンパイルを中断する(既に報告したエラーから連鎖したエラーを抑制する場合に使う)
+/// catch_fatal の外で呼ばれた場合は FatalError を payload とする panic と同様に振る舞う
+pub fn bail() -> ! {
+    panic::resume_unwind(Box::new(FatalError));
+}
+
+/// f を実行し、その途中でコンパイルが中断された場合は None を返す(それ以外の panic はそのまま伝播させる)
+pub fn catch_fatal<T>(f: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) if payload.is::<FatalError>() => None,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// catch_all の実行中に発生した panic の位置
+static PANIC_LOCATION: Mutex<Option<String>> = Mutex::new(None);
+
+/// catch_fatal と同様だが、コンパイラ内部の不具合による panic も内部エラーとして報告した上で None を返す
+/// (compile_src の呼び出し元に panic を伝播させないために、コンパイル全体を包む箇所でのみ使う)
+/// 実行中は panic のメッセージを標準エラー出力に出さないよう、 panic hook を差し替えて位置だけを記録する
+pub fn catch_all<T>(f: impl FnOnce() -> T) -> Option<T> {
+    let prev_hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        *PANIC_LOCATION.try_lock().unwrap() = info.location().map(|loc| loc.to_string());
+    }));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(prev_hook);
+
+    match result {
+        Ok(value) => Some(value),
+        Err(payload) if payload.is::<FatalError>() => None,
+        Err(payload) => {
+            let mut detail = if let Some(msg) = payload.downcast_ref::<&str>() {
+                msg.to_string()
+            } else if let Some(msg) = payload.downcast_ref::<String>() {
+                msg.clone()
+            } else {
+                "unknown panic".to_string()
+            };
+            if let Some(location) = PANIC_LOCATION.try_lock().unwrap().take() {
+                detail = format!("{} at {}", detail, location);
+            }
+            report(Diagnostic::error(
+                "internal-error",
+                format!("コンパイラの内部エラーが発生しました。({})", detail),
+            ));
+            None
+        }
+    }
+}
+
+/// これまでに報告されたエラーの数
+pub fn error_count() -> usize {
+    DIAGNOSTICS
+        .try_lock()
+        .unwrap()
+        .iter()
+        .filter(|d| d.is_error())
+        .count()
+}
+
+pub fn has_errors() -> bool {
+    error_count() > 0
+}
+
+/// シンクに溜まった診断を取り出す
+pub fn take_diagnostics() -> Vec<Diagnostic> {
+    DIAGNOSTICS.try_lock().unwrap().drain(..).collect()
+}
+
+pub fn clear_state() {
+    DIAGNOSTICS.try_lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::globals::tests::test_init;
+
+    #[test]
+    fn render_test() {
+        let _lock = test_init("int main() {\n\treturn 0\n}");
+        let diag = Diagnostic::error(
+            "expected-token",
+            "\";\"を期待した位置で\"}\"が発見されました。",
+        )
+        .with_span(Span::new(0, 3, 0, 1))
+        .with_label(Span::new(0, 1, 4, 4), "関数の定義")
+        .with_note("文の終わりには\";\"が必要です。")
+        .with_fixit(Span::new(0, 2, 9, 0), ";");
+        let rendered = diag.render(false);
+        assert_eq!(
+            rendered,
+            "rscc: Compile Error [expected-token]\n\
+             test:3:0\n\
+             }\n\
+             ^ \";\"を期待した位置で\"}\"が発見されました。\n\
+             test:1:4\n\
+             int main() {\n    \
+             - 関数の定義\n\
+             note: 文の終わりには\";\"が必要です。\n\
+             修正候補: test:2:9に\";\"を挿入\n\
+             \treturn 0;\n\
+             \t        +\n"
+        );
+        assert_eq!(diag.to_string(), rendered);
+        assert!(diag
+            .render(true)
+            .contains("\x1b[31mrscc: Compile Error\x1b[m"));
+
+        let diag = Diagnostic::warning("option", "不明なオプションです。");
+        assert_eq!(
+            diag.render(false),
+            "rscc: Warning [option] - 不明なオプションです。\n"
+        );
+    }
+
+    #[test]
+    fn sink_test() {
+        let _lock = test_init("int x;");
+        report(Diagnostic::warning("w", "1"));
+        assert!(!has_errors());
+        let result = catch_fatal(|| abort(Diagnostic::error("e", "2")));
+        assert!(result.is_none());
+        assert!(has_errors());
+        // 同じ位置のエラーは1度だけ記録される
+        let span = Span::new(0, 1, 4, 1);
+        for _ in 0..2 {
+            let _ = catch_fatal(|| abort(Diagnostic::error("e", "3").with_span(span.clone())));
+        }
+        assert_eq!(catch_fatal(|| 1), Some(1));
+        let diags = take_diagnostics();
+        assert_eq!(diags.len(), 3);
+        assert!(!diags[0].is_error());
+        assert!(diags[1].is_error());
+        assert!(take_diagnostics().is_empty());
+
+        // FatalError 以外の panic は、 catch_all でのみ内部エラーとして記録される
+        let result = catch_all(|| -> i32 { panic!("broken") });
+        assert!(result.is_none());
+        let diags = take_diagnostics();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, "internal-error");
+        assert!(diags[0].message.contains("broken"));
+        assert!(diags[0].message.contains("src/diagnostic.rs:"));
+        // 元の panic hook に戻っている
+        assert!(panic::catch_unwind(|| panic!("restored")).is_err());
+        assert!(PANIC_LOCATION.try_lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn machine_readable_test() {
+        let _lock = test_init("int main() {\n\treturn \"a\\b\" 0\n}");
+        let diags = vec![
+            Diagnostic::error("expected-token", "\"0\"が発見されました。")
+                .with_span(Span::new(0, 2, 14, 1))
+                .with_fixit(Span::new(0, 2, 14, 0), ";"),
+            Diagnostic::warning("return-type", "警告")
+                .with_span(Span::new(0, 3, 0, 1))
+                .with_label(Span::new(0, 1, 4, 4), "関数")
+                .with_note("補足"),
+        ];
+        assert_eq!(
+            to_json(&diags),
+            "[{\"kind\":\"error\",\"rule\":\"expected-token\",\"message\":\"\\\"0\\\"が発見されました。\",\
+             \"location\":{\"file\":\"test\",\"line\":2,\"column\":15,\"end_column\":16},\
+             \"related\":[],\"notes\":[],\"fixits\":[{\"location\":{\"file\":\"test\",\"line\":2,\
+             \"column\":15,\"end_column\":15},\"replacement\":\";\"}]},\
+             {\"kind\":\"warning\",\"rule\":\"return-type\",\"message\":\"警告\",\
+             \"location\":{\"file\":\"test\",\"line\":3,\"column\":1,\"end_column\":2},\
+             \"related\":[{\"location\":{\"file\":\"test\",\"line\":1,\"column\":5,\"end_column\":9},\
+             \"message\":\"関数\"}],\"notes\":[\"補足\"],\"fixits\":[]}]\n"
+        );
+        assert_eq!(to_json(&[]), "[]\n");
+
+        let sarif = to_sarif(&diags);
+        assert!(sarif.starts_with("{\"$schema\":"));
+        assert!(sarif.contains("\"rules\":[{\"id\":\"expected-token\"},{\"id\":\"return-type\"}]"));
+        assert!(sarif.contains("\"ruleId\":\"return-type\",\"ruleIndex\":1,"));
+        assert!(sarif.contains(
+            "\"ruleId\":\"expected-token\",\"ruleIndex\":0,\"level\":\"error\",\"message\":{\"text\":\"\\\"0\\\"が発見されました。\"},\
+             \"locations\":[{\"physicalLocation\":{\"artifactLocation\":{\"uri\":\"test\"},\
+             \"region\":{\"startLine\":2,\"startColumn\":15,\"endLine\":2,\"endColumn\":16}}}]"
+        ));
+        assert!(sarif.contains("\"relatedLocations\":[{\"id\":0,"));
+        assert!(sarif.contains("\"insertedContent\":{\"text\":\";\"}"));
+        assert!(sarif.contains("\"properties\":{\"notes\":[\"補足\"]}"));
+
+        assert_eq!(json_str("a\"b\\c\n\t\u{1}"), "\"a\\\"b\\\\c\\n\\t\\u0001\"");
+    }
+}
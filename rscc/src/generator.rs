@@ -1,17 +1,67 @@
 use crate::{
-    asm::{cast, get_ctrl_count, get_func_count, reg_ax, word_ptr, ARGS_REGISTERS, ASMCODE},
-    asm_write, error_with_node, exit_eprintln, lea, mov, mov_from, mov_from_glb, mov_glb_addr,
-    mov_op, mov_to, movsx,
-    node::{NodeRef, Nodekind},
+    asm::{
+        cast, extend, get_ctrl_count, get_func_count, load, reg_ax, sized_register, word_ptr,
+        ARGS_REGISTERS, ASMCODE,
+    },
+    asm_write, error_with_node,
+    globals::{Mutex, PIC},
+    lea, mov, mov_glb_addr, mov_to,
+    node::{Node, NodeRef, Nodekind},
     operate,
-    parser::ORDERED_LITERALS,
-    typecell::Type,
+    parser::{asm_fixed_register, asm_symbol, ORDERED_LITERALS},
 };
+use std::collections::HashMap;
 use std::rc::Rc;
 
+use once_cell::sync::Lazy;
+
+/// break, continue の飛び先となるラベルのスタック(switch は break の飛び先のみを持つ)
+static BREAK_LABELS: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(vec![]));
+static CONTINUE_LABELS: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// goto のラベルを関数ごとに区別するための、現在処理中の関数の番号
+static CURRENT_FUNC: Lazy<Mutex<u32>> = Lazy::new(|| Mutex::new(0));
+
+/// インラインアセンブリの "r", "m" のオペランドに割り当てるレジスタの候補
+static ASM_SCRATCH_REGISTERS: [&str; 9] =
+    ["rax", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11"];
+
+/// インラインアセンブリで使う場合に、前後で値を保存する必要のある callee-saved なレジスタ
+static CALLEE_SAVED_REGISTERS: [&str; 5] = ["rbx", "r12", "r13", "r14", "r15"];
+
+/// スタック上に動的に領域を確保する関数で、確保済みの領域の下端を保持する変数のオフセット
+static ALLOCA_BOTTOM: Lazy<Mutex<Option<usize>>> = Lazy::new(|| Mutex::new(None));
+
+/// スタックフレームを揃え直した関数で、揃え直す前の rsp (保存した rbp の位置)を保持する変数のオフセット
+static FRAME_BASE: Lazy<Mutex<Option<usize>>> = Lazy::new(|| Mutex::new(None));
+
+/// コンパイル間でコード生成の状態をリセットする
+pub fn clear_state() {
+    BREAK_LABELS.try_lock().unwrap().clear();
+    CONTINUE_LABELS.try_lock().unwrap().clear();
+    *CURRENT_FUNC.try_lock().unwrap() = 0;
+    *ALLOCA_BOTTOM.try_lock().unwrap() = None;
+    *FRAME_BASE.try_lock().unwrap() = None;
+}
+
+/// 保存した rbp の位置(呼び出し側がスタックに積んだ引数はその16バイト上から並ぶ)を指すレジスタ
+/// スタックフレームを揃え直した関数では、保持しておいた値を reg に読み込んで使う
+fn caller_frame(reg: &'static str) -> &'static str {
+    if let Some(base) = *FRAME_BASE.try_lock().unwrap() {
+        mov!(reg, format!("QWORD PTR [rbp-{}]", base));
+        reg
+    } else {
+        "rbp"
+    }
+}
+
 pub fn generate(trees: Vec<NodeRef>) {
     load_literals();
     for tree in trees {
+        // ファイルスコープの asm は、関数と同様に .text に出力する
+        if tree.borrow().kind == Nodekind::InlineAsm {
+            asm_write!("\t.text");
+        }
         gen_expr(&tree);
     }
 }
@@ -35,35 +85,94 @@ fn gen_expr(node: &NodeRef) {
     match kind {
         Nodekind::Global => {
             let node = node.borrow();
-            let name = node.name.clone().unwrap();
+            let name = asm_symbol(node.name.as_ref().unwrap());
             if node.func_typ.is_some() {
-                // プロトタイプ宣言は無視して OK
+                // プロトタイプ宣言は、 weak であれば未定義でもリンクできるようにする以外は無視して OK
                 if node.stmts.is_none() {
+                    if node.is_weak {
+                        asm_write!("\t.weak {}", name);
+                    }
                     return;
                 }
                 let c = get_func_count();
+                *CURRENT_FUNC.try_lock().unwrap() = c;
 
-                asm_write!("\t.text");
-                asm_write!("\t.globl {}", name);
+                if let Some(section) = &node.section {
+                    asm_write!("\t.section {},\"ax\",@progbits", section);
+                } else {
+                    asm_write!("\t.text");
+                }
+                if node.is_weak {
+                    asm_write!("\t.weak {}", name);
+                } else if !node.is_static {
+                    asm_write!("\t.globl {}", name);
+                }
                 asm_write!("\t.type {}, @function", name);
                 asm_write!("{}:", name);
                 asm_write!(".LFB{}:", c); // function begin label
 
                 // プロローグ(変数の格納領域の確保)
+                // 16より大きいアラインメントのローカル変数がある場合は、 rsp をそのアラインメントに揃えてから rbp とする
+                // 揃える前の rsp は、引数の読み出しとエピローグのために固定の領域の末尾に置いた変数に保持する
                 operate!("push", "rbp");
+                let mut pull = node.max_offset.unwrap();
+                let frame_base = if let Some(align) = node.frame_align {
+                    mov!("r11", "rsp");
+                    operate!("and", "rsp", -(align as i64));
+                    pull = (pull + 8).div_ceil(16) * 16;
+                    Some(pull)
+                } else {
+                    None
+                };
                 mov!("rbp", "rsp");
-                let pull = node.max_offset.unwrap();
+                // 動的に領域を確保する場合は、確保済みの領域の下端を保持する変数を固定の領域の末尾に置く
+                // 確保する領域の大きさは16の倍数にするので、固定の領域も16の倍数にしておけば rsp は常に揃ったままになる
+                let bottom = if node.uses_alloca {
+                    pull = (pull + 8).div_ceil(16) * 16;
+                    Some(pull)
+                } else {
+                    None
+                };
+                *ALLOCA_BOTTOM.try_lock().unwrap() = bottom;
+                *FRAME_BASE.try_lock().unwrap() = frame_base;
                 if pull > 0 {
                     operate!("sub", "rsp", pull);
                 }
+                if let Some(frame_base) = frame_base {
+                    mov_to!(8, "rbp", "r11", frame_base);
+                }
+                if let Some(bottom) = bottom {
+                    mov_to!(8, "rbp", "rsp", bottom);
+                }
 
-                // 受け取った引数の挿入: 現在は6つの引数までなのでレジスタから値を持ってくる
-                if node.args.len() > 6 {
-                    exit_eprintln!("現在7つ以上の引数はサポートされていません。");
+                // 可変長引数をとる場合は、レジスタ保存領域に引数レジスタを全て保存する
+                // xmm0 ~ xmm7 は al が0でない(ベクタレジスタで引数が渡された)場合のみ保存すれば良い
+                if let Some(area) = node.va_area {
+                    for (ix, reg) in ARGS_REGISTERS.try_lock().unwrap()[&8].iter().enumerate() {
+                        mov_to!(8, "rbp", reg, area - ix * 8);
+                    }
+                    operate!("test", "al", "al");
+                    operate!("je", format!(".LVaSaved{}", c));
+                    for ix in 0..8 {
+                        asm_write!(
+                            "\tmovups XMMWORD PTR [rbp-{}], xmm{}",
+                            area - 48 - ix * 16,
+                            ix
+                        );
+                    }
+                    asm_write!(".LVaSaved{}:", c);
                 }
+
+                // 受け取った引数の挿入: 6つ目まではレジスタから、7つ目以降は呼び出し側がスタックに積んだもの([rbp+16] から順に8バイトずつ)から値を持ってくる
                 for (ix, arg) in node.args.iter().enumerate() {
                     let offset = *arg.borrow().offset.as_ref().unwrap();
                     let size = arg.borrow().typ.as_ref().unwrap().bytes();
+                    if ix >= 6 {
+                        let frame = caller_frame("rax");
+                        asm_write!("\tmov rax, QWORD PTR [{}+{}]", frame, 16 + (ix - 6) * 8);
+                        mov_to!(size, "rbp", reg_ax(size), offset);
+                        continue;
+                    }
                     let arg_reg = ARGS_REGISTERS.try_lock().unwrap().get(&size).unwrap()[ix];
 
                     mov_to!(size, "rbp", arg_reg, offset);
@@ -81,26 +190,64 @@ fn gen_expr(node: &NodeRef) {
                 let typ = node.typ.clone().unwrap();
                 let bytes = typ.bytes();
                 let is_initialized = !node.init_data.is_empty();
-                asm_write!("\t.globl {}", name);
-                if is_initialized {
+                // 定義のない weak な extern 宣言は、未定義のシンボルとして .weak のみを出力する
+                if node.is_extern {
+                    asm_write!("\t.weak {}", name);
+                    return;
+                }
+                // static なものは外部から参照できないようにする
+                if node.is_weak {
+                    asm_write!("\t.weak {}", name);
+                } else if !node.is_static {
+                    asm_write!("\t.globl {}", name);
+                }
+                if let Some(section) = &node.section {
+                    // section 属性で指定されたセクションには、 const であれば書き込み不可として置く
+                    let flags = if node.is_tls {
+                        "awT"
+                    } else if typ.is_const_object() {
+                        "a"
+                    } else {
+                        "aw"
+                    };
+                    asm_write!("\t.section {},\"{}\",@progbits", section, flags);
+                } else if node.is_tls {
+                    // スレッドローカルな変数は、スレッドごとに複製される初期イメージとして置く
+                    if is_initialized {
+                        asm_write!("\t.section .tdata,\"awT\",@progbits");
+                    } else {
+                        asm_write!("\t.section .tbss,\"awT\",@nobits");
+                    }
+                } else if is_initialized {
                     let is_ptr = if typ.is_array() {
                         typ.get_base_cell().is_pointer()
                     } else {
                         typ.is_pointer()
                     };
-                    if is_ptr {
-                        asm_write!("\t.section .data.rel.local");
+                    if typ.is_const_object() {
+                        // const なものは書き換えられないセクションに置く(アドレスを含む場合は再配置が必要)
+                        if is_ptr {
+                            asm_write!("\t.section .data.rel.ro");
+                        } else {
+                            asm_write!("\t.section .rodata");
+                        }
+                    } else {
+                        if is_ptr {
+                            asm_write!("\t.section .data.rel.local");
+                        }
+                        asm_write!("\t.data");
                     }
-                    asm_write!("\t.data");
                 } else {
                     asm_write!("\t.bss");
                 }
+                asm_write!("\t.align {}", typ.align());
                 asm_write!("\t.type {}, @object", name);
                 asm_write!("\t.size {}, {}", name, bytes);
                 asm_write!("{}:", name);
                 if !node.init_data.is_empty() {
                     for data in &node.init_data {
                         if let Some(label) = &data.label {
+                            let label = asm_symbol(label);
                             if data.size != 8 {
                                 panic!("something wrong with initializing data size");
                             }
@@ -110,26 +257,26 @@ fn gen_expr(node: &NodeRef) {
                                 val => asm_write!(".quad {}{}", label, val),
                             }
                         } else if data.val == 0 {
-                                asm_write!("\t.zero {}", data.size);
-                            } else {
-                                match data.size {
-                                    1 => {
-                                        asm_write!("\t.byte {}", data.val);
-                                    }
-                                    2 => {
-                                        asm_write!("\t.value {}", data.val);
-                                    }
-                                    4 => {
-                                        asm_write!("\t.long {}", data.val);
-                                    }
-                                    8 => {
-                                        asm_write!("\t.quad {}", data.val);
-                                    }
-                                    _ => {
-                                        panic!("something wrong with initializing data size");
-                                    }
+                            asm_write!("\t.zero {}", data.size);
+                        } else {
+                            match data.size {
+                                1 => {
+                                    asm_write!("\t.byte {}", data.val);
+                                }
+                                2 => {
+                                    asm_write!("\t.value {}", data.val);
+                                }
+                                4 => {
+                                    asm_write!("\t.long {}", data.val);
+                                }
+                                8 => {
+                                    asm_write!("\t.quad {}", data.val);
+                                }
+                                _ => {
+                                    panic!("something wrong with initializing data size");
                                 }
                             }
+                        }
                     }
                 } else {
                     asm_write!("\t.zero {}", bytes);
@@ -205,28 +352,32 @@ fn gen_expr(node: &NodeRef) {
         Nodekind::BitNot => {
             gen_expr(node.borrow().left.as_ref().unwrap());
             operate!("not", "rax");
+            // ゼロ拡張されていた上位ビットも反転されるので、 unsigned の場合は拡張し直す
+            let typ = node.borrow().typ.clone().unwrap();
+            if typ.is_unsigned {
+                extend(&typ);
+            }
             return;
         }
         Nodekind::Lvar => {
             // 葉、かつローカル変数なので、あらかじめ代入した値へのアクセスを行う
-            // 配列のみ、それ単体でアドレスとして解釈されるため gen_addr の結果をそのまま使うことにしてスルー
-            let typ = node.borrow().typ.clone();
-            if typ.clone().unwrap().typ != Type::Array {
-                // movsx などで eax を使うことに注意
-                let bytes = typ.unwrap().bytes();
-                let ax = if bytes < 4 { "eax" } else { reg_ax(bytes) };
-
+            // 配列と構造体・共用体は、それ単体でアドレスとして解釈されるため gen_addr の結果をそのまま使うことにしてスルー
+            let typ = node.borrow().typ.clone().unwrap();
+            if !typ.is_aggregate() {
+                // 複合リテラルの場合は、参照する前に初期化を行う
+                if let Some(init) = &node.borrow().init {
+                    gen_expr(init);
+                }
+                // 型に従って rax 全体に拡張した状態で読み込む
                 if node.borrow().is_local {
                     let offset = node.borrow().offset.unwrap();
-                    mov_from!(bytes, ax, "rbp", offset);
+                    load(&typ, &format!("[rbp-{}]", offset));
+                } else if node.borrow().is_tls {
+                    gen_addr(node);
+                    load(&typ, "[rax]");
                 } else {
-                    let name = node.borrow().name.clone().unwrap();
-                    mov_from_glb!(bytes, ax, name);
-                }
-
-                // rax で push するために、 eax ならば符号拡張が必要(現在は4と8しかサポートしていないためこうなる)
-                if bytes == 4 {
-                    operate!("cdqe");
+                    let name = asm_symbol(node.borrow().name.as_ref().unwrap());
+                    load(&typ, &format!("{}[rip]", name));
                 }
             } else {
                 gen_addr(node);
@@ -241,16 +392,12 @@ fn gen_expr(node: &NodeRef) {
             if left.borrow().kind == Nodekind::Addr {
                 gen_expr(left.borrow().left.as_ref().unwrap());
             } else {
-                // 参照を外した後でも配列なのであれば、アドレスが指す値を評価せずそのまま使用する
+                // 参照を外した後でも配列や構造体なのであれば、アドレスが指す値を評価せずそのまま使用する
                 gen_expr(&left);
-                if node.borrow().typ.as_ref().unwrap().typ != Type::Array {
-                    let left_typ = left.borrow().typ.clone().unwrap();
-                    let bytes = if left_typ.typ == Type::Array {
-                        8
-                    } else {
-                        left_typ.bytes()
-                    };
-                    mov_from!(bytes, "rax", "rax");
+                let typ = node.borrow().typ.clone().unwrap();
+                if !typ.is_aggregate() {
+                    // 参照先の型の大きさだけ読み、Lvar と同様に拡張する
+                    load(&typ, "[rax]");
                 }
             }
             return;
@@ -259,19 +406,100 @@ fn gen_expr(node: &NodeRef) {
             gen_addr(node.borrow().left.as_ref().unwrap());
             return;
         }
+        Nodekind::Member => {
+            gen_addr(node);
+            let typ = node.borrow().typ.clone().unwrap();
+            if !typ.is_aggregate() {
+                load(&typ, "[rax]");
+            }
+            return;
+        }
         Nodekind::FunCall => {
-            // 引数をレジスタに格納する処理
+            // 引数をレジスタに格納する処理: 7つ目以降の引数はスタックに残る
             push_args(&node.borrow().args);
+            let stack_args = node.borrow().args.len().saturating_sub(6);
 
+            // call の時点で rsp が16の倍数になるように、スタックに残った引数を align した領域にコピーし直す
+            // 呼び出し後に戻すための元の rsp はそれらの引数の直後に置く
             mov!("rax", "rsp");
+            operate!("sub", "rsp", (stack_args + 1) * 8);
             operate!("and", "rsp", "~0x0f"); // 16の倍数に align
-            operate!("sub", "rsp", 8);
-            operate!("push", "rax");
+            for ix in 0..stack_args {
+                asm_write!("\tmov r10, QWORD PTR [rax+{}]", ix * 8);
+                asm_write!("\tmov QWORD PTR [rsp+{}], r10", ix * 8);
+            }
+            asm_write!("\tmov QWORD PTR [rsp+{}], rax", stack_args * 8);
 
             // この時点で ARGS_REGISTERS に記載の6つのレジスタには引数が入っている必要がある
-            mov!("rax", 0); // 可変長引数をとる際、浮動小数点の数を al に入れる必要があるが、今は浮動小数点がサポートされていないため単に0を入れる
-            operate!("call", node.borrow().name.as_ref().unwrap());
-            operate!("pop", "rsp");
+            // 可変長引数をとる関数のために、ベクタレジスタで渡した引数の数を al に入れる
+            let vector_args = node
+                .borrow()
+                .args
+                .iter()
+                .filter(|arg| arg.borrow().typ.as_ref().unwrap().is_floating())
+                .count();
+            mov!("rax", vector_args);
+            operate!("call", asm_symbol(node.borrow().name.as_ref().unwrap()));
+            asm_write!("\tmov rsp, QWORD PTR [rsp+{}]", stack_args * 8);
+            if stack_args > 0 {
+                operate!("add", "rsp", stack_args * 8);
+            }
+
+            // 戻り値は型の大きさ分の下位ビットしか保証されないので、宣言された型に従って符号拡張(unsigned ならゼロ拡張)する
+            let ret_typ = node.borrow().typ.clone().unwrap();
+            if !ret_typ.is_void() {
+                extend(&ret_typ);
+            }
+            return;
+        }
+        Nodekind::VaStart => {
+            // va_list の各メンバ(gp_offset, fp_offset, overflow_arg_area, reg_save_area)を初期化する
+            // 名前付き引数のうち7つ目以降はスタックで渡されているので、overflow_arg_area はその後ろを指す
+            let node = node.borrow();
+            let area = node.offset.unwrap();
+            let argc = node.val.unwrap() as usize;
+            gen_expr(node.left.as_ref().unwrap());
+            asm_write!("\tmov DWORD PTR [rax], {}", argc.min(6) * 8);
+            asm_write!("\tmov DWORD PTR [rax+4], 48");
+            let frame = caller_frame("rdi");
+            lea!(
+                "rdi",
+                format!("{}+{}", frame, 16 + argc.saturating_sub(6) * 8)
+            );
+            asm_write!("\tmov QWORD PTR [rax+8], rdi");
+            lea!("rdi", "rbp", area);
+            asm_write!("\tmov QWORD PTR [rax+16], rdi");
+            return;
+        }
+        Nodekind::VaArg => {
+            // レジスタ保存領域に残りがあればそこから、なければスタック上の引数から取り出し、そのアドレスを rax に入れる
+            let c = get_ctrl_count();
+            gen_expr(node.borrow().left.as_ref().unwrap());
+            asm_write!("\tmov ecx, DWORD PTR [rax]");
+            operate!("cmp", "ecx", 48);
+            operate!("jae", format!(".LVaStack{}", c));
+            asm_write!("\tmov rdx, QWORD PTR [rax+16]");
+            operate!("add", "rdx", "rcx");
+            operate!("add", "ecx", 8);
+            asm_write!("\tmov DWORD PTR [rax], ecx");
+            operate!("jmp", format!(".LVaEnd{}", c));
+            asm_write!(".LVaStack{}:", c);
+            asm_write!("\tmov rdx, QWORD PTR [rax+8]");
+            lea!("rcx", "rdx+8");
+            asm_write!("\tmov QWORD PTR [rax+8], rcx");
+            asm_write!(".LVaEnd{}:", c);
+            mov!("rax", "rdx");
+            return;
+        }
+        Nodekind::VaCopy => {
+            gen_expr(node.borrow().left.as_ref().unwrap());
+            operate!("push", "rax");
+            gen_expr(node.borrow().right.as_ref().unwrap());
+            operate!("pop", "rdi");
+            for offset in [0, 8, 16].iter() {
+                asm_write!("\tmov rdx, QWORD PTR [rax+{}]", offset);
+                asm_write!("\tmov QWORD PTR [rdi+{}], rdx", offset);
+            }
             return;
         }
         Nodekind::Assign => {
@@ -282,22 +510,137 @@ fn gen_expr(node: &NodeRef) {
 
             // 上記gen_expr2つでスタックに変数の値を格納すべきアドレスと、代入する値(式の評価値)がこの順で積んであるはずなので2回popして代入する
             let typ = node.borrow().typ.clone().unwrap();
-            let bytes = if typ.typ == Type::Array {
-                8
-            } else {
-                typ.bytes()
-            };
             operate!("pop", "rdi");
+            if typ.is_struct() {
+                // 構造体・共用体は右辺のアドレスからバイト単位でコピーし、評価値は左辺のアドレスとする
+                mov!("rsi", "rax");
+                mov!("rdx", "rdi");
+                mov!("rcx", typ.bytes());
+                asm_write!("\trep movsb");
+                mov!("rax", "rdx");
+                return;
+            }
+            let bytes = typ.value_bytes();
+            if typ.is_atomic {
+                // メモリとの xchg は lock を付けなくても不可分に行われ、前後の読み書きとの順序も保たれる
+                mov!("rdx", "rax");
+                asm_write!(
+                    "\txchg {} [rdi], {}",
+                    word_ptr(bytes),
+                    sized_register("rdx", bytes)
+                );
+                mov!("rax", "rdx");
+                return;
+            }
             mov_to!(bytes, "rdi", reg_ax(bytes));
             return;
         }
+        Nodekind::AtomicCas => {
+            let node = node.borrow();
+            gen_expr(node.left.as_ref().unwrap());
+            operate!("push", "rax");
+            gen_expr(node.right.as_ref().unwrap());
+            operate!("push", "rax");
+            gen_expr(node.init.as_ref().unwrap());
+            mov!("rdx", "rax");
+            operate!("pop", "rsi");
+            operate!("pop", "rdi");
+
+            // lock cmpxchg は失敗すると現在の値を rax に読み込むので、成否によらず rax を期待値の側に書き戻せば良い
+            let bytes = node
+                .init
+                .as_ref()
+                .unwrap()
+                .borrow()
+                .typ
+                .as_ref()
+                .unwrap()
+                .value_bytes();
+            asm_write!("\tmov {}, {} [rsi]", reg_ax(bytes), word_ptr(bytes));
+            asm_write!(
+                "\tlock cmpxchg {} [rdi], {}",
+                word_ptr(bytes),
+                sized_register("rdx", bytes)
+            );
+            operate!("sete", "cl");
+            mov_to!(bytes, "rsi", reg_ax(bytes));
+            asm_write!("\tmovzx eax, cl");
+            return;
+        }
+        Nodekind::AtomicAdd => {
+            let node = node.borrow();
+            gen_expr(node.left.as_ref().unwrap());
+            operate!("push", "rax");
+            gen_expr(node.right.as_ref().unwrap());
+            operate!("pop", "rdi");
+
+            // 足す前の値が rax に残る
+            let typ = node.typ.as_ref().unwrap();
+            let bytes = typ.value_bytes();
+            asm_write!("\tlock xadd {} [rdi], {}", word_ptr(bytes), reg_ax(bytes));
+            extend(typ);
+            return;
+        }
+        Nodekind::Fence => {
+            operate!("mfence");
+            return;
+        }
+        Nodekind::PopCount | Nodekind::Clz | Nodekind::Ctz | Nodekind::Bswap => {
+            let left = Rc::clone(node.borrow().left.as_ref().unwrap());
+            gen_expr(&left);
+            let bytes = left.borrow().typ.as_ref().unwrap().value_bytes();
+            let reg = reg_ax(bytes);
+            match node.borrow().kind {
+                Nodekind::PopCount => asm_write!("\tpopcnt {}, {}", reg, reg),
+                Nodekind::Clz => {
+                    // bsr は最上位の1のビットの位置を求めるので、ビット数から1を引いた値との差をとる(引数が0の場合は未定義)
+                    asm_write!("\tbsr {}, {}", reg, reg);
+                    operate!("xor", "eax", bytes * 8 - 1);
+                }
+                Nodekind::Ctz => asm_write!("\tbsf {}, {}", reg, reg),
+                _ => {
+                    // 16ビットの値は上位と下位のバイトを入れ替え、 unsigned short として拡張し直す
+                    if bytes == 2 {
+                        operate!("rol", "ax", 8);
+                        asm_write!("\tmovzx eax, ax");
+                    } else {
+                        asm_write!("\tbswap {}", reg);
+                    }
+                }
+            }
+            return;
+        }
+        Nodekind::MemCpy => {
+            let node = node.borrow();
+            gen_expr(node.left.as_ref().unwrap());
+            operate!("push", "rax");
+            gen_expr(node.right.as_ref().unwrap());
+            operate!("push", "rax");
+            gen_expr(node.init.as_ref().unwrap());
+            mov!("rcx", "rax");
+            operate!("pop", "rsi");
+            operate!("pop", "rdi");
+            // コピー先の先頭アドレスを評価値とする
+            mov!("rax", "rdi");
+            asm_write!("\trep movsb");
+            return;
+        }
+        Nodekind::Trap => {
+            asm_write!("\tud2");
+            return;
+        }
+        Nodekind::Unreachable => {
+            return;
+        }
         Nodekind::Cast => {
             let node = node.borrow();
             let left = node.left.as_ref().unwrap();
-            let from = left.borrow().typ.as_ref().unwrap().typ;
-            let to = node.typ.as_ref().unwrap().typ;
             gen_expr(left);
-            cast(from, to);
+            // void へのキャストは値を捨てるだけ
+            let typ = node.typ.as_ref().unwrap();
+            if !typ.is_void() {
+                cast(left.borrow().typ.as_ref().unwrap(), typ);
+            }
             return;
         }
         Nodekind::Comma => {
@@ -309,7 +652,11 @@ fn gen_expr(node: &NodeRef) {
         Nodekind::Return => {
             // リターンならleftの値を評価してretする。
             gen_expr(node.borrow().left.as_ref().unwrap());
-            mov!("rsp", "rbp");
+            if let Some(base) = *FRAME_BASE.try_lock().unwrap() {
+                mov!("rsp", format!("QWORD PTR [rbp-{}]", base));
+            } else {
+                mov!("rsp", "rbp");
+            }
             operate!("pop", "rbp");
             operate!("ret");
             return;
@@ -353,12 +700,90 @@ fn gen_expr(node: &NodeRef) {
             operate!("cmp", "rax", 0); // falseは0なので、cmp rax, 0が真ならエンドに飛ぶ
             operate!("je", end);
 
+            push_jump_labels(&end, Some(&begin));
             gen_expr(node.borrow().branch.as_ref().unwrap());
+            pop_jump_labels(true);
             operate!("jmp", begin);
 
             asm_write!("{}:", end);
             return;
         }
+        Nodekind::DoWhile => {
+            let c: u32 = get_ctrl_count();
+            let begin: String = format!(".LBegin{}", c);
+            let cont: String = format!(".LContinue{}", c);
+            let end: String = format!(".LEnd{}", c);
+
+            asm_write!("{}:", begin);
+
+            push_jump_labels(&end, Some(&cont));
+            gen_expr(node.borrow().branch.as_ref().unwrap());
+            pop_jump_labels(true);
+
+            asm_write!("{}:", cont);
+            gen_expr(node.borrow().enter.as_ref().unwrap());
+            operate!("cmp", "rax", 0); // 条件が真(0でない)なら先頭に戻る
+            operate!("jne", begin);
+
+            asm_write!("{}:", end);
+            return;
+        }
+        Nodekind::Switch => {
+            let c: u32 = get_ctrl_count();
+            let end: String = format!(".LEnd{}", c);
+
+            // 条件式の値と各 case の値を比較して飛ぶ
+            let enter = Rc::clone(node.borrow().enter.as_ref().unwrap());
+            gen_expr(&enter);
+            let ax = if enter.borrow().typ.as_ref().unwrap().bytes() == 8 {
+                "rax"
+            } else {
+                "eax"
+            };
+            let mut default: Option<String> = None;
+            for (ix, case) in node.borrow().children.iter().enumerate() {
+                let label = format!(".LCase{}.{}", c, ix);
+                let val = case.borrow().val;
+                if let Some(val) = val {
+                    operate!("cmp", ax, val);
+                    operate!("je", label);
+                } else {
+                    default = Some(label.clone());
+                }
+                let _ = case.borrow_mut().name.insert(label);
+            }
+            operate!("jmp", default.as_ref().unwrap_or(&end));
+
+            push_jump_labels(&end, None);
+            gen_expr(node.borrow().branch.as_ref().unwrap());
+            pop_jump_labels(false);
+
+            asm_write!("{}:", end);
+            return;
+        }
+        Nodekind::Case => {
+            asm_write!("{}:", node.borrow().name.as_ref().unwrap());
+            gen_expr(node.borrow().branch.as_ref().unwrap());
+            return;
+        }
+        Nodekind::Break | Nodekind::Continue | Nodekind::Goto => {
+            // 抜けるスコープで確保した可変長配列があれば、ジャンプの前に解放する
+            if let Some(init) = &node.borrow().init {
+                gen_expr(init);
+            }
+            let label = match kind {
+                Nodekind::Break => BREAK_LABELS.try_lock().unwrap().last().unwrap().clone(),
+                Nodekind::Continue => CONTINUE_LABELS.try_lock().unwrap().last().unwrap().clone(),
+                _ => user_label(node.borrow().name.as_ref().unwrap()),
+            };
+            operate!("jmp", label);
+            return;
+        }
+        Nodekind::Label => {
+            asm_write!("{}:", user_label(node.borrow().name.as_ref().unwrap()));
+            gen_expr(node.borrow().branch.as_ref().unwrap());
+            return;
+        }
         Nodekind::For => {
             let c: u32 = get_ctrl_count();
             let begin: String = format!(".LBegin{}", c);
@@ -376,8 +801,12 @@ fn gen_expr(node: &NodeRef) {
                 operate!("je", end);
             }
 
+            let cont: String = format!(".LContinue{}", c);
+            push_jump_labels(&end, Some(&cont));
             gen_expr(node.borrow().branch.as_ref().unwrap()); // for文内の処理
+            pop_jump_labels(true);
 
+            asm_write!("{}:", cont);
             if let Some(routine) = &node.borrow().routine {
                 gen_expr(routine); // インクリメントなどの処理
             }
@@ -392,6 +821,23 @@ fn gen_expr(node: &NodeRef) {
             }
             return;
         }
+        Nodekind::StmtExpr => {
+            // 最後の式文の評価値が rax に残る
+            for child in &node.borrow().children {
+                gen_expr(child);
+            }
+            // 可変長配列の領域を解放する間は、評価値をスタックに退避しておく
+            if let Some(init) = &node.borrow().init {
+                operate!("push", "rax");
+                gen_expr(init);
+                operate!("pop", "rax");
+            }
+            return;
+        }
+        Nodekind::InlineAsm => {
+            gen_inline_asm(node);
+            return;
+        }
         Nodekind::ZeroClr => {
             // これは特殊な Node で、現時点では left に Lvar が繋がっているパターンしかあり得ない
             let left = Rc::clone(node.borrow().left.as_ref().unwrap());
@@ -403,6 +849,58 @@ fn gen_expr(node: &NodeRef) {
         Nodekind::Nop => {
             return;
         }
+        Nodekind::Alloca => {
+            gen_expr(node.borrow().left.as_ref().unwrap());
+            let c = get_ctrl_count();
+            let bottom = ALLOCA_BOTTOM.try_lock().unwrap().unwrap();
+            // 確保する大きさは16の倍数に切り上げる
+            operate!("add", "rax", 15);
+            operate!("and", "rax", -16);
+            // 式の評価の途中でスタックに積まれている値(確保済みの領域の下端から rsp まで)を、確保する大きさだけ下にずらす
+            mov!("rcx", format!("QWORD PTR [rbp-{}]", bottom));
+            mov!("rdx", "rsp");
+            operate!("sub", "rsp", "rax");
+            mov!("rdi", "rsp");
+            asm_write!(".LAllocaCopy{}:", c);
+            operate!("cmp", "rdx", "rcx");
+            operate!("je", format!(".LAllocaEnd{}", c));
+            mov!("r8", "QWORD PTR [rdx]");
+            mov!("QWORD PTR [rdi]", "r8");
+            operate!("add", "rdx", 8);
+            operate!("add", "rdi", 8);
+            operate!("jmp", format!(".LAllocaCopy{}", c));
+            asm_write!(".LAllocaEnd{}:", c);
+            // ずらした値のすぐ上が確保した領域となり、その先頭が新しい下端になる
+            operate!("sub", "rcx", "rax");
+            mov_to!(8, "rbp", "rcx", bottom);
+            mov!("rax", "rcx");
+            return;
+        }
+        Nodekind::StackRestore => {
+            // 可変長配列の先頭アドレスに、確保した(16の倍数に切り上げた)大きさを足した位置まで戻す
+            gen_expr(node.borrow().right.as_ref().unwrap());
+            operate!("add", "rax", 15);
+            operate!("and", "rax", -16);
+            mov!("rdi", "rax");
+            gen_expr(node.borrow().left.as_ref().unwrap());
+            operate!("add", "rax", "rdi");
+            let bottom = ALLOCA_BOTTOM.try_lock().unwrap().unwrap();
+            mov!("rcx", format!("QWORD PTR [rbp-{}]", bottom));
+            mov_to!(8, "rbp", "rax", bottom);
+            // 文式の中で解放する場合は式の評価の途中でスタックに積まれている値があるので、それらを解放後の下端の下まで戻す
+            let c = get_ctrl_count();
+            asm_write!(".LRestoreCopy{}:", c);
+            operate!("cmp", "rcx", "rsp");
+            operate!("je", format!(".LRestoreEnd{}", c));
+            operate!("sub", "rcx", 8);
+            operate!("sub", "rax", 8);
+            mov!("r8", "QWORD PTR [rcx]");
+            mov!("QWORD PTR [rax]", "r8");
+            operate!("jmp", format!(".LRestoreCopy{}", c));
+            asm_write!(".LRestoreEnd{}:", c);
+            mov!("rsp", "rax");
+            return;
+        }
         _ => {} // 他のパターンなら、ここでは何もしない
     }
 
@@ -412,8 +910,10 @@ fn gen_expr(node: &NodeRef) {
     operate!("push", "rax");
     gen_expr(&right);
 
-    // long や long long などが実装されるまではポインタなら8バイト、そうでなければ4バイトのレジスタを使うことにする
-    let (ax, di, dx, cq) = if left.borrow().typ.as_ref().unwrap().ptr_end.is_some() {
+    // 使うレジスタの大きさと符号の有無は、変換を済ませた左辺の型で決まる(ポインタは unsigned long と同様に扱われる)
+    let typ = left.borrow().typ.clone().unwrap();
+    let is_unsigned = typ.is_unsigned;
+    let (ax, di, dx, cq) = if typ.value_bytes() == 8 {
         ("rax", "rdi", "rdx", "cqo")
     } else {
         ("eax", "edi", "edx", "cdq")
@@ -427,7 +927,8 @@ fn gen_expr(node: &NodeRef) {
     operate!("pop", "rax");
 
     // >, >= についてはオペランド入れ替えのもとsetl, setleを使う
-    match node.borrow().kind {
+    let kind = node.borrow().kind;
+    match kind {
         Nodekind::Add => {
             operate!("add", ax, di);
         }
@@ -437,20 +938,25 @@ fn gen_expr(node: &NodeRef) {
         Nodekind::Mul => {
             operate!("imul", ax, di);
         }
-        Nodekind::Div => {
-            operate!(cq); // rax -> rdx:rax に拡張(ただの 0 fill)
-            operate!("idiv", di); // rdi で割る: rax が商で rdx が剰余になる
-        }
-        Nodekind::Mod => {
-            operate!(cq);
-            operate!("idiv", di);
-            mov!(ax, dx);
+        Nodekind::Div | Nodekind::Mod => {
+            // rax -> rdx:rax に拡張してから rdi で割る: rax が商で rdx が剰余になる
+            if is_unsigned {
+                operate!("xor", "edx", "edx");
+                operate!("div", di);
+            } else {
+                operate!(cq);
+                operate!("idiv", di);
+            }
+            if kind == Nodekind::Mod {
+                mov!(ax, dx);
+            }
         }
         Nodekind::LShift => {
             operate!("sal", ax, "cl");
         }
         Nodekind::RShift => {
-            operate!("sar", ax, "cl");
+            // unsigned の場合は論理シフト、 signed の場合は算術シフト
+            operate!(if is_unsigned { "shr" } else { "sar" }, ax, "cl");
         }
         Nodekind::BitAnd => {
             operate!("and", ax, di);
@@ -461,45 +967,78 @@ fn gen_expr(node: &NodeRef) {
         Nodekind::BitXor => {
             operate!("xor", ax, di);
         }
-        Nodekind::Eq => {
-            operate!("cmp", ax, di);
-            operate!("sete", "al");
-            operate!("movzb", "rax", "al");
-        }
-        Nodekind::NEq => {
-            operate!("cmp", ax, di);
-            operate!("setne", "al");
-            operate!("movzb", "rax", "al");
-        }
-        Nodekind::LThan => {
-            operate!("cmp", ax, di);
-            operate!("setl", "al");
-            operate!("movzb", "rax", "al");
-        }
-        Nodekind::LEq => {
+        Nodekind::Eq | Nodekind::NEq | Nodekind::LThan | Nodekind::LEq => {
             operate!("cmp", ax, di);
-            operate!("setle", "al");
+            let set = match (kind, is_unsigned) {
+                (Nodekind::Eq, _) => "sete",
+                (Nodekind::NEq, _) => "setne",
+                (Nodekind::LThan, false) => "setl",
+                (Nodekind::LThan, true) => "setb",
+                (_, false) => "setle",
+                (_, true) => "setbe",
+            };
+            operate!(set, "al");
             operate!("movzb", "rax", "al");
+            return;
         }
         _ => {
             // 上記にないNodekindはここに到達する前にreturnしているはず
-            error_with_node!("不正な Nodekind です。", &*node.borrow());
+            error_with_node!("internal-error", "不正な Nodekind です。", &*node.borrow());
         }
     }
+
+    // 32ビットの演算は rax の上位32ビットをゼロクリアするので、 signed の場合は符号拡張し直す
+    if ax == "eax" && !is_unsigned {
+        operate!("cdqe");
+    }
+}
+
+/// ループや switch に入る際に break, continue の飛び先を積む
+fn push_jump_labels(brk: &str, cont: Option<&str>) {
+    BREAK_LABELS.try_lock().unwrap().push(brk.to_string());
+    if let Some(cont) = cont {
+        CONTINUE_LABELS.try_lock().unwrap().push(cont.to_string());
+    }
+}
+
+fn pop_jump_labels(has_continue: bool) {
+    let _ = BREAK_LABELS.try_lock().unwrap().pop();
+    if has_continue {
+        let _ = CONTINUE_LABELS.try_lock().unwrap().pop();
+    }
+}
+
+/// ソース上のラベルに対応するアセンブリのラベル(関数ごとに区別する)
+fn user_label(name: &str) -> String {
+    format!(".LLabel{}.{}", CURRENT_FUNC.try_lock().unwrap(), name)
 }
 
 /// アドレスを生成し、 rax に保存する
 fn gen_addr(node: &NodeRef) {
+    // 構造体・共用体を評価値とする文式は、評価値がそのままアドレスになる
+    if node.borrow().kind == Nodekind::StmtExpr && node.borrow().typ.as_ref().unwrap().is_struct() {
+        gen_expr(node);
+        return;
+    }
     let node = node.borrow();
     let kind = node.kind;
     match kind {
         Nodekind::Lvar => {
-            if node.is_local {
+            if let Some(init) = &node.init {
+                gen_expr(init);
+            }
+            if node.is_local && node.typ.as_ref().is_some_and(|typ| typ.is_vla()) {
+                // 可変長配列は、動的に確保した領域の先頭アドレスが変数に格納されている
+                let offset = node.offset.unwrap();
+                mov!("rax", format!("QWORD PTR [rbp-{}]", offset));
+            } else if node.is_local {
                 // 変数に対応するアドレスをスタックにプッシュする
                 let offset = node.offset.unwrap();
                 lea!("rax", "rbp", offset);
+            } else if node.is_tls {
+                gen_tls_addr(&asm_symbol(node.name.as_ref().unwrap()), node.is_extern);
             } else {
-                let name = node.name.clone().unwrap();
+                let name = asm_symbol(node.name.as_ref().unwrap());
                 mov_glb_addr!("rax", name);
             }
         }
@@ -507,24 +1046,256 @@ fn gen_addr(node: &NodeRef) {
             // *expr: exprで計算されたアドレスを返したいので直で gen_expr する(例えば&*のような書き方だと打ち消される)
             gen_expr(node.left.as_ref().unwrap());
         }
+        Nodekind::Member => {
+            // 構造体の先頭アドレスにメンバのオフセットを足す
+            gen_addr(node.left.as_ref().unwrap());
+            let offset = node.offset.unwrap();
+            if offset > 0 {
+                operate!("add", "rax", offset);
+            }
+        }
         _ => {
-            error_with_node!("左辺値が変数ではありません。", &*node);
+            error_with_node!("not-lvalue", "左辺値が変数ではありません。", &*node);
         }
     }
 }
 
+/// インラインアセンブリを出力する
+/// テンプレートは gcc と同様に AT&T 記法として扱い、その間だけ記法を切り替える
+/// 出力オペランドのアドレスをスタックに積んでから入力をレジスタに読み込み、テンプレートの後で出力をアドレスに書き戻す
+fn gen_inline_asm(node: &NodeRef) {
+    let node = node.borrow();
+    let asm = node.asm.as_ref().unwrap();
+    if asm.is_basic {
+        emit_asm_template(&unescape_asm(&asm.template));
+        return;
+    }
+    let operands = &asm.operands;
+    let exprs = &node.args;
+
+    // 各オペランドにレジスタを割り当てる(即値には割り当てない)
+    let mut used: Vec<&str> = operands
+        .iter()
+        .filter_map(|op| asm_fixed_register(&op.constraint))
+        .collect();
+    used.extend(asm.clobbers.iter().map(|c| c.as_str()));
+    let mut scratch = ASM_SCRATCH_REGISTERS
+        .iter()
+        .copied()
+        .filter(|reg| !used.contains(reg));
+    let mut regs: Vec<Option<&str>> = vec![];
+    for op in operands {
+        let reg = match op.constraint.as_str() {
+            "i" => None,
+            "r" | "m" => {
+                if let Some(reg) = scratch.next() {
+                    Some(reg)
+                } else {
+                    error_with_node!(
+                        "asm-operand",
+                        "インラインアセンブリのオペランドに割り当てるレジスタが足りません。",
+                        &*node
+                    );
+                }
+            }
+            c => asm_fixed_register(c).or_else(|| regs[c.parse::<usize>().unwrap()]),
+        };
+        regs.push(reg);
+    }
+
+    let saved: Vec<&str> = CALLEE_SAVED_REGISTERS
+        .iter()
+        .copied()
+        .filter(|reg| used.contains(reg))
+        .collect();
+    for reg in &saved {
+        operate!("push", reg);
+    }
+
+    // レジスタに書き戻す出力オペランドのアドレスを積んでおく
+    let outputs: Vec<usize> = (0..operands.len())
+        .filter(|&ix| operands[ix].is_output && operands[ix].constraint != "m")
+        .collect();
+    for &ix in &outputs {
+        gen_addr(&exprs[ix]);
+        operate!("push", "rax");
+    }
+
+    // レジスタに読み込む値(メモリの場合はアドレス)を積み、最後にまとめて各レジスタに取り出す
+    let mut loads: Vec<&str> = vec![];
+    for (j, &ix) in outputs.iter().enumerate() {
+        if operands[ix].is_inout {
+            let depth = outputs.len() - 1 - j + loads.len();
+            mov!("rax", format!("QWORD PTR [rsp+{}]", depth * 8));
+            load(exprs[ix].borrow().typ.as_ref().unwrap(), "[rax]");
+            operate!("push", "rax");
+            loads.push(regs[ix].unwrap());
+        }
+    }
+    for (ix, op) in operands.iter().enumerate() {
+        if op.constraint == "m" {
+            gen_addr(&exprs[ix]);
+        } else if op.is_output || op.constraint == "i" {
+            continue;
+        } else {
+            gen_expr(&exprs[ix]);
+        }
+        operate!("push", "rax");
+        loads.push(regs[ix].unwrap());
+    }
+    for reg in loads.iter().rev() {
+        operate!("pop", reg);
+    }
+
+    let template = unescape_asm(&asm.template);
+    emit_asm_template(&substitute_asm(&node, &template, &regs));
+
+    // 出力の値を積んでから、1つずつ取り出して対応するアドレスに書き込む
+    for &ix in &outputs {
+        operate!("push", regs[ix].unwrap());
+    }
+    for &ix in outputs.iter().rev() {
+        let bytes = exprs[ix].borrow().typ.as_ref().unwrap().value_bytes();
+        operate!("pop", "rax");
+        mov!(
+            "rdi",
+            format!("QWORD PTR [rsp+{}]", (outputs.len() - 1) * 8)
+        );
+        mov_to!(bytes, "rdi", reg_ax(bytes));
+    }
+    if !outputs.is_empty() {
+        operate!("add", "rsp", outputs.len() * 8);
+    }
+    for reg in saved.iter().rev() {
+        operate!("pop", reg);
+    }
+}
+
+/// テンプレートの中のオペランドの参照(%0, %[name], %k1 など)を置き換える
+fn substitute_asm(node: &Node, template: &str, regs: &[Option<&str>]) -> String {
+    let asm = node.asm.as_ref().unwrap();
+    let names: HashMap<&str, usize> = asm
+        .operands
+        .iter()
+        .enumerate()
+        .filter_map(|(ix, op)| op.name.as_deref().map(|name| (name, ix)))
+        .collect();
+    let id = get_ctrl_count();
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('%') => {
+                let _ = chars.next();
+                result.push('%');
+                continue;
+            }
+            Some('=') => {
+                // asm ごとに一意な番号
+                let _ = chars.next();
+                result += &id.to_string();
+                continue;
+            }
+            _ => {}
+        }
+        let modifier = chars.next_if(|c| "bwkqc".contains(*c));
+        let ix = if chars.next_if_eq(&'[').is_some() {
+            let name: String = chars.by_ref().take_while(|c| *c != ']').collect();
+            names.get(name.as_str()).copied()
+        } else {
+            let mut digits = String::new();
+            while let Some(d) = chars.next_if(|c| c.is_ascii_digit()) {
+                digits.push(d);
+            }
+            digits.parse::<usize>().ok()
+        };
+        let Some(ix) = ix.filter(|ix| *ix < regs.len()) else {
+            error_with_node!(
+                "asm-template",
+                "インラインアセンブリのテンプレートのオペランドの参照が不正です。",
+                node
+            );
+        };
+
+        let op = &asm.operands[ix];
+        if let Some(imm) = op.imm {
+            if modifier != Some('c') {
+                result.push('$');
+            }
+            result += &imm.to_string();
+            continue;
+        }
+        let reg = regs[ix].unwrap();
+        if op.constraint == "m" {
+            result += &format!("(%{})", reg);
+            continue;
+        }
+        let bytes = match modifier {
+            Some('b') => 1,
+            Some('w') => 2,
+            Some('k') => 4,
+            Some('q') => 8,
+            _ => node.args[ix].borrow().typ.as_ref().unwrap().value_bytes(),
+        };
+        result += &format!("%{}", sized_register(reg, bytes));
+    }
+    result
+}
+
+/// テンプレートの中のエスケープシーケンスを展開する
+fn unescape_asm(template: &str) -> String {
+    template
+        .replace("\\n", "\n")
+        .replace("\\t", "\t")
+        .replace("\\\\", "\\")
+}
+
+fn emit_asm_template(template: &str) {
+    asm_write!("\t.att_syntax");
+    for line in template.lines() {
+        asm_write!("\t{}", line.trim());
+    }
+    asm_write!("\t.intel_syntax noprefix");
+}
+
+/// スレッドローカルな変数のアドレスを rax に求める
+/// -fPIC の場合は __tls_get_addr を呼び出す general-dynamic モデルを使う
+/// そうでなければ、このソースで定義されたものは local-exec 、 extern 宣言のみのものは initial-exec モデルを使う
+fn gen_tls_addr(name: &str, is_extern: bool) {
+    if *PIC.try_lock().unwrap() {
+        // リンカが命令列を書き換えられるように、 lea から call までは決まった形で出力する
+        // call の時点で rsp が16の倍数になるように揃え、元の rsp はその直後に置く
+        mov!("rax", "rsp");
+        operate!("sub", "rsp", 8);
+        operate!("and", "rsp", "~0x0f");
+        asm_write!("\tmov QWORD PTR [rsp], rax");
+        asm_write!("\tdata16 lea rdi, {}@tlsgd[rip]", name);
+        asm_write!("\t.value 0x6666");
+        asm_write!("\trex64");
+        asm_write!("\tcall __tls_get_addr@PLT");
+        asm_write!("\tmov rsp, QWORD PTR [rsp]");
+    } else if is_extern {
+        mov!("rax", "QWORD PTR fs:0");
+        asm_write!("\tadd rax, QWORD PTR {}@gottpoff[rip]", name);
+    } else {
+        mov!("rax", "QWORD PTR fs:0");
+        asm_write!("\tadd rax, OFFSET FLAT:{}@tpoff", name);
+    }
+}
+
 /// 関数呼び出し時の引数の処理を行う
 fn push_args(args: &[NodeRef]) {
     let argc = args.len();
-    if argc > 6 {
-        exit_eprintln!("現在7つ以上の引数はサポートされていません。");
-    }
 
     // 計算時に rdi などを使う場合があるので、引数はまずはスタックに全て push したままにしておく
     // おそらく、逆順にしておいた方がスタックに引数を積みたくなった場合に都合が良い
     if argc != 0 {
         operate!("sub", "rsp", argc * 8);
-        for (i, arg ) in args.iter().enumerate() {
+        for (i, arg) in args.iter().enumerate() {
             gen_expr(arg);
             if i == 0 {
                 asm_write!("\tmov QWORD PTR[rsp], rax");
@@ -534,27 +1305,13 @@ fn push_args(args: &[NodeRef]) {
         }
     }
 
-    for (i , arg)in args.iter().enumerate() {
-        let typ = arg.borrow().typ.clone().unwrap();
-        let bytes = if typ.typ == Type::Array {
-            8
-        } else {
-            typ.bytes()
-        };
-        let arg_reg = ARGS_REGISTERS.try_lock().unwrap().get(&bytes).unwrap()[i];
-        let arg_reg_r = ARGS_REGISTERS.try_lock().unwrap().get(&8).unwrap()[i];
-        let ax = reg_ax(bytes);
-        operate!("pop", "rax");
-        // rax で push するために符号拡張する
-        match bytes {
-            4 => operate!("cdqe"),
-            b if b < 4 => movsx!("rax", "al"),
-            _ => {}
-        }
-        if bytes < 8 {
-            mov!(arg_reg_r, 0);
-        }
-        mov!(arg_reg, ax);
+    // 6つ目までをレジスタに移す(7つ目以降はスタックの先頭から順に並んだままになる)
+    // 引数の値は型に従って64ビット全体に拡張されているので、そのまま64ビットのレジスタに移せば良い
+    for arg_reg in ARGS_REGISTERS.try_lock().unwrap()[&8]
+        .iter()
+        .take(argc.min(6))
+    {
+        operate!("pop", arg_reg);
     }
 }
 
@@ -592,24 +1349,16 @@ fn zero_clear(mut offset: usize, mut bytes: usize) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::globals::{FILE_NAMES, SRC};
+    use crate::globals::tests::test_init;
     use crate::parser::{expr, parse, tests::parse_stmts};
     use crate::tokenizer::tokenize;
 
-    fn test_init(src: &str) {
-        let mut src_: Vec<String> = src.split('\n').map(|s| s.to_string() + "\n").collect();
-        FILE_NAMES.try_lock().unwrap().push("test".to_string());
-        let mut code = vec!["".to_string()];
-        code.append(&mut src_);
-        SRC.try_lock().unwrap().push(code);
-    }
-
     #[test]
     fn addsub() {
         let src: &str = "
 			1+2+3-1
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_ptr = expr(&mut token_ptr);
@@ -622,7 +1371,7 @@ mod tests {
         let src: &str = "
 			1+2*3-4/2+3%2
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_ptr = expr(&mut token_ptr);
@@ -635,7 +1384,7 @@ mod tests {
         let src: &str = "
 			(1+2)/3-1*20
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_ptr = expr(&mut token_ptr);
@@ -648,7 +1397,7 @@ mod tests {
         let src: &str = "
 			(-1+2)*(-1)+(+3)/(+1)
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_ptr = expr(&mut token_ptr);
@@ -659,26 +1408,30 @@ mod tests {
     #[test]
     fn shift() {
         let src: &str = "
-			200 % 3 << 4 + 8 >> 8
+			200 % 3 << 4 + 8 >> 8;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_ptr = expr(&mut token_ptr);
-        gen_expr(&node_ptr);
+        let node_heads = parse_stmts(&mut token_ptr);
+        for node_ptr in node_heads {
+            gen_expr(&node_ptr);
+        }
         println!("{}", ASMCODE.try_lock().unwrap());
     }
 
     #[test]
     fn eq() {
         let src: &str = "
-			(-1+2)*(-1)+(+3)/(+1) == 30 + 1
+			(-1+2)*(-1)+(+3)/(+1) == 30 + 1;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_ptr = expr(&mut token_ptr);
-        gen_expr(&node_ptr);
+        let node_heads = parse_stmts(&mut token_ptr);
+        for node_ptr in node_heads {
+            gen_expr(&node_ptr);
+        }
         println!("{}", ASMCODE.try_lock().unwrap());
     }
 
@@ -688,7 +1441,7 @@ mod tests {
 			int a;
 			a = 1; a + 1;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -704,7 +1457,7 @@ mod tests {
 			int local, local_value, local_value99;
 			local = 1; local_value = local + 1; local_value99 = local_value + 3;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -717,17 +1470,17 @@ mod tests {
     #[test]
     fn bitops() {
         let src: &str = "
-			int x, y;
+			int x, *y;
 			2 + (3 + 5) * 6;
 			1 ^ 2 | 2 != 3 / 2;
 			1 + -1 ^ 2;
 			3 ^ 2 & 1 | 2 & 9;
 			x = 10;
 			y = &x;
-			3 ^ 2 & *y | 2 & &x;
+			3 ^ 2 & *y | 2 & x;
 			~x ^ ~*y | 2;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -746,7 +1499,7 @@ mod tests {
 			z = 20;
 			q = !x && !!y - z || 0;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -762,7 +1515,7 @@ mod tests {
 			int x, y, z;
 			x = 10, y = 10, z = 10;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -775,12 +1528,12 @@ mod tests {
     #[test]
     fn if_() {
         let src: &str = "
-			int i;
+			int i, x;
 			i = 10;
 			if (1) i + 1;
 			x = i + 10;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -798,7 +1551,7 @@ mod tests {
 			while (i > 1) i = i - 1;
 			i;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -816,7 +1569,7 @@ mod tests {
 			for (i = 0; i < 10; i = i + 1) sum = sum + i;
 			return sum;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -839,7 +1592,7 @@ mod tests {
 			return sum;
 			return;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -859,7 +1612,7 @@ mod tests {
 			k = get(i+j, (i=3), k);
 			return i + j;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -872,13 +1625,13 @@ mod tests {
     #[test]
     fn addr_deref() {
         let src: &str = "
-			int x, y, z;
+			int x, y, *z;
 			x = 3;
 			y = 5;
 			z = &y + 8;
 			return *z;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -891,13 +1644,13 @@ mod tests {
     #[test]
     fn addr_deref2() {
         let src: &str = "
-			int x, y, z;
+			int x, *y, **z;
 			x = 3;
 			y = &x;
 			z = &y;
 			return *&**z;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = parse_stmts(&mut token_ptr);
@@ -926,7 +1679,7 @@ mod tests {
 				return func(i, sum);
 			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let head = tokenize(0);
         let trees = parse(head);
@@ -944,7 +1697,7 @@ mod tests {
 				return fib(10);
 			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let head = tokenize(0);
         let trees = parse(head);
@@ -962,7 +1715,333 @@ mod tests {
 				return 0;
 			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn jump() {
+        let src: &str = "
+			int main() {
+				int sum = 0, i = 0;
+				for (i = 0; i < 10; i++) {
+					if (i == 3) continue;
+					if (i == 8) break;
+					sum += i;
+				}
+				do {
+					i--;
+					switch (i) {
+					case 1:
+						continue;
+					case 2:
+					case 3:
+						sum++;
+						break;
+					default:
+						goto end;
+					}
+				} while (i > 0);
+			end:
+				return sum;
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn storage() {
+        let src: &str = "
+			int tentative;
+			int tentative;
+			static int hidden = 3;
+			static int twice(int x) { return x * 2; }
+			int counter() {
+				static int c;
+				return ++c;
+			}
+			int main() {
+				extern int tentative;
+				counter();
+				return twice(hidden) + counter() + tentative;
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn variadic() {
+        let src: &str = "
+			int sum(int n, ...) {
+				va_list ap;
+				int s = 0;
+				va_start(ap, n);
+				while (n--) s += va_arg(ap, int);
+				va_end(ap);
+				return s;
+			}
+			int main() {
+				return sum(5, 1, 2, 3, 4, 5);
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn many_args() {
+        let src: &str = "
+			int many(int a, int b, int c, int d, int e, int f, int g, char h, int i) {
+				return a + b + c + d + e + f + g + h + i;
+			}
+			int main() {
+				int x = 7;
+				return many(1, 2, 3, 4, 5, 6, x, 8, many(1, 1, 1, 1, 1, 1, 1, 1, 1));
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn frame_align() {
+        let src: &str = "
+			int f(int a, int b, int c, int d, int e, int f, int g) {
+				_Alignas(32) char x = 1;
+				__attribute__((aligned(64))) int y = g;
+				return x + y;
+			}
+			int main() {
+				return f(1, 2, 3, 4, 5, 6, 7);
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        let asm = ASMCODE.try_lock().unwrap();
+        println!("{}", asm);
+        // 揃え直す前の rsp を保持し、スタックで渡された引数とエピローグではその値を使う
+        assert!(asm.contains("\tmov r11, rsp\n\tand rsp, -64\n\tmov rbp, rsp\n"));
+        assert!(asm.contains("\tmov QWORD PTR [rbp-80], r11\n"));
+        assert!(asm.contains("\tmov rax, QWORD PTR [rbp-80]\n\tmov rax, QWORD PTR [rax+16]\n"));
+        assert!(asm.contains("\tmov rsp, QWORD PTR [rbp-80]\n\tpop rbp\n"));
+        assert_eq!(asm.matches("and rsp, -").count(), 1);
+    }
+
+    #[test]
+    fn integer_types() {
+        let src: &str = "
+			int main() {
+				unsigned u = -1;
+				short s = -2;
+				unsigned char c = 255;
+				long l = u;
+				return (u / 3 > s) + (u >> 1) + (l % c) + ~c;
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn vla() {
+        let src: &str = "
+			int sum(int *p, int n) {
+				int s = 0;
+				for (int i = 0; i < n; i++) s += p[i];
+				return s;
+			}
+			int main() {
+				int n = 3, s = 0;
+				for (int i = 0; i < n; i++) {
+					int a[n][i + 1];
+					a[n - 1][i] = i;
+					s += a[n - 1][i] + sizeof a;
+				}
+				int *p = __builtin_alloca(n * 4);
+				return s + sum(p, (p[0] = 1, n));
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn struct_member() {
+        let src: &str = "
+			struct node { char tag; struct node *next; int val[2]; };
+			int main() {
+				struct node a, b;
+				a.next = &b;
+				a.next->val[1] = 3;
+				b = a;
+				return (char) b.next->val[1];
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn attributes() {
+        let src: &str = "
+			int weak_fn(void) __attribute__((weak));
+			int weak_fn(void) { return 1; }
+			extern int undef __attribute__((weak));
+			int sec __attribute__((section(\".mydata\"))) = 2;
+			const int rosec __attribute__((section(\".myro\"))) = 3;
+			__attribute__((section(\".mytext\"))) int sec_fn(void) { return sec + rosec; }
+			long aligned __attribute__((aligned(32)));
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn stmt_expr() {
+        let src: &str = "
+			int main() {
+				int n = 4;
+				int x = 1 + ({ int a[n]; a[n - 1] = 5; a[n - 1]; }) * 2;
+				return ({ int y = x; y + 1; });
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn inline_asm() {
+        let src: &str = "
+			asm(\".globl answer\\nanswer:\\n\\tmovl $42, %eax\\n\\tret\");
+			long counter;
+			int main() {
+				int a = 5, b;
+				unsigned eax, ebx;
+				asm volatile(\"cpuid\" : \"=a\"(eax), \"=b\"(ebx) : \"a\"(0) : \"rcx\", \"rdx\");
+				asm(\"movl %1, %0\\n\\taddl %2, %0\" : \"=r\"(b) : \"r\"(a), \"i\"(3));
+				asm(\"shll %b1, %[x]\" : [x] \"+r\"(b) : \"c\"(2));
+				asm(\"incq %0\" : \"+m\"(counter));
+				asm(\"jmp .L%=\\n.L%=:\" : \"=r\"(a) : \"0\"(a));
+				return b;
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn thread_local() {
+        let src: &str = "
+			__thread int a;
+			_Thread_local long b = 5;
+			extern __thread int c;
+			int *f(void) { static __thread int s = 3; return &s; }
+			int g(void) { a = 1; return a + b + c; }
+		";
+        for pic in [false, true] {
+            let _lock = test_init(src);
+            *PIC.try_lock().unwrap() = pic;
+
+            let head = tokenize(0);
+            let trees = parse(head);
+            generate(trees);
+            println!("{}", ASMCODE.try_lock().unwrap());
+        }
+    }
+
+    #[test]
+    fn atomic() {
+        let src: &str = "
+			_Atomic int counter;
+			_Atomic unsigned char flags;
+			int main() {
+				int x = 0, e = 0;
+				counter = 1;
+				counter *= 3;
+				flags |= 4;
+				__atomic_store_n(&x, 1, 5);
+				__atomic_compare_exchange_n(&x, &e, 2, 0, 5, 5);
+				__sync_synchronize();
+				return __atomic_load_n(&x, 5) + __atomic_fetch_add(&x, 3, 5);
+			}
+		";
+        let _lock = test_init(src);
+
+        let head = tokenize(0);
+        let trees = parse(head);
+        generate(trees);
+        println!("{}", ASMCODE.try_lock().unwrap());
+    }
+
+    #[test]
+    fn builtins() {
+        let src: &str = "
+			struct S { char c; long l; int a[4]; };
+			int tab[__builtin_offsetof(struct S, a[2])];
+			int f(int x) {
+				if (x) return 1;
+				__builtin_unreachable();
+			}
+			int main() {
+				char buf[8];
+				unsigned u = 40;
+				if (__builtin_expect(u > 3, 1))
+					__builtin_memcpy(buf, \"builtin\", 8);
+				if (!__builtin_constant_p(3 * 4))
+					__builtin_trap();
+				return __builtin_popcount(u) + __builtin_clz(u) + __builtin_ctzl(u) + __builtin_bswap32(u) + __builtin_bswap16(u);
+			}
+		";
+        let _lock = test_init(src);
 
         let head = tokenize(0);
         let trees = parse(head);
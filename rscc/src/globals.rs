@@ -1,6 +1,54 @@
-use std::sync::Mutex;
+use std::sync::{self, MutexGuard, TryLockError, TryLockResult};
 
 use once_cell::sync::Lazy;
 
+/// コンパイルエラーによる unwind でロックが poison されても、そのまま使い続けられる Mutex
+/// (中断したコンパイルの状態は次の compile_src の開始時にリセットされる)
+pub struct Mutex<T>(sync::Mutex<T>);
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Mutex<T> {
+        Mutex(sync::Mutex::new(value))
+    }
+
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        match self.0.try_lock() {
+            Err(TryLockError::Poisoned(e)) => Ok(e.into_inner()),
+            result => result,
+        }
+    }
+}
+
 pub static SRC: Lazy<Mutex<Vec<Vec<String>>>> = Lazy::new(|| Mutex::new(vec![]));
 pub static FILE_NAMES: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(vec![]));
+/// 位置独立コード(-fPIC)を出力するかどうか
+pub static PIC: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    use super::{FILE_NAMES, PIC, SRC};
+
+    /// 各テストはグローバル変数を共有するため、このロックを取って1つずつ実行する
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    /// ロックを取った上で、前のテストで使用したグローバル変数の状態をクリアする
+    pub fn test_lock() -> MutexGuard<'static, ()> {
+        let guard = TEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        crate::clear_state();
+        *PIC.try_lock().unwrap() = false;
+        guard
+    }
+
+    /// src を0番目のファイルとして読み込ませる
+    pub fn test_init(src: &str) -> MutexGuard<'static, ()> {
+        let guard = test_lock();
+        let mut src_: Vec<String> = src.split('\n').map(|s| s.to_string() + "\n").collect();
+        FILE_NAMES.try_lock().unwrap().push("test".to_string());
+        let mut code = vec!["".to_string()];
+        code.append(&mut src_);
+        SRC.try_lock().unwrap().push(code);
+        guard
+    }
+}
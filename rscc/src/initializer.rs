@@ -1,72 +1,154 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::{node::NodeRef, typecell::TypeCell};
+use crate::{
+    node::NodeRef,
+    typecell::{Type, TypeCell},
+};
 
 pub type InitializerRef = Rc<RefCell<Initializer>>;
 
+/// 初期化される変数の型と同じ形をした木
+/// 配列であれば要素数分、構造体・共用体であればメンバ数分の子を持ち、指示子によって任意の位置の子を後から上書きできる
 #[derive(Clone, Debug, Default)]
 pub struct Initializer {
-    pub node: Option<NodeRef>,         // 初期化する値に対応する式
-    pub typ: Option<TypeCell>,         // タイプ
-    pub elements: Vec<InitializerRef>, // 配列の各要素
-    pub is_literal: bool,              // リテラルに起因する char[] の場合のみ使用する
+    pub node: Option<NodeRef>, // スカラ(または構造体・共用体の値全体)の初期化式(None の場合は0で初期化される)
+    pub typ: Option<TypeCell>, // タイプ
+    pub elements: Vec<InitializerRef>, // 配列の各要素・構造体の各メンバ(要素数の指定がない配列では初期化子に合わせて伸びる)
 }
 
 impl Initializer {
-    #[inline]
-    pub fn new(typ: TypeCell, node: NodeRef) -> Self {
+    /// 型 typ の形に合わせて、まだ何も初期化されていない Initializer を作る
+    pub fn new(typ: TypeCell) -> Self {
+        let mut elements = vec![];
+        if let Some(size) = typ.array_size {
+            let elem_typ = typ.make_deref().unwrap();
+            for _ in 0..size {
+                elements.push(Rc::new(RefCell::new(Initializer::new(elem_typ.clone()))));
+            }
+        } else if typ.is_struct() {
+            for member in &typ.struct_info.as_ref().unwrap().borrow().members {
+                // フレキシブル配列メンバは要素数0の配列として扱い、初期化できないようにする
+                let mut member_typ = member.typ.clone();
+                if member_typ.is_flex_array() {
+                    let _ = member_typ.array_size.insert(0);
+                }
+                elements.push(Rc::new(RefCell::new(Initializer::new(member_typ))));
+            }
+        }
         Initializer {
-            node: Some(node),
             typ: Some(typ),
+            elements,
             ..Default::default()
         }
     }
 
     #[inline]
-    pub fn insert(&mut self, typ: TypeCell, node: NodeRef) {
-        let _ = self.typ.insert(typ);
-        let _ = self.node.insert(node);
+    pub fn is_flex(&self) -> bool {
+        self.typ.as_ref().unwrap().is_flex_array()
     }
 
     #[inline]
-    pub fn push_element(&mut self, elem: Initializer) {
-        self.elements.push(Rc::new(RefCell::new(elem)));
+    pub fn is_union(&self) -> bool {
+        self.typ.as_ref().unwrap().typ == Type::Union
     }
 
+    /// 中括弧の中で位置を指定せずに初期化できる要素の数(共用体は最初のメンバのみ)
     #[inline]
-    pub fn append_elements(&mut self, elem: &Initializer) {
-        self.elements.append(&mut elem.elements.clone());
+    pub fn capacity(&self) -> usize {
+        if self.is_union() {
+            self.elements.len().min(1)
+        } else {
+            self.elements.len()
+        }
     }
 
-    #[inline]
-    pub fn is_element(&self) -> bool {
-        self.elements.is_empty()
+    /// ix 番目の要素を取得する(要素数の指定がない配列の場合は必要に応じて要素を増やす)
+    /// 共用体は1つのメンバしか初期化できないため、他のメンバの初期化子は捨てる
+    pub fn element(&mut self, ix: usize) -> InitializerRef {
+        if self.is_flex() {
+            let elem_typ = self.typ.as_ref().unwrap().make_deref().unwrap();
+            while self.elements.len() <= ix {
+                self.elements
+                    .push(Rc::new(RefCell::new(Initializer::new(elem_typ.clone()))));
+            }
+        }
+        if self.is_union() {
+            for (i, elem) in self.elements.iter().enumerate() {
+                if i != ix {
+                    let typ = elem.borrow().typ.clone().unwrap();
+                    *elem.borrow_mut() = Initializer::new(typ);
+                }
+            }
+        }
+        Rc::clone(&self.elements[ix])
+    }
+
+    /// 初期化式が1つでも指定されているかどうか
+    pub fn is_initialized(&self) -> bool {
+        self.node.is_some()
+            || self
+                .elements
+                .iter()
+                .any(|elem| elem.borrow().is_initialized())
+    }
+
+    /// 式のノードは共有したまま、木の構造を複製する
+    pub fn deep_clone(&self) -> Self {
+        Initializer {
+            node: self.node.clone(),
+            typ: self.typ.clone(),
+            elements: self
+                .elements
+                .iter()
+                .map(|elem| Rc::new(RefCell::new(elem.borrow().deep_clone())))
+                .collect(),
+        }
+    }
+
+    /// other で初期化式が指定されている要素のみを上書きする
+    pub fn overlay(&mut self, other: &Initializer) {
+        if let Some(node) = &other.node {
+            let _ = self.node.insert(Rc::clone(node));
+        }
+        for (ix, other_elem) in other.elements.iter().enumerate() {
+            if self.is_union() && !other_elem.borrow().is_initialized() {
+                continue;
+            }
+            self.element(ix).borrow_mut().overlay(&other_elem.borrow());
+        }
     }
 
-    // 配列サイズを指定していない場合のサイズ特定を行う
-    // parser::make_lvar_init と似たような処理
+    /// 要素数を指定していない配列の要素数(初期化された最後の要素まで)
+    #[inline]
     pub fn flex_elem_count(&self) -> usize {
-        if self.is_element() {
-            panic!("invalid function on elemental initializer");
+        if !self.is_flex() {
+            panic!("invalid function on non-flexible initializer");
         }
-        let elem_flatten_size = self
-            .typ
-            .as_ref()
-            .unwrap()
-            .make_deref()
-            .unwrap()
-            .flatten_size();
-        let mut count = 0;
-        let mut ix = 0;
-        while ix < self.elements.len() {
-            ix += if self.elements[ix].borrow().is_element() {
-                elem_flatten_size
-            } else {
-                1
-            };
-            count += 1;
+        self.elements.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shape_test() {
+        let typ = TypeCell::new(Type::Int).make_array_of(3).make_array_of(2);
+        let init = Initializer::new(typ);
+        assert_eq!(init.elements.len(), 2);
+        for elem in &init.elements {
+            assert_eq!(elem.borrow().elements.len(), 3);
+            assert!(elem.borrow().elements[0].borrow().node.is_none());
         }
-        count
+
+        let mut flex = TypeCell::new(Type::Char).make_array_of(4).make_array_of(0);
+        flex.array_size = None;
+        let mut init = Initializer::new(flex);
+        assert_eq!(init.flex_elem_count(), 0);
+        let _ = init.element(5);
+        assert_eq!(init.flex_elem_count(), 6);
+        assert_eq!(init.elements[5].borrow().elements.len(), 4);
     }
 }
@@ -1,48 +1,119 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, IsTerminal};
+use std::process;
 
 use clap::Clap;
 
 mod asm;
+mod diagnostic;
 mod generator;
 mod globals;
 mod initializer;
 mod node;
 mod options;
 mod parser;
+mod semantics;
 mod token;
 mod tokenizer;
 mod typecell;
 mod utils;
 use asm::ASMCODE;
+use diagnostic::{bail, catch_all, has_errors};
+pub use diagnostic::{take_diagnostics, to_json, to_sarif, Diagnostic, Severity, Span};
 use generator::generate;
-use globals::{FILE_NAMES, SRC};
+use globals::{FILE_NAMES, PIC, SRC};
 use options::Opts;
 use parser::parse;
 use tokenizer::tokenize;
-use utils::{LIGHTBLUE, RED};
 
 pub fn compile() -> String {
     let opts = Opts::parse();
-    if let Some(path) = opts.input_file {
-        match compile_src(path.as_str()) {
-            Ok(asm) => asm,
-            Err(err) => {
-                exit_eprintln!("{:#?}", err);
+    let color = match opts.color.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => io::stderr().is_terminal(),
+    };
+    let emit = |diags: &[Diagnostic]| emit_diagnostics(diags, &opts.diagnostics_format, color);
+
+    let mut errors = vec![];
+    for flag in &opts.flags {
+        match flag.as_str() {
+            "PIC" | "pic" => *PIC.try_lock().unwrap() = true,
+            "no-PIC" | "no-pic" => *PIC.try_lock().unwrap() = false,
+            _ => errors.push(Diagnostic::error(
+                "option",
+                format!("不明なオプション\"-f{}\"が指定されています。", flag),
+            )),
+        }
+    }
+    if opts.input_file.is_none() {
+        errors.push(Diagnostic::error(
+            "option",
+            "ソースファイルを指定してください。",
+        ));
+    }
+    if !errors.is_empty() {
+        emit(&errors);
+        process::exit(1);
+    }
+
+    match compile_src(opts.input_file.as_deref().unwrap()) {
+        Ok(asm) => {
+            emit(&take_diagnostics());
+            asm
+        }
+        Err(diags) => {
+            emit(&diags);
+            process::exit(1);
+        }
+    }
+}
+
+/// 診断を format で指定された形式で標準エラー出力に表示する
+/// json, sarif は診断がなくても空の結果を出力し、 color は human の場合にのみ使う
+fn emit_diagnostics(diags: &[Diagnostic], format: &str, color: bool) {
+    match format {
+        "json" => eprint!("{}", to_json(diags)),
+        "sarif" => eprint!("{}", to_sarif(diags)),
+        _ => {
+            for diag in diags {
+                eprint!("{}", diag.render(color));
             }
         }
-    } else {
-        eprint!("\x1b[{}mrscc: \x1b[m\x1b[{}mError\x1b[m", LIGHTBLUE, RED);
-        exit_eprintln!(" - ソースファイルを指定してください。");
     }
 }
 
-pub fn compile_src(path: &str) -> std::io::Result<String> {
-    let f: File = File::open(path)?;
+/// path のファイルをコンパイルし、アセンブリを返す
+/// エラーがあればそれまでに発生した全ての診断を返す(成功時の警告は take_diagnostics で取り出せる)
+/// コンパイラの状態はグローバル変数で共有しているため、複数のスレッドから同時に呼ぶことはできない
+pub fn compile_src(path: &str) -> Result<String, Vec<Diagnostic>> {
+    clear_state();
+    let f: File = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(vec![Diagnostic::error(
+                "io",
+                format!("ファイル\"{}\"を開けません。({})", path, e),
+            )])
+        }
+    };
     let reader: BufReader<File> = BufReader::new(f);
     code_load(reader, path);
-    run();
-    Ok(ASMCODE.try_lock().unwrap().drain(..).collect::<String>())
+
+    match catch_all(run) {
+        Some(()) => Ok(ASMCODE.try_lock().unwrap().drain(..).collect::<String>()),
+        None => Err(take_diagnostics()),
+    }
+}
+
+/// 前回のコンパイルで使用したグローバル変数の状態をクリアする
+fn clear_state() {
+    SRC.try_lock().unwrap().clear();
+    FILE_NAMES.try_lock().unwrap().clear();
+    asm::clear_state();
+    parser::clear_state();
+    generator::clear_state();
+    diagnostic::clear_state();
 }
 
 /// ファイルの情報を、グローバル変数の SRC と FILE_NAME に渡す
@@ -59,19 +130,33 @@ fn code_load(reader: BufReader<File>, file_name: impl Into<String>) {
 fn run() {
     let head = tokenize(0);
     let trees = parse(head);
+    // パーサはエラーから復帰して読み進めるので、エラーがあればここで中断する
+    if has_errors() {
+        bail();
+    }
     generate(trees);
 }
 
 #[cfg(test)]
 mod tests {
-    use std::fs::File;
+    use std::fs::{self, File};
     use std::io::BufReader;
 
-    use super::code_load;
-    use crate::globals::{FILE_NAMES, SRC};
+    use super::{code_load, compile_src, take_diagnostics, Severity};
+    use crate::globals::{tests::test_lock, FILE_NAMES, SRC};
+
+    /// src を一時ファイルに書き出して compile_src に渡す
+    fn compile_str(name: &str, src: &str) -> Result<String, Vec<super::Diagnostic>> {
+        let path = std::env::temp_dir().join(format!("rscc_{}_{}.c", name, std::process::id()));
+        fs::write(&path, src).unwrap();
+        let result = compile_src(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        result
+    }
 
     #[test]
     fn code_load_test() {
+        let _lock = test_lock();
         let path = "./csrc/loadtest.c";
         let f: File = File::open(path).unwrap();
         let reader: BufReader<File> = BufReader::new(f);
@@ -84,4 +169,245 @@ mod tests {
         assert_eq!(filenames.len(), 1);
         assert_eq!(filenames[0], path);
     }
+
+    #[test]
+    fn compile_src_error() {
+        let _lock = test_lock();
+        let diags = compile_str(
+            "error",
+            "int f() {}\nint main() {\n\tint a = 1\n\treturn a;\n}",
+        )
+        .unwrap_err();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].severity, Severity::Warning);
+        assert_eq!(diags[0].code, "return-type");
+        assert_eq!(diags[1].severity, Severity::Error);
+        assert_eq!(diags[1].code, "expected-token");
+        let span = diags[1].span.as_ref().unwrap();
+        assert_eq!((span.line, span.column, span.len), (4, 1, 6));
+        assert_eq!(diags[1].fixits.len(), 1);
+        assert_eq!(diags[1].fixits[0].1, ";");
+
+        // default があり break で抜けない switch 文や、 break も continue もない do-while 文の後には戻らない
+        let diags = compile_str(
+            "fall_through",
+            "int f(int x) {\n\tswitch (x) {\n\tcase 1: return 1;\n\tdefault: return 0;\n\t}\n}\n\
+             int g(int x) {\n\tdo { return x; } while (x);\n}\n\
+             int h(int x) {\n\tswitch (x) {\n\tcase 1: return 1;\n\t}\n}\n\
+             int i(int x) {\n\tswitch (x) {\n\tdefault: break;\n\t}\n\treturn 0;\n}\n\
+             int j(int x) {\n\tdo { if (x) continue; return 0; } while (x);\n}\n\
+             int main() { return 0 }",
+        )
+        .unwrap_err();
+        let lines = diags
+            .iter()
+            .map(|d| (d.code, d.span.as_ref().unwrap().line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                ("return-type", 14),
+                ("return-type", 23),
+                ("expected-token", 24)
+            ]
+        );
+
+        // 前回のエラーの状態は持ち越されない
+        let diags = compile_str(
+            "redefined",
+            "int x = 1;\nint x = 2;\nint main() { return x; }",
+        )
+        .unwrap_err();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].labels.len(), 1);
+        assert_eq!(diags[0].labels[0].0.line, 1);
+
+        let diags = compile_str("lex", "int main() { return 0 @ 1; }").unwrap_err();
+        assert_eq!(diags[0].code, "invalid-token");
+
+        // return の値は戻り値の型へ代入するのと同様にチェックされる
+        let diags = compile_str(
+            "return",
+            "int *f(const int *p) {\n\treturn p;\n}\nint main() { return 0; }",
+        )
+        .unwrap_err();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, "discarded-qualifiers");
+        assert_eq!(diags[0].span.as_ref().unwrap().line, 2);
+
+        // コンパイラ内部で panic していた入力も、位置付きのエラーとして報告される
+        let diags = compile_str("literal", "int main() { return 4294967296; }").unwrap_err();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, "literal-out-of-range");
+        assert_eq!(diags[0].span.as_ref().unwrap().column, 20);
+        let diags = compile_str(
+            "designator",
+            "int f(void) { return 1; }\nint main() {\n\tint x = f + 1;\n\treturn x;\n}",
+        )
+        .unwrap_err();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, "unsupported");
+        assert_eq!(diags[0].span.as_ref().unwrap().line, 3);
+
+        // 配列への複合代入や ++, -- も、元の左辺値の位置で報告される
+        let diags = compile_str(
+            "array_modify",
+            "int main() {\n\tint a[2];\n\ta++;\n\t--a;\n\ta += 1;\n\treturn 0;\n}",
+        )
+        .unwrap_err();
+        let lines: Vec<(&str, usize)> = diags
+            .iter()
+            .map(|d| (d.code, d.span.as_ref().unwrap().line))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("not-assignable", 3),
+                ("not-assignable", 4),
+                ("not-assignable", 5)
+            ]
+        );
+
+        // 定数式の0除算は右辺の位置で報告し、溢れる計算は折り返した値とする
+        for (src, column) in [("int g[1 / 0];", 10), ("static int x = 3 % (1 - 1);", 22)] {
+            let diags = compile_str("div", src).unwrap_err();
+            assert_eq!(diags.len(), 1);
+            assert_eq!(diags[0].code, "division-by-zero");
+            assert_eq!(diags[0].span.as_ref().unwrap().column, column);
+        }
+        let asm = compile_str(
+            "overflow",
+            "int g = 2147483647 * 2147483647 * 4;\nint h = 1 << 70;\nint k = 1 >> -1;\nint main() { return 0; }",
+        )
+        .unwrap();
+        assert!(asm.contains("g:\n\t.long 4\n"));
+        assert!(asm.contains("h:\n\t.zero 4\n"));
+        assert!(asm.contains("k:\n\t.zero 4\n"));
+
+        // 可変長配列の実体は16の倍数に揃えて確保するため、それより大きいアラインメントは指定できない
+        let diags = compile_str(
+            "vla_align",
+            "int main(int n) {\n\t_Alignas(32) int v[n];\n\treturn 0;\n}",
+        )
+        .unwrap_err();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, "invalid-alignment");
+
+        // 要素数の分からない配列の大きさは求められない
+        for src in [
+            "int main() { return sizeof(int[]); }",
+            "extern int t[];\nint main() { return sizeof t; }",
+            "int main() {\n\tint (*p)[];\n\treturn sizeof *p;\n}",
+        ] {
+            let diags = compile_str("unsized", src).unwrap_err();
+            assert_eq!(diags.len(), 1);
+            assert_eq!(diags[0].code, "incomplete-type");
+        }
+
+        let diags = compile_src("./csrc/not_exist.c").unwrap_err();
+        assert_eq!(diags[0].code, "io");
+        assert!(diags[0].span.is_none());
+    }
+
+    #[test]
+    fn compile_src_warning() {
+        let _lock = test_lock();
+        let asm = compile_str(
+            "warning",
+            "int main() { return g(); }\nint g() { return 3; }",
+        );
+        assert!(asm.unwrap().contains("main:"));
+        let diags = take_diagnostics();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, "implicit-function-declaration");
+        assert_eq!(diags[0].notes.len(), 1);
+
+        // 終端の '\0' のみが収まらない場合は警告しない
+        let asm = compile_str(
+            "excess",
+            "char s[3] = \"abcdef\";\nchar t[3] = \"abc\";\nint main() { return 0; }",
+        );
+        assert!(asm.is_ok());
+        let diags = take_diagnostics();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, "excess-initializers");
+        let span = diags[0].span.as_ref().unwrap();
+        assert_eq!((span.line, span.column, span.len), (1, 12, 8));
+    }
+
+    #[test]
+    fn compile_src_recovery() {
+        let _lock = test_lock();
+        let diags = compile_str(
+            "recovery",
+            "int f(int x) {\n\tint y = x * 2\n\treturn y;\n}\n\
+             int g(int x) {\n\tretrun x + 1;\n}\n\
+             int h(int x) {\n\treturn x + undefined_var + undefined_var;\n}\n\
+             int main() { return f(1) + g(2) + h(3); }",
+        )
+        .unwrap_err();
+        let lines = diags
+            .iter()
+            .map(|d| (d.severity, d.span.as_ref().unwrap().line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                (Severity::Error, 3),
+                (Severity::Error, 6),
+                (Severity::Error, 9)
+            ]
+        );
+
+        // エラーで読み飛ばした宣言の名前を使っても、連鎖したエラーは報告しない
+        let diags = compile_str(
+            "cascade",
+            "int a[2] = {1 2};\nint main() {\n\tif (a[0]) { a[1] = ; } else { goto out; }\n\
+             \tint b = a[1];\n\treturn b;\n\treturn 1 +;\n}",
+        )
+        .unwrap_err();
+        let lines = diags
+            .iter()
+            .map(|d| d.span.as_ref().unwrap().line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![1, 6]);
+
+        // then 節の文のエラーから復帰する場合は、続く else 節も読み飛ばす
+        let diags = compile_str(
+            "dangling_else",
+            "int main() {\n\tint x = 1;\n\tif (x) y = 2; else x = 3;\n\
+             \tif (x) x = ; else x = 3;\n\treturn x +;\n}",
+        )
+        .unwrap_err();
+        let lines = diags
+            .iter()
+            .map(|d| d.span.as_ref().unwrap().line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![3, 4, 5]);
+
+        // 閉じていない "(" があっても、外側のブロックを閉じる "}" は読み飛ばさない
+        let diags = compile_str(
+            "unclosed_paren",
+            "int k(void) {\n\twhile (1 { }\n\treturn 0;\n}\nint main() { return 0; }",
+        )
+        .unwrap_err();
+        let lines = diags
+            .iter()
+            .map(|d| d.span.as_ref().unwrap().line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![2]);
+
+        // メンバの宣言でエラーが発生しても、読んだメンバで構造体を完全型にして不完全型のエラーを連鎖させない
+        let diags = compile_str(
+            "struct_member",
+            "struct S { int a; int b };\nstruct S s;\n\
+             int main() { struct T { int x y; } t; t.x = 1; s.b = 2; return s.a + sizeof(struct S); }",
+        )
+        .unwrap_err();
+        let lines = diags
+            .iter()
+            .map(|d| (d.code, d.span.as_ref().unwrap().line))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![("expected-token", 1), ("expected-token", 3)]);
+    }
 }
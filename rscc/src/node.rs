@@ -11,41 +11,66 @@ pub type NodeRef = Rc<RefCell<Node>>;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Nodekind {
-    Default, // defalut
-    Add,     // '+'
-    Sub,     // '-'
-    Mul,     // '*'
-    Div,     // '/'
-    Mod,     // '%'
-    LShift,  // "<<"
-    RShift,  // ">>"
-    BitAnd,  // '&'
-    BitOr,   // '|'
-    BitXor,  // '^'
-    BitNot,  // '~'
-    LogAnd,  // "&&"
-    LogOr,   // "||"
-    LogNot,  // '!'
-    Assign,  // '='
-    Lvar,    // 左辺値
-    Cast,    // キャスト
-    Num,     // 数値
-    Addr,    // アドレス参照(&)
-    Deref,   // アドレスの値を読む(*)
-    Eq,      // "=="
-    NEq,     // "!="
-    LThan,   // '<' // '>' や ">=" はパース時に構文木の左右を入れ替えることで調整
-    LEq,     // "<="
-    If,      // "if"
-    For,     // "for"
-    While,   // "while"
-    Return,  // "return"
-    Block,   // {}
-    Comma,   // ','
-    FunCall, // func()
-    Global,  // グローバル変数(関数含む)
-    ZeroClr, // スタックのゼロクリア(配列の初期化など)
-    Nop,     // 何もしない
+    Default,      // defalut
+    Add,          // '+'
+    Sub,          // '-'
+    Mul,          // '*'
+    Div,          // '/'
+    Mod,          // '%'
+    LShift,       // "<<"
+    RShift,       // ">>"
+    BitAnd,       // '&'
+    BitOr,        // '|'
+    BitXor,       // '^'
+    BitNot,       // '~'
+    LogAnd,       // "&&"
+    LogOr,        // "||"
+    LogNot,       // '!'
+    Assign,       // '='
+    Lvar,         // 左辺値
+    Cast,         // キャスト
+    Num,          // 数値
+    Addr,         // アドレス参照(&)
+    Deref,        // アドレスの値を読む(*)
+    Member,       // 構造体・共用体のメンバ(. と ->)
+    Eq,           // "=="
+    NEq,          // "!="
+    LThan,        // '<' // '>' や ">=" はパース時に構文木の左右を入れ替えることで調整
+    LEq,          // "<="
+    If,           // "if"
+    For,          // "for"
+    While,        // "while"
+    DoWhile,      // "do" ~ "while"
+    Switch,       // "switch"
+    Case,         // "case", "default"
+    Break,        // "break"
+    Continue,     // "continue"
+    Goto,         // "goto"
+    Label,        // ラベル
+    Return,       // "return"
+    Block,        // {}
+    StmtExpr,     // ({}) (GNU 拡張の文式)
+    Comma,        // ','
+    FunCall,      // func()
+    VaStart,      // va_start
+    VaArg,        // va_arg (次の可変長引数のアドレスを評価値とする)
+    VaCopy,       // va_copy
+    Global,       // グローバル変数(関数含む)
+    Alloca,       // スタック上への領域の動的な確保(可変長配列, __builtin_alloca)
+    InlineAsm,    // asm("..." : outputs : inputs : clobbers)
+    AtomicCas,    // 不可分な比較交換(__atomic_compare_exchange_n)
+    AtomicAdd,    // 不可分な加算(__atomic_fetch_add)
+    Fence,        // メモリバリア(__sync_synchronize)
+    PopCount,     // 1のビットの数(__builtin_popcount)
+    Clz,          // 上位から続く0のビットの数(__builtin_clz)
+    Ctz,          // 下位から続く0のビットの数(__builtin_ctz)
+    Bswap,        // バイト順の反転(__builtin_bswap32 など)
+    MemCpy,       // メモリのコピー(__builtin_memcpy)
+    Trap,         // 異常終了(__builtin_trap)
+    Unreachable,  // 到達しない位置(__builtin_unreachable)
+    StackRestore, // 動的に確保した領域の解放(left の先頭アドレスから right バイトの領域の確保前までスタックを戻す)
+    ZeroClr,      // スタックのゼロクリア(配列の初期化など)
+    Nop,          // 何もしない
 }
 
 #[derive(Clone, Debug)]
@@ -56,23 +81,24 @@ pub struct Node {
 
     // プロパティとなる数値
     pub val: Option<i32>,
-    pub offset: Option<usize>, // ベースポインタからのオフセット(ローカル変数時のみ)
+    pub offset: Option<usize>, // ベースポインタからのオフセット(ローカル変数時のみ)、構造体・共用体のメンバの場合はメンバのオフセット
 
     // 通常ノード(計算式評価)用の左右ノード
     pub left: Option<NodeRef>,
     pub right: Option<NodeRef>,
 
-    // for (init; enter; routine) branch, if (enter) branch else els, while(enter) branch
+    // for (init; enter; routine) branch, if (enter) branch else els, while(enter) branch, do branch while(enter)
+    // switch(enter) branch, case val: branch, label: branch
     pub init: Option<NodeRef>,
     pub enter: Option<NodeRef>,
     pub routine: Option<NodeRef>,
     pub branch: Option<NodeRef>,
     pub els: Option<NodeRef>,
 
-    // ブロック内のコード
+    // ブロック内のコード(switch の場合は対応する case のノード)
     pub children: Vec<NodeRef>,
 
-    // グローバル変数等で使用
+    // グローバル変数やラベル等で使用
     pub name: Option<String>,
     pub init_data: Vec<InitData>,
 
@@ -81,10 +107,31 @@ pub struct Node {
     pub args: Vec<NodeRef>,
     pub stmts: Option<Vec<NodeRef>>,
     pub max_offset: Option<usize>,
+    pub va_area: Option<usize>, // 可変長引数をとる関数のレジスタ保存領域のオフセット
+    pub uses_alloca: bool, // スタック上に動的に領域を確保する(可変長配列や __builtin_alloca を使う)かどうか
+    pub frame_align: Option<usize>, // 16より大きいアラインメントのローカル変数がある場合の、スタックフレームのアラインメント
 
     // 変数時に使用
     pub is_local: bool,
     pub level: Option<usize>,
+
+    // 記憶域クラス(グローバル変数と関数で使用)
+    pub is_static: bool,
+    pub is_extern: bool,
+    // 関数がインライン定義(非 static なら外部シンボルを提供しない定義、static なら使用されなければ出力しない定義)かどうか
+    pub is_inline: bool,
+    // スレッドローカルな変数かどうか(グローバル変数と、それを参照する Lvar で使用)
+    pub is_tls: bool,
+
+    // GNU 拡張の属性(グローバル変数と関数で使用)
+    pub is_noreturn: bool,
+    pub is_weak: bool,
+    pub is_used: bool,
+    pub is_unused: bool,
+    pub section: Option<String>,
+
+    // インラインアセンブリに使用(オペランドの式は args に出力、入力の順で持たせる)
+    pub asm: Option<InlineAsm>,
 }
 
 // グローバル変数の初期化で使用
@@ -95,6 +142,24 @@ pub struct InitData {
     pub label: Option<String>,
 }
 
+// インラインアセンブリで使用
+#[derive(Clone, Debug, Default)]
+pub struct InlineAsm {
+    pub template: String,
+    pub is_basic: bool, // オペランドの指定のない基本 asm かどうか(テンプレートの % を置換しない)
+    pub operands: Vec<AsmOperand>,
+    pub clobbers: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AsmOperand {
+    pub name: Option<String>, // テンプレート中で %[name] として参照する名前
+    pub constraint: String,   // "=", "+", "&" を除いた制約
+    pub is_output: bool,
+    pub is_inout: bool,   // "+" の指定された、読み書き両方を行う出力かどうか
+    pub imm: Option<i64>, // "i", "n" の制約の場合の定数値
+}
+
 /// 並列で処理することがないものとして、グローバル変数の都合で Send/Sync を使う
 unsafe impl Send for Node {}
 unsafe impl Sync for Node {}
@@ -123,8 +188,21 @@ impl Default for Node {
             args: vec![],
             stmts: None,
             max_offset: None,
+            va_area: None,
+            uses_alloca: false,
+            frame_align: None,
             is_local: false,
             level: None,
+            is_static: false,
+            is_inline: false,
+            is_extern: false,
+            is_tls: false,
+            is_noreturn: false,
+            is_weak: false,
+            is_used: false,
+            is_unused: false,
+            section: None,
+            asm: None,
         }
     }
 }
@@ -144,6 +222,31 @@ impl Display for Node {
         };
         s = format!("{}Nodekind : {:?}{}\n", s, self.kind, scope_attr);
 
+        if self.is_static {
+            s = format!("{}storage: static\n", s);
+        } else if self.is_extern {
+            s = format!("{}storage: extern\n", s);
+        }
+        if self.is_inline {
+            s = format!("{}inline\n", s);
+        }
+        if self.is_tls {
+            s = format!("{}thread_local\n", s);
+        }
+        let attrs: Vec<String> = [
+            (self.is_noreturn, "noreturn"),
+            (self.is_weak, "weak"),
+            (self.is_used, "used"),
+            (self.is_unused, "unused"),
+        ]
+        .iter()
+        .filter(|(b, _)| *b)
+        .map(|(_, name)| name.to_string())
+        .chain(self.section.iter().map(|e| format!("section(\"{}\")", e)))
+        .collect();
+        if !attrs.is_empty() {
+            s = format!("{}attributes: {}\n", s, attrs.join(" "));
+        }
         if let Some(e) = self.level {
             s = format!("{}scope level: {}\n", s, e);
         }
@@ -215,6 +318,30 @@ impl Display for Node {
         if let Some(e) = self.max_offset.as_ref() {
             s = format!("{}max_offset: {}\n", s, e);
         }
+        if let Some(e) = self.va_area.as_ref() {
+            s = format!("{}va_area: {}\n", s, e);
+        }
+        if self.uses_alloca {
+            s = format!("{}uses alloca\n", s);
+        }
+        if let Some(e) = self.frame_align.as_ref() {
+            s = format!("{}frame_align: {}\n", s, e);
+        }
+
+        if let Some(asm) = self.asm.as_ref() {
+            s = format!("{}asm: \"{}\"\n", s, asm.template);
+            for operand in &asm.operands {
+                let prefix = match (operand.is_output, operand.is_inout) {
+                    (true, true) => "+",
+                    (true, false) => "=",
+                    _ => "",
+                };
+                s = format!("{}->constraint: \"{}{}\"\n", s, prefix, operand.constraint);
+            }
+            if !asm.clobbers.is_empty() {
+                s = format!("{}clobbers: {}\n", s, asm.clobbers.join(", "));
+            }
+        }
 
         if !self.init_data.is_empty() {
             s = format!("{}init_data: exist\n", s);
@@ -252,23 +379,24 @@ impl Display for InitData {
 }
 
 /// エラーメッセージ送出時に println! 等と同様の可変長引数を実現するためのマクロ
+/// 第一引数はエラーの分類(発生箇所ごとに決めた、安定した名前)
 #[macro_export]
 macro_rules! error_with_node {
-	($fmt: expr, $tok: expr) => (
+	($code: expr, $fmt: expr, $tok: expr) => (
 		use $crate::node::error_nod;
-		error_nod($fmt, $tok);
+		error_nod($code, $fmt, $tok);
 	);
 
-	($fmt: expr, $tok: expr, $($arg: tt)*) => (
+	($code: expr, $fmt: expr, $tok: expr, $($arg: tt)*) => (
 		use $crate::node::error_nod;
-		error_nod(format!($fmt, $($arg)*).as_str(), $tok);
+		error_nod($code, format!($fmt, $($arg)*).as_str(), $tok);
 	);
 }
 
 /// エラー送出のためのラッパー
-pub fn error_nod(msg: &str, node: &Node) -> ! {
+pub fn error_nod(code: &'static str, msg: &str, node: &Node) -> ! {
     // token.line_offset は token.len 以上であるはずなので負になる可能性をチェックしない
-    error_tok(msg, &node.token.as_ref().unwrap().borrow());
+    error_tok(code, msg, &node.token.as_ref().unwrap().borrow());
 }
 
 #[cfg(test)]
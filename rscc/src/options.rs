@@ -7,12 +7,23 @@ use clap::Clap;
     author = "Yuna Tomida",
     about = "Rust 製の C 言語コンパイラ"
 )]
-
 pub struct Opts {
     // // verbose level
     // #[clap(short, long)]
     // verbose: bool,
 
+    // -fPIC のようなコード生成のオプション(現在は -fPIC でスレッドローカルな変数のアクセス方法のみが変わる)
+    #[clap(short = 'f', number_of_values = 1, multiple_occurrences = true)]
+    pub flags: Vec<String>,
+
+    // エラーや警告の出力形式(human は色付きの位置表示、 json と sarif は機械可読な形式)
+    #[clap(long = "diagnostics-format", default_value = "human", possible_values = &["human", "json", "sarif"])]
+    pub diagnostics_format: String,
+
+    // human 形式の出力に色を付けるかどうか(auto は標準エラー出力が端末である場合のみ付ける)
+    #[clap(long = "color", default_value = "auto", possible_values = &["auto", "always", "never"])]
+    pub color: String,
+
     // 入力ファイル名
     #[clap(name = "FILE")]
     pub input_file: Option<String>,
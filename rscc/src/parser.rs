@@ -1,36 +1,49 @@
 // 再帰下降構文のパーサ
 use std::cell::RefCell;
-use std::collections::{HashMap, LinkedList};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, LinkedList};
+use std::convert::TryFrom;
 use std::rc::Rc;
-use std::sync::Mutex;
 
 use once_cell::sync::Lazy;
 
 use crate::{
-    error_with_node, error_with_token, exit_eprintln,
+    asm::gp_register,
+    diagnostic::{bail, catch_fatal, error_count, report, Diagnostic},
+    error_with_node, error_with_prev, error_with_token,
+    globals::Mutex,
     initializer::Initializer,
-    node::{InitData, Node, NodeRef, Nodekind},
-    token::{TokenRef, Tokenkind},
+    node::{AsmOperand, InitData, InlineAsm, Node, NodeRef, Nodekind},
+    semantics::{arith_conversion, cast_to, default_argument_promotion, promote, shift_conversion},
+    token::{token_ptr_exceed, Token, TokenRef, Tokenkind},
     tokenizer::{
-        at_eof, consume, consume_ident, consume_kind, consume_literal, consume_type, expect,
-        expect_ident, expect_literal, expect_number, expect_type, is, is_kind, is_type,
+        at_eof, consume, consume_ident, consume_kind, consume_literal, consume_type_spec, expect,
+        expect_ident, expect_literal, expect_number, is, is_kind, is_type,
     },
-    typecell::{get_common_type, Type, TypeCell, TypeCellRef},
+    typecell::{Type, TypeCell, TypeCellRef},
+    warning_with_token,
 };
 
 pub fn parse(mut head: TokenRef) -> Vec<NodeRef> {
     program(&mut head)
 }
 
-type LocalMap = HashMap<String, (usize, TypeCell)>;
+type LocalMap = HashMap<String, (usize, TypeCell, Option<String>)>;
+/// ジャンプ先となるラベルや goto のノードと、その位置で生存している可変長配列
+type JumpVlas = (Node, Vec<LiveVla>);
+type LabelMap = HashMap<String, JumpVlas>;
 
-/// ローカル変数名 -> (BP からのオフセット,  型)
-static LOCALS: Lazy<Mutex<Vec<LocalMap>>> =
-    Lazy::new(|| Mutex::new(vec![]));
+/// ローカル変数名 -> (BP からのオフセット,  型, static, extern の場合に参照するグローバルなラベル)
+static LOCALS: Lazy<Mutex<Vec<LocalMap>>> = Lazy::new(|| Mutex::new(vec![]));
 
 /// グローバル変数名 -> 当該ノード
 static GLOBALS: Lazy<Mutex<HashMap<String, Node>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 現在のスコープで次に確保するローカル変数のオフセットの基準と、関数内でのその最大値(スタックフレームの大きさ)
+/// スコープを抜けるとそのスコープで確保した領域は解放され、兄弟のスコープで再利用される
+static LVAR_OFFSET: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
 static LVAR_MAX_OFFSET: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
+static SCOPE_OFFSETS: Lazy<Mutex<Vec<usize>>> = Lazy::new(|| Mutex::new(vec![]));
 
 /// 文字列 -> 対応する内部変数名
 static LITERALS: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
@@ -40,6 +53,101 @@ pub static ORDERED_LITERALS: Lazy<Mutex<LinkedList<(String, String)>>> =
     Lazy::new(|| Mutex::new(LinkedList::new()));
 static LITERAL_COUNT: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
 
+/// static なローカル変数のノード(関数のパースが終わるごとにグローバル変数として出力する)
+static STATIC_LOCALS: Lazy<Mutex<Vec<Node>>> = Lazy::new(|| Mutex::new(vec![]));
+static STATIC_COUNT: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
+
+/// break, continue の対象となる文(ループと switch)のネストと、それぞれの文が置かれたスコープのレベル
+static BREAKABLES: Lazy<Mutex<Vec<(Nodekind, usize)>>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// ラベル名 -> (当該ノード, その位置で生存している可変長配列)(ラベルは関数スコープなので、関数ごとにクリアする)
+static LABELS: Lazy<Mutex<LabelMap>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 関数内の goto のノードと、その位置で生存している可変長配列(飛び先のラベルの存在は関数の終わりでチェックする)
+/// 可変長配列のスコープから抜ける goto は、飛び先が分かった時点で init の位置に領域を解放するノードを埋める
+static GOTOS: Lazy<Mutex<Vec<JumpVlas>>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// 宣言された順に並べた、現在生存している可変長配列
+static LIVE_VLAS: Lazy<Mutex<Vec<LiveVla>>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// 可変長配列の型を読んだ際に生成した、そのバイト数を実行時に計算する式(宣言や sizeof の直前に評価する)
+static VLA_SIZES: Lazy<Mutex<Vec<Node>>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// 仮引数の宣言子を読み始めてから最初の配列の添字までは、 static や型修飾子を書ける(その配列はポインタに調整される)
+static PARAM_ARRAY: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+
+/// 仮引数リストのネストの深さ(仮引数の配列の要素数には、前の仮引数を使った定数でない式も書ける)
+static PARAM_DEPTH: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
+
+/// パース中の関数がスタック上に動的に領域を確保する(可変長配列や __builtin_alloca を使う)かどうか
+static USES_ALLOCA: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+
+/// パース中の関数のローカル変数に必要な最大のアラインメント(16を超える場合はスタックフレームを揃え直す)
+static LVAR_MAX_ALIGN: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
+
+/// ブロックスコープで extern _Thread_local と宣言された変数の名前(ファイルスコープの宣言がなくても TLS として参照するため)
+static EXTERN_THREAD_LOCALS: Lazy<Mutex<HashSet<String>>> =
+    Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// 暗黙的に宣言された(プロトタイプ宣言なしで呼び出された)関数の名前
+static IMPLICIT_FUNCS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// 未定義として報告済みの名前と、エラーで読み飛ばした範囲に現れた識別子(連鎖したエラーを抑制するため)
+static ERROR_NAMES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// パース中の関数が可変長引数をとる場合の (レジスタ保存領域のオフセット, 名前付き引数の数)
+static VA_INFO: Lazy<Mutex<Option<(usize, usize)>>> = Lazy::new(|| Mutex::new(None));
+
+/// 定義をパース中の関数の名前(ファイルスコープでは None)
+static CURRENT_FUNC: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// (宣言されたスコープのレベル(ファイルスコープでは None), 名前, 型) を宣言された順に並べたもの
+type ScopedTypes = Vec<(Option<usize>, String, TypeCell)>;
+
+/// typedef 名と、構造体・共用体のタグ(それぞれ別の名前空間を持ち、スコープを抜けると見えなくなる)
+static TYPEDEFS: Lazy<Mutex<ScopedTypes>> = Lazy::new(|| Mutex::new(builtin_typedefs()));
+static TAGS: Lazy<Mutex<ScopedTypes>> = Lazy::new(|| Mutex::new(vec![]));
+
+/// asm ラベルでアセンブラ上の名前を指定された関数・グローバル変数の名前 -> アセンブラ上の名前
+pub static ASM_LABELS: Lazy<Mutex<HashMap<String, String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 組み込みで提供する typedef 名(va_list はヘッダでの typedef による再宣言も許される)
+fn builtin_typedefs() -> ScopedTypes {
+    vec![(None, "va_list".to_string(), TypeCell::new_va_list())]
+}
+
+/// コンパイル間でパーサの状態をリセットする
+pub fn clear_state() {
+    LOCALS.try_lock().unwrap().clear();
+    GLOBALS.try_lock().unwrap().clear();
+    *LVAR_OFFSET.try_lock().unwrap() = 0;
+    *LVAR_MAX_OFFSET.try_lock().unwrap() = 0;
+    SCOPE_OFFSETS.try_lock().unwrap().clear();
+    LITERALS.try_lock().unwrap().clear();
+    ORDERED_LITERALS.try_lock().unwrap().clear();
+    *LITERAL_COUNT.try_lock().unwrap() = 0;
+    STATIC_LOCALS.try_lock().unwrap().clear();
+    *STATIC_COUNT.try_lock().unwrap() = 0;
+    BREAKABLES.try_lock().unwrap().clear();
+    LABELS.try_lock().unwrap().clear();
+    GOTOS.try_lock().unwrap().clear();
+    LIVE_VLAS.try_lock().unwrap().clear();
+    VLA_SIZES.try_lock().unwrap().clear();
+    *USES_ALLOCA.try_lock().unwrap() = false;
+    *LVAR_MAX_ALIGN.try_lock().unwrap() = 0;
+    *PARAM_ARRAY.try_lock().unwrap() = false;
+    *PARAM_DEPTH.try_lock().unwrap() = 0;
+    *VA_INFO.try_lock().unwrap() = None;
+    IMPLICIT_FUNCS.try_lock().unwrap().clear();
+    ERROR_NAMES.try_lock().unwrap().clear();
+    EXTERN_THREAD_LOCALS.try_lock().unwrap().clear();
+    *CURRENT_FUNC.try_lock().unwrap() = None;
+    *TYPEDEFS.try_lock().unwrap() = builtin_typedefs();
+    TAGS.try_lock().unwrap().clear();
+    ASM_LABELS.try_lock().unwrap().clear();
+}
+
 fn store_literal(body: impl Into<String>) -> String {
     LITERALS
         .try_lock()
@@ -63,6 +171,41 @@ fn get_literal_id() -> usize {
     c
 }
 
+fn get_static_id() -> usize {
+    let mut count = STATIC_COUNT.try_lock().unwrap();
+    let c = *count;
+    *count += 1;
+    c
+}
+
+/// 宣言指定子のうち、型以外のもの
+#[derive(Clone, Debug, Default)]
+struct VarAttr {
+    is_typedef: bool,
+    is_static: bool,
+    is_extern: bool,
+    is_inline: bool,
+    is_thread_local: bool, // _Thread_local (__thread) は static, extern と組み合わせられる
+    align: Option<usize>,  // _Alignas で指定されたアラインメント
+
+    // GNU 拡張の属性(__attribute__((...)))と _Noreturn で指定されたもの
+    is_noreturn: bool,
+    is_packed: bool,
+    is_weak: bool,
+    is_used: bool,
+    is_unused: bool,
+    aligned: Option<usize>, // aligned 属性で指定されたアラインメント
+    section: Option<String>,
+}
+
+/// 生存中の可変長配列: 宣言されたスコープのレベルと、先頭アドレス及びバイト数を格納する変数のオフセット
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct LiveVla {
+    level: usize,
+    ptr: usize,
+    size: usize,
+}
+
 macro_rules! align {
     ($addr:expr, $base:expr) => {
         if $base.count_ones() != 1 {
@@ -141,17 +284,6 @@ macro_rules! tmp_num {
     };
 }
 
-fn get_alignment_base(typ: &TypeCell) -> usize {
-    match typ.bytes() {
-        1 => 1,
-        2 | 3 => 2,
-        4..=7 => 4,
-        8..=15 => 8,
-        16.. => 16,
-        _ => panic!("invalid size detected."),
-    }
-}
-
 // 左辺値に対応するノード: += などの都合で無名の変数を生成する場合があるため、token は Option で受ける
 fn _lvar(
     name: impl Into<String>,
@@ -161,29 +293,40 @@ fn _lvar(
     level: usize,
 ) -> NodeRef {
     if is_local {
-        let (offset, _) = *LOCALS.try_lock().unwrap()[level]
+        let (offset, _, _) = *LOCALS.try_lock().unwrap()[level]
             .entry(name.into())
             .or_insert_with_key(|_| {
-                // 見つからない場合にはオフセットの最大値を伸ばしてスタックを確保
-                let mut max_offset_access = LVAR_MAX_OFFSET.try_lock().unwrap();
+                // 見つからない場合にはオフセットを伸ばしてスタックを確保
+                let mut offset_access = LVAR_OFFSET.try_lock().unwrap();
 
-                // 各変数のサイズ(配列なら1要素のサイズ)に alignment する
+                // 各変数の型の自然なアラインメント(配列なら要素のもの、 _Alignas があればその値)に揃える
+                // rbp は16の倍数(16より大きいアラインメントの変数があればその倍数に揃え直す)なので、 rbp からのオフセットを揃えれば良い
+                // 可変長配列の実体は動的に確保するので、ここではその先頭アドレスを格納する領域のみ確保する
                 let (diff, align_base) = if let Some(t) = &typ {
-                    (t.bytes(), get_alignment_base(t))
+                    if t.has_vla() {
+                        (8, 8)
+                    } else {
+                        (t.bytes(), t.align())
+                    }
                 } else {
                     (8, 8)
                 };
-                *max_offset_access += diff;
-                align!(*max_offset_access, align_base);
+                *offset_access += diff;
+                align!(*offset_access, align_base);
+                let mut max_align_access = LVAR_MAX_ALIGN.try_lock().unwrap();
+                *max_align_access = (*max_align_access).max(align_base);
+                let mut max_offset_access = LVAR_MAX_OFFSET.try_lock().unwrap();
+                *max_offset_access = (*max_offset_access).max(*offset_access);
 
                 // typ に渡されるのは Option だが LOCALS に保存するのは生の TypeCell であることに注意
                 (
-                    *max_offset_access,
+                    *offset_access,
                     if typ.is_some() {
                         typ.clone().unwrap()
                     } else {
                         TypeCell::default()
                     },
+                    None,
                 )
             });
         Rc::new(RefCell::new(Node {
@@ -218,9 +361,10 @@ fn new_lvar(
     _lvar(name, Some(token), Some(typ), is_local, level)
 }
 
+// 無名の変数は現在のスコープに確保する(外側のスコープに確保すると、兄弟のスコープで再利用された領域と重なりうる)
 macro_rules! tmp_lvar {
     () => {
-        _lvar("", None, None, true, 0)
+        _lvar("", None, None, true, current_scope())
     };
 }
 
@@ -244,8 +388,16 @@ fn new_ctrl(
     branch: Option<NodeRef>,
     els: Option<NodeRef>,
 ) -> NodeRef {
-    if ![Nodekind::If, Nodekind::For, Nodekind::While].contains(&kind) {
-        exit_eprintln!("new_ctrl: 制御構文ではありません。");
+    if ![
+        Nodekind::If,
+        Nodekind::For,
+        Nodekind::While,
+        Nodekind::DoWhile,
+        Nodekind::Switch,
+    ]
+    .contains(&kind)
+    {
+        panic!("new_ctrl: 制御構文ではありません。");
     }
     Rc::new(RefCell::new(Node {
         kind,
@@ -258,6 +410,29 @@ fn new_ctrl(
     }))
 }
 
+// case, default のためのノード(default の場合は val が None)
+#[inline]
+fn new_case(val: Option<i32>, branch: NodeRef, token: TokenRef) -> NodeRef {
+    Rc::new(RefCell::new(Node {
+        kind: Nodekind::Case,
+        token: Some(token),
+        val,
+        branch: Some(branch),
+        ..Default::default()
+    }))
+}
+
+// break, continue, goto, ラベルのためのノード
+#[inline]
+fn new_jump(kind: Nodekind, name: Option<String>, token: TokenRef) -> NodeRef {
+    Rc::new(RefCell::new(Node {
+        kind,
+        token: Some(token),
+        name,
+        ..Default::default()
+    }))
+}
+
 #[inline]
 fn new_funcall(name: String, func_typ: TypeCell, args: Vec<NodeRef>, token: TokenRef) -> NodeRef {
     if func_typ.typ != Type::Func {
@@ -275,6 +450,7 @@ fn new_funcall(name: String, func_typ: TypeCell, args: Vec<NodeRef>, token: Toke
 
 /// グローバル変数のノード(new_gvar, new_funcdec によりラップして使う)
 #[inline]
+#[allow(clippy::too_many_arguments)]
 fn _global(
     name: String,
     typ: Option<TypeCell>,
@@ -282,6 +458,7 @@ fn _global(
     args: Vec<NodeRef>,
     stmts: Option<Vec<NodeRef>>,
     max_offset: Option<usize>,
+    attr: VarAttr,
     token: TokenRef,
 ) -> NodeRef {
     let glob = Rc::new(RefCell::new(Node {
@@ -293,8 +470,13 @@ fn _global(
         args,
         stmts,
         max_offset,
+        is_static: attr.is_static,
+        is_extern: attr.is_extern,
+        is_inline: attr.is_inline,
+        is_tls: attr.is_thread_local,
         ..Default::default()
     }));
+    set_attributes(&mut glob.borrow_mut(), &attr);
     let _ = GLOBALS
         .try_lock()
         .unwrap()
@@ -303,8 +485,8 @@ fn _global(
 }
 
 #[inline]
-fn new_gvar(name: String, typ: TypeCell, token: TokenRef) -> NodeRef {
-    _global(name, Some(typ), None, vec![], None, None, token)
+fn new_gvar(name: String, typ: TypeCell, attr: VarAttr, token: TokenRef) -> NodeRef {
+    _global(name, Some(typ), None, vec![], None, None, attr, token)
 }
 
 #[inline]
@@ -314,6 +496,7 @@ fn new_funcdec(
     args: Vec<NodeRef>,
     stmts: Vec<NodeRef>,
     max_offset: usize,
+    attr: VarAttr,
     token: TokenRef,
 ) -> NodeRef {
     _global(
@@ -323,13 +506,14 @@ fn new_funcdec(
         args,
         Some(stmts),
         Some(max_offset),
+        attr,
         token,
     )
 }
 
 #[inline]
-fn proto_func(name: String, func_typ: TypeCell, token: TokenRef) -> NodeRef {
-    _global(name, None, Some(func_typ), vec![], None, None, token)
+fn proto_func(name: String, func_typ: TypeCell, attr: VarAttr, token: TokenRef) -> NodeRef {
+    _global(name, None, Some(func_typ), vec![], None, None, attr, token)
 }
 
 #[inline]
@@ -344,11 +528,57 @@ fn nop() -> NodeRef {
 #[inline]
 fn enter_scope() {
     LOCALS.try_lock().unwrap().push(HashMap::new());
+    let offset = *LVAR_OFFSET.try_lock().unwrap();
+    SCOPE_OFFSETS.try_lock().unwrap().push(offset);
 }
 
+/// スコープを抜ける際は、そのスコープの変数の領域を解放する(最大値は LVAR_MAX_OFFSET に残る)
 #[inline]
 fn leave_scope() {
     let _ = LOCALS.try_lock().unwrap().pop();
+    let offset = SCOPE_OFFSETS.try_lock().unwrap().pop().unwrap();
+    *LVAR_OFFSET.try_lock().unwrap() = offset;
+
+    let n_scopes = LOCALS.try_lock().unwrap().len();
+    LIVE_VLAS
+        .try_lock()
+        .unwrap()
+        .retain(|vla| vla.level < n_scopes);
+    let is_visible = |(level, _, _): &(Option<usize>, String, TypeCell)| {
+        level.is_none_or(|level| level < n_scopes)
+    };
+    TYPEDEFS.try_lock().unwrap().retain(is_visible);
+    TAGS.try_lock().unwrap().retain(is_visible);
+}
+
+/// vlas の先頭の可変長配列を確保する直前の位置までスタックを戻すノード(解放する可変長配列がなければ None)
+/// 確保した領域は16の倍数に切り上げたバイト数なので、先頭アドレスにそれを足せば確保前のスタックの位置になる
+fn restore_stack(vlas: &[LiveVla]) -> Option<NodeRef> {
+    vlas.first().map(|vla| {
+        let restore = tmp_binary!(
+            Nodekind::StackRestore,
+            direct_offset_lvar(vla.ptr, TypeCell::new_size_t()),
+            direct_offset_lvar(vla.size, TypeCell::new_size_t())
+        );
+        let _ = restore
+            .borrow_mut()
+            .typ
+            .insert(TypeCell::new(Type::Invalid));
+        restore
+    })
+}
+
+/// レベル level のスコープより内側で宣言された可変長配列を全て解放するノード
+fn restore_vlas_inside(level: usize) -> Option<NodeRef> {
+    let vlas = LIVE_VLAS.try_lock().unwrap();
+    let ix = vlas.iter().position(|vla| vla.level > level)?;
+    restore_stack(&vlas[ix..])
+}
+
+/// ジャンプの前に可変長配列の領域を解放する(解放するノードはジャンプのノードの init に持たせる)
+fn with_restore(restore: Option<NodeRef>, jump: NodeRef) -> NodeRef {
+    jump.borrow_mut().init = restore;
+    jump
 }
 
 fn current_scope() -> usize {
@@ -359,29 +589,120 @@ fn current_scope() -> usize {
     n_scopes - 1
 }
 
-/// 計算時の暗黙のキャストを行う関数
-fn arith_cast(node: &mut Node) -> TypeCell {
-    let left = Rc::clone(node.left.as_ref().unwrap());
-    let right = Rc::clone(node.right.as_ref().unwrap());
-    let left_typ = left.borrow().typ.clone().unwrap();
-    let right_typ = right.borrow().typ.clone().unwrap();
-    let typ = get_common_type(&left_typ, &right_typ);
-    let _ = node.left.insert(new_cast(&left, typ.clone()));
-    let _ = node.right.insert(new_cast(&right, typ.clone()));
-    typ
+#[inline]
+fn enter_breakable(kind: Nodekind) {
+    let level = current_scope();
+    BREAKABLES.try_lock().unwrap().push((kind, level));
+}
+
+#[inline]
+fn leave_breakable() {
+    let _ = BREAKABLES.try_lock().unwrap().pop();
+}
+
+/// switch の本体から対応する case, default のノードを集める(ネストした switch の中は見ない)
+fn collect_cases(node: &NodeRef, cases: &mut Vec<NodeRef>) {
+    let node_ = node.borrow();
+    match node_.kind {
+        Nodekind::Switch => return,
+        Nodekind::Case => cases.push(Rc::clone(node)),
+        _ => {}
+    }
+    if let Some(n) = &node_.init {
+        collect_cases(n, cases);
+    }
+    if let Some(n) = &node_.branch {
+        collect_cases(n, cases);
+    }
+    if let Some(n) = &node_.els {
+        collect_cases(n, cases);
+    }
+    for child in &node_.children {
+        collect_cases(child, cases);
+    }
 }
 
 fn new_cast(expr: &NodeRef, typ: TypeCell) -> NodeRef {
     confirm_type(expr);
-    let token = expr.borrow().token.clone();
-    let left = Some(Rc::clone(expr));
-    Rc::new(RefCell::new(Node {
-        kind: Nodekind::Cast,
-        token,
-        typ: Some(typ),
-        left,
-        ..Default::default()
-    }))
+    cast_to(expr, typ)
+}
+
+/// from の値を型 to に暗黙に変換(代入、初期化、実引数の受け渡し)できるかをチェックする
+fn check_assignable(to: &TypeCell, from: &NodeRef) {
+    let from_typ = from.borrow().typ.clone().unwrap();
+    if to.is_assignable_from(&from_typ) || (to.is_pointer() && is_null_pointer_constant(from)) {
+        return;
+    }
+    if to.is_pointer() && from_typ.is_pointer_like() {
+        let to_deref = to.make_deref().unwrap();
+        let from_deref = from_typ.make_deref().unwrap();
+        if !to_deref.has_qualifiers_of(&from_deref) {
+            error_with_node!(
+                "discarded-qualifiers",
+                "型\"{}\"から型\"{}\"への変換では、指す先の型の修飾子が失われます。",
+                &from.borrow(),
+                from_typ,
+                to
+            );
+        }
+        error_with_node!(
+            "incompatible-pointer-types",
+            "互換性のないポインタ型\"{}\"から型\"{}\"へは暗黙に変換できません。",
+            &from.borrow(),
+            from_typ,
+            to
+        );
+    }
+    error_with_node!(
+        "incompatible-types",
+        "型\"{}\"から型\"{}\"へは暗黙に変換できません。",
+        &from.borrow(),
+        from_typ,
+        to
+    );
+}
+
+/// 空ポインタ定数(値が0の整数定数式)かどうか
+fn is_null_pointer_constant(node: &NodeRef) -> bool {
+    let node = node.borrow();
+    match node.kind {
+        Nodekind::Num => node.val == Some(0),
+        // (void *)0 も空ポインタ定数になる
+        Nodekind::Cast
+            if node.typ.as_ref().unwrap().is_integer()
+                || node
+                    .typ
+                    .as_ref()
+                    .unwrap()
+                    .make_deref()
+                    .is_ok_and(|typ| typ.is_void() && !typ.is_const && !typ.is_volatile) =>
+        {
+            is_null_pointer_constant(node.left.as_ref().unwrap())
+        }
+        _ => false,
+    }
+}
+
+/// 演算の対象となる値がスカラ型であることをチェックする(void や構造体・共用体の値は演算に使えない)
+/// 値のノードがトークンを持たない場合は、演算のノードの位置でエラーを出す
+fn check_scalar(operand: &Option<NodeRef>, node: &Node) {
+    if let Some(operand) = operand {
+        let typ = operand.borrow().typ.clone().unwrap();
+        if !typ.is_scalar() {
+            let operand = operand.borrow();
+            let at = if operand.token.is_some() {
+                &*operand
+            } else {
+                node
+            };
+            error_with_node!(
+                "invalid-operand",
+                "型\"{}\"の値はこの演算には使用できません。",
+                at,
+                typ
+            );
+        }
+    }
 }
 
 /// 型を構文木全体に対して設定する関数
@@ -414,6 +735,33 @@ fn confirm_type(node: &NodeRef) {
 
     let kind: Nodekind = node.borrow().kind;
     let mut node = node.borrow_mut();
+    match kind {
+        Nodekind::Add
+        | Nodekind::Sub
+        | Nodekind::Mul
+        | Nodekind::Div
+        | Nodekind::Mod
+        | Nodekind::LShift
+        | Nodekind::RShift
+        | Nodekind::BitAnd
+        | Nodekind::BitOr
+        | Nodekind::BitXor
+        | Nodekind::BitNot
+        | Nodekind::LogAnd
+        | Nodekind::LogOr
+        | Nodekind::LogNot
+        | Nodekind::Eq
+        | Nodekind::NEq
+        | Nodekind::LThan
+        | Nodekind::LEq => {
+            check_scalar(&node.left, &node);
+            check_scalar(&node.right, &node);
+        }
+        Nodekind::If | Nodekind::For | Nodekind::While | Nodekind::DoWhile => {
+            check_scalar(&node.enter, &node);
+        }
+        _ => {}
+    }
     match kind {
         Nodekind::Num => {
             let _ = node.typ.insert(TypeCell::new(Type::Int));
@@ -421,8 +769,9 @@ fn confirm_type(node: &NodeRef) {
         Nodekind::Addr => {
             // & は変数やそのポインタにのみ可能であるため、このタイミングで left をチェックして弾くことができる
             let left_kind = node.left.as_ref().unwrap().borrow().kind;
-            if ![Nodekind::Deref, Nodekind::Lvar].contains(&left_kind) {
+            if ![Nodekind::Deref, Nodekind::Lvar, Nodekind::Member].contains(&left_kind) {
                 error_with_node!(
+                    "not-lvalue",
                     "\"&\" では変数として宣言された値のみ参照ができます。",
                     &node
                 );
@@ -432,10 +781,11 @@ fn confirm_type(node: &NodeRef) {
         }
         Nodekind::Deref => {
             let left_typ = node.left.as_ref().unwrap().borrow().typ.clone().unwrap();
-            if left_typ.ptr_end.is_some() {
+            if left_typ.is_pointer_like() {
                 let _ = node.typ.insert(left_typ.make_deref().unwrap());
             } else {
                 error_with_node!(
+                    "invalid-dereference",
                     "\"*\"ではポインタの参照を外すことができますが、型\"{}\"が指定されています。",
                     &node,
                     left_typ.typ
@@ -450,27 +800,41 @@ fn confirm_type(node: &NodeRef) {
 
             if left_typ.is_array() {
                 error_with_node!(
+                    "not-assignable",
                     "左辺値は代入可能な型である必要がありますが、配列型\"{}\"が指定されています。",
                     &left.borrow(),
                     left_typ
                 );
             }
-            let right = new_cast(right, left_typ.clone());
+            check_assignable(&left_typ, right);
+            // 構造体・共用体の代入はメモリ上でコピーするので、値の変換は行わない
+            let right = if left_typ.is_struct() {
+                Rc::clone(right)
+            } else {
+                new_cast(right, left_typ.clone())
+            };
             let _ = node.right.insert(right);
             let _ = node.typ.insert(left_typ);
         }
         Nodekind::Add | Nodekind::Sub => {
             // 暗黙のキャストを行う
-            let typ = arith_cast(&mut node);
+            let typ = arith_conversion(&mut node);
             let _ = node.typ.insert(typ);
         }
         Nodekind::BitNot => {
             // ポインタの bitnot は不可
             let left_typ = node.left.as_ref().unwrap().borrow().typ.clone().unwrap();
-            if left_typ.ptr_end.is_some() {
-                error_with_node!("ポインタのビット反転はできません。", &node);
+            if left_typ.is_pointer_like() {
+                error_with_node!(
+                    "invalid-operand",
+                    "ポインタのビット反転はできません。",
+                    &node
+                );
             }
-            let _ = node.typ.insert(left_typ);
+            let left = promote(node.left.as_ref().unwrap());
+            let typ = left.borrow().typ.clone().unwrap();
+            let _ = node.left.insert(left);
+            let _ = node.typ.insert(typ);
         }
         Nodekind::Mul
         | Nodekind::Div
@@ -480,17 +844,34 @@ fn confirm_type(node: &NodeRef) {
         | Nodekind::BitXor
         | Nodekind::LShift
         | Nodekind::RShift => {
-            let typ = arith_cast(&mut node);
-            if typ.ptr_end.is_some() {
-                error_with_node!("ポインタに対して行えない計算です。", &node);
+            let is_ptr = |n: &Option<NodeRef>| {
+                n.as_ref()
+                    .unwrap()
+                    .borrow()
+                    .typ
+                    .as_ref()
+                    .unwrap()
+                    .is_pointer_like()
+            };
+            if is_ptr(&node.left) || is_ptr(&node.right) {
+                error_with_node!(
+                    "invalid-operand",
+                    "ポインタに対して行えない計算です。",
+                    &node
+                );
             }
+            let typ = if [Nodekind::LShift, Nodekind::RShift].contains(&kind) {
+                shift_conversion(&mut node)
+            } else {
+                arith_conversion(&mut node)
+            };
             let _ = node.typ.insert(typ);
         }
         Nodekind::LogNot | Nodekind::LogAnd | Nodekind::LogOr => {
             let _ = node.typ.insert(TypeCell::new(Type::Int));
         }
         Nodekind::Eq | Nodekind::NEq | Nodekind::LThan | Nodekind::LEq => {
-            let _ = arith_cast(&mut node);
+            let _ = arith_conversion(&mut node);
             let _ = node.typ.insert(TypeCell::new(Type::Int));
         }
         Nodekind::Comma => {
@@ -528,2097 +909,6736 @@ fn confirm_type(node: &NodeRef) {
 fn program(token_ptr: &mut TokenRef) -> Vec<NodeRef> {
     let mut globals: Vec<NodeRef> = Vec::new();
     while !at_eof(token_ptr) {
+        let start = Rc::clone(token_ptr);
         enter_scope();
-        globals.push(global(token_ptr));
+        // エラーが発生した場合は、関数の途中の状態を破棄して次の宣言から読み直す
+        if let Some(node) = catch_fatal(|| global(token_ptr)) {
+            globals.push(node);
+        } else {
+            restore_scopes(1);
+            discard_function_state();
+            synchronize(&start, token_ptr, false);
+        }
         leave_scope();
         assert_eq!(LOCALS.try_lock().unwrap().len(), 0);
 
         // 関数宣言が終わるごとにローカル変数の管理情報をクリア(offset や name としてノードが持っているのでこれ以上必要ない)
         *LVAR_MAX_OFFSET.try_lock().unwrap() = 0;
-    }
-    globals
-}
 
-/// 生成規則:
-/// global = type (function | global-variable)
-fn global(token_ptr: &mut TokenRef) -> NodeRef {
-    let typ = expect_type(token_ptr);
-    if is_func(token_ptr) {
-        function(token_ptr, typ)
-    } else {
-        global_variable(token_ptr, typ)
+        // static なローカル変数はグローバル変数として出力する
+        for svar in STATIC_LOCALS.try_lock().unwrap().drain(..) {
+            globals.push(Rc::new(RefCell::new(svar)));
+        }
     }
+    resolve_tentative(&globals);
+    resolve_inline(&globals);
+    globals
 }
 
-#[inline]
-pub fn is_func(token_ptr: &TokenRef) -> bool {
-    let ptr = &mut Rc::clone(token_ptr);
-    let dummy = TypeCell::default();
-    declarator(ptr, dummy).1.typ == Type::Func
-}
-
-/// 生成規則:
-/// function = func-declarator ("{" stmt* "}")?
-fn function(token_ptr: &mut TokenRef, typ: TypeCell) -> NodeRef {
-    let token = Rc::clone(token_ptr);
-    let (name, typ, args) = func_declarator(token_ptr, typ);
-
-    let (defined, line_num, line_offset) =
-        if let Some(node) = GLOBALS.try_lock().unwrap().get(&name) {
-            let decl = node.token.as_ref().unwrap().borrow();
-            let (_num, _offset) = (decl.line_num, decl.line_offset);
-            if node.typ.is_some() {
-                error_with_token!(
-                    "\"{}\"は位置[{}, {}]で既にグローバル変数として宣言されています。",
-                    &*token.borrow(),
-                    name,
-                    _num,
-                    _offset
-                );
-            }
-            (node.stmts.is_some(), _num, _offset)
-        } else {
-            (false, 0, 0)
+/// エラーから復帰するために、 start から始まる文(宣言)の終わりまで token_ptr を読み進める
+/// エラーの位置 token_ptr の直前以降で、括弧の外の ";" か、開いた括弧を閉じる "}" を文の終わりとする("}" の直後の ";" も読む)
+/// 直後に else や(do 文の) while が続く場合は、文の途中とみなしてさらに読み進める
+/// "(" や "[" の深さは "{" と別に数え、"{" と "}" の境界で 0 に戻す(閉じていない "(" があっても外側のブロックの "}" を読まないようにする)
+/// in_block が真であれば、外側のブロックを閉じる "}" は読まずに残す
+/// 読み飛ばした範囲の識別子は、それに起因する未定義のエラーを抑制するために記録する
+fn synchronize(start: &TokenRef, token_ptr: &mut TokenRef, in_block: bool) {
+    let mut names = ERROR_NAMES.try_lock().unwrap();
+    let mut depth = 0;
+    let mut paren_depth = 0;
+    let mut reached = false;
+    let mut token = Rc::clone(start);
+    let is_do = start.borrow().body.as_deref() == Some("do");
+    loop {
+        let next = token.borrow().next.clone();
+        reached |= Rc::ptr_eq(&token, token_ptr)
+            || next
+                .as_ref()
+                .is_some_and(|next| Rc::ptr_eq(next, token_ptr));
+        let (kind, body) = {
+            let t = token.borrow();
+            (t.kind, t.body.clone().unwrap_or_default())
         };
-
-    if consume(token_ptr, "{") {
-        if typ.is_abstract {
-            error_with_token!("関数の定義時には引数名を省略できません。", &*token.borrow());
+        if kind == Tokenkind::Eof {
+            break;
         }
-        // 既に宣言されている場合をケア
-        let node = GLOBALS
-            .try_lock()
-            .unwrap()
-            .get(&name)
-            .cloned()
-            .unwrap_or_default();
-        match node.kind {
-            Nodekind::Global => {
-                if defined {
-                    error_with_token!(
-                        "関数\"{}\"は位置[{}, {}]で既に定義義されています。",
-                        &*token.borrow(),
-                        name,
-                        line_num,
-                        line_offset
-                    );
+        if kind == Tokenkind::Ident {
+            names.insert(body);
+        } else if kind == Tokenkind::Reserved {
+            match body.as_str() {
+                "(" | "[" => paren_depth += 1,
+                ")" | "]" if paren_depth > 0 => paren_depth -= 1,
+                "{" => {
+                    paren_depth = 0;
+                    depth += 1;
                 }
-                // プロトタイプ宣言時と引数の整合をチェック
-                if typ != *node.func_typ.as_ref().unwrap() {
-                    error_with_token!(
-                        "プロトタイプ宣言との互換性がありません。(宣言位置: [{}, {}])",
-                        &*token.borrow(),
-                        line_num,
-                        line_offset
-                    );
+                ";" if depth == 0 && paren_depth == 0 && reached => {
+                    // if 文の else 節や do-while 文の条件が続く場合は、文の終わりではない
+                    let next_body = next.as_ref().unwrap().borrow().body.clone();
+                    match next_body.as_deref() {
+                        Some("else") => {}
+                        Some("while") if is_do => {}
+                        _ => {
+                            token = next.unwrap();
+                            break;
+                        }
+                    }
                 }
+                "}" if depth == 0 && reached && in_block => break,
+                "}" if depth <= 1 && reached => {
+                    depth = 0;
+                    paren_depth = 0;
+                    let next_body = next.as_ref().unwrap().borrow().body.clone();
+                    match next_body.as_deref() {
+                        Some("else") => {}
+                        Some("while") if is_do => {}
+                        Some(";") => {
+                            token = next.unwrap().borrow().next.clone().unwrap();
+                            break;
+                        }
+                        _ => {
+                            token = next.unwrap();
+                            break;
+                        }
+                    }
+                }
+                "}" if depth > 0 => {
+                    paren_depth = 0;
+                    depth -= 1;
+                }
+                _ => {}
             }
-            Nodekind::Default => {
-                // プロトタイプ宣言がない場合は、再帰のことを考えて定義のパース前に GLOBALS に一旦プロトタイプ宣言の体で保存する
-                let _ = proto_func(name.clone(), typ.clone(), Rc::clone(&token));
-            }
-            _ => {
-                panic!("unreachable");
-            }
-        }
-
-        let mut stmts: Vec<NodeRef> = Vec::new();
-        let mut has_return: bool = false;
-        while !consume(token_ptr, "}") {
-            has_return |= token_ptr.borrow().kind == Tokenkind::Return; // return がローカルの最大のスコープに出現するかどうかを確認 (ブロックでネストされていると対応できないのが難点…)
-            let stmt_ = stmt(token_ptr);
-            confirm_type(&stmt_);
-            stmts.push(stmt_);
         }
+        token = next.unwrap();
+    }
+    *token_ptr = token;
+}
 
-        if !has_return {
-            stmts.push(tmp_unary!(Nodekind::Return, tmp_num!(0)));
-        }
-
-        let mut max_offset_access = LVAR_MAX_OFFSET.try_lock().unwrap();
-        align!(*max_offset_access, 8usize);
-        let max_offset = *max_offset_access;
-
-        new_funcdec(name, typ, args, stmts, max_offset, token)
-    } else {
-        expect(token_ptr, ";");
-        let _ = proto_func(name, typ, token);
-        nop()
+/// スコープの数が n_scopes になるまでスコープを抜ける
+fn restore_scopes(n_scopes: usize) {
+    while LOCALS.try_lock().unwrap().len() > n_scopes {
+        leave_scope();
     }
 }
 
-/// func-declarator = pointers func-name "(" func-args ")"
-fn func_declarator(
-    token_ptr: &mut TokenRef,
-    mut typ: TypeCell,
-) -> (String, TypeCell, Vec<NodeRef>) {
-    typ = pointers(token_ptr, typ);
-    let name = func_name(token_ptr);
+/// エラーにより関数の途中でパースを中断した場合に、関数ごとの状態を破棄する
+fn discard_function_state() {
+    *CURRENT_FUNC.try_lock().unwrap() = None;
+    *VA_INFO.try_lock().unwrap() = None;
+    BREAKABLES.try_lock().unwrap().clear();
+    LABELS.try_lock().unwrap().clear();
+    GOTOS.try_lock().unwrap().clear();
+    VLA_SIZES.try_lock().unwrap().clear();
+    *USES_ALLOCA.try_lock().unwrap() = false;
+    *LVAR_MAX_ALIGN.try_lock().unwrap() = 0;
+    *PARAM_ARRAY.try_lock().unwrap() = false;
+    *PARAM_DEPTH.try_lock().unwrap() = 0;
+}
 
-    expect(token_ptr, "(");
-    let (args, arg_typs) = func_args(token_ptr);
-    let is_abstract = args.len() != arg_typs.len();
-    typ = typ.make_func(arg_typs);
-    typ.is_abstract = is_abstract;
-    expect(token_ptr, ")");
+/// 文を1つ読み、型を確定させる
+/// エラーが発生した場合はパーサの状態を文の前に戻し、次の文まで読み飛ばして None を返す
+fn stmt_or_recover(token_ptr: &mut TokenRef) -> Option<NodeRef> {
+    recover(token_ptr, |token_ptr| {
+        let node = stmt(token_ptr);
+        confirm_type(&node);
+        node
+    })
+}
 
-    (name, typ, args)
+/// ブロック(または構造体・共用体の定義)の中の文や宣言を1つ f で読む
+/// エラーが発生した場合はパーサの状態を読む前に戻し、次の文(宣言)まで読み飛ばして None を返す
+fn recover<T>(token_ptr: &mut TokenRef, f: impl FnOnce(&mut TokenRef) -> T) -> Option<T> {
+    let start = Rc::clone(token_ptr);
+    let n_scopes = LOCALS.try_lock().unwrap().len();
+    let n_breakables = BREAKABLES.try_lock().unwrap().len();
+    let n_vla_sizes = VLA_SIZES.try_lock().unwrap().len();
+    let param_array = *PARAM_ARRAY.try_lock().unwrap();
+    let param_depth = *PARAM_DEPTH.try_lock().unwrap();
+    let result = catch_fatal(|| f(token_ptr));
+    if result.is_none() {
+        restore_scopes(n_scopes);
+        BREAKABLES.try_lock().unwrap().truncate(n_breakables);
+        VLA_SIZES.try_lock().unwrap().truncate(n_vla_sizes);
+        *PARAM_ARRAY.try_lock().unwrap() = param_array;
+        *PARAM_DEPTH.try_lock().unwrap() = param_depth;
+        synchronize(&start, token_ptr, true);
+        // 入力の終わりまで読み飛ばした場合は、外側の宣言まで中断する
+        if at_eof(token_ptr) {
+            bail();
+        }
+    }
+    result
 }
 
-/// func-name = "(" func-name ")" | ident
-fn func_name(token_ptr: &mut TokenRef) -> String {
-    if consume(token_ptr, "(") {
-        let name = func_name(token_ptr);
-        expect(token_ptr, ")");
-        name
-    } else {
-        expect_ident(token_ptr)
+/// 定義されていない名前についてのエラーを送出する
+/// 既に報告した名前や、エラーで読み飛ばした範囲に現れた名前であれば、連鎖したエラーとみなして報告しない
+fn error_undefined(code: &'static str, msg: &str, name: &str, token: &Token) -> ! {
+    if !ERROR_NAMES.try_lock().unwrap().insert(name.to_string()) {
+        bail();
     }
+    error_with_token!(code, msg, token);
 }
 
 /// 生成規則:
-/// func-args = arg ("," arg)* | null
-/// arg = type declarator
-fn func_args(token_ptr: &mut TokenRef) -> (Vec<NodeRef>, Vec<TypeCellRef>) {
-    let mut args: Vec<NodeRef> = vec![];
-    let mut arg_typs: Vec<TypeCellRef> = vec![];
-    let mut argc: usize = 0;
-
-    while is_type(token_ptr) {
-        if argc >= 6 {
+/// global = static-assert | asm-stmt | declspec (";" | typedef-declaration | function | global-variable)
+fn global(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    if is(token_ptr, "_Static_assert") {
+        static_assert(token_ptr);
+        return nop();
+    }
+    if is_asm(token_ptr) {
+        return asm_stmt(token_ptr);
+    }
+    let (typ, attr) = declspec(token_ptr);
+    if attr.is_typedef {
+        typedef_declaration(token_ptr, typ)
+    } else if consume(token_ptr, ";") {
+        // struct S {...}; のようにタグのみを宣言する場合
+        nop()
+    } else if is_func(token_ptr) {
+        if attr.align.is_some() {
             error_with_token!(
-                "現在7つ以上の引数はサポートされていません。",
-                &token_ptr.borrow()
+                "invalid-alignment",
+                "関数にはアラインメントを指定できません。",
+                &token.borrow()
             );
         }
-        arg(token_ptr, &mut args, &mut arg_typs);
-        argc += 1;
-        if !consume(token_ptr, ",") {
-            break;
+        if attr.is_thread_local {
+            error_with_token!(
+                "invalid-storage-class",
+                "関数には _Thread_local を指定できません。",
+                &token.borrow()
+            );
         }
-    }
-    // args.len() != arg_types.len() ならば引数名が省略されており、プロトタイプ宣言であるとみなせる
-    (args, arg_typs)
-}
-
-/// 生成規則:
-/// arg = type declarator
-fn arg(token_ptr: &mut TokenRef, args: &mut Vec<NodeRef>, arg_typs: &mut Vec<TypeCellRef>) {
-    let typ = expect_type(token_ptr);
-    let token = Rc::clone(token_ptr);
-    let (name, typ) = declarator(token_ptr, typ);
-    arg_typs.push(Rc::new(RefCell::new(typ.clone())));
-    if !name.is_empty() {
-        args.push(new_lvar(name, token, typ, true, 0));
+        function(token_ptr, typ, attr)
+    } else {
+        check_not_inline(&attr, &token);
+        global_variable(token_ptr, typ, attr)
     }
 }
 
 /// 生成規則:
-/// global-variable = gvar-decl ("," gvar-decl)* ";"
-fn global_variable(token_ptr: &mut TokenRef, typ: TypeCell) -> NodeRef {
-    let mut node_ptr = gvar_decl(token_ptr, typ.clone());
+/// declspec = (storage-class | qualifier | inline | alignment-specifier | type-specifier | attribute-list | "__extension__")+
+/// storage-class = "typedef" | "static" | "extern" | "_Thread_local" | "__thread"
+/// qualifier = "const" | "volatile" | "__volatile__" | "__volatile" | "restrict" | "__restrict" | "__restrict__" | "_Atomic"
+/// inline = "inline" | "__inline" | "__inline__"
+/// type-specifier = "void" | "char" | "short" | "int" | "long" | "float" | "double" | "signed" | "unsigned"
+///         | "__builtin_va_list" | struct-union-spec | typedef-name | atomic-type-spec
+/// restrict は最適化のための情報なので無視し、 __extension__ は GNU 拡張の警告を抑制するだけなので読み飛ばす
+fn declspec(token_ptr: &mut TokenRef) -> (TypeCell, VarAttr) {
+    let mut attr = VarAttr::default();
+    let mut specs: Vec<String> = vec![];
+    // 構造体・共用体や typedef 名による型は、他の型指定子と組み合わせられない
+    let mut base: Option<TypeCell> = None;
+    let spec_token = Rc::clone(token_ptr);
+    let (mut is_const, mut is_volatile, mut is_atomic) = (false, false, false);
     loop {
-        let comma_token = Rc::clone(token_ptr);
-        if !consume(token_ptr, ",") {
+        let token = Rc::clone(token_ptr);
+        if consume(token_ptr, "const") {
+            is_const = true;
+            continue;
+        } else if consume_volatile(token_ptr) {
+            is_volatile = true;
+            continue;
+        } else if is_atomic_type_spec(token_ptr) {
+            if base.is_some() || !specs.is_empty() {
+                error_with_token!(
+                    "invalid-type-specifier",
+                    "型指定子の組み合わせが不正です。",
+                    &token.borrow()
+                );
+            }
+            base = Some(atomic_type_spec(token_ptr));
+            continue;
+        } else if consume(token_ptr, "_Atomic") {
+            is_atomic = true;
+            continue;
+        } else if is_restrict(token_ptr) || consume(token_ptr, "__extension__") {
+            continue;
+        } else if consume(token_ptr, "inline")
+            || consume(token_ptr, "__inline")
+            || consume(token_ptr, "__inline__")
+        {
+            attr.is_inline = true;
+            continue;
+        } else if consume(token_ptr, "_Alignas") {
+            // 複数指定された場合は最も大きいものが有効になる
+            attr.align = attr.align.max(alignment_specifier(token_ptr));
+            continue;
+        } else if is(token_ptr, "__attribute__") {
+            attribute_list(token_ptr, &mut attr);
+            continue;
+        } else if consume(token_ptr, "_Noreturn") {
+            attr.is_noreturn = true;
+            continue;
+        } else if consume(token_ptr, "_Thread_local") || consume(token_ptr, "__thread") {
+            attr.is_thread_local = true;
+        } else if consume(token_ptr, "typedef") {
+            attr.is_typedef = true;
+        } else if consume(token_ptr, "static") {
+            attr.is_static = true;
+        } else if consume(token_ptr, "extern") {
+            attr.is_extern = true;
+        } else if let Some(spec) = consume_type_spec(token_ptr) {
+            specs.push(spec);
+            continue;
+        } else if is(token_ptr, "struct") || is(token_ptr, "union") {
+            if base.is_some() || !specs.is_empty() {
+                error_with_token!(
+                    "invalid-type-specifier",
+                    "型指定子の組み合わせが不正です。",
+                    &token.borrow()
+                );
+            }
+            base = Some(struct_union_spec(token_ptr));
+            continue;
+        } else if is_typeof(token_ptr) {
+            if base.is_some() || !specs.is_empty() {
+                error_with_token!(
+                    "invalid-type-specifier",
+                    "型指定子の組み合わせが不正です。",
+                    &token.borrow()
+                );
+            }
+            base = Some(typeof_spec(token_ptr));
+            continue;
+        } else if base.is_none() && specs.is_empty() && find_typedef(token_ptr).is_some() {
+            // typedef 名は、他の型指定子がまだない場合のみ型として読む(int T; のような宣言では変数名になる)
+            base = find_typedef(token_ptr);
+            token_ptr_exceed(token_ptr);
+            continue;
+        } else {
             break;
         }
-        node_ptr = new_binary(
-            Nodekind::Comma,
-            node_ptr,
-            gvar_decl(token_ptr, typ.clone()),
-            comma_token,
-        )
-    }
-    expect(token_ptr, ";");
-
-    node_ptr
-}
-
-/// 生成規則:
-/// gvar-decl = declarator ("=" initializer)?
-fn gvar_decl(token_ptr: &mut TokenRef, typ: TypeCell) -> NodeRef {
-    let token = Rc::clone(token_ptr);
-    let (name, typ) = declarator(token_ptr, typ);
-    if name.is_empty() {
-        error_with_token!("変数名を指定する必要があります。", &token.borrow());
-    }
-    if let Some(node) = GLOBALS.try_lock().unwrap().get(&name) {
-        let decl = node.token.as_ref().unwrap().borrow();
-        if node.typ.is_some() {
+        let n_storages = [attr.is_typedef, attr.is_static, attr.is_extern]
+            .iter()
+            .filter(|b| **b)
+            .count();
+        if n_storages > 1 {
             error_with_token!(
-                "\"{}\"は位置[{}, {}]で既にグローバル変数として宣言されています。",
-                &*token.borrow(),
-                name,
-                decl.line_num,
-                decl.line_offset
+                "invalid-storage-class",
+                "記憶域クラス指定子は1つのみ指定できます。",
+                &token.borrow()
             );
-        } else {
+        }
+        if attr.is_typedef && attr.is_thread_local {
             error_with_token!(
-                "\"{}\"は位置[{}, {}]で既に関数として宣言されています。",
-                &*token.borrow(),
-                name,
-                decl.line_num,
-                decl.line_offset
+                "invalid-storage-class",
+                "_Thread_local は typedef と同時に指定できません。",
+                &token.borrow()
             );
         }
     }
-
-    let is_flex = typ.is_flex_array();
-    if consume(token_ptr, "=") {
-        gvar_initializer(token_ptr, name, typ, is_flex, token)
+    let mut typ = if let Some(base) = base {
+        if !specs.is_empty() {
+            error_with_token!(
+                "invalid-type-specifier",
+                "型指定子の組み合わせが不正です。",
+                &spec_token.borrow()
+            );
+        }
+        base
     } else {
-        new_gvar(name, typ, token)
+        if specs.is_empty() {
+            error_with_token!(
+                "missing-type-specifier",
+                "型の指定が必要です。",
+                &*token_ptr.borrow()
+            );
+        }
+        type_specifier(&specs, &spec_token)
+    };
+    typ.is_const |= is_const;
+    typ.is_volatile |= is_volatile;
+    if is_atomic {
+        check_atomic(&typ, &spec_token);
+        typ.is_atomic = true;
     }
+    (typ, attr)
 }
 
-/// 生成規則としては lvar_initializer と同じ
-fn gvar_initializer(
-    token_ptr: &mut TokenRef,
-    name: String,
-    mut typ: TypeCell,
-    is_flex: bool,
-    token: TokenRef,
-) -> NodeRef {
-    if typ.is_array() && !is_kind(token_ptr, Tokenkind::String) && !is(token_ptr, "{") {
-        error_with_token!("配列の初期化の形式が異なります。", &token_ptr.borrow());
-    }
-    if typ.array_dim().0.len() > 1 && is_kind(token_ptr, Tokenkind::String) {
+/// "_Atomic" の直後に "(" が続く場合は型指定子
+fn is_atomic_type_spec(token_ptr: &mut TokenRef) -> bool {
+    let mut _token_ptr = Rc::clone(token_ptr);
+    consume(&mut _token_ptr, "_Atomic") && is(&mut _token_ptr, "(")
+}
+
+/// 生成規則:
+/// atomic-type-spec = "_Atomic" "(" type-name abstract-declarator ")"
+/// 修飾された型は指定できない
+fn atomic_type_spec(token_ptr: &mut TokenRef) -> TypeCell {
+    expect(token_ptr, "_Atomic");
+    expect(token_ptr, "(");
+    let token = Rc::clone(token_ptr);
+    let typ = type_name(token_ptr);
+    let mut typ = declarator(token_ptr, typ).1;
+    expect(token_ptr, ")");
+    if typ.is_const || typ.is_volatile || typ.is_atomic {
         error_with_token!(
-            "2次元以上の配列\"{}\"は単一の文字リテラルでは初期化できません。",
-            &*token_ptr.borrow(),
+            "invalid-atomic",
+            "_Atomic(...) には修飾された型\"{}\"を指定できません。",
+            &token.borrow(),
             typ
         );
     }
+    check_atomic(&typ, &token);
+    typ.is_atomic = true;
+    typ
+}
 
-    let init = initializer(token_ptr, typ.clone());
-    if is_flex {
-        let _ = typ.array_size.insert(init.flex_elem_count());
+/// 不可分に読み書きできるのは、1回のロード・ストアで扱える整数型とポインタ型のみとする
+fn check_atomic(typ: &TypeCell, token: &TokenRef) {
+    if !typ.is_integer() && !typ.is_pointer() {
+        error_with_token!(
+            "invalid-atomic",
+            "_Atomic は整数型かポインタ型にのみ指定できますが、型\"{}\"が指定されています。",
+            &token.borrow(),
+            typ
+        );
     }
-
-    let mut gvar = new_gvar(name, typ.clone(), token);
-    make_gvar_init(&init, typ, &mut gvar);
-    gvar
 }
 
-fn make_gvar_init(init: &Initializer, typ: TypeCell, gvar: &mut NodeRef) {
-    if typ.is_array() {
-        let total_bytes = typ.bytes();
-        let elem_typ = typ.make_deref().unwrap();
-        let elem_bytes = elem_typ.bytes();
-        let elem_flatten_size = elem_typ.flatten_size();
-        let base_typ = typ.get_base_cell();
-        let base_bytes = base_typ.bytes();
-        let mut ix = 0;
-        let mut finished_bytes = 0;
-        while finished_bytes < total_bytes && ix < init.elements.len() {
-            let elem = &init.elements[ix];
-            if elem.borrow().is_element() {
-                // flatten して読む
-                for _ in 0..elem_flatten_size {
-                    let _expr = init.elements[ix].borrow().node.clone().unwrap();
-                    let mut label: Option<String> = None;
-                    let val = eval_const(&_expr, &mut label);
-                    gvar.borrow_mut()
-                        .init_data
-                        .push(InitData::new(base_bytes, val, label));
-                    ix += 1;
-                    finished_bytes += base_bytes;
-                    if finished_bytes >= total_bytes || ix >= init.elements.len() {
-                        break;
-                    }
-                }
-            } else {
-                make_gvar_init(&elem.borrow(), elem_typ.clone(), gvar);
-                ix += 1;
-                finished_bytes += elem_bytes;
-            }
-            // 毎要素、後ろの0をまとめる
-            let mut gvar_bor = gvar.borrow_mut();
-            let init_data = &mut gvar_bor.init_data;
-            let last_data = init_data.last().unwrap();
-            let mut zero_end = last_data.val == 0 && last_data.label.is_none();
-            if zero_end {
-                let mut zeros = InitData::new(0, 0, None);
-                while zero_end && !init_data.is_empty() {
-                    let merge_data = init_data.pop().unwrap();
-                    zeros.size += merge_data.size;
-                    if let Some(last_data) = init_data.last() {
-                        zero_end = last_data.val == 0 && last_data.label.is_none();
-                    }
-                }
-                init_data.push(zeros);
-            }
-        }
-        // 初期化値が指定されていない残りの要素を0埋め
-        if finished_bytes < total_bytes {
-            gvar.borrow_mut()
-                .init_data
-                .push(InitData::new(total_bytes - finished_bytes, 0, None));
+/// 型指定子の組み合わせ(unsigned char, long long int など)から型を決める
+/// 順序は問わないが、それぞれの指定子の個数は決まった組み合わせしか許されない
+fn type_specifier(specs: &[String], token: &TokenRef) -> TypeCell {
+    let count = |spec: &str| specs.iter().filter(|s| *s == spec).count();
+    let check_len = |len: usize| {
+        if specs.len() != len {
+            error_with_token!(
+                "invalid-type-specifier",
+                "型指定子の組み合わせが不正です。",
+                &token.borrow()
+            );
         }
-    } else {
-        let mut label: Option<String> = None;
-        let val = eval_const(init.node.as_ref().unwrap(), &mut label);
-        gvar.borrow_mut()
-            .init_data
-            .push(InitData::new(typ.bytes(), val, label));
+    };
+    if count("__builtin_va_list") > 0 {
+        check_len(1);
+        return TypeCell::new_va_list();
+    }
+    if count("void") > 0 {
+        check_len(1);
+        return TypeCell::new(Type::Void);
+    }
+    if count("float") > 0 {
+        check_len(1);
+        return TypeCell::new(Type::Float);
+    }
+    if count("double") > 0 {
+        // long double のみ long と組み合わせられる
+        let long = count("long");
+        check_len(1 + long);
+        return TypeCell::new(if long == 1 {
+            Type::LDouble
+        } else {
+            Type::Double
+        });
     }
-}
 
-macro_rules! eval_const_left {
-    ($node: expr, $label: expr) => {
-        eval_const(($node).borrow().left.as_ref().unwrap(), ($label))
-    };
-}
+    let (signed, unsigned) = (count("signed"), count("unsigned"));
+    let (char_, short, int, long) = (count("char"), count("short"), count("int"), count("long"));
+    let is_valid = signed + unsigned <= 1
+        && int <= 1
+        && match (char_, short, long) {
+            (1, 0, 0) => int == 0,
+            (0, 1, 0) | (0, 0, 0) | (0, 0, 1) | (0, 0, 2) => true,
+            _ => false,
+        };
+    if !is_valid {
+        error_with_token!(
+            "invalid-type-specifier",
+            "型指定子の組み合わせが不正です。",
+            &token.borrow()
+        );
+    }
 
-macro_rules! eval_const_right {
-    ($node: expr, $label: expr) => {
-        eval_const(($node).borrow().right.as_ref().unwrap(), ($label))
+    // long long は long と同じ8バイトの整数として扱う
+    let base = if char_ > 0 {
+        Type::Char
+    } else if short > 0 {
+        Type::Short
+    } else if long > 0 {
+        Type::Long
+    } else {
+        Type::Int
     };
+    let mut typ = TypeCell::new(base);
+    typ.is_unsigned = unsigned > 0;
+    typ
 }
 
-/// コンパイル時定数の処理を行う関数
-/// label はグローバル変数への参照があった場合にどの変数を参照しているかを持つ
-fn eval_const(node: &NodeRef, label: &mut Option<String>) -> i64 {
-    confirm_type(node);
-    let typ = node.borrow().typ.clone().unwrap();
-    let kind = node.borrow().kind;
-    match kind {
-        Nodekind::Add => eval_const_left!(node, label) + eval_const_right!(node, label),
-        Nodekind::Sub => {
-            let left_val = eval_const_left!(node, label);
-            let left_label = label.clone();
-            let right_val = eval_const_right!(node, label);
-            if let (Some(l), Some(r)) = (left_label, &label) {
-                    // コンパイル時のポインタ同士の引き算は、同じラベル同士でのみ可能(打ち消し合うのでラベルをクリアする)
-                    if l != *r {
-                        error_with_node!(
-                            "コンパイル時定数のみが使用可能です。",
-                            &node.borrow().right.as_ref().unwrap().borrow()
-                        );
-                    }
-                    let _ = label.take();
-            }
-            left_val - right_val
-        }
-        Nodekind::Mul => eval_const_left!(node, label) * eval_const_right!(node, label),
-        Nodekind::Div => eval_const_left!(node, label) / eval_const_right!(node, label),
-        Nodekind::Mod => eval_const_left!(node, label) % eval_const_right!(node, label),
-        Nodekind::LShift => eval_const_left!(node, label) << eval_const_right!(node, label),
-        Nodekind::RShift => eval_const_left!(node, label) >> eval_const_right!(node, label),
-        Nodekind::BitAnd => eval_const_left!(node, label) & eval_const_right!(node, label),
-        Nodekind::BitOr => eval_const_left!(node, label) | eval_const_right!(node, label),
-        Nodekind::BitXor => eval_const_left!(node, label) ^ eval_const_right!(node, label),
-        Nodekind::BitNot => !eval_const_left!(node, label),
-        Nodekind::LogAnd => !(eval_const_left!(node, label) == 0 || eval_const_right!(node, label) == 0) as i64,
-        Nodekind::LogOr => !(eval_const_left!(node, label) == 0 && eval_const_right!(node, label) == 0) as i64,
-        Nodekind::LogNot => (eval_const_left!(node, label) == 0) as i64,
-        Nodekind::Cast => {
-            let val = eval_const_left!(node, label);
-            match typ.typ.bytes() {
-                1 => {
-                    if typ.is_unsigned {
-                        val as u8 as i64
-                    } else {
-                        val as i8 as i64
-                    }
-                }
-                2 => {
-                    if typ.is_unsigned {
-                        val as u16 as i64
-                    } else {
-                        val as i16 as i64
-                    }
-                }
-                4 => {
-                    if typ.is_unsigned {
-                        val as u32 as i64
-                    } else {
-                        val as i32 as i64
-                    }
-                }
-                _ => val,
-            }
-        }
-        Nodekind::Addr => eval_label(node.borrow().left.as_ref().unwrap(), label),
-        Nodekind::Num => node.borrow().val.unwrap() as i64,
-        _ => {
-            error_with_node!("コンパイル時定数のみが使用可能です。", &node.borrow());
+/// 生成規則:
+/// alignment-specifier = "_Alignas" "(" (type-name abstract-declarator | const-expr) ")"
+/// 0 を指定した場合は指定しなかったものとして扱う
+fn alignment_specifier(token_ptr: &mut TokenRef) -> Option<usize> {
+    expect(token_ptr, "(");
+    let token = Rc::clone(token_ptr);
+    let align = if is_type_name(token_ptr) {
+        let typ = type_name(token_ptr);
+        declarator(token_ptr, typ).1.align()
+    } else {
+        let val = const_expr(token_ptr);
+        if val < 0 || (val as u64).count_ones() > 1 {
+            error_with_token!(
+                "invalid-alignment",
+                "アラインメントには0または2の累乗を指定する必要があります。",
+                &token.borrow()
+            );
         }
+        val as usize
+    };
+    expect(token_ptr, ")");
+    if align == 0 {
+        None
+    } else {
+        Some(align)
     }
 }
 
-/// グローバル変数のアドレス評価時などに使用する関数
-fn eval_label(node: &NodeRef, label: &mut Option<String>) -> i64 {
-    let kind = node.borrow().kind;
-    match kind {
-        Nodekind::Deref => {
-            eval_const_left!(node, label)
-        }
-        Nodekind::Lvar => {
-            // 初期化時に仮で生成される変数はグローバルスコープでも(!is_local な) Lvar であることに注意
-            // また、 Initializer のパース時に定義されていないグローバル変数は弾かれるため、ここでは宣言チェック不要
-            if node.borrow().is_local {
-                error_with_node!("コンパイル時定数のみが使用可能です。", &node.borrow());
-            }
-            let _ = label.insert(node.borrow().name.clone().unwrap());
-            0
+/// _Alignas と aligned 属性で指定されたアラインメントを宣言した変数の型に反映する
+/// _Alignas では型本来のアラインメントより弱いものは指定できず(C11 6.7.5)、 aligned 属性では単に無視する
+fn apply_alignas(typ: &mut TypeCell, attr: &VarAttr, token: &TokenRef) {
+    if let Some(align) = attr.align {
+        if align < typ.align() {
+            error_with_token!(
+                "invalid-alignment",
+                "型\"{}\"のアラインメント{}より小さいアラインメント{}は指定できません。",
+                &token.borrow(),
+                typ,
+                typ.align(),
+                align
+            );
         }
-        _ => {
-            error_with_node!("コンパイル時定数のみが使用可能です。", &node.borrow());
+        let _ = typ.align.insert(align);
+    }
+    if let Some(align) = attr.aligned {
+        if align > typ.align() {
+            let _ = typ.align.insert(align);
         }
     }
 }
 
-/// 生成規則:
-/// declarator = pointers ("(" declarator ")" | ident ) type-suffix
-fn declarator(token_ptr: &mut TokenRef, mut typ: TypeCell) -> (String, TypeCell) {
-    typ = pointers(token_ptr, typ);
-    if consume(token_ptr, "(") {
-        let token = &mut Rc::clone(token_ptr);
-        let dummy = TypeCell::default();
-        let _ = declarator(token, dummy);
-        expect(token, ")");
-        typ = type_suffix(token, typ);
-        let nt = declarator(token_ptr, typ);
-        *token_ptr = token.clone();
+/// 宣言の始まりになるトークンかどうか(式の前に置かれることもある __extension__ は読み飛ばして判断する)
+#[inline]
+fn is_declspec(token_ptr: &mut TokenRef) -> bool {
+    let token_ptr = &mut Rc::clone(token_ptr);
+    while consume(token_ptr, "__extension__") {}
+    is_type_name(token_ptr)
+        || is(token_ptr, "_Alignas")
+        || is(token_ptr, "typedef")
+        || is(token_ptr, "static")
+        || is(token_ptr, "extern")
+        || is(token_ptr, "inline")
+        || is(token_ptr, "__inline")
+        || is(token_ptr, "__inline__")
+        || is(token_ptr, "__attribute__")
+        || is(token_ptr, "_Noreturn")
+        || is(token_ptr, "_Thread_local")
+        || is(token_ptr, "__thread")
+}
 
-        nt
-    } else {
-        let name = if let Some(n) = consume_ident(token_ptr) {
-            n
-        } else {
-            String::new()
-        };
-        typ = type_suffix(token_ptr, typ);
+/// 型名の始まりになるトークン(型指定子または型修飾子)かどうか
+#[inline]
+fn is_type_name(token_ptr: &mut TokenRef) -> bool {
+    is_type(token_ptr)
+        || is(token_ptr, "const")
+        || is(token_ptr, "volatile")
+        || is(token_ptr, "__volatile__")
+        || is(token_ptr, "__volatile")
+        || is(token_ptr, "restrict")
+        || is(token_ptr, "__restrict")
+        || is(token_ptr, "__restrict__")
+        || is(token_ptr, "_Atomic")
+        || is(token_ptr, "struct")
+        || is(token_ptr, "union")
+        || is_typeof(token_ptr)
+        || find_typedef(token_ptr).is_some()
+}
 
-        (name, typ)
-    }
+#[inline]
+fn is_typeof(token_ptr: &mut TokenRef) -> bool {
+    is(token_ptr, "typeof") || is(token_ptr, "__typeof__") || is(token_ptr, "__typeof")
 }
 
 /// 生成規則:
-/// pointers = ("*")*
-fn pointers(token_ptr: &mut TokenRef, mut typ: TypeCell) -> TypeCell {
-    while consume(token_ptr, "*") {
-        typ = typ.make_ptr_to();
-    }
+/// typeof-spec = ("typeof" | "__typeof__" | "__typeof") "(" (type-name abstract-declarator | expr) ")"
+/// 式は型を求めるためだけに読み、評価はしない(配列や関数の型はポインタに変換されない)
+fn typeof_spec(token_ptr: &mut TokenRef) -> TypeCell {
+    token_ptr_exceed(token_ptr);
+    expect(token_ptr, "(");
+    let typ = if is_type_name(token_ptr) {
+        let typ = type_name(token_ptr);
+        declarator(token_ptr, typ).1
+    } else {
+        let node_ptr = expr(token_ptr);
+        confirm_type(&node_ptr);
+        let typ = node_ptr.borrow().typ.clone().unwrap();
+        typ
+    };
+    expect(token_ptr, ")");
     typ
 }
 
-/// 生成規則:
-/// type-suffix = "(" func-args ")" | "[" array-suffix | null
-fn type_suffix(token_ptr: &mut TokenRef, typ: TypeCell) -> TypeCell {
-    if consume(token_ptr, "(") {
-        let (_, arg_typs) = func_args(token_ptr);
-        expect(token_ptr, ")");
-        typ.make_func(arg_typs)
-    } else if consume(token_ptr, "[") {
-        array_suffix(token_ptr, typ)
+#[inline]
+fn consume_volatile(token_ptr: &mut TokenRef) -> bool {
+    consume(token_ptr, "volatile")
+        || consume(token_ptr, "__volatile__")
+        || consume(token_ptr, "__volatile")
+}
+
+/// restrict 修飾子を読み飛ばす
+#[inline]
+fn is_restrict(token_ptr: &mut TokenRef) -> bool {
+    consume(token_ptr, "restrict")
+        || consume(token_ptr, "__restrict")
+        || consume(token_ptr, "__restrict__")
+}
+
+/// typedef 名や構造体のタグを宣言するスコープのレベル: ファイルスコープでは None
+/// 関数の外でも仮引数リストの中はファイルスコープではないことに注意
+fn decl_level() -> Option<usize> {
+    if CURRENT_FUNC.try_lock().unwrap().is_none() && *PARAM_DEPTH.try_lock().unwrap() == 0 {
+        None
     } else {
-        typ
+        Some(current_scope())
+    }
+}
+
+/// トークンが typedef 名であればその型を返す
+/// typedef 名より内側のスコープで同じ名前の変数が宣言されている場合は、変数の方が見える
+fn find_typedef(token_ptr: &TokenRef) -> Option<TypeCell> {
+    let token = token_ptr.borrow();
+    if token.kind != Tokenkind::Ident {
+        return None;
+    }
+    let name = token.body.as_ref().unwrap();
+    let (level, typ) = TYPEDEFS
+        .try_lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|(_, n, _)| n == name)
+        .map(|(level, _, typ)| (*level, typ.clone()))?;
+    let shadowed = LOCALS
+        .try_lock()
+        .unwrap()
+        .iter()
+        .enumerate()
+        .any(|(l, scope)| level.is_none_or(|level| l > level) && scope.contains_key(name));
+    if shadowed {
+        None
+    } else {
+        Some(typ)
     }
 }
 
 /// 生成規則:
-/// declaration = type lvar-decl ("," lvar-decl )* ";"
-fn declaration(token_ptr: &mut TokenRef) -> NodeRef {
-    let typ = expect_type(token_ptr);
-    let mut node_ptr = lvar_decl(token_ptr, typ.clone());
+/// typedef-declaration = declarator ("," declarator)* ";"
+/// 同じスコープでの再宣言は、同じ型の場合のみ許される
+fn typedef_declaration(token_ptr: &mut TokenRef, typ: TypeCell) -> NodeRef {
+    let level = decl_level();
     loop {
         let token = Rc::clone(token_ptr);
-        if !consume(token_ptr, ",") {
-            break;
+        let mut attr = VarAttr::default();
+        let (name, mut typ) = declarator_attr(token_ptr, typ.clone(), &mut attr);
+        if name.is_empty() {
+            error_with_token!(
+                "missing-declarator",
+                "typedef 名を指定する必要があります。",
+                &token.borrow()
+            );
+        }
+        apply_alignas(&mut typ, &attr, &token);
+        let mut typedefs = TYPEDEFS.try_lock().unwrap();
+        if let Some((_, _, prev)) = typedefs.iter().find(|(l, n, _)| *l == level && *n == name) {
+            if !prev.is_same(&typ) {
+                error_with_token!(
+                    "conflicting-types",
+                    "typedef 名\"{}\"は型\"{}\"として宣言されています。",
+                    &token.borrow(),
+                    name,
+                    prev
+                );
+            }
+        } else {
+            typedefs.push((level, name, typ));
+        }
+        if !consume(token_ptr, ",") {
+            break;
         }
-        node_ptr = new_binary(
-            Nodekind::Comma,
-            node_ptr,
-            lvar_decl(token_ptr, typ.clone()),
-            token,
-        )
     }
     expect(token_ptr, ";");
-
-    node_ptr
+    // ブロックスコープでは typedef int A[n]; のように可変長配列の型も宣言でき、その大きさは宣言の位置で計算する
+    with_vla_sizes(nop())
 }
 
 /// 生成規則:
-/// lvar-decl = declarator ("=" initializer)?
-fn lvar_decl(token_ptr: &mut TokenRef, typ: TypeCell) -> NodeRef {
-    let token = Rc::clone(token_ptr);
-    let (name, typ) = declarator(token_ptr, typ);
-    if !name.is_empty() {
-        error_with_token!("変数名を指定する必要があります。", &token.borrow());
-    }
-    if LOCALS
-        .try_lock()
-        .unwrap()
-        .last()
-        .unwrap()
-        .contains_key(&name)
-    {
-        error_with_token!("既に宣言された変数です。", &token.borrow());
+/// struct-union-spec = ("struct" | "union") attribute-list (ident? "{" struct-members "}" attribute-list | ident)
+/// 同じタグの型は同じ実体を共有する(タグのみの参照は、見えるスコープにあるタグの型を使う)
+/// packed 属性はメンバを詰めて配置し、 aligned 属性は構造体・共用体全体のアラインメントを強める
+fn struct_union_spec(token_ptr: &mut TokenRef) -> TypeCell {
+    let is_union = consume(token_ptr, "union");
+    if !is_union {
+        expect(token_ptr, "struct");
     }
+    let mut attr = VarAttr::default();
+    attribute_list(token_ptr, &mut attr);
+    let token = Rc::clone(token_ptr);
+    let tag = consume_ident(token_ptr);
+    let level = decl_level();
 
-    let is_flex = typ.is_flex_array();
-    if consume(token_ptr, "=") {
-        lvar_initializer(token_ptr, name, typ, is_flex, token)
-    } else {
-        // 初期化しない場合は何もアセンブリを吐かない
-        if is_flex {
+    if !is(token_ptr, "{") {
+        let tag = if let Some(tag) = tag {
+            tag
+        } else {
             error_with_token!(
-                "初期化しない場合は完全な配列サイズが必要です。",
+                "missing-tag",
+                "構造体・共用体にはタグか定義が必要です。",
                 &token.borrow()
             );
-        }
-        let _ = new_lvar(name, token, typ, true, current_scope());
-        nop()
+        };
+        // struct S; のような前方宣言は、外側のスコープに同じタグがあっても現在のスコープで新たに宣言する
+        let prev = find_tag(&tag, is(token_ptr, ";").then_some(level));
+        return if let Some(typ) = prev {
+            check_tag_kind(&typ, is_union, &tag, &token);
+            typ
+        } else {
+            declare_tag(tag, is_union, level)
+        };
     }
-}
-
-/// 生成規則:
-/// array-suffix = const-expr? "]" ("[" array-suffix)?
-fn array_suffix(token_ptr: &mut TokenRef, mut typ: TypeCell) -> TypeCell {
-    let ptr_err = Rc::clone(token_ptr);
 
-    let array_size = if consume(token_ptr, "]") {
-        None
-    } else {
-        let size = const_expr(token_ptr);
-        if size < 0 {
-            error_with_token!(
-                "配列のサイズは0以上である必要があります。",
-                &ptr_err.borrow()
-            );
+    // 同じスコープで前方宣言されていれば、その型を完全型にする
+    let typ = if let Some(tag) = tag {
+        match find_tag(&tag, Some(level)) {
+            Some(typ) => {
+                check_tag_kind(&typ, is_union, &tag, &token);
+                if !typ.is_incomplete() {
+                    error_with_token!(
+                        "redefinition",
+                        "構造体・共用体\"{}\"は既に定義されています。",
+                        &token.borrow(),
+                        typ
+                    );
+                }
+                typ
+            }
+            None => declare_tag(tag, is_union, level),
         }
-        expect(token_ptr, "]");
-        Some(size)
+    } else {
+        TypeCell::new_struct(None, is_union)
     };
+    expect(token_ptr, "{");
+    let members = struct_members(token_ptr);
+    attribute_list(token_ptr, &mut attr);
+    typ.struct_info.as_ref().unwrap().borrow_mut().complete(
+        members,
+        is_union,
+        attr.is_packed,
+        attr.aligned,
+    );
+    typ
+}
 
-    // 配列の次元は後ろから処理する
-    if consume(token_ptr, "[") {
-        let ptr_err = Rc::clone(token_ptr);
-        if consume(token_ptr, "]") {
-            error_with_token!(
-                "2次元目以降の要素サイズは必ず指定する必要があります。",
-                &ptr_err.borrow()
-            );
-        }
-        typ = array_suffix(token_ptr, typ);
-    }
-
-    if let Some(size) = array_size {
-        typ.make_array_of(size as usize)
-    } else {
-        typ.make_flex_array_of()
-    }
+/// 見えるスコープにあるタグを探す(level を指定した場合はそのスコープで宣言されたもののみ)
+fn find_tag(tag: &str, level: Option<Option<usize>>) -> Option<TypeCell> {
+    TAGS.try_lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|(l, n, _)| n == tag && level.is_none_or(|level| *l == level))
+        .map(|(_, _, typ)| typ.clone())
 }
 
-fn const_expr(token_ptr: &mut TokenRef) -> i64 {
-    let node_ptr = &expr(token_ptr);
-    let label = &mut None;
-    let val = eval_const(node_ptr, label);
-    if label.is_some() {
-        error_with_node!("定数ではありません", &node_ptr.borrow());
-    }
-    val
+fn declare_tag(tag: String, is_union: bool, level: Option<usize>) -> TypeCell {
+    let typ = TypeCell::new_struct(Some(tag.clone()), is_union);
+    TAGS.try_lock().unwrap().push((level, tag, typ.clone()));
+    typ
 }
 
-/// 規則 initializer により Initializer を生成し、Assign による代入へと変換する
-fn lvar_initializer(
-    token_ptr: &mut TokenRef,
-    name: String,
-    mut typ: TypeCell,
-    is_flex: bool,
-    token: TokenRef,
-) -> NodeRef {
-    if typ.is_array() && !is_kind(token_ptr, Tokenkind::String) && !is(token_ptr, "{") {
-        error_with_token!("配列の初期化の形式が異なります。", &token_ptr.borrow());
-    }
-    if typ.array_dim().0.len() > 1 && is_kind(token_ptr, Tokenkind::String) {
+/// 同じタグを struct と union の両方で使うことはできない
+fn check_tag_kind(typ: &TypeCell, is_union: bool, tag: &str, token: &TokenRef) {
+    if (typ.typ == Type::Union) != is_union {
         error_with_token!(
-            "2次元以上の配列\"{}\"は単一の文字リテラルでは初期化できません。",
-            &*token_ptr.borrow(),
+            "conflicting-types",
+            "タグ\"{}\"は\"{}\"として宣言されています。",
+            &token.borrow(),
+            tag,
             typ
         );
     }
+}
 
-    let init = initializer(token_ptr, typ.clone());
-    if is_flex {
-        let _ = typ.array_size.insert(init.flex_elem_count());
-    }
-
-    let lvar = new_lvar(name, Rc::clone(&token), typ.clone(), true, current_scope());
-    let offset = lvar.borrow().offset.unwrap();
-    match typ.typ {
-        Type::Array => {
-            let zero_clear = new_unary(Nodekind::ZeroClr, lvar, Rc::clone(&token));
-            new_binary(
-                Nodekind::Comma,
-                zero_clear,
-                make_lvar_init(init, typ, offset, false, Rc::clone(&token)),
-                token,
-            )
-        }
-        _ => make_lvar_init(init, typ, offset, true, token),
+/// 生成規則:
+/// struct-members = (static-assert | declspec (member-declarator ("," member-declarator)*)? ";")*
+/// member-declarator = declarator
+/// 宣言子のない構造体・共用体のメンバは、名前のないメンバとしてそのメンバを外側に展開する
+/// 最後のメンバのみ、要素数を省略した配列(フレキシブル配列メンバ)にできる
+/// メンバの宣言でエラーが発生した場合は次の宣言まで読み飛ばし、それまでに読んだメンバで完全型にする
+/// (後でこの型の変数を宣言したときに、不完全型のエラーが連鎖しないようにするため)
+fn struct_members(token_ptr: &mut TokenRef) -> Vec<(Option<String>, TypeCell)> {
+    let mut members: Vec<(Option<String>, TypeCell)> = vec![];
+    let mut names: Vec<String> = vec![];
+    let mut has_flex = false;
+    while !consume(token_ptr, "}") {
+        recover(token_ptr, |token_ptr| {
+            member_decl(token_ptr, &mut members, &mut names, &mut has_flex)
+        });
     }
+    members
 }
 
 /// 生成規則:
-/// initializer = "{" array-initializer | char-array-initializer | assign
-fn initializer(token_ptr: &mut TokenRef, typ: TypeCell) -> Initializer {
-    // char の1次元配列のみ文字列リテラルで初期化できるため、特別扱い
-    if typ.is_char_1d_array() {
-        // string-literal か "{" string-literal "}" の形であれば char-array-initializer を呼ぶ(トークンの先読みが必要なため、clone してから読んでいることに注意)
-        let mut _token_ptr = Rc::clone(token_ptr);
-        let braced = consume(&mut _token_ptr, "{");
-        let token = Rc::clone(&_token_ptr);
-        if let Some(body) = consume_literal(&mut _token_ptr) {
-            if braced {
-                expect(token_ptr, "{");
-            }
-            let _ = expect_literal(token_ptr);
-            let init = char_array_initializer(body, typ.array_size, token);
-            let _ = consume(token_ptr, ",");
-            if braced && !consume(token_ptr, "}") {
-                error_with_token!(
-                    "char の1次元配列を文字列リテラルで初期化する場合は1つのみ配置してください。",
-                    &token_ptr.borrow()
-                );
-            }
-            return init;
+/// member-decl = static-assert | declspec (member-declarator ("," member-declarator)*)? ";"
+/// 読んだメンバは、宣言の終わりの ";" を読む前に members に追加する
+fn member_decl(
+    token_ptr: &mut TokenRef,
+    members: &mut Vec<(Option<String>, TypeCell)>,
+    names: &mut Vec<String>,
+    has_flex: &mut bool,
+) {
+    if is(token_ptr, "_Static_assert") {
+        static_assert(token_ptr);
+        return;
+    }
+    let token = Rc::clone(token_ptr);
+    let (base, attr) = declspec(token_ptr);
+    if attr.is_typedef || attr.is_static || attr.is_extern || attr.is_thread_local || attr.is_inline
+    {
+        error_with_token!(
+            "invalid-storage-class",
+            "構造体・共用体のメンバには記憶域クラスを指定できません。",
+            &token.borrow()
+        );
+    }
+    if base.is_struct() && consume(token_ptr, ";") {
+        for member in &base.struct_info.as_ref().unwrap().borrow().members {
+            check_member_name(names, &member.name, &token);
         }
+        members.push((None, base));
+        return;
     }
-
-    let mut init = Initializer::default();
-    if consume(token_ptr, "{") {
-        if typ.is_non_array() {
-            // スカラ値に代入することになるため、最初の要素以外読み飛ばす
-            let mut _init = Initializer::default();
-            let arr_init = array_initializer(token_ptr, typ.clone());
-            init.insert(typ, Rc::clone(arr_init.node.as_ref().unwrap()));
-            init
-        } else {
-            array_initializer(token_ptr, typ)
+    loop {
+        let token = Rc::clone(token_ptr);
+        let mut attr = attr.clone();
+        let (name, mut typ) = declarator_attr(token_ptr, base.clone(), &mut attr);
+        if name.is_empty() {
+            error_with_token!(
+                "missing-declarator",
+                "メンバ名を指定する必要があります。",
+                &token.borrow()
+            );
         }
-    } else {
-        if is_kind(token_ptr, Tokenkind::String)
-            && typ.is_array()
-            && !typ
-                .make_deref()
-                .unwrap()
-                .is_one_of(&[Type::Char, Type::Ptr, Type::Array])
-        {
+        // packed 属性を指定したメンバは、アラインメントを1として詰めて配置する
+        if attr.is_packed {
+            let _ = typ.align.insert(1);
+        }
+        apply_alignas(&mut typ, &attr, &token);
+        if *has_flex {
             error_with_token!(
-                "文字列リテラルで\"{}\"型の変数を初期化することはできません",
-                &*token_ptr.borrow(),
+                "invalid-member",
+                "フレキシブル配列メンバは最後のメンバである必要があります。",
+                &token.borrow()
+            );
+        }
+        if typ.has_vla() || !VLA_SIZES.try_lock().unwrap().is_empty() {
+            error_with_token!(
+                "invalid-member",
+                "構造体・共用体のメンバには可変長配列を使用できません。",
+                &token.borrow()
+            );
+        }
+        if typ.typ == Type::Func || is_incomplete_object(&typ) {
+            error_with_token!(
+                "invalid-member",
+                "型\"{}\"のメンバは宣言できません。",
+                &token.borrow(),
                 typ
             );
         }
-        init.insert(typ, assign(token_ptr));
-        init
+        *has_flex = typ.is_flex_array();
+        check_member_name(names, &name, &token);
+        members.push((Some(name), typ));
+        if !consume(token_ptr, ",") {
+            break;
+        }
+    }
+    expect(token_ptr, ";");
+}
+
+fn check_member_name(names: &mut Vec<String>, name: &str, token: &TokenRef) {
+    if names.iter().any(|n| n == name) {
+        error_with_token!(
+            "duplicate-member",
+            "メンバ\"{}\"は既に宣言されています。",
+            &token.borrow(),
+            name
+        );
     }
+    names.push(name.to_string());
 }
 
 /// 生成規則:
-/// char-array-initializer = string-literal
-fn char_array_initializer(body: String, array_size: Option<usize>, token: TokenRef) -> Initializer {
-    let mut init = Initializer::default();
-    let elems = body.as_bytes().iter().map(|c| *c as i32);
-    let elem_typ = TypeCell::new(Type::Char);
-    let size = if let Some(_size) = array_size {
-        // 配列は、どんな型であれ初期値の指定がない箇所は0で初期化されるため、固定長の場合は終端'\0'としての (int)0 を生成するノードは不要
-        let mut ix: usize = 0;
-        for e in elems {
-            if ix >= _size {
-                break;
+/// attribute-list = ("__attribute__" "(" "(" (attribute ("," attribute)*)? ")" ")")*
+/// 読んだ属性は attr に反映する
+fn attribute_list(token_ptr: &mut TokenRef, attr: &mut VarAttr) {
+    while consume(token_ptr, "__attribute__") {
+        expect(token_ptr, "(");
+        expect(token_ptr, "(");
+        if !consume(token_ptr, ")") {
+            loop {
+                attribute(token_ptr, attr);
+                if !consume(token_ptr, ",") {
+                    break;
+                }
             }
-            ix += 1;
-            init.push_element(Initializer::new(
-                elem_typ.clone(),
-                new_num(e, Rc::clone(&token)),
-            ));
-        }
-        while ix < _size {
-            ix += 1;
-            // 0 パディング
-            init.push_element(Initializer::new(
-                elem_typ.clone(),
-                new_num(0, Rc::clone(&token)),
-            ));
-        }
-        _size
-    } else {
-        for e in elems {
-            init.push_element(Initializer::new(
-                elem_typ.clone(),
-                new_num(e, Rc::clone(&token)),
-            ));
-        }
-        init.push_element(Initializer::new(
-            elem_typ.clone(),
-            new_num(0, Rc::clone(&token)),
-        ));
-        init.elements.len()
-    };
-    // この関数が呼ばれている時点でネストが深すぎるということはないため、ここで持たせる node はなんでも良い
-    init.insert(elem_typ.make_array_of(size), new_num(0, token));
-    init
+            expect(token_ptr, ")");
+        }
+        expect(token_ptr, ")");
+    }
 }
 
-// 配列の初期化について
-// 要素数が足りない時:
-// "int x[3] = {1, 2};" -> x[0] = 1, x[1] = 2, x[2] = 0;
-// - これは単に0埋めである
-//
-// ネストが浅すぎる時:
-// "int x[2][2] = {1, 2};" or "int x[2][2] = {1, {2, 1}}" -> x[0][0] = 1, x[0][1] = 2;
-// - これは opening brace で始まらない要素があると、そこからそのレベルの1要素におけるベース型の格納個数だけ要素を読むという処理に起因している
-// - 例えば、 int x[4][2][1] = {1, {2, 3}, 4, 5, {6}, 7, 8, 9}; は
-// - int x[4][2][1] = {{{1}, {2}}, {{4}, {5}}, {{6}, {0}}, {{7}, {8}}}; と同じ
-//
-// ネストが深すぎる時:
-// "int x[2][2] = {{{1, 2, 3}, 10}, 20};" -> x[0][0] = 1, x[0][1] = 10, x[1][0] = 20;
-// - これは、それ以上の sub-array がない場合には先頭の要素のみを扱うことになっている
-// - 例えば、 int x = {{2, 3}, 4}; なども valid であり、これは単に int x = 2; と同じ
-//
-// 文字列リテラルによる初期化のルール
-// [`char str[] = "abc"; と char str[] = {"abc"};`] は char str[] = {'a', 'b', 'c', '\0'}; と同じ(1つめが例外的表現)
-// 「ネストが深すぎる時」に該当する場合を除き、文字列リテラルを char 配列以外の初期化に使用することはできない
-// また、2次以上の配列を中括弧なしの文字列で初期化することはできない
-//
-// ネストが浅すぎる時:
-// char str[]~[2] = {"abc~", "~", ...}; のようなパターンだと、最下位レベルの配列要素の数まで各リテラルを打ち切り、各リテラルと同じレベルに展開する
-// - 例えば、 char str[][2][2] = {"abc", "def", "ghi"}; とするとこの初期化は {'a', 'b', 'd', 'e', 'g', 'h'} と同じ
-// - この時、 {{'a', 'b'}, {'d', 'e'}, {'g', 'h'}} とはならないことに注意
-// - よって、基本的な初期化のルールに従って char str[2][2][2] = {{{'a', 'b'}, {'d', 'e'}}, {{'g', 'h'}, {}}}; と同様の初期化であると解釈される
-//
-// ネストが深すぎる時:
-// 単にその文字列リテラルへのポインタを要素として代入することになり、冗長な要素の読み飛ばしは基本的なネストのルールに従う
-// - 例えば、 char str[][2] = {{{"abc"}}, "def"}; は {{"abc", 0}, 'd', 'e'} すなわち {{(char)&.LC0, 0}, {'d', 'e'}} である
-// - これは make_lvar_init など Initializer を Node に変換する時に処理するものとする
-//
-// また、char[] を文字列で初期化する場合に、例えば
-// char str[] = {"abc", "def"};
-// はスカラの初期化と同様に2つ目の要素を飛ばせばよさそうに見えるが、 gcc では コンパイルエラーとなる。
-// gcc では char str[] = "abc", "def"; のようにパースされているのかもしれないが、よく分からない。
-// clang では3行上の例は valid な文法としてコンパイル可能。
-//
 /// 生成規則:
-/// array-initializer = (initializer ("," initializer)* ","? "}"
-/// C99 以降の designator は現段階ではサポートしない
-fn array_initializer(token_ptr: &mut TokenRef, typ: TypeCell) -> Initializer {
-    let mut init = Initializer::default();
-    let elem_typ = if let Ok(_typ) = typ.make_deref() {
-        _typ
-    } else {
-        typ.clone()
+/// attribute = attribute-name ("(" balanced-tokens ")")?
+/// 属性名は noreturn と __noreturn__ のどちらの形でも良く、 const のような予約語も使われる
+/// 解釈しない属性は引数ごと読み飛ばす
+fn attribute(token_ptr: &mut TokenRef, attr: &mut VarAttr) {
+    let token = Rc::clone(token_ptr);
+    let body = match token.borrow().kind {
+        Tokenkind::Ident | Tokenkind::Reserved => token.borrow().body.clone().unwrap(),
+        _ => {
+            error_with_token!("invalid-attribute", "属性名が必要です。", &token.borrow());
+        }
     };
-    loop {
-        if is(token_ptr, "{") || elem_typ.is_non_array() {
-            init.push_element(initializer(token_ptr, elem_typ.clone()));
-        } else {
-            // この深さではまだ配列が来るべきであるにも関わらず、初期化文のネストが浅かった場合の処理
-            let (base_typ, elem_flatten_size) = if is_kind(token_ptr, Tokenkind::String)
-                && elem_typ.get_base_cell().typ != Type::Ptr
-            {
-                // 文字列リテラルかつ最小要素の型がポインタでない場合は、ベースの型を1次元配列とみなして読む(型チェックは initializer() で行うためここではスルー)
-                let _typ = elem_typ.get_last_level_array().unwrap();
-                let _flatten_size = elem_typ.flatten_size() / _typ.array_size.unwrap();
-                (_typ, _flatten_size)
+    if !body.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        error_with_token!("invalid-attribute", "属性名が必要です。", &token.borrow());
+    }
+    token_ptr_exceed(token_ptr);
+    let name = body
+        .strip_prefix("__")
+        .and_then(|b| b.strip_suffix("__"))
+        .unwrap_or(&body);
+    match name {
+        "noreturn" => attr.is_noreturn = true,
+        "packed" => attr.is_packed = true,
+        "weak" => attr.is_weak = true,
+        "used" => attr.is_used = true,
+        "unused" => attr.is_unused = true,
+        // 引数のない aligned は、そのターゲットでの最大のアラインメントになる
+        "aligned" => {
+            let align = if consume(token_ptr, "(") {
+                let align_token = Rc::clone(token_ptr);
+                let val = const_expr(token_ptr);
+                if val <= 0 || (val as u64).count_ones() > 1 {
+                    error_with_token!(
+                        "invalid-alignment",
+                        "aligned 属性には2の累乗を指定する必要があります。",
+                        &align_token.borrow()
+                    );
+                }
+                expect(token_ptr, ")");
+                val as usize
             } else {
-                (elem_typ.get_base_cell(), elem_typ.flatten_size())
+                16
             };
-            for _ in 0..elem_flatten_size {
-                let elem = initializer(token_ptr, base_typ.clone());
-                // base_typ が Array (つまり上記で文字リテラルを読んでいてかつポインタ型配列でない)の場合には、要素数カウントを正しく行うため、elem.elements を init.elements に append する
-                if base_typ.is_array() {
-                    init.append_elements(&elem);
-                } else {
-                    init.push_element(elem);
-                }
-                let _ = consume(token_ptr, ",");
-                if is(token_ptr, "}") {
-                    break;
-                }
+            attr.aligned = Some(attr.aligned.unwrap_or(0).max(align));
+        }
+        "section" => {
+            expect(token_ptr, "(");
+            let _ = attr.section.insert(expect_literal(token_ptr));
+            expect(token_ptr, ")");
+        }
+        _ => {
+            if consume(token_ptr, "(") {
+                skip_balanced(token_ptr);
             }
         }
-        let _ = consume(token_ptr, ",");
-        if consume(token_ptr, "}") {
-            break;
+    }
+}
+
+/// 対応する ")" まで読み飛ばす(括弧の中の括弧は対応を取る)
+fn skip_balanced(token_ptr: &mut TokenRef) {
+    let mut depth = 1;
+    while depth > 0 {
+        if at_eof(token_ptr) {
+            error_with_token!("expected-token", "\")\"が必要です。", &token_ptr.borrow());
+        }
+        if consume(token_ptr, "(") {
+            depth += 1;
+        } else if consume(token_ptr, ")") {
+            depth -= 1;
+        } else {
+            token_ptr_exceed(token_ptr);
         }
     }
+}
 
-    // 配列の Initializer の node は最初の要素を指すことにする
-    let first_elem = init.elements[0].borrow().clone();
-    init.insert(typ, Rc::clone(first_elem.node.as_ref().unwrap()));
-    init
+/// 生成規則:
+/// asm-label = ("asm" | "__asm__" | "__asm") "(" string-literal+ ")"
+/// 関数やグローバル変数のアセンブラ上の名前を指定する(連続する文字列リテラルは連結する)
+fn asm_label(token_ptr: &mut TokenRef, name: &str) {
+    if !is_asm(token_ptr) {
+        return;
+    }
+    token_ptr_exceed(token_ptr);
+    expect(token_ptr, "(");
+    let mut label = expect_literal(token_ptr);
+    while let Some(literal) = consume_literal(token_ptr) {
+        label.push_str(&literal);
+    }
+    expect(token_ptr, ")");
+    let _ = ASM_LABELS
+        .try_lock()
+        .unwrap()
+        .insert(name.to_string(), label);
 }
 
-/// オフセットで直接代入したい場合の Lvar
-#[inline]
-fn direct_offset_lvar(offset: usize, typ: TypeCell) -> NodeRef {
-    Rc::new(RefCell::new(Node {
-        kind: Nodekind::Lvar,
-        typ: Some(typ),
-        offset: Some(offset),
-        is_local: true,
-        ..Default::default()
-    }))
+/// アセンブラ上の名前(asm ラベルが指定されていればその名前)
+pub fn asm_symbol(name: &str) -> String {
+    ASM_LABELS
+        .try_lock()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .unwrap_or_else(|| name.to_string())
 }
 
-/// initializer が存在する要素に対応する部分のみノードを作る(この時、flex であっても先に要素数は確定しており typ.array_size を利用して処理できる)
-/// int x[2] = {1, 2}; のようなパターンは int x[2]; x[0] = 1, x[1] = 2; のように展開する
-/// ただし、それぞれの要素アクセスのためにわざわざポインタ計算を生成せず、単に各要素が格納されるべき位置に対応するベースポインタからオフセットを持つローカル変数であるとみなす
-fn make_lvar_init(
-    init: Initializer,
-    typ: TypeCell,
-    offset: usize,
-    is_scalar: bool,
-    token: TokenRef,
-) -> NodeRef {
+/// 多次元配列の最小要素の型(配列でなければ自身)
+fn element_base(typ: &TypeCell) -> TypeCell {
     if typ.is_array() {
-        let total_bytes = typ.bytes();
-        let elem_typ = typ.make_deref().unwrap();
-        let elem_bytes = elem_typ.bytes();
-        let elem_flatten_size = elem_typ.flatten_size();
-        let base_typ = typ.get_base_cell();
-        let base_bytes = base_typ.bytes();
-        let mut node_ptr = nop();
-
-        let mut ix = 0;
-        let mut finished_bytes = 0;
-        while finished_bytes < total_bytes && ix < init.elements.len() {
-            let elem = Rc::clone(&init.elements[ix]);
-            if elem.borrow().is_element() {
-                // flatten して読む
-                for _ in 0..elem_flatten_size {
-                    let _expr = init.elements[ix].borrow().node.clone().unwrap();
-                    let _val = _expr.borrow().val;
-                    // ゼロクリアが必ず入るため、0 を代入するだけのノードは無視する
-                    if _val.is_none() || _val.unwrap() != 0 {
-                        let _assign = assign_op(
-                            Nodekind::Assign,
-                            direct_offset_lvar(offset - finished_bytes, base_typ.clone()),
-                            _expr,
-                            Rc::clone(&token),
-                        );
-                        node_ptr =
-                            new_binary(Nodekind::Comma, node_ptr, _assign, Rc::clone(&token));
-                    }
-                    ix += 1;
-                    finished_bytes += base_bytes;
-                    if finished_bytes >= total_bytes || ix >= init.elements.len() {
-                        break;
-                    }
-                }
-            } else {
-                node_ptr = new_binary(
-                    Nodekind::Comma,
-                    node_ptr,
-                    make_lvar_init(
-                        elem.borrow().clone(),
-                        elem_typ.clone(),
-                        offset - finished_bytes,
-                        false,
-                        Rc::clone(&token),
-                    ),
-                    Rc::clone(&token),
-                );
-                ix += 1;
-                finished_bytes += elem_bytes;
-            }
-        }
-
-        node_ptr
+        element_base(&typ.make_deref().unwrap())
     } else {
-        let node_ptr = Rc::clone(init.node.as_ref().unwrap());
-        let val = node_ptr.borrow().val;
-        // スカラ値の初期化時は0でもちゃんと初期化を行う Assign を生成する必要がある
-        if val.is_none() || val.unwrap() != 0 || is_scalar {
-            assign_op(
-                Nodekind::Assign,
-                direct_offset_lvar(offset, typ),
-                node_ptr,
-                token,
-            )
-        } else {
-            nop()
-        }
+        typ.clone()
     }
 }
 
-/// 生成規則:
-/// stmt = expr? ";"
-///     | declaration
-///     | "{" stmt* "}"
-///     | "if" "(" expr ")" stmt ("else" stmt)?
-///     | "while" "(" expr ")" stmt
-///     | "for" "(" expr? ";" expr? ";" expr? ")" stmt
-///     | "return" expr? ";"
-fn stmt(token_ptr: &mut TokenRef) -> NodeRef {
-    let token = Rc::clone(token_ptr);
-    if consume(token_ptr, ";") {
-        tmp_num!(0)
-    } else if is_type(token_ptr) {
-        declaration(token_ptr)
-    } else if consume(token_ptr, "{") {
-        enter_scope();
-        let mut children: Vec<NodeRef> = vec![];
-        loop {
-            if !consume(token_ptr, "}") {
-                if at_eof(token_ptr) {
-                    exit_eprintln!("\'{{\'にマッチする\'}}\'が見つかりません。");
-                }
-                let _stmt = stmt(token_ptr);
-                confirm_type(&_stmt);
-                children.push(_stmt);
-            } else {
-                break;
-            }
-        }
-        leave_scope();
-
-        new_block(children)
-    } else if consume(token_ptr, "if") {
-        expect(token_ptr, "(");
-        let enter = Some(expr(token_ptr));
-        expect(token_ptr, ")");
-        let branch = Some(stmt(token_ptr));
-        let els = if consume(token_ptr, "else") {
-            Some(stmt(token_ptr))
-        } else {
-            None
-        };
-
-        new_ctrl(Nodekind::If, None, enter, None, branch, els)
-    } else if consume(token_ptr, "while") {
-        expect(token_ptr, "(");
-        let enter = Some(expr(token_ptr));
-        expect(token_ptr, ")");
-        let branch = Some(stmt(token_ptr));
-
-        new_ctrl(Nodekind::While, None, enter, None, branch, None)
-    } else if consume(token_ptr, "for") {
-        expect(token_ptr, "(");
-        enter_scope();
-        // consumeできた場合exprが何も書かれていないことに注意
-        let init: Option<NodeRef> = if is_type(token_ptr) {
-            Some(declaration(token_ptr))
-        } else if consume(token_ptr, ";") {
-            None
-        } else {
-            let _init = Some(expr(token_ptr));
-            expect(token_ptr, ";");
-            _init
-        };
-
-        let enter: Option<NodeRef> = if consume(token_ptr, ";") {
-            None
-        } else {
-            let _enter = Some(expr(token_ptr));
-            expect(token_ptr, ";");
-            _enter
-        };
-
-        let routine: Option<NodeRef> = if consume(token_ptr, ")") {
-            None
-        } else {
-            let _routine = Some(expr(token_ptr));
-            expect(token_ptr, ")");
-            _routine
-        };
-
-        let branch: Option<NodeRef> = Some(stmt(token_ptr));
-        leave_scope();
+/// 要素数を省略した配列(初期化子で要素数が決まる変数や、フレキシブル配列メンバ)は、要素の型で完全型かどうかを判断する
+fn is_incomplete_object(typ: &TypeCell) -> bool {
+    if typ.is_flex_array() {
+        typ.make_deref().unwrap().is_incomplete()
+    } else {
+        typ.is_incomplete()
+    }
+}
 
-        new_ctrl(Nodekind::For, init, enter, routine, branch, None)
-    } else if consume_kind(token_ptr, Tokenkind::Return) {
-        // exprなしのパターン: 実質Num 0があるのと同じと捉えれば良い
-        let left: NodeRef = if consume(token_ptr, ";") {
-            tmp_num!(0)
-        } else {
-            let _left: NodeRef = expr(token_ptr);
-            expect(token_ptr, ";");
-            _left
-        };
+/// 変数として宣言できる型かどうかをチェックする
+fn check_object_type(typ: &TypeCell, token: &TokenRef) {
+    if is_incomplete_object(typ) {
+        error_with_token!(
+            "incomplete-type",
+            "不完全型\"{}\"の変数は宣言できません。",
+            &token.borrow(),
+            typ
+        );
+    }
+    if element_base(typ).is_floating() {
+        error_with_token!(
+            "unsupported",
+            "浮動小数点型はまだサポートしていません。",
+            &token.borrow()
+        );
+    }
+}
 
-        new_unary(Nodekind::Return, left, token)
-    } else {
-        let node_ptr: NodeRef = expr(token_ptr);
-        expect(token_ptr, ";");
-        node_ptr
+/// 関数の定義や呼び出しでは、浮動小数点型と構造体・共用体の値の受け渡しをまだ扱えない
+fn check_value_types(func_typ: &TypeCell, token: &TokenRef) {
+    let ret_typ = func_typ.ret_typ.as_ref().unwrap().borrow().clone();
+    let arg_typs = func_typ.arg_typs.as_ref().unwrap();
+    for typ in std::iter::once(ret_typ).chain(arg_typs.iter().map(|t| t.borrow().clone())) {
+        if typ.is_floating() {
+            error_with_token!(
+                "unsupported",
+                "浮動小数点型はまだサポートしていません。",
+                &token.borrow()
+            );
+        }
+        if typ.is_struct() {
+            error_with_token!(
+                "unsupported",
+                "構造体・共用体の値の受け渡しはまだサポートしていません。",
+                &token.borrow()
+            );
+        }
     }
 }
 
 /// 生成規則:
-/// expr = assign ("," expr)?
-pub fn expr(token_ptr: &mut TokenRef) -> NodeRef {
-    let node_ptr: NodeRef = assign(token_ptr);
+/// type-name = (qualifier | type-specifier)+
+/// 引数や sizeof の型名には記憶域クラスやアラインメントは指定できない
+fn type_name(token_ptr: &mut TokenRef) -> TypeCell {
     let token = Rc::clone(token_ptr);
-    if consume(token_ptr, ",") {
-        new_binary(Nodekind::Comma, node_ptr, expr(token_ptr), token)
-    } else {
-        node_ptr
+    let (typ, attr) = declspec(token_ptr);
+    if attr.is_typedef || attr.is_static || attr.is_extern || attr.is_thread_local || attr.is_inline
+    {
+        error_with_token!(
+            "invalid-storage-class",
+            "ここでは記憶域クラスを指定できません。",
+            &token.borrow()
+        );
+    }
+    if attr.align.is_some() {
+        error_with_token!(
+            "invalid-alignment",
+            "ここではアラインメントを指定できません。",
+            &token.borrow()
+        );
     }
+    typ
 }
 
 /// 生成規則:
-/// assign = logor (assign-op assign)?
-/// assign-op = "="
-///         | "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "^=" | "|="
-///         | "<<=" | ">>="
-/// 禁止代入(例えば x + y = 10; や x & y = 10; など)は generator 側で弾く
-fn assign(token_ptr: &mut TokenRef) -> NodeRef {
-    let node_ptr: NodeRef = logor(token_ptr);
-    let token = Rc::clone(token_ptr);
-    if consume(token_ptr, "=") {
-        assign_op(Nodekind::Assign, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "+=") {
-        assign_op(Nodekind::Add, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "-=") {
-        assign_op(Nodekind::Sub, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "*=") {
-        assign_op(Nodekind::Mul, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "/=") {
-        assign_op(Nodekind::Div, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "%=") {
-        assign_op(Nodekind::Mod, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "&=") {
-        assign_op(Nodekind::BitAnd, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "^=") {
-        assign_op(Nodekind::BitXor, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "|=") {
-        assign_op(Nodekind::BitOr, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, "<<=") {
-        assign_op(Nodekind::LShift, node_ptr, assign(token_ptr), token)
-    } else if consume(token_ptr, ">>=") {
-        assign_op(Nodekind::RShift, node_ptr, assign(token_ptr), token)
-    } else {
-        node_ptr
+/// qualifiers = (qualifier | attribute-list)*
+fn qualifiers(token_ptr: &mut TokenRef, mut typ: TypeCell) -> TypeCell {
+    loop {
+        if consume(token_ptr, "const") {
+            typ.is_const = true;
+        } else if consume_volatile(token_ptr) {
+            typ.is_volatile = true;
+        } else if consume(token_ptr, "_Atomic") {
+            typ.is_atomic = true;
+        } else if is_restrict(token_ptr) {
+        } else if is(token_ptr, "__attribute__") {
+            attribute_list(token_ptr, &mut VarAttr::default());
+        } else {
+            break;
+        }
     }
+    typ
 }
 
-/// a += b; -->  tmp = &a, *tmp = *tmp + b; と読み替える
-fn assign_op(kind: Nodekind, left: NodeRef, right: NodeRef, token: TokenRef) -> NodeRef {
-    // 左右の型を確定させておく
-    confirm_type(&left);
-    confirm_type(&right);
+/// inline は関数にのみ指定できる
+fn check_not_inline(attr: &VarAttr, token: &TokenRef) {
+    if attr.is_inline {
+        error_with_token!(
+            "invalid-function-specifier",
+            "inline は関数にのみ指定できます。",
+            &token.borrow()
+        );
+    }
+}
 
-    // この式全体の評価値は left (a += b の a) の型とする
-    let assign_ = if kind == Nodekind::Assign {
-        new_binary(Nodekind::Assign, left, right, token)
+/// C99 の inline の規則に従い、以前の宣言と合わせてインライン定義になるかどうかを決める
+/// 非 static な関数は、全ての宣言が extern のない inline である場合のみインライン定義となり、外部シンボルを提供しない
+/// static な関数は、いずれかの宣言が inline であれば使用されない場合に出力を省略する
+fn merge_inline(attr: &mut VarAttr, prev: Option<&Node>) {
+    let is_inline = if attr.is_static {
+        attr.is_inline
     } else {
-        // tmp として通常は認められない無名の変数を使うことで重複を避ける
-        let typ = left.borrow().typ.clone().unwrap();
-        let tmp_lvar = tmp_lvar!();
-        let _ = tmp_lvar.borrow_mut().typ.insert(typ.make_ptr_to());
-        let tmp_deref = tmp_unary!(Nodekind::Deref, tmp_lvar);
+        attr.is_inline && !attr.is_extern
+    };
+    attr.is_inline = match prev {
+        Some(prev) if attr.is_static => prev.is_inline || is_inline,
+        Some(prev) => prev.is_inline && is_inline,
+        None => is_inline,
+    };
+}
 
-        let expr_left = tmp_binary!(
-            Nodekind::Assign,
-            tmp_lvar,
-            tmp_unary!(Nodekind::Addr, left)
-        );
+/// 関数・グローバル変数の属性は、以前の宣言で指定されたものも引き継ぐ
+fn merge_attributes(attr: &mut VarAttr, prev: &Node) {
+    attr.is_noreturn |= prev.is_noreturn;
+    attr.is_weak |= prev.is_weak;
+    attr.is_used |= prev.is_used;
+    attr.is_unused |= prev.is_unused;
+    if attr.section.is_none() {
+        attr.section = prev.section.clone();
+    }
+}
 
-        let token2 = Rc::clone(&token);
-        let tmp_deref2 = Rc::clone(&tmp_deref);
-        let op = match kind {
-            Nodekind::Add => new_add(tmp_deref2, right, token2),
-            Nodekind::Sub => new_sub(tmp_deref2, right, token2),
-            _ => new_binary(kind, tmp_deref2, right, token2),
-        };
+/// 属性をグローバル変数・関数のノードに反映する
+fn set_attributes(glob: &mut Node, attr: &VarAttr) {
+    glob.is_noreturn = attr.is_noreturn;
+    glob.is_weak = attr.is_weak;
+    glob.is_used = attr.is_used;
+    glob.is_unused = attr.is_unused;
+    glob.section = attr.section.clone();
+}
 
-        let expr_right = tmp_binary!(Nodekind::Assign, tmp_deref, op);
+/// 最終的な宣言の属性を、出力する定義のノードに反映する
+fn inherit_attributes(def: &mut Node, decl: &Node) {
+    def.is_noreturn = decl.is_noreturn;
+    def.is_weak = decl.is_weak;
+    def.is_used = decl.is_used;
+    def.is_unused = decl.is_unused;
+    def.section = decl.section.clone();
+}
 
-        confirm_type(&expr_left);
-        confirm_type(&expr_right);
-        new_binary(Nodekind::Comma, expr_left, expr_right, token)
-    };
-    confirm_type(&assign_);
-    assign_
+/// weak 属性は外部リンケージを持つものにのみ指定できる
+fn check_weak(attr: &VarAttr, token: &TokenRef) {
+    if attr.is_weak && attr.is_static {
+        error_with_token!(
+            "invalid-attribute",
+            "static な宣言には weak 属性を指定できません。",
+            &token.borrow()
+        );
+    }
 }
 
-/// 生成規則:
-/// logor = logand ("||" logand)*
-fn logor(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = logand(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if !consume(token_ptr, "||") {
-            break;
-        }
-        node_ptr = new_binary(Nodekind::LogOr, node_ptr, logand(token_ptr), token);
+/// 以前の宣言とのリンケージの整合をチェックする
+/// static な宣言の後の extern 宣言や記憶域クラスのない関数宣言は、内部リンケージを引き継ぐ
+/// _Thread_local はオブジェクトの全ての宣言で一致している必要がある
+fn check_thread_local(attr: &VarAttr, prev: &Node, token: &TokenRef, name: &str) {
+    if attr.is_thread_local != prev.is_tls {
+        let prev_kind = if prev.is_tls { "" } else { "でない" };
+        error_with_prev!(
+            "conflicting-storage-class",
+            "\"{}\"は _Thread_local {}変数として宣言されています。",
+            &token.borrow(),
+            &prev.token.as_ref().unwrap().borrow(),
+            name,
+            prev_kind
+        );
     }
-    node_ptr
 }
 
-/// 生成規則:
-/// logand = bitor ("&&" bitor)*
-fn logand(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = bitor(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if !consume(token_ptr, "&&") {
-            break;
+fn check_linkage(attr: &mut VarAttr, prev: &Node, is_func: bool, token: &TokenRef, name: &str) {
+    let decl = prev.token.as_ref().unwrap().borrow();
+    if attr.is_static && !prev.is_static {
+        error_with_prev!(
+            "conflicting-storage-class",
+            "\"{}\"は static でない宣言がされています。",
+            &token.borrow(),
+            &decl,
+            name
+        );
+    }
+    if prev.is_static && !attr.is_static {
+        if attr.is_extern || is_func {
+            attr.is_static = true;
+        } else {
+            error_with_prev!(
+                "conflicting-storage-class",
+                "\"{}\"は static として宣言されています。",
+                &token.borrow(),
+                &decl,
+                name
+            );
         }
-        node_ptr = new_binary(Nodekind::LogAnd, node_ptr, bitor(token_ptr), token);
     }
-    node_ptr
 }
 
-/// 生成規則:
-/// bitor = bitxor ("|" bitxor)*
-fn bitor(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = bitxor(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if !consume(token_ptr, "|") {
-            break;
-        }
-        node_ptr = new_binary(Nodekind::BitOr, node_ptr, bitxor(token_ptr), token);
+/// 仮定義の解決
+/// 同名のグローバル変数の宣言のうち、初期化子のあるもの(なければ extern でない最初の宣言)のみを実体として出力し、他は Nop にする
+fn resolve_tentative(globals: &[NodeRef]) {
+    let mut decls: Vec<NodeRef> = vec![];
+    for glob in globals {
+        collect_gvars(glob, &mut decls);
     }
-    node_ptr
-}
 
-/// 生成規則:
-/// bitxor = bitand ("^" bitand)*
-fn bitxor(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = bitand(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if !consume(token_ptr, "^") {
-            break;
+    // 初期化子のある宣言、 extern でない宣言、 extern 宣言の順に優先する
+    // extern 宣言しかない変数は、 weak であれば未定義のシンボルとして .weak のみを出力する
+    let rank = |decl: &NodeRef| {
+        let decl = decl.borrow();
+        if !decl.init_data.is_empty() {
+            2
+        } else if !decl.is_extern {
+            1
+        } else {
+            0
+        }
+    };
+    let mut defs: HashMap<String, NodeRef> = HashMap::new();
+    for decl in &decls {
+        let name = decl.borrow().name.clone().unwrap();
+        let replace = defs.get(&name).is_none_or(|def| rank(decl) > rank(def));
+        if replace {
+            let _ = defs.insert(name, Rc::clone(decl));
+        }
+    }
+    defs.retain(|name, def| {
+        rank(def) > 0 || GLOBALS.try_lock().unwrap().get(name).unwrap().is_weak
+    });
+
+    for decl in &decls {
+        let name = decl.borrow().name.clone().unwrap();
+        if !defs.get(&name).is_some_and(|def| Rc::ptr_eq(def, decl)) {
+            decl.borrow_mut().kind = Nodekind::Nop;
         }
-        node_ptr = new_binary(Nodekind::BitXor, node_ptr, bitand(token_ptr), token);
     }
-    node_ptr
-}
 
-/// 生成規則:
-/// bitand = equality ("&" equality)*
-fn bitand(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = equality(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if !consume(token_ptr, "&") {
-            break;
+    // 最終的な型とリンケージを反映する
+    let globals_access = GLOBALS.try_lock().unwrap();
+    for (name, def) in defs {
+        let glob = globals_access.get(&name).unwrap();
+        let mut def = def.borrow_mut();
+        def.is_static = glob.is_static;
+        inherit_attributes(&mut def, glob);
+        if def.init_data.is_empty() {
+            let mut typ = glob.typ.clone().unwrap();
+            // 最後まで要素数が指定されなかった配列の仮定義は要素数1とする
+            if typ.is_flex_array() {
+                let _ = typ.array_size.insert(1);
+            }
+            let _ = def.typ.insert(typ);
         }
-        node_ptr = new_binary(Nodekind::BitAnd, node_ptr, equality(token_ptr), token);
     }
-    node_ptr
 }
 
-/// 生成規則:
-/// equality = relational ("==" relational | "!=" relational)?
-fn equality(token_ptr: &mut TokenRef) -> NodeRef {
-    let node_ptr: NodeRef = relational(token_ptr);
-    let token = Rc::clone(token_ptr);
-    if consume(token_ptr, "==") {
-        new_binary(Nodekind::Eq, node_ptr, relational(token_ptr), token)
-    } else if consume(token_ptr, "!=") {
-        new_binary(Nodekind::NEq, node_ptr, relational(token_ptr), token)
-    } else {
-        node_ptr
+/// インライン定義の関数のうち、出力しないものを Nop にする
+/// 非 static なインライン定義は出力せず(外部シンボルは別の翻訳単位が提供する)、static inline な関数は出力される関数から呼ばれる場合のみ出力する
+fn resolve_inline(globals: &[NodeRef]) {
+    let mut funcs: HashMap<String, NodeRef> = HashMap::new();
+    let mut emitted: Vec<String> = vec![];
+    {
+        let globals_access = GLOBALS.try_lock().unwrap();
+        for glob in globals {
+            if glob.borrow().kind != Nodekind::Global || glob.borrow().stmts.is_none() {
+                continue;
+            }
+            let name = glob.borrow().name.clone().unwrap();
+            // 定義の後の宣言も考慮するため、最終的な GLOBALS の情報を使う
+            let decl = globals_access.get(&name).unwrap();
+            glob.borrow_mut().is_static = decl.is_static;
+            glob.borrow_mut().is_inline = decl.is_inline;
+            inherit_attributes(&mut glob.borrow_mut(), decl);
+            // used 属性のある static な関数は、使用されなくても出力する
+            if !decl.is_inline || (decl.is_static && decl.is_used) {
+                emitted.push(name.clone());
+            }
+            let _ = funcs.insert(name, Rc::clone(glob));
+        }
+    }
+    warn_unused_funcs(globals, &funcs);
+
+    // 出力される関数から呼び出される static inline な関数を辿る
+    let mut used: HashSet<String> = emitted.iter().cloned().collect();
+    while let Some(name) = emitted.pop() {
+        let mut callees: HashSet<String> = HashSet::new();
+        for stmt in funcs[&name].borrow().stmts.as_ref().unwrap() {
+            collect_calls(stmt, &mut callees);
+        }
+        for callee in callees {
+            let is_static_inline = funcs
+                .get(&callee)
+                .is_some_and(|f| f.borrow().is_static && f.borrow().is_inline);
+            if is_static_inline && used.insert(callee.clone()) {
+                emitted.push(callee);
+            }
+        }
     }
-}
 
-/// 生成規則:
-/// relational = shift ("<" shift | "<=" shift | ">" shift | ">=" shift)*
-fn relational(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = shift(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if consume(token_ptr, "<") {
-            node_ptr = new_binary(Nodekind::LThan, node_ptr, shift(token_ptr), token);
-        } else if consume(token_ptr, "<=") {
-            node_ptr = new_binary(Nodekind::LEq, node_ptr, shift(token_ptr), token);
-        } else if consume(token_ptr, ">") {
-            node_ptr = new_binary(Nodekind::LThan, shift(token_ptr), node_ptr, token);
-        } else if consume(token_ptr, ">=") {
-            node_ptr = new_binary(Nodekind::LEq, shift(token_ptr), node_ptr, token);
-        } else {
-            break;
+    for (name, func) in funcs {
+        if !used.contains(&name) {
+            func.borrow_mut().kind = Nodekind::Nop;
         }
     }
-    node_ptr
 }
 
-/// 生成規則:
-/// shift = add ("<<" add | ">>" add)*
-fn shift(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = add(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if consume(token_ptr, "<<") {
-            node_ptr = new_binary(Nodekind::LShift, node_ptr, add(token_ptr), token);
-        } else if consume(token_ptr, ">>") {
-            node_ptr = new_binary(Nodekind::RShift, node_ptr, add(token_ptr), token);
-        } else {
-            break;
+/// どの関数からも呼び出されない static 関数に警告を出す(static inline な関数と、 unused, used 属性のある関数は除く)
+fn warn_unused_funcs(globals: &[NodeRef], funcs: &HashMap<String, NodeRef>) {
+    let mut callees: HashSet<String> = HashSet::new();
+    for func in funcs.values() {
+        for stmt in func.borrow().stmts.as_ref().unwrap() {
+            collect_calls(stmt, &mut callees);
+        }
+    }
+    for glob in globals {
+        let glob = glob.borrow();
+        if glob.kind != Nodekind::Global || glob.stmts.is_none() {
+            continue;
+        }
+        let name = glob.name.as_ref().unwrap();
+        if glob.is_static
+            && !glob.is_inline
+            && !glob.is_used
+            && !glob.is_unused
+            && !callees.contains(name)
+        {
+            warning_with_token!(
+                "unused-function",
+                "static 関数\"{}\"は定義されていますが使用されていません。",
+                &glob.token.as_ref().unwrap().borrow(),
+                name
+            );
         }
     }
-    node_ptr
 }
 
-fn new_add(mut left: NodeRef, mut right: NodeRef, token: TokenRef) -> NodeRef {
-    confirm_type(&left);
-    confirm_type(&right);
-
-    // それぞれ配列の場合でも true になるが、それで良い
-    let left_is_ptr = left.borrow().typ.as_ref().unwrap().ptr_end.is_some();
-    let right_is_ptr = right.borrow().typ.as_ref().unwrap().ptr_end.is_some();
-    if left_is_ptr && right_is_ptr {
-        error_with_token!(
-            "ポインタ演算は整数型との加算か、ポインタ同士の引き算のみ可能です。",
-            &token.borrow()
-        );
+fn collect_calls(node: &NodeRef, callees: &mut HashSet<String>) {
+    let node = node.borrow();
+    if node.kind == Nodekind::FunCall {
+        let _ = callees.insert(node.name.clone().unwrap());
     }
+    let branches = [
+        &node.left,
+        &node.right,
+        &node.init,
+        &node.enter,
+        &node.routine,
+        &node.branch,
+        &node.els,
+    ];
+    for n in branches.iter().copied().flatten() {
+        collect_calls(n, callees);
+    }
+    for n in node.children.iter().chain(node.args.iter()) {
+        collect_calls(n, callees);
+    }
+}
 
-    if !left_is_ptr && !right_is_ptr {
-        new_binary(Nodekind::Add, left, right, token)
-    } else {
-        // num + ptr の場合には ptr + num として扱うべく左右を入れ替える
-        if !left_is_ptr {
-            std::mem::swap(&mut left, &mut right);
+fn collect_gvars(node: &NodeRef, decls: &mut Vec<NodeRef>) {
+    let kind = node.borrow().kind;
+    match kind {
+        Nodekind::Comma => {
+            collect_gvars(node.borrow().left.as_ref().unwrap(), decls);
+            collect_gvars(node.borrow().right.as_ref().unwrap(), decls);
         }
-
-        // 配列の場合、サイズを考慮する必要があることに注意
-        let ptr_cell = left.borrow().typ.clone().unwrap();
-        let bytes = ptr_cell.ptr_to.as_ref().unwrap().borrow().bytes() as i32;
-        let pointer_offset = tmp_binary!(Nodekind::Mul, tmp_num!(bytes), right);
-        let add_ = new_binary(Nodekind::Add, left, pointer_offset, token);
-        confirm_type(&add_);
-        let _ = add_.borrow_mut().typ.insert(ptr_cell);
-        add_
+        Nodekind::Global if node.borrow().func_typ.is_none() => {
+            decls.push(Rc::clone(node));
+        }
+        _ => {}
     }
 }
 
-fn new_sub(left: NodeRef, right: NodeRef, token: TokenRef) -> NodeRef {
-    confirm_type(&left);
-    confirm_type(&right);
-    let left_typ = left.borrow().typ.clone().unwrap();
-    let right_typ = right.borrow().typ.clone().unwrap();
+/// ident ":" の形であればラベルとみなす
+#[inline]
+fn is_label(token_ptr: &TokenRef) -> bool {
+    let ptr = &mut Rc::clone(token_ptr);
+    consume_ident(ptr).is_some() && is(ptr, ":")
+}
 
-    // それぞれ配列の場合でも true になるが、それで良い
-    let left_is_ptr = left_typ.ptr_end.is_some();
-    let right_is_ptr = right_typ.ptr_end.is_some();
+#[inline]
+pub fn is_func(token_ptr: &TokenRef) -> bool {
+    let ptr = &mut Rc::clone(token_ptr);
+    let dummy = TypeCell::default();
+    declarator(ptr, dummy).1.typ == Type::Func
+}
 
-    let (sub_, type_cell) = if !left_is_ptr && !right_is_ptr {
-        return new_binary(Nodekind::Sub, left, right, token);
-    } else if left_is_ptr && right_is_ptr {
-        // ptr - ptr はそれが変数何個分のオフセットに相当するかを計算する
-        if left_typ != right_typ {
-            error_with_token!(
-                "違う型へのポインタ同士の演算はサポートされません。: \"{}\", \"{}\"",
-                &token.borrow(),
-                left_typ,
-                right_typ
+/// 生成規則:
+/// function = func-declarator attribute-list asm-label? attribute-list ("{" stmt* "}")?
+fn function(token_ptr: &mut TokenRef, typ: TypeCell, mut attr: VarAttr) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    let (name, mut typ, args) = func_declarator(token_ptr, typ);
+    attribute_list(token_ptr, &mut attr);
+    asm_label(token_ptr, &name);
+    attribute_list(token_ptr, &mut attr);
+
+    let defined = if let Some(node) = GLOBALS.try_lock().unwrap().get(&name) {
+        let decl = node.token.as_ref().unwrap().borrow();
+        if node.typ.is_some() {
+            error_with_prev!(
+                "conflicting-types",
+                "\"{}\"は既にグローバル変数として宣言されています。",
+                &*token.borrow(),
+                &decl,
+                name
             );
         }
-
-        let bytes = left_typ.ptr_to.as_ref().unwrap().borrow().bytes() as i32;
-        let pointer_offset = tmp_binary!(Nodekind::Sub, left, right);
-        confirm_type(&pointer_offset);
-        (
-            new_binary(Nodekind::Div, pointer_offset, tmp_num!(bytes), token),
-            TypeCell::new(Type::Int),
-        )
+        // 同じ関数の宣言どうしは互換性のある型である必要があり、以降は合成型として扱う
+        // () の定義は仮引数を持たないので、プロトタイプのある宣言とは (void) として比べる
+        let prev_typ = node.func_typ.as_ref().unwrap();
+        let cmp_typ = TypeCell {
+            is_unprototyped: typ.is_unprototyped && !is(token_ptr, "{"),
+            ..typ.clone()
+        };
+        if !cmp_typ.is_compatible(prev_typ) {
+            error_with_prev!(
+                "conflicting-types",
+                "型\"{}\"はプロトタイプ宣言の型\"{}\"と互換性がありません。",
+                &*token.borrow(),
+                &decl,
+                typ,
+                prev_typ
+            );
+        }
+        typ = typ.composite(prev_typ);
+        check_linkage(&mut attr, node, true, &token, &name);
+        merge_inline(&mut attr, Some(node));
+        merge_attributes(&mut attr, node);
+        node.stmts.is_some()
     } else {
-        // num - ptr は invalid
-        if !left_is_ptr {
+        merge_inline(&mut attr, None);
+        false
+    };
+    check_weak(&attr, &token);
+
+    if consume(token_ptr, "{") {
+        check_value_types(&typ, &token);
+        for arg_typ in typ.arg_typs.as_ref().unwrap() {
+            if arg_typ.borrow().is_incomplete() {
+                error_with_token!(
+                    "incomplete-type",
+                    "不完全型\"{}\"の仮引数は定義できません。",
+                    &*token.borrow(),
+                    arg_typ.borrow()
+                );
+            }
+        }
+        if typ.is_abstract {
             error_with_token!(
-                "整数型の値からポインタを引くことはできません。",
-                &token.borrow()
+                "missing-declarator",
+                "関数の定義時には引数名を省略できません。",
+                &*token.borrow()
+            );
+        }
+        // 既に宣言されている場合をケア
+        let node = GLOBALS
+            .try_lock()
+            .unwrap()
+            .get(&name)
+            .cloned()
+            .unwrap_or_default();
+        match node.kind {
+            Nodekind::Global => {
+                if defined {
+                    error_with_prev!(
+                        "redefinition",
+                        "関数\"{}\"は既に定義されています。",
+                        &*token.borrow(),
+                        &node.token.as_ref().unwrap().borrow(),
+                        name
+                    );
+                }
+                // 本体の中で参照できるように、定義で指定された属性を反映しておく
+                set_attributes(GLOBALS.try_lock().unwrap().get_mut(&name).unwrap(), &attr);
+            }
+            Nodekind::Default => {
+                // プロトタイプ宣言がない場合は、再帰のことを考えて定義のパース前に GLOBALS に一旦プロトタイプ宣言の体で保存する
+                let _ = proto_func(name.clone(), typ.clone(), attr.clone(), Rc::clone(&token));
+            }
+            _ => {
+                panic!("unreachable");
+            }
+        }
+
+        // 可変長引数をとる場合は、引数レジスタの値を保存しておく領域(System V ABI のレジスタ保存領域)を確保する
+        let va_area = if typ.is_variadic {
+            let area = _lvar(
+                ".va_area",
+                None,
+                Some(TypeCell::new(Type::Char).make_array_of(176)),
+                true,
+                0,
             );
+            let offset = area.borrow().offset.unwrap();
+            *VA_INFO.try_lock().unwrap() = Some((offset, args.len()));
+            Some(offset)
+        } else {
+            None
+        };
+
+        *CURRENT_FUNC.try_lock().unwrap() = Some(name.clone());
+        let mut stmts: Vec<NodeRef> = Vec::new();
+        // 仮引数の型に含まれる可変長配列の大きさは、関数の先頭で計算する
+        if !VLA_SIZES.try_lock().unwrap().is_empty() {
+            stmts.push(with_vla_sizes(nop()));
+        }
+        let n_errors = error_count();
+        while !is(token_ptr, "}") {
+            stmts.extend(stmt_or_recover(token_ptr));
+        }
+        let end_token = Rc::clone(token_ptr);
+        expect(token_ptr, "}");
+        check_labels();
+        *VA_INFO.try_lock().unwrap() = None;
+        *CURRENT_FUNC.try_lock().unwrap() = None;
+
+        // 末尾まで到達しうる場合は return 0; を補う(main 以外では警告を出す)
+        if can_fall_through_all(&stmts) {
+            // 本体にエラーがあった場合は、読み飛ばした文による誤った警告になりうるので出さない
+            if error_count() == n_errors {
+                if attr.is_noreturn {
+                    warning_with_token!(
+                        "invalid-noreturn",
+                        "noreturn 属性の関数\"{}\"が呼び出し元に戻る可能性があります。",
+                        &end_token.borrow(),
+                        name
+                    );
+                } else if !typ.ret_typ.as_ref().unwrap().borrow().is_void() && name != "main" {
+                    warning_with_token!(
+                        "return-type",
+                        "void 型でない関数\"{}\"の末尾に return 文がありません。",
+                        &end_token.borrow(),
+                        name
+                    );
+                }
+            }
+            stmts.push(tmp_unary!(Nodekind::Return, tmp_num!(0)));
         }
 
-        let bytes = left_typ.ptr_to.as_ref().unwrap().borrow().bytes() as i32;
-        let pointer_offset = tmp_binary!(Nodekind::Mul, tmp_num!(bytes), right);
-        confirm_type(&pointer_offset);
-        (
-            new_binary(Nodekind::Sub, left, pointer_offset, token),
-            left_typ,
-        )
-    };
-    let _ = sub_.borrow_mut().typ.insert(type_cell);
-    sub_
-}
+        let mut max_offset_access = LVAR_MAX_OFFSET.try_lock().unwrap();
+        align!(*max_offset_access, 8usize);
+        let max_offset = *max_offset_access;
 
-/// 生成規則:
-/// add = mul ("+" mul | "-" mul)*
-fn add(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = mul(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if consume(token_ptr, "+") {
-            node_ptr = new_add(node_ptr, mul(token_ptr), token);
-        } else if consume(token_ptr, "-") {
-            node_ptr = new_sub(node_ptr, mul(token_ptr), token);
+        let func = new_funcdec(name, typ, args, stmts, max_offset, attr, token);
+        func.borrow_mut().va_area = va_area;
+        func.borrow_mut().uses_alloca =
+            std::mem::replace(&mut *USES_ALLOCA.try_lock().unwrap(), false);
+        let max_align = std::mem::replace(&mut *LVAR_MAX_ALIGN.try_lock().unwrap(), 0);
+        func.borrow_mut().frame_align = (max_align > 16).then_some(max_align);
+        func
+    } else {
+        expect(token_ptr, ";");
+        VLA_SIZES.try_lock().unwrap().clear();
+        if defined {
+            // 定義後の宣言では、リンケージと inline と属性の情報のみを反映する
+            let mut globals_access = GLOBALS.try_lock().unwrap();
+            let glob = globals_access.get_mut(&name).unwrap();
+            glob.is_static = attr.is_static;
+            glob.is_inline = attr.is_inline;
+            set_attributes(glob, &attr);
+            nop()
+        } else if attr.is_weak {
+            // 定義のない weak な関数は、未定義でもリンクできるように .weak を出力する
+            proto_func(name, typ, attr, token)
         } else {
-            break;
+            let _ = proto_func(name, typ, attr, token);
+            nop()
         }
     }
-    node_ptr
 }
 
-/// 生成規則:
-/// mul = unary ("*" unary | "/" unary | "%" unary)*
-fn mul(token_ptr: &mut TokenRef) -> NodeRef {
-    let mut node_ptr: NodeRef = unary(token_ptr);
-    loop {
-        let token = Rc::clone(token_ptr);
-        if consume(token_ptr, "*") {
-            node_ptr = new_binary(Nodekind::Mul, node_ptr, unary(token_ptr), token);
-        } else if consume(token_ptr, "/") {
-            node_ptr = new_binary(Nodekind::Div, node_ptr, unary(token_ptr), token);
-        } else if consume(token_ptr, "%") {
-            node_ptr = new_binary(Nodekind::Mod, node_ptr, unary(token_ptr), token);
-        } else {
-            break;
+/// 文の並びを実行した後に、後続の文へ制御が移りうるかどうか
+/// return や noreturn な関数の呼び出しの後は移らないが、ラベルがあればそこから再び到達しうる
+fn can_fall_through_all(stmts: &[NodeRef]) -> bool {
+    let mut falls = true;
+    for stmt in stmts {
+        let kind = stmt.borrow().kind;
+        if kind == Nodekind::Label || kind == Nodekind::Case {
+            falls = true;
+        }
+        if !can_fall_through(stmt) {
+            falls = false;
         }
     }
-    node_ptr
+    falls
 }
 
-/// !+x; や ~-y; は valid
-/// unary = tailed
-///     | ("sizeof") ( "(" (type | expr) ")" | unary)
-///     | ("~" | "!") unary
-///     | ("*" | "&") unary
-///     | ("+" | "-") unary
-///     | ("++" | "--") unary
-// TODO: *+x; *-y; みたいな構文を禁止したい
-fn unary(token_ptr: &mut TokenRef) -> NodeRef {
-    let token = Rc::clone(token_ptr);
-    if consume(token_ptr, "sizeof") {
-        // 型名を使用する場合は括弧が必要なので sizeof type になっていないか先にチェックする
-        let ptr_ = Rc::clone(token_ptr);
-        if let Some(typ) = consume_type(token_ptr) {
-            error_with_token!("型名を使用した sizeof 演算子の使用では、 \"(\" と \")\" で囲う必要があります。 -> \"({})\"", &ptr_.borrow(), typ);
+/// 文を実行した後に、後続の文へ制御が移りうるかどうか(判断できない場合は移りうるものとする)
+/// ループは条件式が0でない定数で、 break で抜けない場合のみ無限ループとみなす
+/// do-while 文は、本体の終わりに到達せず continue もなければ条件式を評価しない
+/// switch 文は、 default があれば必ず本体に移るので、本体の終わりに到達せず break もなければ後続の文へ移らない
+fn can_fall_through(node: &NodeRef) -> bool {
+    let node = node.borrow();
+    let is_endless = |enter: &Option<NodeRef>| {
+        enter.as_ref().is_none_or(|enter| {
+            let enter = enter.borrow();
+            enter.kind == Nodekind::Num && enter.val != Some(0)
+        })
+    };
+    match node.kind {
+        Nodekind::Return | Nodekind::Goto | Nodekind::Trap | Nodekind::Unreachable => false,
+        Nodekind::FunCall => !GLOBALS
+            .try_lock()
+            .unwrap()
+            .get(node.name.as_ref().unwrap())
+            .is_some_and(|func| func.is_noreturn),
+        Nodekind::Block | Nodekind::StmtExpr => can_fall_through_all(&node.children),
+        Nodekind::Label | Nodekind::Case => can_fall_through(node.branch.as_ref().unwrap()),
+        Nodekind::Comma => {
+            can_fall_through(node.left.as_ref().unwrap())
+                && can_fall_through(node.right.as_ref().unwrap())
         }
-        let typ: TypeCell = if consume(token_ptr, "(") {
-            let typ_: TypeCell = if let Some(t) = consume_type(token_ptr) {
-                t
-            } else {
-                let exp = expr(token_ptr);
-                confirm_type(&exp);
-                let _typ = exp.borrow().typ.clone().unwrap();
-                _typ
-            };
-            expect(token_ptr, ")");
-            typ_
-        } else {
-            let una = unary(token_ptr);
-            confirm_type(&una);
-            let _typ = una.borrow().typ.clone().unwrap();
-            _typ
-        };
-        new_num(typ.bytes() as i32, token)
-    } else if consume(token_ptr, "~") {
-        new_unary(Nodekind::BitNot, unary(token_ptr), token)
-    } else if consume(token_ptr, "!") {
-        new_unary(Nodekind::LogNot, unary(token_ptr), token)
-    } else if consume(token_ptr, "*") {
-        let node_ptr = unary(token_ptr);
-        confirm_type(&node_ptr);
-        new_unary(Nodekind::Deref, node_ptr, token)
-    } else if consume(token_ptr, "&") {
-        let node_ptr = unary(token_ptr);
-        confirm_type(&node_ptr);
-        new_unary(Nodekind::Addr, node_ptr, token)
-    } else if consume(token_ptr, "+") {
-        // 単項演算子のプラスは0に足す形にする。こうすることで &+var のような表現を generator 側で弾ける
-        new_binary(Nodekind::Add, tmp_num!(0), primary(token_ptr), token)
-    } else if consume(token_ptr, "-") {
-        // 単項演算のマイナスは0から引く形にする。
-        new_binary(Nodekind::Sub, tmp_num!(0), primary(token_ptr), token)
-    } else if consume(token_ptr, "++") {
-        assign_op(Nodekind::Add, unary(token_ptr), tmp_num!(1), token)
-    } else if consume(token_ptr, "--") {
-        assign_op(Nodekind::Sub, unary(token_ptr), tmp_num!(1), token)
-    } else {
-        tailed(token_ptr)
+        Nodekind::If => {
+            node.els.is_none()
+                || can_fall_through(node.branch.as_ref().unwrap())
+                || can_fall_through(node.els.as_ref().unwrap())
+        }
+        Nodekind::For | Nodekind::While => {
+            !is_endless(&node.enter) || has_jump(node.branch.as_ref().unwrap(), Nodekind::Break)
+        }
+        Nodekind::DoWhile => {
+            let branch = node.branch.as_ref().unwrap();
+            has_jump(branch, Nodekind::Break)
+                || (!is_endless(&node.enter)
+                    && (can_fall_through(branch) || has_jump(branch, Nodekind::Continue)))
+        }
+        Nodekind::Switch => {
+            let branch = node.branch.as_ref().unwrap();
+            !node.children.iter().any(|case| case.borrow().val.is_none())
+                || has_jump(branch, Nodekind::Break)
+                || can_fall_through(branch)
+        }
+        _ => true,
     }
 }
 
-/// 生成規則:
-/// tailed = primary (primary-tail)?
-/// primary-tail = "++" | "--"
-fn tailed(token_ptr: &mut TokenRef) -> NodeRef {
-    let node_ptr: NodeRef = primary(token_ptr);
-    let token = Rc::clone(token_ptr);
-    if consume(token_ptr, "++") {
-        inc_dec(node_ptr, true, false, token)
-    } else if consume(token_ptr, "--") {
-        inc_dec(node_ptr, false, false, token)
-    } else {
-        node_ptr
+/// 文の中に、その文を囲むループや switch へ向かう kind (break か continue) の文があるかどうか
+/// (内側のループの break, continue と、内側の switch の break は除く)
+fn has_jump(node: &NodeRef, kind: Nodekind) -> bool {
+    let node = node.borrow();
+    match node.kind {
+        k if k == kind => true,
+        Nodekind::For | Nodekind::While | Nodekind::DoWhile => false,
+        Nodekind::Switch if kind == Nodekind::Break => false,
+        _ => {
+            let branches = [&node.left, &node.right, &node.branch, &node.els];
+            branches
+                .iter()
+                .copied()
+                .flatten()
+                .any(|node| has_jump(node, kind))
+                || node.children.iter().any(|node| has_jump(node, kind))
+        }
     }
 }
 
-/// ++a; -> a+=1; および a++; -> (a+=1)-1; と読み替える
-fn inc_dec(node: NodeRef, is_inc: bool, is_prefix: bool, token: TokenRef) -> NodeRef {
-    let kind = if is_inc {
-        Nodekind::Add
-    } else {
-        Nodekind::Sub
-    };
-    confirm_type(&node);
-    let _assign = assign_op(kind, node, tmp_num!(1), Rc::clone(&token));
-    if is_prefix {
-        // ++i は (i+=1) として読み替えると良い
-        _assign
-    } else {
-        // i++ は (i+=1)-1 として読み替えると良い
-        if is_inc {
-            new_sub(_assign, tmp_num!(1), token)
+/// goto の飛び先となるラベルが関数内で定義されているかをチェックする
+/// 併せて、 goto で抜ける可変長配列のスコープの領域を解放するノードを埋める
+fn check_labels() {
+    let gotos: Vec<JumpVlas> = GOTOS.try_lock().unwrap().drain(..).collect();
+    let labels: LabelMap = LABELS.try_lock().unwrap().drain().collect();
+    for (goto, live) in gotos {
+        let name = goto.name.as_ref().unwrap();
+        let label_live = if let Some((_, label_live)) = labels.get(name) {
+            label_live
         } else {
-            new_add(_assign, tmp_num!(1), token)
+            error_undefined(
+                "undefined-label",
+                &format!("ラベル\"{}\"は定義されていません。", name),
+                name,
+                &goto.token.as_ref().unwrap().borrow(),
+            );
+        };
+        if label_live.iter().any(|vla| !live.contains(vla)) {
+            error_with_token!(
+                "jump-into-vla",
+                "goto で可変長配列のスコープの中へ飛び込むことはできません。",
+                &*goto.token.as_ref().unwrap().borrow()
+            );
+        }
+        let ix = live.iter().position(|vla| !label_live.contains(vla));
+        if let (Some(restore), Some(node)) = (
+            goto.init.as_ref(),
+            ix.and_then(|ix| restore_stack(&live[ix..])),
+        ) {
+            *restore.borrow_mut() = node.borrow().clone();
         }
     }
 }
 
+/// func-declarator = pointers func-name "(" func-args ")"
+fn func_declarator(
+    token_ptr: &mut TokenRef,
+    mut typ: TypeCell,
+) -> (String, TypeCell, Vec<NodeRef>) {
+    typ = pointers(token_ptr, typ);
+    let name = func_name(token_ptr);
+
+    expect(token_ptr, "(");
+    let is_unprototyped = is(token_ptr, ")");
+    let (args, arg_typs, is_variadic) = func_args(token_ptr);
+    let is_abstract = args.len() != arg_typs.len();
+    typ = typ.make_func(arg_typs);
+    typ.is_abstract = is_abstract;
+    typ.is_variadic = is_variadic;
+    typ.is_unprototyped = is_unprototyped;
+    expect(token_ptr, ")");
+
+    (name, typ, args)
+}
+
+/// func-name = "(" func-name ")" | ident
+fn func_name(token_ptr: &mut TokenRef) -> String {
+    if consume(token_ptr, "(") {
+        let name = func_name(token_ptr);
+        expect(token_ptr, ")");
+        name
+    } else {
+        expect_ident(token_ptr)
+    }
+}
+
 /// 生成規則:
-/// params = assign ("," assign)* | null
-fn params(token_ptr: &mut TokenRef) -> Vec<NodeRef> {
+/// func-args = arg ("," arg)* ("," "...")? | "void" | null
+/// null の場合(プロトタイプでない宣言)は、呼び出し側で仮引数の個数や型を調べない
+/// arg = type declarator
+fn func_args(token_ptr: &mut TokenRef) -> (Vec<NodeRef>, Vec<TypeCellRef>, bool) {
     let mut args: Vec<NodeRef> = vec![];
-    if !consume(token_ptr, ")") {
-        let arg = assign(token_ptr);
-        confirm_type(&arg);
-        args.push(arg);
+    let mut arg_typs: Vec<TypeCellRef> = vec![];
+    let mut is_variadic = false;
 
-        loop {
-            if !consume(token_ptr, ",") {
-                expect(token_ptr, ")"); // 括弧が閉じないような書き方になっているとここで止まるため、if at_eof ~ のようなチェックは不要
-                break;
-            }
-            let arg = assign(token_ptr);
-            confirm_type(&arg);
-            args.push(arg);
+    // (void) は引数がないことを表す
+    let mut lookahead = Rc::clone(token_ptr);
+    if consume(&mut lookahead, "void") && is(&mut lookahead, ")") {
+        *token_ptr = lookahead;
+        return (args, arg_typs, is_variadic);
+    }
+
+    *PARAM_DEPTH.try_lock().unwrap() += 1;
+    while is_type_name(token_ptr) {
+        arg(token_ptr, &mut args, &mut arg_typs);
+        if !consume(token_ptr, ",") {
+            break;
+        }
+        if consume(token_ptr, "...") {
+            is_variadic = true;
+            break;
         }
     }
-    args
+    *PARAM_DEPTH.try_lock().unwrap() -= 1;
+    // args.len() != arg_types.len() ならば引数名が省略されており、プロトタイプ宣言であるとみなせる
+    (args, arg_typs, is_variadic)
 }
 
 /// 生成規則:
-/// primary = num
-///         | string-literal
-///         | ident ( "(" params ")" | "[" expr "]")?
-///         | "(" expr ")"
-fn primary(token_ptr: &mut TokenRef) -> NodeRef {
+/// arg = type-name declarator
+/// 配列型の仮引数は要素へのポインタに調整する(int m[][4] は int (*m)[4], int a[const 3] は int *const a になる)
+/// 関数型の仮引数も同様に関数へのポインタに調整する
+fn arg(token_ptr: &mut TokenRef, args: &mut Vec<NodeRef>, arg_typs: &mut Vec<TypeCellRef>) {
+    let typ = type_name(token_ptr);
     let token = Rc::clone(token_ptr);
-    if consume(token_ptr, "(") {
-        let node_ptr: NodeRef = expr(token_ptr);
-        expect(token_ptr, ")");
-        node_ptr
-    } else if let Some(name) = consume_ident(token_ptr) {
-        if consume(token_ptr, "(") {
-            let func_typ: TypeCell;
-            let args: Vec<NodeRef> = params(token_ptr);
-            // 本来、宣言されているかを contains_key で確認したいが、今は外部の C ソースとリンクさせているため、このコンパイラの処理でパースした関数に対してのみ引数の数チェックをするにとどめる。
-            let glb_access = GLOBALS.try_lock().unwrap();
-            if glb_access.contains_key(&name) {
-                let glob = glb_access.get(&name).unwrap();
-                func_typ = if let Some(_typ) = glob.func_typ.clone() {
-                    _typ
-                } else {
-                    error_with_token!(
-                        "型\"{}\"は関数として扱えません。",
-                        &*token.borrow(),
-                        glob.typ.clone().unwrap()
-                    );
-                };
-
-                // 現在利用できる型は一応全て エラーレベルで compatible (ただしまともなコンパイラは warning を出す) なので、引数の数があっていれば良いものとする
-                let argc = func_typ.arg_typs.as_ref().unwrap().len();
-                if args.len() != argc {
-                    error_with_token!(
-                        "\"{}\" の引数は{}個で宣言されていますが、{}個が渡されました。",
-                        &*token.borrow(),
-                        name,
-                        argc,
-                        args.len()
-                    );
-                }
-                new_funcall(name, func_typ, args, token)
-            } else {
-                // 外部ソースの関数の戻り値の型をコンパイル時に得ることはできないため、int で固定とする
-                // また、引数の型は正しいとして args のものをコピーする
-                let mut arg_typs = vec![];
-                for arg in &args {
-                    arg_typs.push(Rc::new(RefCell::new(arg.borrow().typ.clone().unwrap())));
-                }
-                func_typ = TypeCell::new(Type::Int).make_func(arg_typs);
-                new_funcall(name, func_typ, args, token)
-            }
-        } else {
-            // グローバル変数については、外部ソースとのリンクは禁止として、LOCALS, GLOBALS に当たらなければエラーになるようにする
-            let mut typ: TypeCell = TypeCell::default();
-            let mut is_local = false;
-            let mut level = 0;
-            for (l, scope) in LOCALS.try_lock().unwrap().iter_mut().enumerate().rev() {
-                let _ = scope.entry(name.clone()).and_modify(|(_, t)| {
-                    typ = t.clone();
-                    level = l;
-                    is_local = true;
-                });
-                if is_local {
-                    break;
-                }
-            }
-
-            if !is_local {
-                typ = GLOBALS
-                    .try_lock()
-                    .unwrap()
-                    .entry(name.clone())
-                    .or_insert_with(|| {
-                        error_with_token!("定義されていない変数です。", &*token.borrow());
-                    })
-                    .typ
-                    .clone()
-                    .unwrap();
-            }
-
-            let mut node_ptr = new_lvar(name, Rc::clone(&token), typ, is_local, level);
-            while consume(token_ptr, "[") {
-                let index_token = Rc::clone(token_ptr);
-                let index = expr(token_ptr);
-                node_ptr = new_unary(
-                    Nodekind::Deref,
-                    new_add(node_ptr, index, index_token),
-                    Rc::clone(&token),
-                );
-                expect(token_ptr, "]");
-            }
-            node_ptr
+    *PARAM_ARRAY.try_lock().unwrap() = true;
+    let (name, typ) = declarator(token_ptr, typ);
+    *PARAM_ARRAY.try_lock().unwrap() = false;
+    let typ = if typ.is_array() {
+        TypeCell {
+            is_const: typ.is_const,
+            is_volatile: typ.is_volatile,
+            ..typ.make_deref().unwrap().make_ptr_to()
         }
-    } else if let Some(literal) = consume_literal(token_ptr) {
-        let size = literal.len() + 1;
-        let name = store_literal(literal);
-        new_lvar(
-            name,
-            token,
-            TypeCell::new(Type::Char).make_array_of(size),
-            false,
-            0,
-        )
+    } else if typ.typ == Type::Func {
+        typ.make_ptr_to()
     } else {
-        new_num(expect_number(token_ptr), token)
+        typ
+    };
+    if typ.is_void() {
+        error_with_token!(
+            "invalid-parameter",
+            "仮引数に void 型は使用できません。",
+            &token.borrow()
+        );
+    }
+    arg_typs.push(Rc::new(RefCell::new(typ.clone())));
+    // プロトタイプ宣言では不完全型の仮引数も書けるが、大きさが分からないので変数としては確保しない(定義では別途エラーになる)
+    if !name.is_empty() && !typ.is_incomplete() {
+        args.push(new_lvar(name, token, typ, true, 0));
     }
 }
 
-#[cfg(test)]
-pub mod tests {
-    use super::*;
-    use crate::globals::{FILE_NAMES, SRC};
-    use crate::tokenizer::tokenize;
-
-    static REP: usize = 40;
-
-    fn test_init(src: &str) {
-        let mut src_: Vec<String> = src.split('\n').map(|s| s.to_string() + "\n").collect();
-        FILE_NAMES.try_lock().unwrap().push("test".to_string());
-        let mut code = vec!["".to_string()];
-        code.append(&mut src_);
-        SRC.try_lock().unwrap().push(code);
+/// 生成規則:
+/// global-variable = gvar-decl ("," gvar-decl)* ";"
+fn global_variable(token_ptr: &mut TokenRef, typ: TypeCell, attr: VarAttr) -> NodeRef {
+    let mut node_ptr = gvar_decl(token_ptr, typ.clone(), attr.clone());
+    loop {
+        let comma_token = Rc::clone(token_ptr);
+        if !consume(token_ptr, ",") {
+            break;
+        }
+        node_ptr = new_binary(
+            Nodekind::Comma,
+            node_ptr,
+            gvar_decl(token_ptr, typ.clone(), attr.clone()),
+            comma_token,
+        )
     }
+    expect(token_ptr, ";");
 
-    fn search_tree(tree: &NodeRef) {
-        let node: &Node = &tree.borrow();
-        println!("{}", node);
+    node_ptr
+}
 
-        if node.left.is_some() {
-            search_tree(node.left.as_ref().unwrap());
+/// 生成規則:
+/// gvar-decl = declarator ("=" initializer)?
+/// 初期化子のない宣言は仮定義として扱い、同じ変数を何度でも宣言できる(実体の出力は resolve_tentative で決める)
+fn gvar_decl(token_ptr: &mut TokenRef, typ: TypeCell, mut attr: VarAttr) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    let (name, mut typ) = declarator_attr(token_ptr, typ, &mut attr);
+    if name.is_empty() {
+        error_with_token!(
+            "missing-declarator",
+            "変数名を指定する必要があります。",
+            &token.borrow()
+        );
+    }
+    asm_label(token_ptr, &name);
+    attribute_list(token_ptr, &mut attr);
+    if !attr.is_extern {
+        check_object_type(&typ, &token);
+    }
+    apply_alignas(&mut typ, &attr, &token);
+    let prev = GLOBALS.try_lock().unwrap().get(&name).cloned();
+    if let Some(prev) = &prev {
+        let decl = prev.token.as_ref().unwrap().borrow();
+        let prev_typ = if let Some(t) = &prev.typ {
+            t.clone()
+        } else {
+            error_with_prev!(
+                "conflicting-types",
+                "\"{}\"は既に関数として宣言されています。",
+                &*token.borrow(),
+                &decl,
+                name
+            );
+        };
+        check_linkage(&mut attr, prev, false, &token, &name);
+        check_thread_local(&attr, prev, &token, &name);
+        merge_attributes(&mut attr, prev);
+
+        // 配列の要素数はどちらか一方の宣言でのみ指定されていても良い(合成型で補完する)
+        if !prev_typ.is_compatible(&typ) {
+            error_with_prev!(
+                "conflicting-types",
+                "\"{}\"は型\"{}\"として宣言されています。",
+                &*token.borrow(),
+                &decl,
+                name,
+                prev_typ
+            );
         }
-        if node.right.is_some() {
-            search_tree(node.right.as_ref().unwrap());
+        typ = typ.composite(&prev_typ);
+        if is(token_ptr, "=") && !prev.init_data.is_empty() {
+            error_with_prev!(
+                "redefinition",
+                "\"{}\"は既に定義されています。",
+                &*token.borrow(),
+                &decl,
+                name
+            );
         }
-        if node.init.is_some() {
-            search_tree(node.init.as_ref().unwrap());
+    }
+    check_weak(&attr, &token);
+
+    let is_flex = typ.is_flex_array();
+    if consume(token_ptr, "=") {
+        gvar_initializer(token_ptr, name, typ, is_flex, attr, token)
+    } else {
+        let gvar = new_gvar(name.clone(), typ, attr, token);
+        // 既に初期化子付きで定義されている場合は、その情報を残しておく
+        if let Some(prev) = prev {
+            if !prev.init_data.is_empty() {
+                let _ = GLOBALS.try_lock().unwrap().insert(name, prev);
+            }
         }
-        if node.enter.is_some() {
-            search_tree(node.enter.as_ref().unwrap());
+        gvar
+    }
+}
+
+/// 生成規則としては lvar_initializer と同じ
+fn gvar_initializer(
+    token_ptr: &mut TokenRef,
+    name: String,
+    mut typ: TypeCell,
+    is_flex: bool,
+    attr: VarAttr,
+    token: TokenRef,
+) -> NodeRef {
+    if typ.is_array() && !is_kind(token_ptr, Tokenkind::String) && !is(token_ptr, "{") {
+        error_with_token!(
+            "invalid-initializer",
+            "配列の初期化の形式が異なります。",
+            &token_ptr.borrow()
+        );
+    }
+    if typ.array_dim().0.len() > 1 && is_kind(token_ptr, Tokenkind::String) {
+        error_with_token!(
+            "invalid-initializer",
+            "2次元以上の配列\"{}\"は単一の文字リテラルでは初期化できません。",
+            &*token_ptr.borrow(),
+            typ
+        );
+    }
+
+    let mut init = Initializer::new(typ.clone());
+    if typ.is_struct() && !is(token_ptr, "{") {
+        // 中括弧を省略できるのは初期化子の並びの中のみなので、式による構造体・共用体の代入として扱う
+        let _ = init.node.insert(assign(token_ptr));
+    } else {
+        initializer(token_ptr, &mut init);
+    }
+    if is_flex {
+        let _ = typ.array_size.insert(init.flex_elem_count());
+    }
+
+    let mut gvar = new_gvar(name.clone(), typ.clone(), attr, token);
+    make_gvar_init(&init, typ, &mut gvar);
+    // 初期化済みであることを GLOBALS にも反映する
+    let _ = GLOBALS
+        .try_lock()
+        .unwrap()
+        .insert(name, gvar.borrow().clone());
+    gvar
+}
+
+/// 初期化子の木を型の形に沿ってたどり、出力するデータを作る(初期化子のない要素は0埋め)
+fn make_gvar_init(init: &Initializer, typ: TypeCell, gvar: &mut NodeRef) {
+    if typ.is_array() {
+        let elem_typ = typ.make_deref().unwrap();
+        for elem in &init.elements {
+            make_gvar_init(&elem.borrow(), elem_typ.clone(), gvar);
         }
-        if node.routine.is_some() {
-            search_tree(node.routine.as_ref().unwrap());
+    } else if typ.is_struct() && init.node.is_none() {
+        // メンバをオフセット順に出力し、隙間と末尾はパディングとして0埋めする
+        // 共用体(や名前のない共用体のメンバ)のように位置が重なるメンバは、初期化されたものを1つだけ出力する
+        let info = typ.struct_info.as_ref().unwrap().borrow();
+        let mut end = 0;
+        for (ix, member) in info.members.iter().enumerate() {
+            if member.offset < end {
+                continue;
+            }
+            let overlapped = info.members[ix + 1..]
+                .iter()
+                .zip(&init.elements[ix + 1..])
+                .take_while(|(other, _)| other.offset == member.offset)
+                .find(|(_, elem)| elem.borrow().is_initialized());
+            let elem = if init.elements[ix].borrow().is_initialized() {
+                &init.elements[ix]
+            } else if let Some((_, elem)) = overlapped {
+                elem
+            } else {
+                &init.elements[ix]
+            };
+            let elem = elem.borrow();
+            let elem_typ = elem.typ.clone().unwrap();
+            push_padding(gvar, member.offset - end);
+            make_gvar_init(&elem, elem_typ.clone(), gvar);
+            end = member.offset + elem_typ.bytes();
         }
-        if node.branch.is_some() {
-            search_tree(node.branch.as_ref().unwrap());
+        push_padding(gvar, info.bytes - end);
+    } else {
+        let data = if let Some(node) = &init.node {
+            let mut label: Option<String> = None;
+            let val = eval_const(node, &mut label);
+            InitData::new(typ.bytes(), val, label)
+        } else {
+            InitData::new(typ.bytes(), 0, None)
+        };
+        push_init_data(gvar, data);
+    }
+}
+
+#[inline]
+fn push_padding(gvar: &mut NodeRef, bytes: usize) {
+    if bytes > 0 {
+        push_init_data(gvar, InitData::new(bytes, 0, None));
+    }
+}
+
+/// 連続する0はまとめて1つの .zero として出力する
+fn push_init_data(gvar: &mut NodeRef, data: InitData) {
+    let is_zero = |data: &InitData| data.val == 0 && data.label.is_none();
+    let mut gvar_bor = gvar.borrow_mut();
+    let init_data = &mut gvar_bor.init_data;
+    if is_zero(&data) {
+        if let Some(last_data) = init_data.last_mut() {
+            if is_zero(last_data) {
+                last_data.size += data.size;
+                return;
+            }
         }
-        if node.els.is_some() {
-            search_tree(node.els.as_ref().unwrap());
+    }
+    init_data.push(data);
+}
+
+macro_rules! eval_const_left {
+    ($node: expr, $label: expr) => {
+        eval_const(($node).borrow().left.as_ref().unwrap(), ($label))
+    };
+}
+
+macro_rules! eval_const_right {
+    ($node: expr, $label: expr) => {
+        eval_const(($node).borrow().right.as_ref().unwrap(), ($label))
+    };
+}
+
+/// コンパイル時定数の処理を行う関数
+/// label はグローバル変数への参照があった場合にどの変数を参照しているかを持つ
+fn eval_const(node: &NodeRef, label: &mut Option<String>) -> i64 {
+    confirm_type(node);
+    let typ = node.borrow().typ.clone().unwrap();
+    let kind = node.borrow().kind;
+    match kind {
+        // 符号付き整数のオーバーフローは未定義動作だが、 gcc と同様に2の補数で折り返した値とする
+        Nodekind::Add => truncate_const(
+            eval_const_left!(node, label).wrapping_add(eval_const_right!(node, label)),
+            &typ,
+        ),
+        Nodekind::Sub => {
+            let left_val = eval_const_left!(node, label);
+            let left_label = label.clone();
+            let right_val = eval_const_right!(node, label);
+            if let (Some(l), Some(r)) = (left_label, &label) {
+                // コンパイル時のポインタ同士の引き算は、同じラベル同士でのみ可能(打ち消し合うのでラベルをクリアする)
+                if l != *r {
+                    error_with_node!(
+                        "not-constant",
+                        "コンパイル時定数のみが使用可能です。",
+                        &node.borrow().right.as_ref().unwrap().borrow()
+                    );
+                }
+                let _ = label.take();
+            }
+            truncate_const(left_val.wrapping_sub(right_val), &typ)
         }
-        for child in &node.children {
-            search_tree(child);
+        Nodekind::Mul => truncate_const(
+            eval_const_left!(node, label).wrapping_mul(eval_const_right!(node, label)),
+            &typ,
+        ),
+        // 除算と右シフトは unsigned な型の場合に結果が変わる
+        // シフト幅が負または64以上の場合は、全てのビットを押し出した値とする
+        Nodekind::Div | Nodekind::Mod | Nodekind::RShift => {
+            let left_val = eval_const_left!(node, label);
+            let right_val = eval_const_right!(node, label);
+            if kind != Nodekind::RShift && right_val == 0 {
+                error_with_node!(
+                    "division-by-zero",
+                    "定数式の中で0による除算が行われています。",
+                    &node.borrow().right.as_ref().unwrap().borrow()
+                );
+            }
+            let shift = u32::try_from(right_val).ok();
+            let val = if typ.is_unsigned {
+                let (l, r) = (left_val as u64, right_val as u64);
+                (match kind {
+                    Nodekind::Div => l.checked_div(r),
+                    Nodekind::Mod => l.checked_rem(r),
+                    _ => Some(shift.and_then(|r| l.checked_shr(r)).unwrap_or(0)),
+                })
+                .unwrap() as i64
+            } else {
+                // i64::MIN / -1 のみ溢れるので、折り返した値とする
+                match kind {
+                    Nodekind::Div => left_val
+                        .checked_div(right_val)
+                        .unwrap_or(left_val.wrapping_div(right_val)),
+                    Nodekind::Mod => left_val
+                        .checked_rem(right_val)
+                        .unwrap_or(left_val.wrapping_rem(right_val)),
+                    _ => shift
+                        .and_then(|r| left_val.checked_shr(r))
+                        .unwrap_or(left_val >> 63),
+                }
+            };
+            truncate_const(val, &typ)
         }
-        for arg in &node.args {
-            search_tree(arg);
+        Nodekind::LShift => {
+            let left_val = eval_const_left!(node, label);
+            let shift = u32::try_from(eval_const_right!(node, label)).ok();
+            truncate_const(
+                shift.and_then(|r| left_val.checked_shl(r)).unwrap_or(0),
+                &typ,
+            )
         }
-        if node.stmts.is_some() {
-            for stmt_ in node.stmts.as_ref().unwrap() {
-                search_tree(stmt_);
+        Nodekind::BitAnd => eval_const_left!(node, label) & eval_const_right!(node, label),
+        Nodekind::BitOr => eval_const_left!(node, label) | eval_const_right!(node, label),
+        Nodekind::BitXor => eval_const_left!(node, label) ^ eval_const_right!(node, label),
+        Nodekind::BitNot => !eval_const_left!(node, label),
+        Nodekind::LogAnd => {
+            !(eval_const_left!(node, label) == 0 || eval_const_right!(node, label) == 0) as i64
+        }
+        Nodekind::LogOr => {
+            !(eval_const_left!(node, label) == 0 && eval_const_right!(node, label) == 0) as i64
+        }
+        Nodekind::LogNot => (eval_const_left!(node, label) == 0) as i64,
+        // 比較は通常の算術型変換後の左辺の型が unsigned であれば符号なしで行う
+        Nodekind::Eq | Nodekind::NEq | Nodekind::LThan | Nodekind::LEq => {
+            let left_val = eval_const_left!(node, label);
+            let right_val = eval_const_right!(node, label);
+            let is_unsigned = node
+                .borrow()
+                .left
+                .as_ref()
+                .unwrap()
+                .borrow()
+                .typ
+                .as_ref()
+                .unwrap()
+                .is_unsigned;
+            let ord = if is_unsigned {
+                (left_val as u64).cmp(&(right_val as u64))
+            } else {
+                left_val.cmp(&right_val)
+            };
+            (match kind {
+                Nodekind::Eq => ord == Ordering::Equal,
+                Nodekind::NEq => ord != Ordering::Equal,
+                Nodekind::LThan => ord == Ordering::Less,
+                _ => ord != Ordering::Greater,
+            }) as i64
+        }
+        Nodekind::Cast => truncate_const(eval_const_left!(node, label), &typ),
+        Nodekind::Addr => eval_label(node.borrow().left.as_ref().unwrap(), label),
+        // 配列(文字列リテラルや複合リテラルを含む)は先頭要素のアドレスとして評価される
+        Nodekind::Lvar if typ.is_array() => eval_label(node, label),
+        Nodekind::Num => node.borrow().val.unwrap() as i64,
+        _ => {
+            error_with_node!(
+                "not-constant",
+                "コンパイル時定数のみが使用可能です。",
+                &node.borrow()
+            );
+        }
+    }
+}
+
+/// 定数を型の大きさに切り詰め、型に従って符号拡張(unsigned ならゼロ拡張)する
+fn truncate_const(val: i64, typ: &TypeCell) -> i64 {
+    match (typ.value_bytes(), typ.is_unsigned) {
+        (1, true) => val as u8 as i64,
+        (1, false) => val as i8 as i64,
+        (2, true) => val as u16 as i64,
+        (2, false) => val as i16 as i64,
+        (4, true) => val as u32 as i64,
+        (4, false) => val as i32 as i64,
+        _ => val,
+    }
+}
+
+/// グローバル変数のアドレス評価時などに使用する関数
+fn eval_label(node: &NodeRef, label: &mut Option<String>) -> i64 {
+    let kind = node.borrow().kind;
+    match kind {
+        Nodekind::Deref => {
+            eval_const_left!(node, label)
+        }
+        Nodekind::Lvar => {
+            // 初期化時に仮で生成される変数はグローバルスコープでも(!is_local な) Lvar であることに注意
+            // また、 Initializer のパース時に定義されていないグローバル変数は弾かれるため、ここでは宣言チェック不要
+            if node.borrow().is_local {
+                error_with_node!(
+                    "not-constant",
+                    "コンパイル時定数のみが使用可能です。",
+                    &node.borrow()
+                );
+            }
+            if node.borrow().is_tls {
+                error_with_node!(
+                    "not-constant",
+                    "スレッドローカルな変数のアドレスはコンパイル時定数ではありません。",
+                    &node.borrow()
+                );
+            }
+            let _ = label.insert(node.borrow().name.clone().unwrap());
+            0
+        }
+        _ => {
+            error_with_node!(
+                "not-constant",
+                "コンパイル時定数のみが使用可能です。",
+                &node.borrow()
+            );
+        }
+    }
+}
+
+/// 宣言子の後ろに書かれた属性は読み捨てる
+fn declarator(token_ptr: &mut TokenRef, typ: TypeCell) -> (String, TypeCell) {
+    declarator_attr(token_ptr, typ, &mut VarAttr::default())
+}
+
+/// 生成規則:
+/// declarator = pointers ("(" declarator ")" | ident ) type-suffix attribute-list
+/// 宣言子の後ろに書かれた属性は attr に反映する
+fn declarator_attr(
+    token_ptr: &mut TokenRef,
+    mut typ: TypeCell,
+    attr: &mut VarAttr,
+) -> (String, TypeCell) {
+    typ = pointers(token_ptr, typ);
+    if consume(token_ptr, "(") {
+        let token = &mut Rc::clone(token_ptr);
+        let dummy = TypeCell::default();
+        let _ = declarator(token, dummy);
+        expect(token, ")");
+        typ = type_suffix(token, typ);
+        attribute_list(token, attr);
+        let nt = declarator_attr(token_ptr, typ, attr);
+        *token_ptr = token.clone();
+
+        nt
+    } else {
+        let name = consume_ident(token_ptr).unwrap_or_default();
+        typ = type_suffix(token_ptr, typ);
+        attribute_list(token_ptr, attr);
+
+        (name, typ)
+    }
+}
+
+/// 生成規則:
+/// pointers = ("*" qualifiers)*
+fn pointers(token_ptr: &mut TokenRef, mut typ: TypeCell) -> TypeCell {
+    while consume(token_ptr, "*") {
+        typ = qualifiers(token_ptr, typ.make_ptr_to());
+    }
+    typ
+}
+
+/// 生成規則:
+/// type-suffix = "(" func-args ")" | "[" array-suffix | null
+fn type_suffix(token_ptr: &mut TokenRef, typ: TypeCell) -> TypeCell {
+    if consume(token_ptr, "(") {
+        let is_unprototyped = is(token_ptr, ")");
+        let (_, arg_typs, is_variadic) = func_args(token_ptr);
+        expect(token_ptr, ")");
+        let mut typ = typ.make_func(arg_typs);
+        typ.is_variadic = is_variadic;
+        typ.is_unprototyped = is_unprototyped;
+        typ
+    } else if consume(token_ptr, "[") {
+        array_suffix(token_ptr, typ)
+    } else {
+        typ
+    }
+}
+
+/// 生成規則:
+/// declaration = declspec (";" | typedef-declaration | lvar-decl ("," lvar-decl )* ";")
+fn declaration(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    let (typ, attr) = declspec(token_ptr);
+    check_not_inline(&attr, &token);
+    if attr.is_typedef {
+        return typedef_declaration(token_ptr, typ);
+    }
+    if consume(token_ptr, ";") {
+        return nop();
+    }
+    if attr.is_static || attr.is_extern {
+        return static_declaration(token_ptr, typ, attr);
+    }
+    if attr.is_thread_local {
+        error_with_token!("invalid-storage-class", 
+            "ブロックスコープの _Thread_local な変数には static か extern を指定する必要があります。",
+            &token.borrow()
+        );
+    }
+    let mut node_ptr = lvar_decl(token_ptr, typ.clone(), attr.clone());
+    loop {
+        let token = Rc::clone(token_ptr);
+        if !consume(token_ptr, ",") {
+            break;
+        }
+        node_ptr = new_binary(
+            Nodekind::Comma,
+            node_ptr,
+            lvar_decl(token_ptr, typ.clone(), attr.clone()),
+            token,
+        )
+    }
+    expect(token_ptr, ";");
+
+    node_ptr
+}
+
+/// static, extern なローカル変数の宣言
+/// static の場合はグローバル変数と同様に .data, .bss に置き、関数内でのみ参照できる固有のラベルを付ける
+/// extern の場合は同名のグローバル変数を参照する
+/// 生成規則:
+/// static-declaration = svar-decl ("," svar-decl)* ";"
+/// svar-decl = declarator ("=" initializer)?
+fn static_declaration(token_ptr: &mut TokenRef, typ: TypeCell, attr: VarAttr) -> NodeRef {
+    loop {
+        let token = Rc::clone(token_ptr);
+        let mut attr = attr.clone();
+        let (name, mut typ) = declarator_attr(token_ptr, typ.clone(), &mut attr);
+        if name.is_empty() {
+            error_with_token!(
+                "missing-declarator",
+                "変数名を指定する必要があります。",
+                &token.borrow()
+            );
+        }
+        if typ.typ == Type::Func {
+            block_func_decl(name, typ, &attr, &token);
+            if !consume(token_ptr, ",") {
+                break;
             }
+            continue;
+        }
+        if typ.has_vla() || !VLA_SIZES.try_lock().unwrap().is_empty() {
+            error_with_token!(
+                "invalid-vla",
+                "static な変数の型には可変長配列を使用できません。",
+                &token.borrow()
+            );
+        }
+        if !attr.is_extern {
+            check_object_type(&typ, &token);
+        }
+        apply_alignas(&mut typ, &attr, &token);
+        if LOCALS
+            .try_lock()
+            .unwrap()
+            .last()
+            .unwrap()
+            .contains_key(&name)
+        {
+            error_with_token!("redeclaration", "既に宣言された変数です。", &token.borrow());
+        }
+
+        let (typ, label) = if attr.is_extern {
+            if is(token_ptr, "=") {
+                error_with_token!(
+                    "invalid-initializer",
+                    "ブロックスコープの extern 宣言は初期化できません。",
+                    &token.borrow()
+                );
+            }
+            if let Some(prev) = GLOBALS.try_lock().unwrap().get(&name) {
+                if prev.typ.is_some() {
+                    check_thread_local(&attr, prev, &token, &name);
+                }
+            }
+            if attr.is_thread_local {
+                let _ = EXTERN_THREAD_LOCALS
+                    .try_lock()
+                    .unwrap()
+                    .insert(name.clone());
+            }
+            (typ, name.clone())
+        } else {
+            let label = format!("{}.{}", name, get_static_id());
+            let is_flex = typ.is_flex_array();
+            let svar = if consume(token_ptr, "=") {
+                gvar_initializer(token_ptr, label.clone(), typ, is_flex, attr, token)
+            } else {
+                if is_flex {
+                    error_with_token!(
+                        "incomplete-type",
+                        "初期化しない場合は完全な配列サイズが必要です。",
+                        &token.borrow()
+                    );
+                }
+                new_gvar(label.clone(), typ, attr, token)
+            };
+            STATIC_LOCALS
+                .try_lock()
+                .unwrap()
+                .push(svar.borrow().clone());
+            let typ = svar.borrow().typ.clone().unwrap();
+            (typ, label)
+        };
+        let _ = LOCALS
+            .try_lock()
+            .unwrap()
+            .last_mut()
+            .unwrap()
+            .insert(name, (0, typ, Some(label)));
+
+        if !consume(token_ptr, ",") {
+            break;
+        }
+    }
+    expect(token_ptr, ";");
+    nop()
+}
+
+/// ブロックスコープの関数の宣言: 同名の関数と同じ実体を指すので、その宣言とは互換性のある型である必要がある
+/// 宣言したスコープの中での呼び出しには、合成型を使う
+fn block_func_decl(name: String, mut typ: TypeCell, attr: &VarAttr, token: &TokenRef) {
+    if attr.is_static {
+        error_with_token!(
+            "invalid-storage-class",
+            "ブロックスコープの関数の宣言には static を指定できません。",
+            &token.borrow()
+        );
+    }
+    if attr.is_thread_local {
+        error_with_token!(
+            "invalid-storage-class",
+            "関数には _Thread_local を指定できません。",
+            &token.borrow()
+        );
+    }
+    if attr.align.is_some() {
+        error_with_token!(
+            "invalid-alignment",
+            "関数にはアラインメントを指定できません。",
+            &token.borrow()
+        );
+    }
+    let prev = LOCALS
+        .try_lock()
+        .unwrap()
+        .last()
+        .unwrap()
+        .get(&name)
+        .map(|(_, typ, _)| typ.clone());
+    if let Some(prev_typ) = prev {
+        if prev_typ.typ != Type::Func {
+            error_with_token!("redeclaration", "既に宣言された変数です。", &token.borrow());
+        }
+        typ = compose_func_decl(typ, &prev_typ, None, token);
+    }
+    if let Some(glob) = GLOBALS.try_lock().unwrap().get(&name) {
+        let decl = glob.token.as_ref().unwrap().borrow();
+        if let Some(prev_typ) = &glob.func_typ {
+            typ = compose_func_decl(typ, prev_typ, Some(&decl), token);
+        } else {
+            error_with_prev!(
+                "conflicting-types",
+                "\"{}\"は既にグローバル変数として宣言されています。",
+                &*token.borrow(),
+                &decl,
+                name
+            );
+        }
+    }
+    let _ = LOCALS
+        .try_lock()
+        .unwrap()
+        .last_mut()
+        .unwrap()
+        .insert(name.clone(), (0, typ, Some(name)));
+}
+
+/// 同じ関数の2つの宣言の型が互換性を持つことをチェックして、合成型を返す
+fn compose_func_decl(
+    typ: TypeCell,
+    prev_typ: &TypeCell,
+    decl: Option<&Token>,
+    token: &TokenRef,
+) -> TypeCell {
+    if !typ.is_compatible(prev_typ) {
+        let msg = format!(
+            "型\"{}\"はプロトタイプ宣言の型\"{}\"と互換性がありません。",
+            typ, prev_typ
+        );
+        if let Some(decl) = decl {
+            error_with_prev!("conflicting-types", &msg, &*token.borrow(), decl);
+        }
+        error_with_token!("conflicting-types", &msg, &token.borrow());
+    }
+    typ.composite(prev_typ)
+}
+
+/// 生成規則:
+/// lvar-decl = declarator ("=" initializer)?
+fn lvar_decl(token_ptr: &mut TokenRef, typ: TypeCell, mut attr: VarAttr) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    let (name, mut typ) = declarator_attr(token_ptr, typ, &mut attr);
+    if name.is_empty() {
+        error_with_token!(
+            "missing-declarator",
+            "変数名を指定する必要があります。",
+            &token.borrow()
+        );
+    }
+    // int g(int); のような関数の宣言は、変数として領域を確保しない
+    if typ.typ == Type::Func {
+        block_func_decl(name, typ, &attr, &token);
+        return nop();
+    }
+    check_object_type(&typ, &token);
+    apply_alignas(&mut typ, &attr, &token);
+    // 可変長配列の実体は16の倍数に揃えて動的に確保するため、それより大きいアラインメントは保証できない
+    if typ.is_vla() && typ.align() > 16 {
+        error_with_token!(
+            "invalid-alignment",
+            "可変長配列には16より大きいアラインメントを指定できません。",
+            &token.borrow()
+        );
+    }
+    if LOCALS
+        .try_lock()
+        .unwrap()
+        .last()
+        .unwrap()
+        .contains_key(&name)
+    {
+        error_with_token!("redeclaration", "既に宣言された変数です。", &token.borrow());
+    }
+
+    if typ.is_vla() {
+        if is(token_ptr, "=") {
+            error_with_token!(
+                "invalid-initializer",
+                "可変長配列は初期化できません。",
+                &token_ptr.borrow()
+            );
+        }
+        // 可変長配列は、宣言の位置でバイト数を計算してスタック上に領域を確保する
+        let var = new_lvar(name, Rc::clone(&token), typ.clone(), true, current_scope());
+        let ptr = var.borrow().offset.unwrap();
+        let size = typ.vla_size.unwrap();
+        let alloca = new_unary(Nodekind::Alloca, size_of(&typ), Rc::clone(&token));
+        let _ = alloca
+            .borrow_mut()
+            .typ
+            .insert(TypeCell::new(Type::Char).make_ptr_to());
+        let assign = new_binary(
+            Nodekind::Assign,
+            direct_offset_lvar(ptr, TypeCell::new(Type::Char).make_ptr_to()),
+            alloca,
+            token,
+        );
+        confirm_type(&assign);
+        *USES_ALLOCA.try_lock().unwrap() = true;
+        LIVE_VLAS.try_lock().unwrap().push(LiveVla {
+            level: current_scope(),
+            ptr,
+            size,
+        });
+        return with_vla_sizes(assign);
+    }
+
+    let is_flex = typ.is_flex_array();
+    // int (*p)[n]; のように可変長配列を指すポインタの場合も、ここで指す先の大きさを計算する
+    with_vla_sizes(if consume(token_ptr, "=") {
+        lvar_initializer(token_ptr, name, typ, is_flex, token)
+    } else {
+        // 初期化しない場合は何もアセンブリを吐かない
+        if is_flex {
+            error_with_token!(
+                "incomplete-type",
+                "初期化しない場合は完全な配列サイズが必要です。",
+                &token.borrow()
+            );
+        }
+        let _ = new_lvar(name, token, typ, true, current_scope());
+        nop()
+    })
+}
+
+/// 生成規則:
+/// array-suffix = ("static" | "const" | "volatile")* expr? "]" ("[" array-suffix)?
+/// ブロックスコープと仮引数では、要素数が定数式でない配列は可変長配列になる
+/// 添字の中の static や型修飾子は仮引数の最も外側の配列でのみ書くことができ、型修飾子は調整後のポインタの修飾子として配列の型に持たせる
+fn array_suffix(token_ptr: &mut TokenRef, mut typ: TypeCell) -> TypeCell {
+    let ptr_err = Rc::clone(token_ptr);
+
+    let is_param = std::mem::replace(&mut *PARAM_ARRAY.try_lock().unwrap(), false);
+    let mut quals = TypeCell::default();
+    let mut is_static = false;
+    loop {
+        let token = Rc::clone(token_ptr);
+        if consume(token_ptr, "static") {
+            is_static = true;
+        } else if is(token_ptr, "const") || is(token_ptr, "volatile") {
+            quals = qualifiers(token_ptr, quals);
+        } else {
+            break;
+        }
+        if !is_param {
+            error_with_token!(
+                "invalid-array-declarator",
+                "配列の添字の中の static や型修飾子は、仮引数の最も外側の配列でのみ使用できます。",
+                &token.borrow()
+            );
+        }
+    }
+
+    let mut vla_len = None;
+    let array_size = if consume(token_ptr, "]") {
+        if is_static {
+            error_with_token!(
+                "invalid-array-declarator",
+                "static を指定した場合は要素数が必要です。",
+                &ptr_err.borrow()
+            );
+        }
+        None
+    } else {
+        let len = expr(token_ptr);
+        confirm_type(&len);
+        let is_vla_allowed =
+            CURRENT_FUNC.try_lock().unwrap().is_some() || *PARAM_DEPTH.try_lock().unwrap() > 0;
+        let size = if is_vla_allowed && !is_const_expr(&len) {
+            vla_len = Some(len);
+            0
+        } else {
+            eval_const_expr(&len)
+        };
+        if size < 0 {
+            error_with_token!(
+                "invalid-array-size",
+                "配列のサイズは0以上である必要があります。",
+                &ptr_err.borrow()
+            );
+        }
+        expect(token_ptr, "]");
+        Some(size)
+    };
+
+    // 配列の次元は後ろから処理する
+    if consume(token_ptr, "[") {
+        let ptr_err = Rc::clone(token_ptr);
+        if consume(token_ptr, "]") {
+            error_with_token!(
+                "invalid-array-declarator",
+                "2次元目以降の要素サイズは必ず指定する必要があります。",
+                &ptr_err.borrow()
+            );
+        }
+        typ = array_suffix(token_ptr, typ);
+    }
+
+    // 要素が可変長配列であれば、要素数が定数でも全体の大きさは実行時にしか分からない
+    // ただし、要素数を省略した仮引数の配列はポインタに調整されるので、大きさを計算する必要はない
+    let array_typ = if vla_len.is_some() || (typ.has_vla() && array_size.is_some()) {
+        let len = vla_len.unwrap_or_else(|| tmp_num!(array_size.unwrap() as i32));
+        vla_of(typ, len, ptr_err)
+    } else if let Some(size) = array_size {
+        typ.make_array_of(size as usize)
+    } else if typ.has_vla() && !is_param {
+        error_with_token!(
+            "invalid-array-declarator",
+            "可変長配列を要素とする配列の要素数は省略できません。",
+            &ptr_err.borrow()
+        );
+    } else {
+        typ.make_flex_array_of()
+    };
+    TypeCell {
+        is_const: quals.is_const,
+        is_volatile: quals.is_volatile,
+        ..array_typ
+    }
+}
+
+/// 要素の型が typ で要素数が len の可変長配列の型を作る
+/// 配列全体のバイト数は隠しローカル変数に格納することとし、それを計算する式を VLA_SIZES に積む
+fn vla_of(typ: TypeCell, len: NodeRef, token: TokenRef) -> TypeCell {
+    if !len.borrow().typ.as_ref().unwrap().is_integer() {
+        error_with_node!(
+            "invalid-array-size",
+            "配列の要素数は整数型である必要があります。",
+            &len.borrow()
+        );
+    }
+    let name = format!(".vla_size.{}", get_static_id());
+    let size_var = _lvar(
+        name,
+        Some(Rc::clone(&token)),
+        Some(TypeCell::new_size_t()),
+        true,
+        current_scope(),
+    );
+    let offset = size_var.borrow().offset.unwrap();
+    let elem_size = size_of(&typ);
+    let bytes = tmp_binary!(
+        Nodekind::Mul,
+        new_cast(&len, TypeCell::new_size_t()),
+        elem_size
+    );
+    let assign = new_binary(Nodekind::Assign, size_var, bytes, token);
+    confirm_type(&assign);
+    VLA_SIZES.try_lock().unwrap().push(assign.borrow().clone());
+    typ.make_vla_of(offset)
+}
+
+/// 型 typ の大きさを size_t で評価するノード: 可変長配列であれば、実行時に計算した値を格納した変数を読む
+fn size_of(typ: &TypeCell) -> NodeRef {
+    let node_ptr = if let Some(offset) = typ.vla_size {
+        direct_offset_lvar(offset, TypeCell::new_size_t())
+    } else {
+        tmp_num!(typ.bytes() as i32)
+    };
+    let _ = node_ptr.borrow_mut().typ.insert(TypeCell::new_size_t());
+    node_ptr
+}
+
+/// 可変長配列のバイト数を計算する式が残っていれば、 node_ptr の評価の前に実行する
+fn with_vla_sizes(node_ptr: NodeRef) -> NodeRef {
+    let sizes: Vec<Node> = VLA_SIZES.try_lock().unwrap().drain(..).collect();
+    let node_ptr = sizes.into_iter().rev().fold(node_ptr, |acc, size| {
+        tmp_binary!(Nodekind::Comma, Rc::new(RefCell::new(size)), acc)
+    });
+    confirm_type(&node_ptr);
+    node_ptr
+}
+
+/// 生成規則:
+/// static-assert = "_Static_assert" "(" assign ("," string)? ")" ";"
+/// メッセージの省略は C23 の仕様だが、 gcc と同様に受け付ける
+fn static_assert(token_ptr: &mut TokenRef) {
+    expect(token_ptr, "_Static_assert");
+    expect(token_ptr, "(");
+    let token = Rc::clone(token_ptr);
+    // メッセージとの区切りのコンマを読まないように、代入式の範囲で読む
+    let val = eval_const_expr(&assign(token_ptr));
+    let msg = if consume(token_ptr, ",") {
+        Some(expect_literal(token_ptr))
+    } else {
+        None
+    };
+    expect(token_ptr, ")");
+    expect(token_ptr, ";");
+    if val == 0 {
+        if let Some(msg) = msg {
+            error_with_token!(
+                "static-assert",
+                "静的アサーションに失敗しました: \"{}\"",
+                &token.borrow(),
+                msg
+            );
+        } else {
+            error_with_token!(
+                "static-assert",
+                "静的アサーションに失敗しました。",
+                &token.borrow()
+            );
+        }
+    }
+}
+
+#[inline]
+fn is_asm(token_ptr: &mut TokenRef) -> bool {
+    is(token_ptr, "asm") || is(token_ptr, "__asm__") || is(token_ptr, "__asm")
+}
+
+/// 生成規則:
+/// asm-stmt = ("asm" | "__asm__" | "__asm") asm-qualifier* "(" string-literal+
+///         (":" asm-operands? (":" asm-operands? (":" asm-clobbers?)?)?)? ")" ";"
+/// asm-qualifier = "volatile" | "__volatile__" | "__volatile" | "inline" | "__inline" | "__inline__"
+/// asm-clobbers = string-literal ("," string-literal)*
+/// 最適化を行わないので、 volatile や inline の有無は区別しない
+/// オペランドを指定しない基本 asm はファイルスコープでも使用でき、テンプレートをそのまま出力する
+fn asm_stmt(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    token_ptr_exceed(token_ptr);
+    while consume_volatile(token_ptr)
+        || consume(token_ptr, "inline")
+        || consume(token_ptr, "__inline")
+        || consume(token_ptr, "__inline__")
+    {}
+    if is(token_ptr, "goto") {
+        error_with_token!(
+            "unsupported",
+            "asm goto はサポートしていません。",
+            &token_ptr.borrow()
+        );
+    }
+    expect(token_ptr, "(");
+    let mut template = expect_literal(token_ptr);
+    while let Some(literal) = consume_literal(token_ptr) {
+        template.push_str(&literal);
+    }
+    let mut asm = InlineAsm {
+        template,
+        is_basic: true,
+        ..Default::default()
+    };
+    let mut args: Vec<NodeRef> = vec![];
+    if is(token_ptr, ":") {
+        if CURRENT_FUNC.try_lock().unwrap().is_none() {
+            error_with_token!(
+                "outside-function",
+                "オペランドを指定する asm は関数の中でのみ使用できます。",
+                &token.borrow()
+            );
+        }
+        asm.is_basic = false;
+        for is_output in [true, false] {
+            if consume(token_ptr, ":") {
+                asm_operands(token_ptr, is_output, &mut asm, &mut args);
+            }
+        }
+        if consume(token_ptr, ":") && !is(token_ptr, ")") {
+            loop {
+                let clobber_token = Rc::clone(token_ptr);
+                let clobber = expect_literal(token_ptr);
+                let clobber = asm_clobber(&clobber, &clobber_token);
+                check_asm_register(&asm, clobber, &clobber_token);
+                asm.clobbers.push(clobber.to_string());
+                if !consume(token_ptr, ",") {
+                    break;
+                }
+            }
+        }
+    }
+    expect(token_ptr, ")");
+    expect(token_ptr, ";");
+
+    Rc::new(RefCell::new(Node {
+        kind: Nodekind::InlineAsm,
+        token: Some(token),
+        typ: Some(TypeCell::new(Type::Void)),
+        args,
+        asm: Some(asm),
+        ..Default::default()
+    }))
+}
+
+/// 生成規則:
+/// asm-operands = asm-operand ("," asm-operand)*
+/// asm-operand = ("[" ident "]")? string-literal "(" expr ")"
+fn asm_operands(
+    token_ptr: &mut TokenRef,
+    is_output: bool,
+    asm: &mut InlineAsm,
+    args: &mut Vec<NodeRef>,
+) {
+    if is(token_ptr, ":") || is(token_ptr, ")") {
+        return;
+    }
+    loop {
+        let name = if consume(token_ptr, "[") {
+            let name = expect_ident(token_ptr);
+            expect(token_ptr, "]");
+            Some(name)
+        } else {
+            None
+        };
+        let token = Rc::clone(token_ptr);
+        let constraint = expect_literal(token_ptr);
+        expect(token_ptr, "(");
+        let node_ptr = expr(token_ptr);
+        confirm_type(&node_ptr);
+        expect(token_ptr, ")");
+
+        let operand = asm_operand(&constraint, name, is_output, &node_ptr, asm, &token);
+        if let Some(reg) = asm_fixed_register(&operand.constraint) {
+            // 同じ種類のオペランドどうしでは、同じレジスタを指定できない
+            let is_dup = asm.operands.iter().any(|other| {
+                other.is_output == is_output && asm_fixed_register(&other.constraint) == Some(reg)
+            });
+            if is_dup {
+                error_with_token!(
+                    "asm-operand",
+                    "レジスタ\"{}\"が複数のオペランドに指定されています。",
+                    &token.borrow(),
+                    reg
+                );
+            }
+        }
+        asm.operands.push(operand);
+        args.push(node_ptr);
+        if !consume(token_ptr, ",") {
+            break;
+        }
+    }
+}
+
+/// 制約を読み、オペランドの式がその制約に使えるかを確かめる
+/// 複数の制約が指定された場合は、レジスタ("r"), 特定のレジスタ, メモリ("m"), 即値("i")の順に優先して1つを選ぶ
+fn asm_operand(
+    raw: &str,
+    name: Option<String>,
+    is_output: bool,
+    node_ptr: &NodeRef,
+    asm: &InlineAsm,
+    token: &TokenRef,
+) -> AsmOperand {
+    let is_inout = raw.starts_with('+');
+    if is_output && !raw.starts_with('=') && !is_inout {
+        error_with_token!(
+            "asm-operand",
+            "出力オペランドの制約\"{}\"は\"=\"か\"+\"で始まる必要があります。",
+            &token.borrow(),
+            raw
+        );
+    }
+    if !is_output && (raw.contains('=') || raw.contains('+')) {
+        error_with_token!(
+            "asm-operand",
+            "入力オペランドの制約\"{}\"に\"=\"や\"+\"は指定できません。",
+            &token.borrow(),
+            raw
+        );
+    }
+    let body: String = raw.chars().filter(|c| !"=+&".contains(*c)).collect();
+    let constraint = if !is_output && !body.is_empty() && body.chars().all(|c| c.is_ascii_digit()) {
+        body.clone()
+    } else if body.chars().any(|c| "rqg".contains(c)) {
+        "r".to_string()
+    } else if let Some(c) = body.chars().find(|c| "abcdSD".contains(*c)) {
+        c.to_string()
+    } else if body.contains('m') {
+        "m".to_string()
+    } else if !is_output && body.chars().any(|c| "in".contains(c)) {
+        "i".to_string()
+    } else {
+        error_with_token!(
+            "asm-operand",
+            "制約\"{}\"はサポートしていません。",
+            &token.borrow(),
+            raw
+        );
+    };
+
+    let node = node_ptr.borrow();
+    let typ = node.typ.clone().unwrap();
+    let is_lvalue = [Nodekind::Lvar, Nodekind::Deref, Nodekind::Member].contains(&node.kind);
+    if is_output {
+        if !is_lvalue {
+            error_with_node!(
+                "asm-operand",
+                "出力オペランドは左辺値である必要があります。",
+                &node
+            );
+        }
+        if typ.is_const {
+            error_with_node!(
+                "read-only-assignment",
+                "const 修飾された型\"{}\"の左辺値は変更できません。",
+                &node,
+                typ
+            );
+        }
+    }
+    let mut imm = None;
+    match constraint.as_str() {
+        "m" => {
+            if !is_lvalue {
+                error_with_node!(
+                    "asm-operand",
+                    "メモリの制約を持つオペランドは左辺値である必要があります。",
+                    &node
+                );
+            }
+        }
+        "i" => {
+            if !typ.is_integer() || !is_const_expr(node_ptr) {
+                error_with_node!(
+                    "asm-operand",
+                    "即値の制約を持つオペランドは整数定数式である必要があります。",
+                    &node
+                );
+            }
+            imm = Some(eval_const_expr(node_ptr));
+        }
+        _ => {
+            // レジスタに載せる値はスカラ型である必要がある(入力の配列はポインタになる)
+            if typ.is_struct() || typ.is_void() || (is_output && typ.is_array()) {
+                error_with_node!(
+                    "asm-operand",
+                    "レジスタに割り当てるオペランドの型\"{}\"はスカラ型である必要があります。",
+                    &node,
+                    typ
+                );
+            }
+        }
+    }
+    if let Ok(ix) = constraint.parse::<usize>() {
+        // 対応する出力オペランドと同じレジスタを使う
+        let output = asm.operands.get(ix).filter(|op| op.is_output);
+        if output.is_none_or(|op| op.constraint == "m") {
+            error_with_token!(
+                "asm-operand",
+                "制約\"{}\"に対応する、レジスタの制約を持つ出力オペランドがありません。",
+                &token.borrow(),
+                raw
+            );
+        }
+    }
+    AsmOperand {
+        name,
+        constraint,
+        is_output,
+        is_inout,
+        imm,
+    }
+}
+
+/// "a", "D" のような特定のレジスタを指す制約であれば、そのレジスタ
+pub fn asm_fixed_register(constraint: &str) -> Option<&'static str> {
+    match constraint {
+        "a" => Some("rax"),
+        "b" => Some("rbx"),
+        "c" => Some("rcx"),
+        "d" => Some("rdx"),
+        "S" => Some("rsi"),
+        "D" => Some("rdi"),
+        _ => None,
+    }
+}
+
+/// 破壊されるレジスタの指定を64ビットのレジスタ名にする("memory" と "cc" はそのまま)
+fn asm_clobber<'a>(clobber: &'a str, token: &TokenRef) -> &'a str {
+    if clobber == "memory" || clobber == "cc" {
+        return clobber;
+    }
+    match gp_register(clobber.trim_start_matches('%')) {
+        Some("rbp") | Some("rsp") => {
+            error_with_token!(
+                "asm-clobber",
+                "\"{}\"は破壊されるレジスタに指定できません。",
+                &token.borrow(),
+                clobber
+            );
+        }
+        Some(reg) => reg,
+        None => {
+            error_with_token!(
+                "asm-clobber",
+                "\"{}\"はレジスタではありません。",
+                &token.borrow(),
+                clobber
+            );
+        }
+    }
+}
+
+/// 破壊されるレジスタがオペランドに指定されていないかを確かめる
+fn check_asm_register(asm: &InlineAsm, clobber: &str, token: &TokenRef) {
+    let is_operand = asm
+        .operands
+        .iter()
+        .any(|op| asm_fixed_register(&op.constraint) == Some(clobber));
+    if is_operand {
+        error_with_token!(
+            "asm-clobber",
+            "破壊されるレジスタ\"{}\"がオペランドにも指定されています。",
+            &token.borrow(),
+            clobber
+        );
+    }
+}
+
+fn const_expr(token_ptr: &mut TokenRef) -> i64 {
+    let node_ptr = expr(token_ptr);
+    eval_const_expr(&node_ptr)
+}
+
+fn eval_const_expr(node_ptr: &NodeRef) -> i64 {
+    let label = &mut None;
+    let val = eval_const(node_ptr, label);
+    if label.is_some() {
+        error_with_node!("not-constant", "定数ではありません", &node_ptr.borrow());
+    }
+    val
+}
+
+/// eval_const で値を計算できる整数定数式かどうか
+fn is_const_expr(node_ptr: &NodeRef) -> bool {
+    let node = node_ptr.borrow();
+    match node.kind {
+        Nodekind::Num => true,
+        Nodekind::Cast | Nodekind::BitNot | Nodekind::LogNot => {
+            is_const_expr(node.left.as_ref().unwrap())
+        }
+        Nodekind::Add
+        | Nodekind::Sub
+        | Nodekind::Mul
+        | Nodekind::Div
+        | Nodekind::Mod
+        | Nodekind::LShift
+        | Nodekind::RShift
+        | Nodekind::BitAnd
+        | Nodekind::BitOr
+        | Nodekind::BitXor
+        | Nodekind::LogAnd
+        | Nodekind::LogOr
+        | Nodekind::Eq
+        | Nodekind::NEq
+        | Nodekind::LThan
+        | Nodekind::LEq => {
+            is_const_expr(node.left.as_ref().unwrap())
+                && is_const_expr(node.right.as_ref().unwrap())
+        }
+        _ => false,
+    }
+}
+
+/// 規則 initializer により Initializer を生成し、Assign による代入へと変換する
+fn lvar_initializer(
+    token_ptr: &mut TokenRef,
+    name: String,
+    mut typ: TypeCell,
+    is_flex: bool,
+    token: TokenRef,
+) -> NodeRef {
+    if typ.is_array() && !is_kind(token_ptr, Tokenkind::String) && !is(token_ptr, "{") {
+        error_with_token!(
+            "invalid-initializer",
+            "配列の初期化の形式が異なります。",
+            &token_ptr.borrow()
+        );
+    }
+    if typ.array_dim().0.len() > 1 && is_kind(token_ptr, Tokenkind::String) {
+        error_with_token!(
+            "invalid-initializer",
+            "2次元以上の配列\"{}\"は単一の文字リテラルでは初期化できません。",
+            &*token_ptr.borrow(),
+            typ
+        );
+    }
+
+    let mut init = Initializer::new(typ.clone());
+    if typ.is_struct() && !is(token_ptr, "{") {
+        // 中括弧を省略できるのは初期化子の並びの中のみなので、式による構造体・共用体の代入として扱う
+        let _ = init.node.insert(assign(token_ptr));
+    } else {
+        initializer(token_ptr, &mut init);
+    }
+    if is_flex {
+        let _ = typ.array_size.insert(init.flex_elem_count());
+    }
+
+    let lvar = new_lvar(name, Rc::clone(&token), typ.clone(), true, current_scope());
+    let offset = lvar.borrow().offset.unwrap();
+    match typ.typ {
+        // 構造体・共用体も、中括弧による初期化であればメンバごとに代入する
+        Type::Struct | Type::Union if init.node.is_some() => {
+            make_lvar_init(&init, typ, offset, true, &mut HashMap::new(), token)
+        }
+        Type::Array | Type::Struct | Type::Union => {
+            let zero_clear = new_unary(Nodekind::ZeroClr, lvar, Rc::clone(&token));
+            new_binary(
+                Nodekind::Comma,
+                zero_clear,
+                make_lvar_init(
+                    &init,
+                    typ,
+                    offset,
+                    false,
+                    &mut HashMap::new(),
+                    Rc::clone(&token),
+                ),
+                token,
+            )
+        }
+        _ => make_lvar_init(&init, typ, offset, true, &mut HashMap::new(), token),
+    }
+}
+
+// 配列の初期化について(C11 6.7.9)
+// Initializer は変数の型と同じ形の木で、初期化子を読みながら対応する位置の要素を埋めていく
+// 初期化子が指定されなかった要素は0で初期化される
+//
+// 中括弧の省略(brace elision):
+// 配列の要素の位置で "{" から始まらない初期化子が現れた場合は、その要素の中括弧が省略されたものとして、要素が埋まるまで続きの初期化子を読む
+// - 例えば、 int x[4][2][1] = {1, {2}, 4, 5, {6}, 7, 8}; は
+// - int x[4][2][1] = {{{1}, {2}}, {{4}, {5}}, {{6}, {0}}, {{7}, {8}}}; と同じ
+//
+// スカラを中括弧で囲った場合:
+// 最初の要素のみを扱い、残りは警告を出して読み飛ばす
+// - 例えば、 int x = {{2, 3}, 4}; は単に int x = 2; と同じ
+//
+// 指示子(designator):
+// [3] = x のように位置を指定して初期化でき、続く初期化子はその次の要素から読む
+// - [1][2] = x のように入れ子にした場合は、続く初期化子は中括弧を省略したものとして x[1][3] から読む
+// - 同じ要素を複数回初期化した場合は後のものが有効になる
+// - GNU 拡張の範囲指示子 [1 ... 5] = x では、範囲内の各要素について同じ初期化子を読む
+// - 構造体・共用体では .member = x のようにメンバを指定でき、続く初期化子はその次のメンバから読む
+// - 共用体は1つのメンバのみを初期化でき、指示子がなければ最初のメンバ、複数指定した場合は最後のメンバが有効になる
+//
+// 構造体・共用体の初期化:
+// 配列と同様にメンバを宣言順に初期化し、同じ型の式でなければ中括弧を省略したものとして読む
+// - 例えば、 struct {struct {int a, b;} in; int c;} x = {1, 2, 3}; は {{1, 2}, 3} と同じ
+// - 中括弧の外では省略できないため、 struct S s = t; は式による代入として扱う
+//
+// 文字列リテラルによる初期化のルール
+// [`char str[] = "abc"; と char str[] = {"abc"};`] は char str[] = {'a', 'b', 'c', '\0'}; と同じ(1つめが例外的表現)
+// 配列に収まらない部分は警告を出して切り捨てられる(char str[2][2] = {"abc", "def"}; は {{'a', 'b'}, {'d', 'e'}} と同じ)
+// char 配列以外の配列を文字列リテラルで初期化することはできず、また2次以上の配列を中括弧なしの文字列で初期化することもできない
+//
+// また、char[] を文字列で初期化する場合に、例えば
+// char str[] = {"abc", "def"};
+// はスカラの初期化と同様に2つ目の要素を飛ばせばよさそうに見えるが、 gcc では コンパイルエラーとなる。
+// gcc では char str[] = "abc", "def"; のようにパースされているのかもしれないが、よく分からない。
+// clang では3行上の例は valid な文法としてコンパイル可能。
+//
+/// 生成規則:
+/// initializer = string-initializer | "{" initializer-list | assign
+/// 配列の要素の位置で "{" から始まらない場合は、中括弧を省略したものとして読む
+fn initializer(token_ptr: &mut TokenRef, init: &mut Initializer) {
+    let typ = init.typ.clone().unwrap();
+    // char の1次元配列のみ文字列リテラルで初期化できるため、特別扱い
+    if typ.is_char_1d_array() && string_initializer(token_ptr, init) {
+        return;
+    }
+
+    if typ.is_array() {
+        if is_kind(token_ptr, Tokenkind::String)
+            && !typ.get_base_cell().is_one_of(&[Type::Char, Type::Ptr])
+        {
+            error_with_token!(
+                "invalid-initializer",
+                "文字列リテラルで\"{}\"型の変数を初期化することはできません",
+                &*token_ptr.borrow(),
+                typ
+            );
+        }
+        if consume(token_ptr, "{") {
+            // 中括弧で囲った初期化子は、指示子で既に初期化されていた要素も含めて全体を初期化し直す
+            *init = Initializer::new(typ);
+            initializer_list(token_ptr, init);
+        } else {
+            elided_initializer_list(token_ptr, init, 0);
+        }
+    } else if typ.is_struct() {
+        if consume(token_ptr, "{") {
+            *init = Initializer::new(typ);
+            initializer_list(token_ptr, init);
+            return;
+        }
+        // 文字列リテラルは構造体・共用体の値にはならないので、最初のメンバの初期化子として読む
+        if is_kind(token_ptr, Tokenkind::String) {
+            let elem = init.element(0);
+            initializer(token_ptr, &mut elem.borrow_mut());
+            elided_initializer_list(token_ptr, init, 1);
+            return;
+        }
+        // 同じ型の式であれば値全体で初期化し、そうでなければ中括弧が省略されたものとして最初のメンバから読む
+        let node_ptr = assign(token_ptr);
+        confirm_type(&node_ptr);
+        if node_ptr.borrow().typ.as_ref().unwrap().is_struct() {
+            *init = Initializer::new(typ);
+            let _ = init.node.insert(node_ptr);
+        } else {
+            elided_struct_initializer(token_ptr, init, node_ptr);
+        }
+    } else if consume(token_ptr, "{") {
+        // スカラ値に代入することになるため、最初の要素以外読み飛ばす
+        if consume_end(token_ptr) {
+            return;
+        }
+        if is(token_ptr, "[") || is(token_ptr, ".") {
+            error_with_token!(
+                "invalid-designator",
+                "配列でない型\"{}\"の初期化に指示子は使用できません。",
+                &*token_ptr.borrow(),
+                typ
+            );
+        }
+        initializer(token_ptr, init);
+        if !consume_end(token_ptr) {
+            expect(token_ptr, ",");
+            warning_with_token!(
+                "excess-initializers",
+                "スカラの初期化子に余分な要素があります。",
+                &*token_ptr.borrow()
+            );
+            skip_excess_elements(token_ptr);
+        }
+    } else {
+        let _ = init.node.insert(assign(token_ptr));
+    }
+}
+
+/// 生成規則:
+/// string-initializer = string-literal | "{" string-literal ","? "}"
+/// 文字列リテラルで初期化できた場合は true を返す
+fn string_initializer(token_ptr: &mut TokenRef, init: &mut Initializer) -> bool {
+    // string-literal か "{" string-literal "}" の形であれば読む(トークンの先読みが必要なため、clone してから読んでいることに注意)
+    let mut _token_ptr = Rc::clone(token_ptr);
+    let braced = consume(&mut _token_ptr, "{");
+    let token = Rc::clone(&_token_ptr);
+    let body = if let Some(body) = consume_literal(&mut _token_ptr) {
+        body
+    } else {
+        return false;
+    };
+    if braced {
+        expect(token_ptr, "{");
+    }
+    let _ = expect_literal(token_ptr);
+    if braced && !consume_end(token_ptr) {
+        error_with_token!(
+            "invalid-initializer",
+            "char の1次元配列を文字列リテラルで初期化する場合は1つのみ配置してください。",
+            &token_ptr.borrow()
+        );
+    }
+    *init = Initializer::new(init.typ.clone().unwrap());
+
+    // 終端の '\0' を含めて、配列に収まる分だけ要素を初期化する(要素数の指定がない場合は全体)
+    let mut chars: Vec<i32> = body.as_bytes().iter().map(|c| *c as i32).collect();
+    // 終端の '\0' のみが収まらない場合は C の仕様上許されるので、警告しない
+    if !init.is_flex() && chars.len() > init.elements.len() {
+        warning_with_token!(
+            "excess-initializers",
+            "文字列リテラルが配列\"{}\"に収まらないため、切り捨てられます。",
+            &token.borrow(),
+            init.typ.as_ref().unwrap()
+        );
+    }
+    chars.push(0);
+    if !init.is_flex() {
+        chars.truncate(init.elements.len());
+    }
+    for (ix, c) in chars.into_iter().enumerate() {
+        let _ = init
+            .element(ix)
+            .borrow_mut()
+            .node
+            .insert(new_num(c, Rc::clone(&token)));
+    }
+    true
+}
+
+/// 生成規則:
+/// initializer-list = (designation? initializer ("," designation? initializer)*)? ","? "}"
+/// designation = designator+ "="
+fn initializer_list(token_ptr: &mut TokenRef, init: &mut Initializer) {
+    let mut ix = 0;
+    let mut warned = false;
+    let mut first = true;
+    while !consume_end(token_ptr) {
+        if !first {
+            expect(token_ptr, ",");
+        }
+        first = false;
+
+        if is(token_ptr, "[") || is(token_ptr, ".") {
+            ix = designation(token_ptr, init);
+            continue;
+        }
+        if init.is_flex() || ix < init.capacity() {
+            let elem = init.element(ix);
+            initializer(token_ptr, &mut elem.borrow_mut());
+        } else {
+            if !warned {
+                warning_with_token!(
+                    "excess-initializers",
+                    "\"{}\"型の初期化子に余分な要素があります。",
+                    &*token_ptr.borrow(),
+                    init.typ.as_ref().unwrap()
+                );
+                warned = true;
+            }
+            skip_excess_element(token_ptr);
+        }
+        ix += 1;
+    }
+}
+
+/// 中括弧が省略された配列・構造体の初期化子を、 start 番目の要素から全体が埋まるか並びが終わるまで読む
+/// 指示子が現れた場合はその位置から外側の並びで読み直すため、そこで止める
+fn elided_initializer_list(token_ptr: &mut TokenRef, init: &mut Initializer, start: usize) {
+    for ix in start..init.capacity() {
+        if is_end(token_ptr) {
+            return;
+        }
+        let mut _token_ptr = Rc::clone(token_ptr);
+        if ix > 0 {
+            expect(&mut _token_ptr, ",");
+        }
+        if is(&mut _token_ptr, "[") || is(&mut _token_ptr, ".") {
+            return;
+        }
+        *token_ptr = _token_ptr;
+        let elem = init.element(ix);
+        initializer(token_ptr, &mut elem.borrow_mut());
+    }
+}
+
+/// 中括弧が省略された構造体・共用体の初期化子を読む
+/// 既に読んだ最初の初期化式 node_ptr で先頭のスカラを初期化し、残りのメンバを続く初期化子で埋める
+fn elided_struct_initializer(token_ptr: &mut TokenRef, init: &mut Initializer, node_ptr: NodeRef) {
+    if init.typ.as_ref().unwrap().is_scalar() {
+        let _ = init.node.insert(node_ptr);
+        return;
+    }
+    if init.capacity() == 0 {
+        error_with_node!(
+            "invalid-initializer",
+            "\"{}\"型の初期化子に余分な要素があります。",
+            &node_ptr.borrow(),
+            init.typ.as_ref().unwrap()
+        );
+    }
+    let elem = init.element(0);
+    elided_struct_initializer(token_ptr, &mut elem.borrow_mut(), node_ptr);
+    elided_initializer_list(token_ptr, init, 1);
+}
+
+/// 指示子で指定した要素を初期化し、続く初期化子で初期化する要素の位置を返す
+/// 範囲指示子の場合は、範囲内の各要素について同じトークン列を読み直す
+fn designation(token_ptr: &mut TokenRef, init: &mut Initializer) -> usize {
+    let typ = init.typ.clone().unwrap();
+    if is(token_ptr, ".") {
+        if !typ.is_struct() {
+            error_with_token!(
+                "invalid-designator",
+                "構造体・共用体でない型\"{}\"の初期化にメンバ指示子は使用できません。",
+                &*token_ptr.borrow(),
+                typ
+            );
+        }
+        let ix = member_designator(token_ptr, init);
+        let is_nested = is(token_ptr, "[") || is(token_ptr, ".");
+        let elem = init.element(ix);
+        designated_initializer(token_ptr, &mut elem.borrow_mut(), is_nested);
+        return ix + 1;
+    }
+    if typ.is_non_array() {
+        error_with_token!(
+            "invalid-designator",
+            "配列でない型\"{}\"の初期化に指示子は使用できません。",
+            &*token_ptr.borrow(),
+            typ
+        );
+    }
+
+    let (begin, end) = array_designator(token_ptr, init);
+    let is_nested = is(token_ptr, "[") || is(token_ptr, ".");
+    if begin == end {
+        let elem = init.element(begin);
+        designated_initializer(token_ptr, &mut elem.borrow_mut(), is_nested);
+        return end + 1;
+    }
+
+    // 範囲指示子の初期化子は一度だけ読み、範囲内の各要素で同じ式を共有する(副作用は一度だけ評価される)
+    let mut designated = Initializer::new(typ.make_deref().unwrap());
+    if is_nested {
+        // 入れ子の指示子に続く初期化子は、範囲の最後の要素に対してのみ読む(gcc と同じ)
+        let next = designation(token_ptr, &mut designated);
+        for ix in begin..=end {
+            init.element(ix).borrow_mut().overlay(&designated);
+        }
+        elided_initializer_list(token_ptr, &mut init.element(end).borrow_mut(), next);
+    } else {
+        expect(token_ptr, "=");
+        initializer(token_ptr, &mut designated);
+        for ix in begin..=end {
+            *init.element(ix).borrow_mut() = designated.deep_clone();
+        }
+    }
+    end + 1
+}
+
+/// 指示子の後ろに続く初期化子を読む
+/// 入れ子の指示子に続く初期化子は、指示された要素の次から中括弧を省略したものとして読む
+fn designated_initializer(token_ptr: &mut TokenRef, init: &mut Initializer, is_nested: bool) {
+    if is_nested {
+        let next = designation(token_ptr, init);
+        elided_initializer_list(token_ptr, init, next);
+    } else {
+        expect(token_ptr, "=");
+        initializer(token_ptr, init);
+    }
+}
+
+/// 生成規則:
+/// designator = "[" const-expr ("..." const-expr)? "]"
+fn array_designator(token_ptr: &mut TokenRef, init: &Initializer) -> (usize, usize) {
+    expect(token_ptr, "[");
+    let token = Rc::clone(token_ptr);
+    let begin = const_expr(token_ptr);
+    let end = if consume(token_ptr, "...") {
+        const_expr(token_ptr)
+    } else {
+        begin
+    };
+    expect(token_ptr, "]");
+    if begin < 0 || end < begin {
+        error_with_token!(
+            "invalid-designator",
+            "指示子の範囲[{}, {}]が不正です。",
+            &token.borrow(),
+            begin,
+            end
+        );
+    }
+    if !init.is_flex() && end as usize >= init.elements.len() {
+        error_with_token!(
+            "invalid-designator",
+            "指示子の位置{}が配列の要素数{}を超えています。",
+            &token.borrow(),
+            end,
+            init.elements.len()
+        );
+    }
+    (begin as usize, end as usize)
+}
+
+/// 生成規則:
+/// designator = "." ident
+/// 指定したメンバが何番目のメンバかを返す
+fn member_designator(token_ptr: &mut TokenRef, init: &Initializer) -> usize {
+    expect(token_ptr, ".");
+    let token = Rc::clone(token_ptr);
+    let name = expect_ident(token_ptr);
+    let typ = init.typ.as_ref().unwrap();
+    let ix = typ
+        .struct_info
+        .as_ref()
+        .unwrap()
+        .borrow()
+        .members
+        .iter()
+        .position(|member| member.name == name);
+    if let Some(ix) = ix {
+        ix
+    } else {
+        error_with_token!(
+            "no-member",
+            "型\"{}\"にメンバ\"{}\"はありません。",
+            &token.borrow(),
+            typ,
+            name
+        );
+    }
+}
+
+/// 初期化子の並びの終わり("}" または "," "}")であれば読み進めて true を返す
+fn consume_end(token_ptr: &mut TokenRef) -> bool {
+    let mut _token_ptr = Rc::clone(token_ptr);
+    let _ = consume(&mut _token_ptr, ",");
+    if consume(&mut _token_ptr, "}") {
+        *token_ptr = _token_ptr;
+        true
+    } else {
+        false
+    }
+}
+
+#[inline]
+fn is_end(token_ptr: &mut TokenRef) -> bool {
+    consume_end(&mut Rc::clone(token_ptr))
+}
+
+/// 初期化する要素のない余分な初期化子を読み飛ばす
+fn skip_excess_element(token_ptr: &mut TokenRef) {
+    if consume(token_ptr, "{") {
+        skip_excess_elements(token_ptr);
+    } else {
+        let _ = assign(token_ptr);
+    }
+}
+
+/// "}" までの余分な初期化子を全て読み飛ばす
+fn skip_excess_elements(token_ptr: &mut TokenRef) {
+    let mut first = true;
+    while !consume_end(token_ptr) {
+        if !first {
+            expect(token_ptr, ",");
+        }
+        first = false;
+        skip_excess_element(token_ptr);
+    }
+}
+
+/// オフセットで直接代入したい場合の Lvar
+#[inline]
+fn direct_offset_lvar(offset: usize, typ: TypeCell) -> NodeRef {
+    Rc::new(RefCell::new(Node {
+        kind: Nodekind::Lvar,
+        typ: Some(typ),
+        offset: Some(offset),
+        is_local: true,
+        ..Default::default()
+    }))
+}
+
+/// 初期化子の木のうち、初期化式が存在する要素に対応する部分のみノードを作る
+/// int x[2] = {1, 2}; のようなパターンは int x[2]; x[0] = 1, x[1] = 2; のように展開する
+/// ただし、それぞれの要素アクセスのためにわざわざポインタ計算を生成せず、単に各要素が格納されるべき位置に対応するベースポインタからオフセットを持つローカル変数であるとみなす
+/// 範囲指示子によって複数の要素で共有された式は、最初の要素でのみ評価し、残りの要素にはその値をコピーする(shared は式 -> 最初の要素のオフセット)
+fn make_lvar_init(
+    init: &Initializer,
+    typ: TypeCell,
+    offset: usize,
+    is_scalar: bool,
+    shared: &mut HashMap<*const RefCell<Node>, usize>,
+    token: TokenRef,
+) -> NodeRef {
+    if typ.is_array() || (typ.is_struct() && init.node.is_none()) {
+        let mut node_ptr = nop();
+        for (ix, elem) in init.elements.iter().enumerate() {
+            let elem_typ = elem.borrow().typ.clone().unwrap();
+            let elem_offset = if typ.is_array() {
+                ix * elem_typ.bytes()
+            } else {
+                typ.struct_info.as_ref().unwrap().borrow().members[ix].offset
+            };
+            let elem_init = make_lvar_init(
+                &elem.borrow(),
+                elem_typ,
+                offset - elem_offset,
+                false,
+                shared,
+                Rc::clone(&token),
+            );
+            if elem_init.borrow().kind != Nodekind::Nop {
+                node_ptr = new_binary(Nodekind::Comma, node_ptr, elem_init, Rc::clone(&token));
+            }
+        }
+        node_ptr
+    } else {
+        let node_ptr = if let Some(node) = &init.node {
+            if node.borrow().val.is_some() {
+                Rc::clone(node)
+            } else if let Some(&first) = shared.get(&Rc::as_ptr(node)) {
+                direct_offset_lvar(first, typ.clone())
+            } else {
+                let _ = shared.insert(Rc::as_ptr(node), offset);
+                Rc::clone(node)
+            }
+        } else {
+            new_num(0, Rc::clone(&token))
+        };
+        let val = node_ptr.borrow().val;
+        // 配列の要素はゼロクリアが必ず入るため、0 を代入するだけのノードは無視する
+        // スカラ値の初期化時は0でもちゃんと初期化を行う Assign を生成する必要がある
+        if val.is_none() || val.unwrap() != 0 || is_scalar {
+            assign_op(
+                Nodekind::Assign,
+                direct_offset_lvar(offset, typ),
+                node_ptr,
+                token,
+            )
+        } else {
+            nop()
+        }
+    }
+}
+
+/// 生成規則:
+/// stmt = expr? ";"
+///     | declaration
+///     | static-assert
+///     | asm-stmt
+///     | "{" stmt* "}"
+///     | "if" "(" expr ")" stmt ("else" stmt)?
+///     | "while" "(" expr ")" stmt
+///     | "for" "(" expr? ";" expr? ";" expr? ")" stmt
+///     | "do" stmt "while" "(" expr ")" ";"
+///     | "switch" "(" expr ")" stmt
+///     | "case" const-expr ":" stmt
+///     | "default" ":" stmt
+///     | "break" ";"
+///     | "continue" ";"
+///     | "goto" ident ";"
+///     | ident ":" stmt
+///     | "return" expr? ";"
+fn stmt(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    if consume(token_ptr, ";") {
+        tmp_num!(0)
+    } else if is(token_ptr, "_Static_assert") {
+        static_assert(token_ptr);
+        nop()
+    } else if is_asm(token_ptr) {
+        asm_stmt(token_ptr)
+    } else if is_declspec(token_ptr) {
+        declaration(token_ptr)
+    } else if consume(token_ptr, "{") {
+        enter_scope();
+        let mut children: Vec<NodeRef> = vec![];
+        loop {
+            if !consume(token_ptr, "}") {
+                if at_eof(token_ptr) {
+                    error_with_token!(
+                        "expected-token",
+                        "\'{{\'にマッチする\'}}\'が見つかりません。",
+                        &token.borrow()
+                    );
+                }
+                children.extend(stmt_or_recover(token_ptr));
+            } else {
+                break;
+            }
+        }
+        // ブロックを抜ける際は、その中で確保した可変長配列の領域を解放する
+        children.extend(restore_vlas_inside(current_scope() - 1));
+        leave_scope();
+
+        new_block(children)
+    } else if consume(token_ptr, "if") {
+        expect(token_ptr, "(");
+        let enter = Some(expr(token_ptr));
+        expect(token_ptr, ")");
+        let branch = Some(stmt(token_ptr));
+        let els = if consume(token_ptr, "else") {
+            Some(stmt(token_ptr))
+        } else {
+            None
+        };
+
+        new_ctrl(Nodekind::If, None, enter, None, branch, els)
+    } else if consume(token_ptr, "while") {
+        expect(token_ptr, "(");
+        let enter = Some(expr(token_ptr));
+        expect(token_ptr, ")");
+        enter_breakable(Nodekind::While);
+        let branch = Some(stmt(token_ptr));
+        leave_breakable();
+
+        new_ctrl(Nodekind::While, None, enter, None, branch, None)
+    } else if consume(token_ptr, "do") {
+        enter_breakable(Nodekind::DoWhile);
+        let branch = Some(stmt(token_ptr));
+        leave_breakable();
+        expect(token_ptr, "while");
+        expect(token_ptr, "(");
+        let enter = Some(expr(token_ptr));
+        expect(token_ptr, ")");
+        expect(token_ptr, ";");
+
+        new_ctrl(Nodekind::DoWhile, None, enter, None, branch, None)
+    } else if consume(token_ptr, "switch") {
+        expect(token_ptr, "(");
+        let enter = expr(token_ptr);
+        confirm_type(&enter);
+        if !enter.borrow().typ.as_ref().unwrap().is_integer() {
+            error_with_node!(
+                "invalid-operand",
+                "switch の条件式は整数型である必要があります。",
+                &enter.borrow()
+            );
+        }
+        expect(token_ptr, ")");
+        enter_breakable(Nodekind::Switch);
+        let branch = stmt(token_ptr);
+        leave_breakable();
+
+        // case の値の重複と、default が複数ないかをチェックする
+        let mut cases = vec![];
+        collect_cases(&branch, &mut cases);
+        let mut vals: HashMap<Option<i32>, TokenRef> = HashMap::new();
+        for case in &cases {
+            let case = case.borrow();
+            let case_token = Rc::clone(case.token.as_ref().unwrap());
+            if let Some(prev) = vals.insert(case.val, Rc::clone(&case_token)) {
+                let prev = prev.borrow();
+                if let Some(val) = case.val {
+                    error_with_prev!(
+                        "duplicate-case",
+                        "case {} は既に使用されています。",
+                        &case_token.borrow(),
+                        &prev,
+                        val
+                    );
+                } else {
+                    error_with_prev!(
+                        "duplicate-case",
+                        "default は既に使用されています。",
+                        &case_token.borrow(),
+                        &prev
+                    );
+                }
+            }
+        }
+
+        let node_ptr = new_ctrl(
+            Nodekind::Switch,
+            None,
+            Some(enter),
+            None,
+            Some(branch),
+            None,
+        );
+        node_ptr.borrow_mut().children = cases;
+        node_ptr
+    } else if consume(token_ptr, "case") {
+        if !BREAKABLES
+            .try_lock()
+            .unwrap()
+            .iter()
+            .any(|(kind, _)| *kind == Nodekind::Switch)
+        {
+            error_with_token!(
+                "invalid-jump",
+                "case は switch 文の中でのみ使用できます。",
+                &token.borrow()
+            );
+        }
+        let val = const_expr(token_ptr) as i32;
+        expect(token_ptr, ":");
+        new_case(Some(val), stmt(token_ptr), token)
+    } else if consume(token_ptr, "default") {
+        if !BREAKABLES
+            .try_lock()
+            .unwrap()
+            .iter()
+            .any(|(kind, _)| *kind == Nodekind::Switch)
+        {
+            error_with_token!(
+                "invalid-jump",
+                "default は switch 文の中でのみ使用できます。",
+                &token.borrow()
+            );
+        }
+        expect(token_ptr, ":");
+        new_case(None, stmt(token_ptr), token)
+    } else if consume(token_ptr, "break") {
+        let level = if let Some((_, level)) = BREAKABLES.try_lock().unwrap().last() {
+            *level
+        } else {
+            error_with_token!(
+                "invalid-jump",
+                "break はループまたは switch 文の中でのみ使用できます。",
+                &token.borrow()
+            );
+        };
+        expect(token_ptr, ";");
+        // ループや switch の中で宣言された可変長配列は、抜ける前に解放する
+        with_restore(
+            restore_vlas_inside(level),
+            new_jump(Nodekind::Break, None, token),
+        )
+    } else if consume(token_ptr, "continue") {
+        let level = if let Some((_, level)) = BREAKABLES
+            .try_lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(kind, _)| *kind != Nodekind::Switch)
+        {
+            *level
+        } else {
+            error_with_token!(
+                "invalid-jump",
+                "continue はループの中でのみ使用できます。",
+                &token.borrow()
+            );
+        };
+        expect(token_ptr, ";");
+        with_restore(
+            restore_vlas_inside(level),
+            new_jump(Nodekind::Continue, None, token),
+        )
+    } else if consume(token_ptr, "goto") {
+        let name = expect_ident(token_ptr);
+        expect(token_ptr, ";");
+        // 飛び先で生存していない可変長配列の解放は、関数の終わりでラベルが分かってから埋める
+        let live = LIVE_VLAS.try_lock().unwrap().clone();
+        let restore = if live.is_empty() { None } else { Some(nop()) };
+        let node_ptr = with_restore(restore, new_jump(Nodekind::Goto, Some(name), token));
+        GOTOS
+            .try_lock()
+            .unwrap()
+            .push((node_ptr.borrow().clone(), live));
+        node_ptr
+    } else if is_label(token_ptr) {
+        let name = expect_ident(token_ptr);
+        expect(token_ptr, ":");
+        if let Some((prev, _)) = LABELS.try_lock().unwrap().get(&name) {
+            let prev = prev.token.as_ref().unwrap().borrow();
+            error_with_prev!(
+                "redefinition",
+                "ラベル\"{}\"は既に定義されています。",
+                &token.borrow(),
+                &prev,
+                name
+            );
+        }
+        let node_ptr = new_jump(Nodekind::Label, Some(name.clone()), token);
+        let live = LIVE_VLAS.try_lock().unwrap().clone();
+        let _ = LABELS
+            .try_lock()
+            .unwrap()
+            .insert(name, (node_ptr.borrow().clone(), live));
+        let branch = stmt(token_ptr);
+        let _ = node_ptr.borrow_mut().branch.insert(branch);
+        node_ptr
+    } else if consume(token_ptr, "for") {
+        expect(token_ptr, "(");
+        enter_scope();
+        // consumeできた場合exprが何も書かれていないことに注意
+        let init: Option<NodeRef> = if is_declspec(token_ptr) {
+            Some(declaration(token_ptr))
+        } else if consume(token_ptr, ";") {
+            None
+        } else {
+            let _init = Some(expr(token_ptr));
+            expect(token_ptr, ";");
+            _init
+        };
+
+        let enter: Option<NodeRef> = if consume(token_ptr, ";") {
+            None
+        } else {
+            let _enter = Some(expr(token_ptr));
+            expect(token_ptr, ";");
+            _enter
+        };
+
+        let routine: Option<NodeRef> = if consume(token_ptr, ")") {
+            None
+        } else {
+            let _routine = Some(expr(token_ptr));
+            expect(token_ptr, ")");
+            _routine
+        };
+
+        enter_breakable(Nodekind::For);
+        let branch: Option<NodeRef> = Some(stmt(token_ptr));
+        leave_breakable();
+        let restore = restore_vlas_inside(current_scope() - 1);
+        leave_scope();
+
+        let node_ptr = new_ctrl(Nodekind::For, init, enter, routine, branch, None);
+        // 初期化節で宣言した可変長配列は、ループを抜けた後で解放する
+        if let Some(restore) = restore {
+            new_block(vec![node_ptr, restore])
+        } else {
+            node_ptr
+        }
+    } else if consume_kind(token_ptr, Tokenkind::Return) {
+        if let Some(func) = current_func().filter(|func| func.is_noreturn) {
+            warning_with_token!(
+                "invalid-noreturn",
+                "noreturn 属性の関数\"{}\"に return 文があります。",
+                &token.borrow(),
+                func.name.unwrap()
+            );
+        }
+        // exprなしのパターン: 実質Num 0があるのと同じと捉えれば良い
+        let left: NodeRef = if consume(token_ptr, ";") {
+            tmp_num!(0)
+        } else {
+            if current_ret_typ().is_some_and(|typ| typ.is_void()) {
+                error_with_token!(
+                    "invalid-return",
+                    "void 型の関数では値を返すことはできません。",
+                    &token.borrow()
+                );
+            }
+            let _left: NodeRef = expr(token_ptr);
+            expect(token_ptr, ";");
+            confirm_type(&_left);
+            // 戻り値は代入と同様に関数の戻り値の型に変換する
+            match current_ret_typ() {
+                Some(ret_typ) if !ret_typ.is_void() => {
+                    check_assignable(&ret_typ, &_left);
+                    if ret_typ.is_struct() {
+                        _left
+                    } else {
+                        new_cast(&_left, ret_typ)
+                    }
+                }
+                _ => _left,
+            }
+        };
+
+        new_unary(Nodekind::Return, left, token)
+    } else {
+        let node_ptr: NodeRef = expr(token_ptr);
+        expect(token_ptr, ";");
+        node_ptr
+    }
+}
+
+/// グローバル変数の参照がスレッドローカルな変数であれば印を付ける
+/// この時点で定義(extern でない宣言)があるかどうかで、生成するアクセス方法が変わる
+fn mark_thread_local(node_ptr: &NodeRef) {
+    let mut node = node_ptr.borrow_mut();
+    if node.is_local {
+        return;
+    }
+    let name = node.name.clone().unwrap();
+    let is_defined = if let Some(glob) = GLOBALS.try_lock().unwrap().get(&name) {
+        glob.is_tls.then_some(!glob.is_extern)
+    } else {
+        None
+    };
+    let is_defined = is_defined.or_else(|| {
+        EXTERN_THREAD_LOCALS
+            .try_lock()
+            .unwrap()
+            .contains(&name)
+            .then_some(false)
+    });
+    if let Some(is_defined) = is_defined {
+        node.is_tls = true;
+        node.is_extern = !is_defined;
+    }
+}
+
+/// 定義をパース中の関数のノード
+fn current_func() -> Option<Node> {
+    let name = CURRENT_FUNC.try_lock().unwrap().clone()?;
+    let func = GLOBALS.try_lock().unwrap().get(&name).cloned();
+    func
+}
+
+/// 定義をパース中の関数の戻り値の型
+fn current_ret_typ() -> Option<TypeCell> {
+    let func_typ = current_func()?.func_typ?;
+    let ret_typ = func_typ.ret_typ.as_ref().unwrap().borrow().clone();
+    Some(ret_typ)
+}
+
+/// 生成規則:
+/// expr = assign ("," expr)?
+pub fn expr(token_ptr: &mut TokenRef) -> NodeRef {
+    let node_ptr: NodeRef = assign(token_ptr);
+    let token = Rc::clone(token_ptr);
+    if consume(token_ptr, ",") {
+        new_binary(Nodekind::Comma, node_ptr, expr(token_ptr), token)
+    } else {
+        node_ptr
+    }
+}
+
+/// 生成規則:
+/// assign = logor (assign-op assign)?
+/// assign-op = "="
+///         | "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "^=" | "|="
+///         | "<<=" | ">>="
+/// 禁止代入(例えば x + y = 10; や x & y = 10; など)は generator 側で弾く
+fn assign(token_ptr: &mut TokenRef) -> NodeRef {
+    let node_ptr: NodeRef = logor(token_ptr);
+    let token = Rc::clone(token_ptr);
+    if consume(token_ptr, "=") {
+        modify(Nodekind::Assign, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "+=") {
+        modify(Nodekind::Add, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "-=") {
+        modify(Nodekind::Sub, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "*=") {
+        modify(Nodekind::Mul, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "/=") {
+        modify(Nodekind::Div, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "%=") {
+        modify(Nodekind::Mod, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "&=") {
+        modify(Nodekind::BitAnd, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "^=") {
+        modify(Nodekind::BitXor, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "|=") {
+        modify(Nodekind::BitOr, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, "<<=") {
+        modify(Nodekind::LShift, node_ptr, assign(token_ptr), token)
+    } else if consume(token_ptr, ">>=") {
+        modify(Nodekind::RShift, node_ptr, assign(token_ptr), token)
+    } else {
+        node_ptr
+    }
+}
+
+/// 代入式や ++, -- による左辺値の変更(初期化はこれを通さずに assign_op を直接使う)
+fn modify(kind: Nodekind, left: NodeRef, right: NodeRef, token: TokenRef) -> NodeRef {
+    // 配列と const な左辺値は変更できない
+    // (複合代入では一時的なノードを経由して代入するため、元の左辺値の位置で報告できるようにここで調べる)
+    confirm_type(&left);
+    let left_typ = left.borrow().typ.clone().unwrap();
+    if left_typ.is_array() {
+        error_with_node!(
+            "not-assignable",
+            "左辺値は代入可能な型である必要がありますが、配列型\"{}\"が指定されています。",
+            &left.borrow(),
+            left_typ
+        );
+    }
+    if left_typ.is_const {
+        error_with_node!(
+            "read-only-assignment",
+            "const 修飾された型\"{}\"の左辺値は変更できません。",
+            &left.borrow(),
+            left_typ
+        );
+    }
+    assign_op(kind, left, right, token)
+}
+
+/// a += b; -->  tmp = &a, *tmp = *tmp + b; と読み替える
+fn assign_op(kind: Nodekind, left: NodeRef, right: NodeRef, token: TokenRef) -> NodeRef {
+    // 左右の型を確定させておく
+    confirm_type(&left);
+    confirm_type(&right);
+
+    // この式全体の評価値は left (a += b の a) の型とする
+    let assign_ = if kind == Nodekind::Assign {
+        new_binary(Nodekind::Assign, left, right, token)
+    } else if left.borrow().typ.as_ref().unwrap().is_atomic {
+        atomic_assign_op(kind, left, right, token)
+    } else {
+        // tmp として通常は認められない無名の変数を使うことで重複を避ける
+        let typ = left.borrow().typ.clone().unwrap();
+        let tmp_lvar = tmp_lvar!();
+        let _ = tmp_lvar.borrow_mut().typ.insert(typ.make_ptr_to());
+        let tmp_deref = tmp_unary!(Nodekind::Deref, tmp_lvar);
+
+        let expr_left = tmp_binary!(Nodekind::Assign, tmp_lvar, tmp_unary!(Nodekind::Addr, left));
+
+        let token2 = Rc::clone(&token);
+        let tmp_deref2 = Rc::clone(&tmp_deref);
+        let op = match kind {
+            Nodekind::Add => new_add(tmp_deref2, right, token2),
+            Nodekind::Sub => new_sub(tmp_deref2, right, token2),
+            _ => new_binary(kind, tmp_deref2, right, token2),
+        };
+
+        let expr_right = tmp_binary!(Nodekind::Assign, tmp_deref, op);
+
+        confirm_type(&expr_left);
+        confirm_type(&expr_right);
+        new_binary(Nodekind::Comma, expr_left, expr_right, token)
+    };
+    confirm_type(&assign_);
+    assign_
+}
+
+/// _Atomic な左辺値への複合代入は、計算の間に他のスレッドが書き込んだ場合に計算をやり直す比較交換のループにする
+/// a += b; --> tmp = &a, val = b, old = *tmp, while (!cas(tmp, &old, new = old + val)); new; と読み替える
+fn atomic_assign_op(kind: Nodekind, left: NodeRef, right: NodeRef, token: TokenRef) -> NodeRef {
+    let left_typ = left.borrow().typ.clone().unwrap();
+    let typ = left_typ.unqualified();
+    let right_typ = right.borrow().typ.as_ref().unwrap().param_adjusted();
+    let hidden_lvar = |typ: TypeCell| {
+        let name = format!(".atomic.{}", get_static_id());
+        _lvar(name, None, Some(typ), true, current_scope())
+    };
+    let ptr = hidden_lvar(left_typ.make_ptr_to());
+    let val = hidden_lvar(right_typ);
+    let old = hidden_lvar(typ.clone());
+    let new = hidden_lvar(typ);
+
+    let set_ptr = tmp_binary!(
+        Nodekind::Assign,
+        Rc::clone(&ptr),
+        tmp_unary!(Nodekind::Addr, left)
+    );
+    let set_val = tmp_binary!(Nodekind::Assign, Rc::clone(&val), right);
+    let set_old = tmp_binary!(
+        Nodekind::Assign,
+        Rc::clone(&old),
+        tmp_unary!(Nodekind::Deref, ptr)
+    );
+    let token2 = Rc::clone(&token);
+    let op = match kind {
+        Nodekind::Add => new_add(Rc::clone(&old), val, token2),
+        Nodekind::Sub => new_sub(Rc::clone(&old), val, token2),
+        _ => new_binary(kind, Rc::clone(&old), val, token2),
+    };
+    let desired = tmp_binary!(Nodekind::Assign, Rc::clone(&new), op);
+    confirm_type(&desired);
+    let cas = new_atomic_cas(
+        ptr,
+        tmp_unary!(Nodekind::Addr, old),
+        desired,
+        Rc::clone(&token),
+    );
+    let retry = new_ctrl(
+        Nodekind::While,
+        None,
+        Some(tmp_unary!(Nodekind::LogNot, cas)),
+        None,
+        Some(nop()),
+        None,
+    );
+
+    let mut node_ptr = new;
+    for expr in [retry, set_old, set_val, set_ptr] {
+        confirm_type(&expr);
+        node_ptr = new_binary(Nodekind::Comma, expr, node_ptr, Rc::clone(&token));
+    }
+    node_ptr
+}
+
+/// 不可分な比較交換のノード: 成功すれば1、失敗すれば0を評価値とする
+fn new_atomic_cas(ptr: NodeRef, expected: NodeRef, desired: NodeRef, token: TokenRef) -> NodeRef {
+    Rc::new(RefCell::new(Node {
+        kind: Nodekind::AtomicCas,
+        token: Some(token),
+        typ: Some(TypeCell::new(Type::Int)),
+        left: Some(ptr),
+        right: Some(expected),
+        init: Some(desired),
+        ..Default::default()
+    }))
+}
+
+/// 生成規則:
+/// logor = logand ("||" logand)*
+fn logor(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = logand(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if !consume(token_ptr, "||") {
+            break;
+        }
+        node_ptr = new_binary(Nodekind::LogOr, node_ptr, logand(token_ptr), token);
+    }
+    node_ptr
+}
+
+/// 生成規則:
+/// logand = bitor ("&&" bitor)*
+fn logand(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = bitor(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if !consume(token_ptr, "&&") {
+            break;
+        }
+        node_ptr = new_binary(Nodekind::LogAnd, node_ptr, bitor(token_ptr), token);
+    }
+    node_ptr
+}
+
+/// 生成規則:
+/// bitor = bitxor ("|" bitxor)*
+fn bitor(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = bitxor(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if !consume(token_ptr, "|") {
+            break;
+        }
+        node_ptr = new_binary(Nodekind::BitOr, node_ptr, bitxor(token_ptr), token);
+    }
+    node_ptr
+}
+
+/// 生成規則:
+/// bitxor = bitand ("^" bitand)*
+fn bitxor(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = bitand(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if !consume(token_ptr, "^") {
+            break;
+        }
+        node_ptr = new_binary(Nodekind::BitXor, node_ptr, bitand(token_ptr), token);
+    }
+    node_ptr
+}
+
+/// 生成規則:
+/// bitand = equality ("&" equality)*
+fn bitand(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = equality(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if !consume(token_ptr, "&") {
+            break;
+        }
+        node_ptr = new_binary(Nodekind::BitAnd, node_ptr, equality(token_ptr), token);
+    }
+    node_ptr
+}
+
+/// 生成規則:
+/// equality = relational ("==" relational | "!=" relational)?
+fn equality(token_ptr: &mut TokenRef) -> NodeRef {
+    let node_ptr: NodeRef = relational(token_ptr);
+    let token = Rc::clone(token_ptr);
+    if consume(token_ptr, "==") {
+        new_binary(Nodekind::Eq, node_ptr, relational(token_ptr), token)
+    } else if consume(token_ptr, "!=") {
+        new_binary(Nodekind::NEq, node_ptr, relational(token_ptr), token)
+    } else {
+        node_ptr
+    }
+}
+
+/// 生成規則:
+/// relational = shift ("<" shift | "<=" shift | ">" shift | ">=" shift)*
+fn relational(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = shift(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if consume(token_ptr, "<") {
+            node_ptr = new_binary(Nodekind::LThan, node_ptr, shift(token_ptr), token);
+        } else if consume(token_ptr, "<=") {
+            node_ptr = new_binary(Nodekind::LEq, node_ptr, shift(token_ptr), token);
+        } else if consume(token_ptr, ">") {
+            node_ptr = new_binary(Nodekind::LThan, shift(token_ptr), node_ptr, token);
+        } else if consume(token_ptr, ">=") {
+            node_ptr = new_binary(Nodekind::LEq, shift(token_ptr), node_ptr, token);
+        } else {
+            break;
+        }
+    }
+    node_ptr
+}
+
+/// 生成規則:
+/// shift = add ("<<" add | ">>" add)*
+fn shift(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = add(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if consume(token_ptr, "<<") {
+            node_ptr = new_binary(Nodekind::LShift, node_ptr, add(token_ptr), token);
+        } else if consume(token_ptr, ">>") {
+            node_ptr = new_binary(Nodekind::RShift, node_ptr, add(token_ptr), token);
+        } else {
+            break;
+        }
+    }
+    node_ptr
+}
+
+/// ポインタ演算で使う、指す先の要素の大きさ(可変長配列であれば実行時に計算した値)
+/// void へのポインタは GNU 拡張と同様に大きさ1として扱う
+fn elem_size(ptr_typ: &TypeCell, token: &TokenRef) -> NodeRef {
+    let elem_typ = ptr_typ.make_deref().unwrap();
+    if elem_typ.is_incomplete() && !elem_typ.is_void() {
+        error_with_token!(
+            "incomplete-type",
+            "不完全型\"{}\"へのポインタの演算はできません。",
+            &token.borrow(),
+            elem_typ
+        );
+    }
+    if elem_typ.is_vla() {
+        size_of(&elem_typ)
+    } else {
+        tmp_num!(elem_typ.bytes() as i32)
+    }
+}
+
+fn new_add(mut left: NodeRef, mut right: NodeRef, token: TokenRef) -> NodeRef {
+    confirm_type(&left);
+    confirm_type(&right);
+
+    // それぞれ配列の場合でも true になるが、それで良い
+    let left_is_ptr = left.borrow().typ.as_ref().unwrap().is_pointer_like();
+    let right_is_ptr = right.borrow().typ.as_ref().unwrap().is_pointer_like();
+    if left_is_ptr && right_is_ptr {
+        error_with_token!(
+            "invalid-pointer-arith",
+            "ポインタ演算は整数型との加算か、ポインタ同士の引き算のみ可能です。",
+            &token.borrow()
+        );
+    }
+
+    if !left_is_ptr && !right_is_ptr {
+        new_binary(Nodekind::Add, left, right, token)
+    } else {
+        // num + ptr の場合には ptr + num として扱うべく左右を入れ替える
+        if !left_is_ptr {
+            std::mem::swap(&mut left, &mut right);
+        }
+
+        // 配列の場合、サイズを考慮する必要があることに注意
+        let ptr_cell = left.borrow().typ.clone().unwrap();
+        let pointer_offset = tmp_binary!(Nodekind::Mul, elem_size(&ptr_cell, &token), right);
+        let add_ = new_binary(Nodekind::Add, left, pointer_offset, token);
+        confirm_type(&add_);
+        let _ = add_.borrow_mut().typ.insert(ptr_cell);
+        add_
+    }
+}
+
+fn new_sub(left: NodeRef, right: NodeRef, token: TokenRef) -> NodeRef {
+    confirm_type(&left);
+    confirm_type(&right);
+    let left_typ = left.borrow().typ.clone().unwrap();
+    let right_typ = right.borrow().typ.clone().unwrap();
+
+    // それぞれ配列の場合でも true になるが、それで良い
+    let left_is_ptr = left_typ.is_pointer_like();
+    let right_is_ptr = right_typ.is_pointer_like();
+
+    let (sub_, type_cell) = if !left_is_ptr && !right_is_ptr {
+        return new_binary(Nodekind::Sub, left, right, token);
+    } else if left_is_ptr && right_is_ptr {
+        // ptr - ptr はそれが変数何個分のオフセットに相当するかを計算する(指す先の型は修飾子を除いて互換である必要がある)
+        let left_deref = left_typ.make_deref().unwrap().unqualified();
+        let right_deref = right_typ.make_deref().unwrap().unqualified();
+        if !left_deref.is_compatible(&right_deref) {
+            error_with_token!(
+                "invalid-pointer-arith",
+                "違う型へのポインタ同士の演算はサポートされません。: \"{}\", \"{}\"",
+                &token.borrow(),
+                left_typ,
+                right_typ
+            );
+        }
+
+        // アドレスの差は ptrdiff_t (long) として要素の大きさで割る
+        let bytes = new_cast(&elem_size(&left_typ, &token), TypeCell::new(Type::Long));
+        let pointer_offset = tmp_binary!(Nodekind::Sub, left, right);
+        confirm_type(&pointer_offset);
+        let _ = pointer_offset
+            .borrow_mut()
+            .typ
+            .insert(TypeCell::new(Type::Long));
+        let div_ = new_binary(Nodekind::Div, pointer_offset, bytes, token);
+        confirm_type(&div_);
+        return div_;
+    } else {
+        // num - ptr は invalid
+        if !left_is_ptr {
+            error_with_token!(
+                "invalid-pointer-arith",
+                "整数型の値からポインタを引くことはできません。",
+                &token.borrow()
+            );
+        }
+
+        let pointer_offset = tmp_binary!(Nodekind::Mul, elem_size(&left_typ, &token), right);
+        confirm_type(&pointer_offset);
+        (
+            new_binary(Nodekind::Sub, left, pointer_offset, token),
+            left_typ,
+        )
+    };
+    let _ = sub_.borrow_mut().typ.insert(type_cell);
+    sub_
+}
+
+/// 生成規則:
+/// add = mul ("+" mul | "-" mul)*
+fn add(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = mul(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if consume(token_ptr, "+") {
+            node_ptr = new_add(node_ptr, mul(token_ptr), token);
+        } else if consume(token_ptr, "-") {
+            node_ptr = new_sub(node_ptr, mul(token_ptr), token);
+        } else {
+            break;
+        }
+    }
+    node_ptr
+}
+
+/// 生成規則:
+/// mul = unary ("*" unary | "/" unary | "%" unary)*
+fn mul(token_ptr: &mut TokenRef) -> NodeRef {
+    let mut node_ptr: NodeRef = unary(token_ptr);
+    loop {
+        let token = Rc::clone(token_ptr);
+        if consume(token_ptr, "*") {
+            node_ptr = new_binary(Nodekind::Mul, node_ptr, unary(token_ptr), token);
+        } else if consume(token_ptr, "/") {
+            node_ptr = new_binary(Nodekind::Div, node_ptr, unary(token_ptr), token);
+        } else if consume(token_ptr, "%") {
+            node_ptr = new_binary(Nodekind::Mod, node_ptr, unary(token_ptr), token);
+        } else {
+            break;
+        }
+    }
+    node_ptr
+}
+
+/// !+x; や ~-y; は valid
+/// unary = tailed
+///     | ("sizeof" | "_Alignof") ( "(" (type | expr) ")" | unary)
+///     | ("~" | "!") unary
+///     | ("*" | "&") unary
+///     | ("+" | "-") unary
+///     | ("++" | "--") unary
+///     | "__extension__" unary
+///     | cast
+// TODO: *+x; *-y; みたいな構文を禁止したい
+fn unary(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    let is_sizeof = consume(token_ptr, "sizeof");
+    if is_sizeof || consume(token_ptr, "_Alignof") {
+        // 型名を使用する場合は括弧が必要なので sizeof type になっていないか先にチェックする
+        let ptr_ = Rc::clone(token_ptr);
+        if is_type(token_ptr) {
+            let typ = type_name(token_ptr);
+            error_with_token!("expected-token", "型名を使用した {} 演算子の使用では、 \"(\" と \")\" で囲う必要があります。 -> \"({})\"", &ptr_.borrow(), token.borrow().body.as_ref().unwrap(), typ);
+        }
+        let typ: TypeCell = if consume(token_ptr, "(") {
+            if is_type_name(token_ptr) {
+                // sizeof(int **) のような抽象宣言子を読む
+                let t = type_name(token_ptr);
+                let t = declarator(token_ptr, t).1;
+                expect(token_ptr, ")");
+                if is(token_ptr, "{") {
+                    // sizeof (int[]){1, 2} のように複合リテラルが続く場合は、その式の型を使う
+                    let node_ptr = compound_literal_body(token_ptr, t, Rc::clone(&ptr_));
+                    let node_ptr = postfix(token_ptr, node_ptr, Rc::clone(&ptr_));
+                    confirm_type(&node_ptr);
+                    let _typ = node_ptr.borrow().typ.clone().unwrap();
+                    _typ
+                } else {
+                    t
+                }
+            } else {
+                let exp = expr(token_ptr);
+                confirm_type(&exp);
+                expect(token_ptr, ")");
+                let _typ = exp.borrow().typ.clone().unwrap();
+                _typ
+            }
+        } else {
+            let una = unary(token_ptr);
+            confirm_type(&una);
+            let _typ = una.borrow().typ.clone().unwrap();
+            _typ
+        };
+        // sizeof, _Alignof の結果は size_t (unsigned long) 型になる
+        // 式に対する _Alignof は GNU 拡張で、変数であれば _Alignas で指定したアラインメントになる
+        // 可変長配列の sizeof は、実行時に計算したバイト数になる(sizeof(int[n]) のような型名の場合はここで計算する)
+        if typ.is_incomplete() && !typ.is_void() {
+            error_with_token!(
+                "incomplete-type",
+                "不完全型\"{}\"には {} 演算子を適用できません。",
+                &token.borrow(),
+                typ,
+                token.borrow().body.as_ref().unwrap()
+            );
+        }
+        let size = if is_sizeof && typ.is_vla() {
+            size_of(&typ)
+        } else {
+            let val = if is_sizeof { typ.bytes() } else { typ.align() };
+            let size = new_num(val as i32, Rc::clone(&token));
+            let _ = size.borrow_mut().typ.insert(TypeCell::new_size_t());
+            size
+        };
+        let _ = size.borrow_mut().token.insert(token);
+        with_vla_sizes(size)
+    } else if consume(token_ptr, "~") {
+        new_unary(Nodekind::BitNot, unary(token_ptr), token)
+    } else if consume(token_ptr, "!") {
+        new_unary(Nodekind::LogNot, unary(token_ptr), token)
+    } else if consume(token_ptr, "*") {
+        let node_ptr = unary(token_ptr);
+        confirm_type(&node_ptr);
+        new_unary(Nodekind::Deref, node_ptr, token)
+    } else if consume(token_ptr, "&") {
+        let node_ptr = unary(token_ptr);
+        confirm_type(&node_ptr);
+        new_unary(Nodekind::Addr, node_ptr, token)
+    } else if consume(token_ptr, "+") {
+        // 単項演算子のプラスは0に足す形にする。こうすることで &+var のような表現を generator 側で弾ける
+        new_binary(Nodekind::Add, tmp_num!(0), unary(token_ptr), token)
+    } else if consume(token_ptr, "-") {
+        // 単項演算のマイナスは0から引く形にする。
+        new_binary(Nodekind::Sub, tmp_num!(0), unary(token_ptr), token)
+    } else if consume(token_ptr, "__extension__") {
+        unary(token_ptr)
+    } else if consume(token_ptr, "++") {
+        modify(Nodekind::Add, unary(token_ptr), tmp_num!(1), token)
+    } else if consume(token_ptr, "--") {
+        modify(Nodekind::Sub, unary(token_ptr), tmp_num!(1), token)
+    } else if is_cast(token_ptr) {
+        cast(token_ptr)
+    } else {
+        tailed(token_ptr)
+    }
+}
+
+/// 生成規則:
+/// tailed = primary (primary-tail)?
+/// primary-tail = "++" | "--"
+fn tailed(token_ptr: &mut TokenRef) -> NodeRef {
+    let node_ptr: NodeRef = primary(token_ptr);
+    let token = Rc::clone(token_ptr);
+    if consume(token_ptr, "++") {
+        inc_dec(node_ptr, true, false, token)
+    } else if consume(token_ptr, "--") {
+        inc_dec(node_ptr, false, false, token)
+    } else {
+        node_ptr
+    }
+}
+
+/// ++a; -> a+=1; および a++; -> (a+=1)-1; と読み替える
+fn inc_dec(node: NodeRef, is_inc: bool, is_prefix: bool, token: TokenRef) -> NodeRef {
+    let kind = if is_inc { Nodekind::Add } else { Nodekind::Sub };
+    confirm_type(&node);
+    let _assign = modify(kind, node, tmp_num!(1), Rc::clone(&token));
+    if is_prefix {
+        // ++i は (i+=1) として読み替えると良い
+        _assign
+    } else {
+        // i++ は (i+=1)-1 として読み替えると良い
+        if is_inc {
+            new_sub(_assign, tmp_num!(1), token)
+        } else {
+            new_add(_assign, tmp_num!(1), token)
+        }
+    }
+}
+
+/// 生成規則:
+/// params = assign ("," assign)* | null
+fn params(token_ptr: &mut TokenRef) -> Vec<NodeRef> {
+    let mut args: Vec<NodeRef> = vec![];
+    if !consume(token_ptr, ")") {
+        let arg = assign(token_ptr);
+        confirm_type(&arg);
+        args.push(arg);
+
+        loop {
+            if !consume(token_ptr, ",") {
+                expect(token_ptr, ")"); // 括弧が閉じないような書き方になっているとここで止まるため、if at_eof ~ のようなチェックは不要
+                break;
+            }
+            let arg = assign(token_ptr);
+            confirm_type(&arg);
+            args.push(arg);
+        }
+    }
+    args
+}
+
+/// va_start などと、 stdarg.h のマクロが展開される先の __builtin_va_start などを同じものとして読む
+fn consume_va(token_ptr: &mut TokenRef, name: &str) -> bool {
+    consume(token_ptr, &format!("va_{}", name))
+        || consume(token_ptr, &format!("__builtin_va_{}", name))
+}
+
+fn is_va_builtin(token_ptr: &mut TokenRef) -> bool {
+    ["start", "arg", "end", "copy"].iter().any(|name| {
+        is(token_ptr, &format!("va_{}", name)) || is(token_ptr, &format!("__builtin_va_{}", name))
+    })
+}
+
+/// 生成規則:
+/// va-builtin = "va_start" "(" va-list "," assign ")"
+///         | "va_arg" "(" va-list "," type-name declarator ")"
+///         | "va_end" "(" va-list ")"
+///         | "va_copy" "(" va-list "," va-list ")"
+/// va_list の中身は System V ABI に従う
+fn va_builtin(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    if consume_va(token_ptr, "start") {
+        let (offset, argc) = if let Some(info) = *VA_INFO.try_lock().unwrap() {
+            info
+        } else {
+            error_with_token!(
+                "outside-function",
+                "va_start は可変長引数をとる関数の中でのみ使用できます。",
+                &token.borrow()
+            );
+        };
+        expect(token_ptr, "(");
+        let ap = va_list(token_ptr);
+        expect(token_ptr, ",");
+        // 最後の名前付き引数を指定するが、レジスタ保存領域での位置は引数の数から分かるので式として読むだけにする
+        let _ = assign(token_ptr);
+        expect(token_ptr, ")");
+        Rc::new(RefCell::new(Node {
+            kind: Nodekind::VaStart,
+            token: Some(token),
+            typ: Some(TypeCell::new(Type::Int)),
+            left: Some(ap),
+            val: Some(argc as i32),
+            offset: Some(offset),
+            ..Default::default()
+        }))
+    } else if consume_va(token_ptr, "arg") {
+        expect(token_ptr, "(");
+        let ap = va_list(token_ptr);
+        expect(token_ptr, ",");
+        let typ_token = Rc::clone(token_ptr);
+        let typ = type_name(token_ptr);
+        let typ = declarator(token_ptr, typ).1;
+        if typ.is_one_of(&[Type::Array, Type::Func]) {
+            error_with_token!(
+                "invalid-builtin",
+                "va_arg では型\"{}\"の値を取り出せません。",
+                &typ_token.borrow(),
+                typ
+            );
+        }
+        expect(token_ptr, ")");
+        // 次の引数が置かれているアドレスを求め、その参照を外す
+        let arg_addr = Rc::new(RefCell::new(Node {
+            kind: Nodekind::VaArg,
+            token: Some(Rc::clone(&token)),
+            typ: Some(typ.make_ptr_to()),
+            left: Some(ap),
+            ..Default::default()
+        }));
+        new_unary(Nodekind::Deref, arg_addr, token)
+    } else if consume_va(token_ptr, "end") {
+        // System V ABI では後始末は必要ない
+        expect(token_ptr, "(");
+        let _ = va_list(token_ptr);
+        expect(token_ptr, ")");
+        new_num(0, token)
+    } else {
+        if !consume_va(token_ptr, "copy") {
+            expect(token_ptr, "va_copy");
+        }
+        expect(token_ptr, "(");
+        let dest = va_list(token_ptr);
+        expect(token_ptr, ",");
+        let src = va_list(token_ptr);
+        expect(token_ptr, ")");
+        Rc::new(RefCell::new(Node {
+            kind: Nodekind::VaCopy,
+            token: Some(token),
+            typ: Some(TypeCell::new(Type::Int)),
+            left: Some(dest),
+            right: Some(src),
+            ..Default::default()
+        }))
+    }
+}
+
+/// 実引数を仮引数の型に変換する
+/// 対応する仮引数のない実引数(プロトタイプのない関数や可変長引数の部分)には既定の実引数拡張を行う
+fn convert_args(args: Vec<NodeRef>, params: &[TypeCellRef]) -> Vec<NodeRef> {
+    args.into_iter()
+        .enumerate()
+        .map(|(ix, arg)| {
+            let arg_typ = arg.borrow().typ.clone().unwrap();
+            if let Some(param) = params.get(ix) {
+                let param = param.borrow().clone();
+                check_assignable(&param, &arg);
+                new_cast(&arg, param)
+            } else if arg_typ.is_integer() {
+                default_argument_promotion(&arg)
+            } else if !arg_typ.is_scalar() {
+                error_with_node!(
+                    "invalid-argument",
+                    "型\"{}\"の値は実引数として渡せません。",
+                    &arg.borrow(),
+                    arg_typ
+                );
+            } else {
+                arg
+            }
+        })
+        .collect()
+}
+
+/// va_list を指す式(va_list 型の変数、または関数の引数として渡された va_list)
+fn va_list(token_ptr: &mut TokenRef) -> NodeRef {
+    let node = assign(token_ptr);
+    confirm_type(&node);
+    let typ = node.borrow().typ.clone().unwrap();
+    if typ.make_deref().map_or(true, |t| t.typ != Type::Char) {
+        error_with_node!(
+            "invalid-builtin",
+            "va_list が必要ですが、型\"{}\"が指定されています。",
+            &node.borrow(),
+            typ
+        );
+    }
+    node
+}
+
+#[inline]
+fn is_atomic_builtin(token_ptr: &mut TokenRef) -> bool {
+    is(token_ptr, "__atomic_load_n")
+        || is(token_ptr, "__atomic_store_n")
+        || is(token_ptr, "__atomic_fetch_add")
+        || is(token_ptr, "__atomic_compare_exchange_n")
+        || is(token_ptr, "__sync_synchronize")
+}
+
+/// 生成規則:
+/// atomic-builtin = "__atomic_load_n" "(" assign "," memorder ")"
+///         | "__atomic_store_n" "(" assign "," assign "," memorder ")"
+///         | "__atomic_fetch_add" "(" assign "," assign "," memorder ")"
+///         | "__atomic_compare_exchange_n" "(" assign "," assign "," assign "," assign "," memorder "," memorder ")"
+///         | "__sync_synchronize" "(" ")"
+/// x86-64 では整列されたロードはそのまま不可分であり、ストアは xchg 、加算は lock xadd 、比較交換は lock cmpxchg で行う
+/// メモリオーダーは常に最も強い順序(__ATOMIC_SEQ_CST)として扱う
+fn atomic_builtin(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    if consume(token_ptr, "__sync_synchronize") {
+        expect(token_ptr, "(");
+        expect(token_ptr, ")");
+        return Rc::new(RefCell::new(Node {
+            kind: Nodekind::Fence,
+            token: Some(token),
+            typ: Some(TypeCell::new(Type::Void)),
+            ..Default::default()
+        }));
+    }
+    let name = token.borrow().body.clone().unwrap();
+    token_ptr_exceed(token_ptr);
+    expect(token_ptr, "(");
+    let ptr = atomic_ptr(token_ptr, &name);
+    let ptr_typ = ptr.borrow().typ.clone().unwrap();
+    let typ = ptr_typ.make_deref().unwrap().unqualified();
+    let node_ptr = if name == "__atomic_load_n" {
+        memorder(token_ptr);
+        // 読み出した値は左辺値ではない
+        new_cast(&new_unary(Nodekind::Deref, ptr, Rc::clone(&token)), typ)
+    } else if name == "__atomic_store_n" {
+        expect(token_ptr, ",");
+        let val = assign(token_ptr);
+        memorder(token_ptr);
+        // 指す先を _Atomic とみなして代入することで xchg によるストアにする
+        let target = new_unary(Nodekind::Deref, ptr, Rc::clone(&token));
+        confirm_type(&target);
+        target.borrow_mut().typ.as_mut().unwrap().is_atomic = true;
+        let store = modify(Nodekind::Assign, target, val, Rc::clone(&token));
+        new_cast(&store, TypeCell::new(Type::Void))
+    } else if name == "__atomic_fetch_add" {
+        expect(token_ptr, ",");
+        let val = assign(token_ptr);
+        memorder(token_ptr);
+        confirm_type(&val);
+        if !typ.is_integer() || !val.borrow().typ.as_ref().unwrap().is_integer() {
+            error_with_token!(
+                "invalid-builtin",
+                "\"{}\" では整数にのみ整数を足すことができます。",
+                &token.borrow(),
+                name
+            );
+        }
+        check_atomic_target(&ptr_typ, &token, &name);
+        Rc::new(RefCell::new(Node {
+            kind: Nodekind::AtomicAdd,
+            token: Some(token),
+            typ: Some(typ.clone()),
+            left: Some(ptr),
+            right: Some(new_cast(&val, typ)),
+            ..Default::default()
+        }))
+    } else {
+        expect(token_ptr, ",");
+        let expected = assign(token_ptr);
+        confirm_type(&expected);
+        let expected_typ = expected.borrow().typ.as_ref().unwrap().param_adjusted();
+        let is_same_ptr = expected_typ.is_pointer()
+            && !expected_typ.make_deref().unwrap().is_const
+            && expected_typ
+                .make_deref()
+                .unwrap()
+                .unqualified()
+                .is_compatible(&typ);
+        if !is_same_ptr {
+            error_with_node!("invalid-builtin", 
+                "\"{}\" の第2引数は、第1引数と同じ型を指す const でないポインタである必要がありますが、型\"{}\"が指定されています。",
+                &expected.borrow(),
+                name,
+                expected_typ
+            );
+        }
+        expect(token_ptr, ",");
+        let desired = assign(token_ptr);
+        confirm_type(&desired);
+        check_assignable(&typ, &desired);
+        // weak の指定は、失敗した場合に再試行するかどうかの違いなので無視する
+        expect(token_ptr, ",");
+        let _ = assign(token_ptr);
+        memorder(token_ptr);
+        memorder(token_ptr);
+        check_atomic_target(&ptr_typ, &token, &name);
+        new_atomic_cas(ptr, expected, new_cast(&desired, typ), token)
+    };
+    expect(token_ptr, ")");
+    node_ptr
+}
+
+/// 不可分な操作の対象を指すポインタ(整数かポインタを指すもの)
+fn atomic_ptr(token_ptr: &mut TokenRef, name: &str) -> NodeRef {
+    let node_ptr = assign(token_ptr);
+    confirm_type(&node_ptr);
+    let typ = node_ptr.borrow().typ.clone().unwrap();
+    let is_valid = typ.is_pointer()
+        && typ
+            .make_deref()
+            .is_ok_and(|t| t.is_integer() || t.is_pointer());
+    if !is_valid {
+        error_with_node!("invalid-builtin", 
+            "\"{}\" の第1引数は整数かポインタを指すポインタである必要がありますが、型\"{}\"が指定されています。",
+            &node_ptr.borrow(),
+            name,
+            typ
+        );
+    }
+    node_ptr
+}
+
+/// 書き込みを行う不可分な操作の対象は const であってはならない
+fn check_atomic_target(ptr_typ: &TypeCell, token: &TokenRef, name: &str) {
+    if ptr_typ.make_deref().unwrap().is_const {
+        error_with_token!(
+            "read-only-assignment",
+            "\"{}\" では const 修飾された型\"{}\"の値は変更できません。",
+            &token.borrow(),
+            name,
+            ptr_typ.make_deref().unwrap()
+        );
+    }
+}
+
+/// 生成規則:
+/// memorder = "," assign
+/// 常に最も強い順序で行うので、整数であることを確かめて読み捨てる
+fn memorder(token_ptr: &mut TokenRef) {
+    expect(token_ptr, ",");
+    let node_ptr = assign(token_ptr);
+    confirm_type(&node_ptr);
+    if !node_ptr.borrow().typ.as_ref().unwrap().is_integer() {
+        error_with_node!(
+            "invalid-builtin",
+            "メモリオーダーは整数で指定する必要があります。",
+            &node_ptr.borrow()
+        );
+    }
+}
+
+/// 引数1つの整数を受け取り、関数を呼び出さずに命令で計算する組み込み関数: (名前, ノードの種類, 引数の型の大きさ)
+const BIT_BUILTINS: [(&str, Nodekind, usize); 12] = [
+    ("__builtin_popcount", Nodekind::PopCount, 4),
+    ("__builtin_popcountl", Nodekind::PopCount, 8),
+    ("__builtin_popcountll", Nodekind::PopCount, 8),
+    ("__builtin_clz", Nodekind::Clz, 4),
+    ("__builtin_clzl", Nodekind::Clz, 8),
+    ("__builtin_clzll", Nodekind::Clz, 8),
+    ("__builtin_ctz", Nodekind::Ctz, 4),
+    ("__builtin_ctzl", Nodekind::Ctz, 8),
+    ("__builtin_ctzll", Nodekind::Ctz, 8),
+    ("__builtin_bswap16", Nodekind::Bswap, 2),
+    ("__builtin_bswap32", Nodekind::Bswap, 4),
+    ("__builtin_bswap64", Nodekind::Bswap, 8),
+];
+
+#[inline]
+fn is_builtin(token_ptr: &mut TokenRef) -> bool {
+    BIT_BUILTINS.iter().any(|(name, _, _)| is(token_ptr, name))
+        || is(token_ptr, "__builtin_expect")
+        || is(token_ptr, "__builtin_unreachable")
+        || is(token_ptr, "__builtin_trap")
+        || is(token_ptr, "__builtin_offsetof")
+        || is(token_ptr, "__builtin_memcpy")
+        || is(token_ptr, "__builtin_constant_p")
+}
+
+/// 生成規則:
+/// builtin = bit-builtin "(" assign ")"
+///         | "__builtin_expect" "(" assign "," assign ")"
+///         | ("__builtin_unreachable" | "__builtin_trap") "(" ")"
+///         | "__builtin_offsetof" "(" type-name abstract-declarator "," member-designator ")"
+///         | "__builtin_memcpy" "(" assign "," assign "," assign ")"
+///         | "__builtin_constant_p" "(" assign ")"
+/// 外部の関数は呼び出さず、命令列や定数に置き換える
+fn builtin(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    let name = token.borrow().body.clone().unwrap();
+    token_ptr_exceed(token_ptr);
+    expect(token_ptr, "(");
+    let node_ptr = if let Some((_, kind, bytes)) = BIT_BUILTINS.iter().find(|b| b.0 == name) {
+        // popcount, clz, ctz は unsigned int などを、 bswap は uint16_t などを引数にとる
+        let arg = integer_arg(token_ptr, &name);
+        let arg_typ = TypeCell {
+            is_unsigned: true,
+            ..TypeCell::new(match bytes {
+                2 => Type::Short,
+                4 => Type::Int,
+                _ => Type::Long,
+            })
+        };
+        let typ = if *kind == Nodekind::Bswap {
+            arg_typ.clone()
+        } else {
+            TypeCell::new(Type::Int)
+        };
+        let node_ptr = new_unary(*kind, new_cast(&arg, arg_typ), token);
+        let _ = node_ptr.borrow_mut().typ.insert(typ);
+        node_ptr
+    } else if name == "__builtin_expect" {
+        // 分岐予測のための情報なので、期待する値は読み捨てて式の値をそのまま評価値とする
+        let exp = integer_arg(token_ptr, &name);
+        expect(token_ptr, ",");
+        let _ = assign(token_ptr);
+        new_cast(&exp, TypeCell::new(Type::Long))
+    } else if name == "__builtin_unreachable" || name == "__builtin_trap" {
+        let kind = if name == "__builtin_trap" {
+            Nodekind::Trap
+        } else {
+            Nodekind::Unreachable
+        };
+        Rc::new(RefCell::new(Node {
+            kind,
+            token: Some(token),
+            typ: Some(TypeCell::new(Type::Void)),
+            ..Default::default()
+        }))
+    } else if name == "__builtin_offsetof" {
+        let offset = offsetof(token_ptr);
+        let node_ptr = new_num(offset as i32, token);
+        let _ = node_ptr.borrow_mut().typ.insert(TypeCell::new_size_t());
+        node_ptr
+    } else if name == "__builtin_memcpy" {
+        let dest = pointer_arg(token_ptr, &name);
+        expect(token_ptr, ",");
+        let src = pointer_arg(token_ptr, &name);
+        expect(token_ptr, ",");
+        let size = integer_arg(token_ptr, &name);
+        let dest_typ = dest.borrow().typ.as_ref().unwrap().param_adjusted();
+        if dest_typ.make_deref().unwrap().is_const_object() {
+            error_with_node!(
+                "read-only-assignment",
+                "\"{}\" では const 修飾された型\"{}\"の値は変更できません。",
+                &dest.borrow(),
+                name,
+                dest_typ.make_deref().unwrap()
+            );
+        }
+        Rc::new(RefCell::new(Node {
+            kind: Nodekind::MemCpy,
+            token: Some(token),
+            typ: Some(TypeCell::new(Type::Void).make_ptr_to()),
+            left: Some(dest),
+            right: Some(src),
+            init: Some(new_cast(&size, TypeCell::new_size_t())),
+            ..Default::default()
+        }))
+    } else {
+        // 式は評価せず、整数定数式であるかどうかのみを調べる
+        let exp = assign(token_ptr);
+        confirm_type(&exp);
+        new_num(is_const_expr(&exp) as i32, token)
+    };
+    expect(token_ptr, ")");
+    node_ptr
+}
+
+/// 組み込み関数の整数の引数
+fn integer_arg(token_ptr: &mut TokenRef, name: &str) -> NodeRef {
+    let node_ptr = assign(token_ptr);
+    confirm_type(&node_ptr);
+    let typ = node_ptr.borrow().typ.clone().unwrap();
+    if !typ.is_integer() {
+        error_with_node!(
+            "invalid-builtin",
+            "\"{}\" の引数は整数型である必要がありますが、型\"{}\"が指定されています。",
+            &node_ptr.borrow(),
+            name,
+            typ
+        );
+    }
+    node_ptr
+}
+
+/// 組み込み関数のポインタの引数(配列はポインタとして扱う)
+fn pointer_arg(token_ptr: &mut TokenRef, name: &str) -> NodeRef {
+    let node_ptr = assign(token_ptr);
+    confirm_type(&node_ptr);
+    let typ = node_ptr.borrow().typ.clone().unwrap();
+    if !typ.is_pointer_like() {
+        error_with_node!(
+            "invalid-builtin",
+            "\"{}\" の引数はポインタである必要がありますが、型\"{}\"が指定されています。",
+            &node_ptr.borrow(),
+            name,
+            typ
+        );
+    }
+    node_ptr
+}
+
+/// 生成規則:
+/// member-designator = ident ("." ident | "[" const-expr "]")*
+/// 構造体・共用体の型 typ の中での、指示子が指すメンバのオフセットを求める
+fn offsetof(token_ptr: &mut TokenRef) -> usize {
+    let typ_token = Rc::clone(token_ptr);
+    let typ = type_name(token_ptr);
+    let mut typ = declarator(token_ptr, typ).1;
+    expect(token_ptr, ",");
+    let mut offset = 0;
+    let mut token = Rc::clone(token_ptr);
+    let mut name = expect_ident(token_ptr);
+    if !typ.is_struct() {
+        error_with_token!("invalid-builtin", 
+            "\"__builtin_offsetof\" には構造体・共用体の型が必要ですが、型\"{}\"が指定されています。",
+            &typ_token.borrow(),
+            typ
+        );
+    }
+    loop {
+        if let Some(member) = typ.member(&name) {
+            offset += member.offset;
+            typ = member.typ;
+        } else {
+            error_with_token!(
+                "no-member",
+                "型\"{}\"にメンバ\"{}\"はありません。",
+                &token.borrow(),
+                typ,
+                name
+            );
+        }
+        // 配列の要素は添字で指定する
+        while consume(token_ptr, "[") {
+            let index_token = Rc::clone(token_ptr);
+            if !typ.is_array() || typ.is_vla() {
+                error_with_token!(
+                    "invalid-builtin",
+                    "型\"{}\"の要素は添字で指定できません。",
+                    &index_token.borrow(),
+                    typ
+                );
+            }
+            let index = const_expr(token_ptr);
+            expect(token_ptr, "]");
+            typ = typ.make_deref().unwrap();
+            offset = (offset as i64 + index * typ.bytes() as i64) as usize;
+        }
+        if !consume(token_ptr, ".") {
+            break;
+        }
+        token = Rc::clone(token_ptr);
+        name = expect_ident(token_ptr);
+        if !typ.is_struct() {
+            error_with_token!(
+                "invalid-builtin",
+                "型\"{}\"はメンバを持ちません。",
+                &token.borrow(),
+                typ
+            );
+        }
+    }
+    offset
+}
+
+/// 呼び出す関数の名前から、宣言された関数の型を探す(ブロックスコープの宣言が外側の宣言より優先される)
+/// 関数でないものとして宣言されていれば、その型を Err で返す
+fn find_func(name: &str) -> Option<Result<TypeCell, TypeCell>> {
+    for scope in LOCALS.try_lock().unwrap().iter().rev() {
+        if let Some((_, typ, _)) = scope.get(name) {
+            return Some(if typ.typ == Type::Func {
+                Ok(typ.clone())
+            } else {
+                Err(typ.clone())
+            });
+        }
+    }
+    let glob = GLOBALS.try_lock().unwrap().get(name).cloned()?;
+    let Node { func_typ, typ, .. } = glob;
+    Some(func_typ.ok_or_else(|| typ.unwrap()))
+}
+
+/// 生成規則:
+/// primary = num
+///         | string-literal
+///         | ident ("(" params ")")? postfix
+///         | "(" expr ")" postfix
+///         | va-builtin postfix
+///         | atomic-builtin postfix
+///         | builtin postfix
+///         | generic-selection postfix
+fn primary(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    if is_stmt_expr(token_ptr) {
+        let node_ptr = stmt_expr(token_ptr);
+        postfix(token_ptr, node_ptr, token)
+    } else if consume(token_ptr, "(") {
+        let node_ptr: NodeRef = expr(token_ptr);
+        expect(token_ptr, ")");
+        postfix(token_ptr, node_ptr, token)
+    } else if is_va_builtin(token_ptr) {
+        let node_ptr = va_builtin(token_ptr);
+        postfix(token_ptr, node_ptr, token)
+    } else if is_atomic_builtin(token_ptr) {
+        let node_ptr = atomic_builtin(token_ptr);
+        postfix(token_ptr, node_ptr, token)
+    } else if is_builtin(token_ptr) {
+        let node_ptr = builtin(token_ptr);
+        postfix(token_ptr, node_ptr, token)
+    } else if is(token_ptr, "_Generic") {
+        let node_ptr = generic_selection(token_ptr);
+        postfix(token_ptr, node_ptr, token)
+    } else if consume(token_ptr, "__func__") {
+        // 関数名を内容とする static な char の配列で、文字列リテラルと同様に扱う
+        let name = if let Some(name) = CURRENT_FUNC.try_lock().unwrap().clone() {
+            name
+        } else {
+            error_with_token!(
+                "outside-function",
+                "__func__ は関数の中でのみ使用できます。",
+                &token.borrow()
+            );
+        };
+        let size = name.len() + 1;
+        let label = store_literal(name);
+        let node_ptr = new_lvar(
+            label,
+            Rc::clone(&token),
+            TypeCell::new(Type::Char).make_array_of(size),
+            false,
+            0,
+        );
+        postfix(token_ptr, node_ptr, token)
+    } else if consume(token_ptr, "__builtin_alloca") {
+        // 確保した領域は関数から戻るまで有効(ブロックを抜けても解放されない)
+        expect(token_ptr, "(");
+        let size = assign(token_ptr);
+        confirm_type(&size);
+        expect(token_ptr, ")");
+        if !size.borrow().typ.as_ref().unwrap().is_integer() {
+            error_with_node!(
+                "invalid-builtin",
+                "__builtin_alloca の引数は整数型である必要があります。",
+                &size.borrow()
+            );
+        }
+        if CURRENT_FUNC.try_lock().unwrap().is_none() {
+            error_with_token!(
+                "outside-function",
+                "__builtin_alloca は関数の中でのみ使用できます。",
+                &token.borrow()
+            );
+        }
+        *USES_ALLOCA.try_lock().unwrap() = true;
+        let node_ptr = new_unary(
+            Nodekind::Alloca,
+            new_cast(&size, TypeCell::new_size_t()),
+            token,
+        );
+        let _ = node_ptr
+            .borrow_mut()
+            .typ
+            .insert(TypeCell::new(Type::Void).make_ptr_to());
+        node_ptr
+    } else if let Some(name) = consume_ident(token_ptr) {
+        if consume(token_ptr, "(") {
+            let func_typ: TypeCell;
+            let args: Vec<NodeRef> = params(token_ptr);
+            // 宣言されている関数(外部ソースの関数もプロトタイプ宣言により登録される)は、宣言された引数と戻り値の型を使う
+            if let Some(decl) = find_func(&name) {
+                func_typ = match decl {
+                    Ok(typ) => typ,
+                    Err(typ) => {
+                        error_with_token!(
+                            "not-a-function",
+                            "型\"{}\"は関数として扱えません。",
+                            &*token.borrow(),
+                            typ
+                        );
+                    }
+                };
+
+                // プロトタイプのない関数は実引数の個数を調べない(仮引数の型がないので、既定の実引数拡張のみを行う)
+                if !func_typ.is_unprototyped {
+                    let argc = func_typ.arg_typs.as_ref().unwrap().len();
+                    if func_typ.is_variadic && args.len() < argc {
+                        error_with_token!(
+                            "argument-count",
+                            "\"{}\" の引数は少なくとも{}個必要ですが、{}個が渡されました。",
+                            &*token.borrow(),
+                            name,
+                            argc,
+                            args.len()
+                        );
+                    } else if !func_typ.is_variadic && args.len() != argc {
+                        error_with_token!(
+                            "argument-count",
+                            "\"{}\" の引数は{}個で宣言されていますが、{}個が渡されました。",
+                            &*token.borrow(),
+                            name,
+                            argc,
+                            args.len()
+                        );
+                    }
+                }
+                check_value_types(&func_typ, &token);
+                let args = convert_args(args, func_typ.arg_typs.as_ref().unwrap());
+                let node_ptr = new_funcall(name, func_typ, args, Rc::clone(&token));
+                postfix(token_ptr, node_ptr, token)
+            } else {
+                // プロトタイプ宣言のない関数は、戻り値を int として扱い、引数には既定の実引数拡張のみを行う
+                // エラーで読み飛ばした宣言の関数であれば、連鎖した警告とみなして出さない
+                if IMPLICIT_FUNCS.try_lock().unwrap().insert(name.clone())
+                    && !ERROR_NAMES.try_lock().unwrap().contains(&name)
+                {
+                    report(
+                        Diagnostic::warning(
+                            "implicit-function-declaration",
+                            format!("関数\"{}\"は暗黙的に宣言されています。", name),
+                        )
+                        .with_span(token.borrow().span())
+                        .with_note("戻り値は int 型であるとみなします。"),
+                    );
+                }
+                let args = convert_args(args, &[]);
+                let mut arg_typs = vec![];
+                for arg in &args {
+                    arg_typs.push(Rc::new(RefCell::new(arg.borrow().typ.clone().unwrap())));
+                }
+                func_typ = TypeCell::new(Type::Int).make_func(arg_typs);
+                let node_ptr = new_funcall(name, func_typ, args, Rc::clone(&token));
+                postfix(token_ptr, node_ptr, token)
+            }
+        } else {
+            // 外部ソースのグローバル変数は extern 宣言により GLOBALS に登録されるので、LOCALS, GLOBALS に当たらなければエラーになるようにする
+            let mut typ: TypeCell = TypeCell::default();
+            let mut is_local = false;
+            let mut level = 0;
+            let mut label: Option<String> = None;
+            for (l, scope) in LOCALS.try_lock().unwrap().iter_mut().enumerate().rev() {
+                let _ = scope.entry(name.clone()).and_modify(|(_, t, g)| {
+                    typ = t.clone();
+                    level = l;
+                    is_local = true;
+                    label = g.clone();
+                });
+                if is_local {
+                    break;
+                }
+            }
+
+            if !is_local {
+                let glob_typ = GLOBALS.try_lock().unwrap().get(&name).map(|glob| {
+                    glob.typ
+                        .clone()
+                        .unwrap_or_else(|| glob.func_typ.clone().unwrap())
+                });
+                typ = glob_typ.unwrap_or_else(|| {
+                    error_undefined(
+                        "undeclared-identifier",
+                        "定義されていない変数です。",
+                        &name,
+                        &token.borrow(),
+                    );
+                });
+            }
+            // 関数ポインタはまだサポートしていないため、呼び出し以外で関数名は使用できない
+            if typ.typ == Type::Func {
+                error_with_token!(
+                    "unsupported",
+                    "関数\"{}\"は呼び出し以外では使用できません。",
+                    &token.borrow(),
+                    name
+                );
+            }
+
+            // static, extern なローカル変数はグローバルなラベルで参照する
+            let node_ptr = if let Some(label) = label {
+                new_lvar(label, Rc::clone(&token), typ, false, 0)
+            } else {
+                new_lvar(name, Rc::clone(&token), typ, is_local, level)
+            };
+            mark_thread_local(&node_ptr);
+            postfix(token_ptr, node_ptr, token)
+        }
+    } else if let Some(literal) = consume_literal(token_ptr) {
+        let size = literal.len() + 1;
+        let name = store_literal(literal);
+        new_lvar(
+            name,
+            token,
+            TypeCell::new(Type::Char).make_array_of(size),
+            false,
+            0,
+        )
+    } else {
+        new_num(expect_number(token_ptr), token)
+    }
+}
+
+/// 生成規則:
+/// generic-selection = "_Generic" "(" assign ("," generic-association)+ ")"
+/// generic-association = (type-name declarator | "default") ":" assign
+/// 制御式の型(配列はポインタに変換し、修飾子は除く)と互換性のある型の式を選ぶ(選ばれなかった式は評価されない)
+fn generic_selection(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    expect(token_ptr, "_Generic");
+    expect(token_ptr, "(");
+    let ctrl = assign(token_ptr);
+    confirm_type(&ctrl);
+    let ctrl_typ = ctrl.borrow().typ.as_ref().unwrap().param_adjusted();
+
+    let mut typs: Vec<TypeCell> = vec![];
+    let mut selected: Option<NodeRef> = None;
+    let mut default: Option<NodeRef> = None;
+    while consume(token_ptr, ",") {
+        let assoc_token = Rc::clone(token_ptr);
+        if consume(token_ptr, "default") {
+            if default.is_some() {
+                error_with_token!(
+                    "invalid-generic",
+                    "_Generic に default が複数指定されています。",
+                    &assoc_token.borrow()
+                );
+            }
+            expect(token_ptr, ":");
+            default = Some(assign(token_ptr));
+            continue;
+        }
+
+        let typ = type_name(token_ptr);
+        let typ = declarator(token_ptr, typ).1;
+        if typ.has_vla() {
+            error_with_token!(
+                "invalid-generic",
+                "_Generic の選択肢には可変長配列の型を指定できません。",
+                &assoc_token.borrow()
+            );
+        }
+        if let Some(prev) = typs.iter().find(|prev| prev.is_compatible(&typ)) {
+            error_with_token!(
+                "invalid-generic",
+                "型\"{}\"は既に指定された型\"{}\"と互換性があります。",
+                &assoc_token.borrow(),
+                typ,
+                prev
+            );
+        }
+        expect(token_ptr, ":");
+        let node_ptr = assign(token_ptr);
+        if typ.is_compatible(&ctrl_typ) {
+            selected = Some(node_ptr);
+        }
+        typs.push(typ);
+    }
+    expect(token_ptr, ")");
+
+    if let Some(node_ptr) = selected.or(default) {
+        node_ptr
+    } else {
+        error_with_token!(
+            "invalid-generic",
+            "_Generic に型\"{}\"と互換性のある選択肢がありません。",
+            &token.borrow(),
+            ctrl_typ
+        );
+    }
+}
+
+#[inline]
+fn is_stmt_expr(token_ptr: &mut TokenRef) -> bool {
+    let mut _token_ptr = Rc::clone(token_ptr);
+    consume(&mut _token_ptr, "(") && is(&mut _token_ptr, "{")
+}
+
+/// 生成規則:
+/// stmt-expr = "(" "{" stmt* "}" ")"
+/// 最後の文が式文であればその値が評価値となり、そうでなければ void 型となる
+/// ブロック内で確保した可変長配列の領域を解放するノードは init に持たせる
+fn stmt_expr(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    if CURRENT_FUNC.try_lock().unwrap().is_none() {
+        error_with_token!(
+            "outside-function",
+            "文式は関数の中でのみ使用できます。",
+            &token.borrow()
+        );
+    }
+    expect(token_ptr, "(");
+    let brace = Rc::clone(token_ptr);
+    expect(token_ptr, "{");
+    enter_scope();
+    let mut children: Vec<NodeRef> = vec![];
+    let mut is_expr_stmt = false;
+    while !consume(token_ptr, "}") {
+        if at_eof(token_ptr) {
+            error_with_token!(
+                "expected-token",
+                "\'{{\'にマッチする\'}}\'が見つかりません。",
+                &brace.borrow()
+            );
+        }
+        let is_not_expr =
+            is(token_ptr, ";") || is(token_ptr, "_Static_assert") || is_declspec(token_ptr);
+        if let Some(_stmt) = stmt_or_recover(token_ptr) {
+            is_expr_stmt = !is_not_expr && !is_stmt_kind(_stmt.borrow().kind);
+            children.push(_stmt);
+        }
+    }
+    let restore = restore_vlas_inside(current_scope() - 1);
+    leave_scope();
+    expect(token_ptr, ")");
+
+    // 配列は先頭要素へのポインタとして値になる
+    let typ = match children.last() {
+        Some(last) if is_expr_stmt => last.borrow().typ.as_ref().unwrap().param_adjusted(),
+        _ => TypeCell::new(Type::Void),
+    };
+    Rc::new(RefCell::new(Node {
+        kind: Nodekind::StmtExpr,
+        token: Some(token),
+        typ: Some(typ),
+        init: restore,
+        children,
+        ..Default::default()
+    }))
+}
+
+/// 式文ではない文のノードかどうか
+#[inline]
+fn is_stmt_kind(kind: Nodekind) -> bool {
+    [
+        Nodekind::If,
+        Nodekind::For,
+        Nodekind::While,
+        Nodekind::DoWhile,
+        Nodekind::Switch,
+        Nodekind::Case,
+        Nodekind::Break,
+        Nodekind::Continue,
+        Nodekind::Goto,
+        Nodekind::Label,
+        Nodekind::Return,
+        Nodekind::Block,
+        Nodekind::InlineAsm,
+        Nodekind::Nop,
+    ]
+    .contains(&kind)
+}
+
+/// 生成規則:
+/// postfix = ("[" expr "]" | "." ident | "->" ident)*
+/// a[i] は *(a + i) と、 a->m は (*a).m と読み替える
+fn postfix(token_ptr: &mut TokenRef, mut node_ptr: NodeRef, token: TokenRef) -> NodeRef {
+    loop {
+        let op_token = Rc::clone(token_ptr);
+        if consume(token_ptr, "[") {
+            let index_token = Rc::clone(token_ptr);
+            let index = expr(token_ptr);
+            node_ptr = new_unary(
+                Nodekind::Deref,
+                new_add(node_ptr, index, index_token),
+                Rc::clone(&token),
+            );
+            expect(token_ptr, "]");
+        } else if consume(token_ptr, ".") {
+            node_ptr = struct_ref(token_ptr, node_ptr, op_token);
+        } else if consume(token_ptr, "->") {
+            confirm_type(&node_ptr);
+            let deref = new_unary(Nodekind::Deref, node_ptr, Rc::clone(&op_token));
+            confirm_type(&deref);
+            node_ptr = struct_ref(token_ptr, deref, op_token);
+        } else {
+            return node_ptr;
+        }
+    }
+}
+
+/// 構造体・共用体のメンバを参照するノード(構造体の修飾子はメンバにも引き継がれる)
+fn struct_ref(token_ptr: &mut TokenRef, node_ptr: NodeRef, token: TokenRef) -> NodeRef {
+    confirm_type(&node_ptr);
+    let typ = node_ptr.borrow().typ.clone().unwrap();
+    if !typ.is_struct() {
+        error_with_token!(
+            "invalid-member-access",
+            "構造体・共用体でない型\"{}\"のメンバは参照できません。",
+            &token.borrow(),
+            typ
+        );
+    }
+    if typ.is_incomplete() {
+        error_with_token!(
+            "incomplete-type",
+            "不完全型\"{}\"のメンバは参照できません。",
+            &token.borrow(),
+            typ
+        );
+    }
+    let name_token = Rc::clone(token_ptr);
+    let name = expect_ident(token_ptr);
+    let member = if let Some(member) = typ.member(&name) {
+        member
+    } else {
+        error_with_token!(
+            "no-member",
+            "型\"{}\"にメンバ\"{}\"はありません。",
+            &name_token.borrow(),
+            typ,
+            name
+        );
+    };
+    let mut member_typ = member.typ;
+    member_typ.is_const |= typ.is_const;
+    member_typ.is_volatile |= typ.is_volatile;
+    Rc::new(RefCell::new(Node {
+        kind: Nodekind::Member,
+        token: Some(name_token),
+        typ: Some(member_typ),
+        left: Some(node_ptr),
+        offset: Some(member.offset),
+        name: Some(name),
+        ..Default::default()
+    }))
+}
+
+/// "(" の直後に型名が続く場合はキャスト式か複合リテラル
+fn is_cast(token_ptr: &mut TokenRef) -> bool {
+    let mut _token_ptr = Rc::clone(token_ptr);
+    consume(&mut _token_ptr, "(") && is_type_name(&mut _token_ptr)
+}
+
+/// 生成規則:
+/// cast = "(" type-name abstract-declarator ")" unary
+///      | "(" type-name abstract-declarator ")" "{" initializer-list postfix
+/// 型名の後に "{" が続く場合は複合リテラルになる
+fn cast(token_ptr: &mut TokenRef) -> NodeRef {
+    let token = Rc::clone(token_ptr);
+    expect(token_ptr, "(");
+    let typ = type_name(token_ptr);
+    let typ = declarator(token_ptr, typ).1;
+    expect(token_ptr, ")");
+    if is(token_ptr, "{") {
+        let node_ptr = compound_literal_body(token_ptr, typ, Rc::clone(&token));
+        return postfix(token_ptr, node_ptr, token);
+    }
+    let operand = unary(token_ptr);
+    confirm_type(&operand);
+    let from = operand.borrow().typ.clone().unwrap();
+    // void へのキャストは値を捨てるだけなので、どの型の値でも良い
+    if !typ.is_void() {
+        if !typ.is_scalar() {
+            error_with_token!(
+                "invalid-cast",
+                "型\"{}\"へはキャストできません。",
+                &token.borrow(),
+                typ
+            );
+        }
+        if !from.is_scalar() {
+            error_with_token!(
+                "invalid-cast",
+                "型\"{}\"の値はキャストできません。",
+                &token.borrow(),
+                from
+            );
+        }
+    }
+    // キャストの結果は左辺値ではないので、修飾子は付かない
+    let mut typ = typ;
+    typ.is_const = false;
+    typ.is_volatile = false;
+    typ.is_atomic = false;
+    let node_ptr = cast_to(&operand, typ);
+    let _ = node_ptr.borrow_mut().token.insert(token);
+    with_vla_sizes(node_ptr)
+}
+
+/// 複合リテラルの初期化子を読み、無名の変数を参照するノードを返す
+/// ブロックスコープでは自動記憶域期間の無名のローカル変数として、参照するたびに初期化を行う(初期化のノードは Lvar の init に持たせる)
+/// ファイルスコープでは static な無名のグローバル変数として扱うため、そのアドレスをグローバル変数の初期化に使用できる
+fn compound_literal_body(token_ptr: &mut TokenRef, typ: TypeCell, token: TokenRef) -> NodeRef {
+    if typ.has_vla() {
+        error_with_token!(
+            "invalid-vla",
+            "複合リテラルの型に可変長配列は使用できません。",
+            &token.borrow()
+        );
+    }
+    let is_flex = typ.is_flex_array();
+    if CURRENT_FUNC.try_lock().unwrap().is_none() {
+        let label = format!("__compound_literal.{}", get_static_id());
+        let attr = VarAttr {
+            is_static: true,
+            ..Default::default()
+        };
+        let gvar = gvar_initializer(
+            token_ptr,
+            label.clone(),
+            typ,
+            is_flex,
+            attr,
+            Rc::clone(&token),
+        );
+        STATIC_LOCALS
+            .try_lock()
+            .unwrap()
+            .push(gvar.borrow().clone());
+        let typ = gvar.borrow().typ.clone().unwrap();
+        new_lvar(label, token, typ, false, 0)
+    } else {
+        let name = format!(".compound_literal.{}", get_static_id());
+        let init = lvar_initializer(token_ptr, name.clone(), typ, is_flex, Rc::clone(&token));
+        confirm_type(&init);
+        let level = current_scope();
+        let typ = LOCALS.try_lock().unwrap()[level][&name].1.clone();
+        let lvar = new_lvar(name, token, typ, true, level);
+        let _ = lvar.borrow_mut().init.insert(init);
+        lvar
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::explicit_counter_loop, clippy::unnecessary_unwrap)]
+pub mod tests {
+    use super::*;
+    use crate::globals::tests::test_init;
+    use crate::tokenizer::tokenize;
+
+    static REP: usize = 40;
+
+    fn search_tree(tree: &NodeRef) {
+        let node: &Node = &tree.borrow();
+        println!("{}", node);
+
+        if node.left.is_some() {
+            search_tree(node.left.as_ref().unwrap());
+        }
+        if node.right.is_some() {
+            search_tree(node.right.as_ref().unwrap());
+        }
+        if node.init.is_some() {
+            search_tree(node.init.as_ref().unwrap());
+        }
+        if node.enter.is_some() {
+            search_tree(node.enter.as_ref().unwrap());
+        }
+        if node.routine.is_some() {
+            search_tree(node.routine.as_ref().unwrap());
+        }
+        if node.branch.is_some() {
+            search_tree(node.branch.as_ref().unwrap());
+        }
+        if node.els.is_some() {
+            search_tree(node.els.as_ref().unwrap());
+        }
+        for child in &node.children {
+            search_tree(child);
+        }
+        for arg in &node.args {
+            search_tree(arg);
+        }
+        if node.stmts.is_some() {
+            for stmt_ in node.stmts.as_ref().unwrap() {
+                search_tree(stmt_);
+            }
+        }
+    }
+
+    pub fn parse_stmts(token_ptr: &mut TokenRef) -> Vec<NodeRef> {
+        let mut stmts: Vec<NodeRef> = Vec::new();
+        enter_scope();
+        while !at_eof(token_ptr) {
+            let stmt_ = stmt(token_ptr);
+            confirm_type(&stmt_);
+            stmts.push(stmt_);
+        }
+        leave_scope();
+        stmts
+    }
+
+    #[test]
+    fn basic_calc() {
+        let src: &str = "
+			int x, y, z;
+			x = 1 + 2 / 1;
+			y = 200 % (3 + 1);
+			z = 30 % 3 + 2 * 4;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn shift() {
+        let src: &str = "
+			int x;
+			x = 10 << 2 + 3 % 2 >> 3;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn bitops() {
+        let src: &str = "
+			int x, z;
+			int* y;
+			2 + (3 + 5) * 6;
+			1 ^ 2 | 2 != 3 / 2;
+			1 + -1 ^ 2;
+			3 ^ 2 & 1 | 2 & 9;
+			x = 10;
+			y = &x;
+			3 ^ 2 & *y | 2 & x;
+			z = ~x;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn logops() {
+        let src: &str = "
+			1 && 2 || 3 && 4;
+			1 && 2 ^ 3 || 4 && 5 || 6;
+			!2;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn inc_dec() {
+        let src: &str = "
+			int i;
+			i = 0;
+			++i;
+			--i;
+			i++;
+			i--;
+			int *p;
+			++*p;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn for_() {
+        let src: &str = "
+			int sum, i;
+			sum = 10;
+			sum = sum + i;
+			for (i = 1 ; i < 10; i = i + 1) sum = sum +i;
+			return sum;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn while_() {
+        let src: &str = "
+			int sum;
+			sum = 10;
+			while(sum > 0) sum = sum - 1;
+			return sum;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn if_() {
+        let src: &str = "
+			int i;
+			i = 10;
+			if (i == 10) i = i / 5;
+			if (i == 2) i = i + 5; else i = i / 5;
+			return i;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn ctrls() {
+        let src: &str = "
+			int i, sum;
+			sum = 0;
+			i = 10;
+			if (i == 10) while(i < 0) for(;;) sum = sum + 1;
+			return sum;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn block() {
+        let src: &str = "
+			int i;
+			for( i = 10; ; ) {i = i + 1;}
+			{}
+			{i = i + 1; 10;}
+			return 10;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn block2() {
+        let src: &str = "
+			int i, x;
+			while(i < 10) {i = i + 1; i = i * 2;}
+			x = 10;
+			if ( x == 10 ){
+				x = x + 200;
+				x = x / 20;
+			} else {
+				x = x - 20;
+				;
+			}
+			{{}}
+			{i = i + 1; 10;}
+			return 200;
+			return;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn func() {
+        let src: &str = "
+			int i, j;
+			call_fprint();
+			i = getOne();
+			j = getTwo();
+			return i + j;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn func2() {
+        let src: &str = "
+			int i, j, k;
+			call_fprint();
+			i = get(1);
+			j = get(2, 3, 4);
+			k = get(i+j, (i=3), k);
+			return i + j;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn addr_deref() {
+        let src: &str = "
+			int x, y, *z;
+			x = 3;
+			y = 5;
+			z = &y + 8;
+			return *z;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn addr_deref2() {
+        let src: &str = "
+			int x;
+			int *y;
+			int **z;
+			x = 3;
+			y = &x;
+			z = &y;
+			return *&**z;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn comma() {
+        let src: &str = "
+			int x, y, z;
+			x = 3, y = 4, z = 10;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn assign_op() {
+        let src: &str = "
+			int x;
+			x = 10;
+			x += 1;
+			x <<= 1;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn sizeof() {
+        let src: &str = "
+			int x, y, z;
+			x = 0; y = 0; z = 0;
+			int *p; p = &x;
+			int **pp; pp = &p;
+
+			sizeof(int);
+			sizeof(int **);
+			sizeof(0);
+			sizeof(x);
+			sizeof x;
+			sizeof ++x;
+			sizeof ++p;
+			sizeof(x+y);
+			sizeof x + y * z;
+			sizeof(x && x);
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn array() {
+        let src: &str = "
+			int x[20];
+			int y[10][20];
+			int *p[10][20][30];
+			int *q;
+			int z;
+			sizeof(*y);
+			sizeof(x);
+			x - q;
+			x + 10;
+			*y - q;
+			**p - *p[1];
+			&p - z;
+			****p;
+			*****&p;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = parse_stmts(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
         }
     }
 
-    pub fn parse_stmts(token_ptr: &mut TokenRef) -> Vec<NodeRef> {
-        let mut stmts: Vec<NodeRef> = Vec::new();
-        enter_scope();
-        while !at_eof(token_ptr) {
-            let stmt_ = stmt(token_ptr);
-            confirm_type(&stmt_);
-            stmts.push(stmt_);
+    #[test]
+    fn declare() {
+        let src: &str = "
+			int func(int x, int y) {
+				return x + y;
+			}
+			int calc(int a, int b, int c, int d, int e, int f) {
+				return a*b + c - d + e/f;
+			}
+			int main() {
+				int i, sum;
+				i = 0;
+				sum = 0;
+				for (; i < 10; i=i+1) {
+					sum = sum + i;
+				}
+				return func(i, sum);
+			}
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = program(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
         }
-        leave_scope();
-        stmts
     }
 
     #[test]
-    fn basic_calc() {
+    fn no_return() {
         let src: &str = "
-			int x, y, z;
-			x = 1 + 2 / 1;
-			y = 200 % (3 + 1);
-			z = 30 % 3 + 2 * 4;
+			int func(int x, int y) {
+				return x + y;
+			}
+			int main() {
+				int i, sum, x, y, z;
+				i = 0;
+				sum = 0;
+				for (; i < 10; i=i+1) {
+					sum = sum + i;
+				}
+				func(x=1, (y=1, z=1));
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn shift() {
+    fn array_access() {
         let src: &str = "
-			int x;
-			x = 10 << 2 + 3 % 2 >> 3;
+		int main() {
+			int X[10][10][10];
+			X[0][0][0] = 10;
+			return ***X;
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn bitops() {
+    fn gvar() {
         let src: &str = "
-			int x, z;
-			int* y;
-			2 + (3 + 5) * 6;
-			1 ^ 2 | 2 != 3 / 2;
-			1 + -1 ^ 2;
-			3 ^ 2 & 1 | 2 & 9;
-			x = 10;
-			y = &x;
-			3 ^ 2 & *y | 2 & &x;
-			z = ~x;
+		int x;
+		int X[0][0][0];
+		int func(int x, int y) {
+			return x + y;
+		}
+		int main() {
+			X;
+			int X[10][10][10];
+			X[0][0][0] = 10;
+			func(1, 3) + 1;
+			return ***X;
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn logops() {
+    fn literal() {
         let src: &str = "
-			1 && 2 || 3 && 4;
-			1 && 2 ^ 3 || 4 && 5 || 6;
-			!2;
+		int main() {
+			char *c = \"aaaa\";
+			\"bbbb\";
+			*c = 60;
+			return 0;
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn inc_dec() {
+    fn lvar_init() {
         let src: &str = "
-			int i;
-			i = 0;
-			++i;
-			--i;
-			i++;
-			i--;
-			int *p;
-			++*p;
+			int main() {
+				int x = {4, 5};
+				int X[4][2][1] = {1, {2, 3}, x, 5, {6}, 7, 8, 9, };
+				char str[][2][2] = {{{\"s\", }}};
+				char str2[][2][3] = {\"s\", \"abcd\", \"pqrs\"};
+				char str3[] = {\"str\", };
+				// int str4[][2] = {\"str\"}; // invalid
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn for_() {
+    fn designator() {
         let src: &str = "
-			int sum, i;
-			sum = 10;
-			sum = sum + i;
-			for (i = 1 ; i < 10; i = i + 1) sum = sum +i;
-			return sum;
+			int g[2][3] = {[1][0] = 7, [0 ... 1][1] = 5, 6};
+			char s[][4] = {\"ab\", [3] = \"xyz\", \"q\"};
+			int main() {
+				int c = 0;
+				int x[5] = {[3] = 7, 1, [0] = 2, [3] = c};
+				int y[2][2] = {[0] = {1, 2}, [0] = {3}, 4, 5, 6};
+				int z[4] = {[0 ... 3] = c++};
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn while_() {
+    fn struct_initializer() {
         let src: &str = "
-			int sum;
-			sum = 10;
-			while(sum > 0) sum = sum - 1;
-			return sum;
+			struct P { char c; int x; long y; };
+			struct Q { struct P p; int a[2]; union { int i; char ch; } u; };
+			struct P gp = {.x = 5, 1, .c = 'a'};
+			struct Q gq = {1, 2, 3, 4, 5, .u.ch = 7};
+			union U { char c; int i; } gu = {.c = 1, .i = 300};
+			struct { char s[4]; int n; } gs[2] = {\"abc\", 4, [1].s[1] = 'x'};
+			int main() {
+				struct P p = {.y = 8, .c = 2};
+				struct Q q = {{1}, .a[1] = 6, 7};
+				struct Q r = {p, 3};
+				struct P s = p;
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn if_() {
+    fn compound_literal() {
         let src: &str = "
-			int i;
-			i = 10;
-			if (i == 10) i = i / 5;
-			if (i == 2) i = i + 5; else i = i / 5;
-			return i;
+			int *gp = (int[]){10, 20, 30};
+			char *gs = \"str\";
+			int main() {
+				int *p = (int[]){1, 2, 3};
+				int *q = &(int){5};
+				(int){1} += 2;
+				return (int[]){4, 5, 6}[2] + sizeof (char[10]){\"ab\"} + *p + *q;
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn ctrls() {
+    fn vla() {
         let src: &str = "
-			int i, sum;
-			sum = 0;
-			i = 10;
-			if (i == 10) while(i < 0) for(;;) sum = sum + 1;
-			return sum;
+			int main() {
+				int n = 3, m = 4;
+				int a[n][m];
+				int (*p)[m] = a;
+				a[2][3] = sizeof a + sizeof(int[n]);
+				for (int i = 0; i < n; i++) {
+					int b[i + 1];
+					if (i) continue;
+				}
+				char *q = __builtin_alloca(n);
+				return p[2][3] + (p + 1 - p) + *q;
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn block() {
+    fn array_params() {
         let src: &str = "
-			for( i = 10; ; ) {i = i + 1;}
-			{}
-			{i = i + 1; 10;}
-			return 10;
+			int sum(int a[], int n);
+			int msum(int m[][4], int rows) {
+				return m[rows - 1][3] + sizeof m;
+			}
+			int vm(int n, int m[const static 2][n]) {
+				return m[1][n - 1];
+			}
+			int main() {
+				int x[3][4], a[5];
+				return msum(x, 3) + sum(a, 5) + vm(4, x);
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{}{}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn block2() {
+    fn c11_conveniences() {
         let src: &str = "
-			int i, x;
-			while(i < 10) {i = i + 1; i = i * 2;}
-			x = 10;
-			if ( x == 10 ){
-				x = x + 200;
-				x = x / 20;
-			} else {
-				x = x - 20;
-				;
+			_Static_assert(sizeof(long) == 8, \"long is 8 bytes\");
+			int main() {
+				_Static_assert(-1 < 0);
+				int x, arr[3];
+				char *name = __func__;
+				x = _Generic(x, int: name)[1];
+				return _Generic(x, long: 1, int: 2, default: 3) + _Generic(arr, int *: arr[0], default: 0) + name[0];
 			}
-			{{}}
-			{i = i + 1; 10;}
-			return 200;
-			return;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn func() {
+    fn gvar_init() {
         let src: &str = "
-			int i, j;
-			call_fprint();
-			i = getOne();
-			j = getTwo();
-			return i + j;
+			int x = {4+10, 5};
+			int y = 10;
+			int *p = &x - &x + &y; // valid
+			int *q = &y + 10; // valid
+			// int *r = &x - &y; // invalid
+			int z[][2][10] = {{1, 4, 5, {10, x}}, 2, 3, 4, 2}; // valid
+			// int w[][2][10] = {{1, 4, 5, {10, xx}}, 2, 3, 4, 2}; // invalid
+			char c[10][1000] = {\"abcd\", \"str\", {\'c\'}}, d[] = \"compiler\";
+			int (*X)(int, int*(*)()) = 10;
+			int (*Y)[] = 10;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
-    #[test]
-    fn func2() {
-        let src: &str = "
-			int i, j, k;
-			call_fprint();
-			i = get(1);
-			j = get(2, 3, 4);
-			k = get(i+j, (i=3), k);
-			return i + j;
+    #[test]
+    fn scope() {
+        let src: &str = "
+		int main() {
+			int a = {0};
+			int i = 10;
+			for (int i = 0; i < 10; i++) {
+				int i = 10;
+				a++;
+			}
+			print_helper(i);
+			print_helper(a);
+
+			return 0;
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn addr_deref() {
+    fn jump() {
         let src: &str = "
-			int x, y, z;
-			x = 3;
-			y = 5;
-			z = &y + 8;
-			return *z;
+		int main() {
+			int i = 0, x = 0;
+			while (1) {
+				if (i++ > 10) break;
+				else continue;
+			}
+			do x++; while (x < 10);
+			switch (x) {
+			case 10:
+				for (;;) {
+					switch (i) {
+					default:
+						break;
+					}
+					continue;
+				}
+			case 1 + 2:
+				x = 3;
+			default:
+				goto out;
+			}
+		out:
+		again:
+			if (x--) goto again;
+			return x;
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn addr_deref2() {
+    fn storage() {
         let src: &str = "
-			int x;
-			int *y;
-			int **z;
-			x = 3;
-			y = &x;
-			z = &y;
-			return *&**z;
+		int x;
+		extern int x;
+		int arr[];
+		int arr[4];
+		static int y = 1;
+		extern int y;
+		static int f(int a);
+		int f(int a) { return a; }
+		int main() {
+			static int count = 0, buf[3];
+			extern int x;
+			count++;
+			return x + y + f(count) + buf[0];
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn comma() {
+    fn qualifier() {
         let src: &str = "
-			int x, y, z;
-			x = 3, y = 4, z = 10;
+		const int table[2] = {1, 2};
+		int count(const char *const s, volatile int n);
+		int main() {
+			const char *s = \"abc\";
+			volatile int v = sizeof(const int);
+			int const *const p = table;
+			s++;
+			return *p + v;
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn assign_op() {
+    fn variadic() {
         let src: &str = "
-			int x;
-			x = 10;
-			x += 1;
-			x <<= 1;
+		int vprintf(char *fmt, va_list ap);
+		int sum(int n, ...) {
+			va_list ap, aq;
+			int s = 0;
+			va_start(ap, n);
+			va_copy(aq, ap);
+			while (n--) s += va_arg(ap, int);
+			vprintf(va_arg(aq, char *), ap);
+			s += __builtin_va_arg(aq, char *)[0];
+			va_end(aq);
+			va_end(ap);
+			return s;
+		}
+		int main() {
+			return sum(3, 1, 2, 3);
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn sizeof() {
+    fn inline() {
         let src: &str = "
-			int x, y, z;
-			x = 0; y = 0; z = 0;
-			int *p; p = &x;
-			int *pp; pp = &p;
-
-			sizeof(int);
-			sizeof(int **);
-			sizeof(0);
-			sizeof(x);
-			sizeof x;
-			sizeof ++x;
-			sizeof ++p;
-			sizeof(x+y);
-			sizeof x + y * z;
-			sizeof(x && x);
+		static inline int sq(int x) { return x * x; }
+		static inline int unused(int x) { return sq(x); }
+		inline int twice(int x) { return x * 2; }
+		inline int external(int x) { return x; }
+		extern int external(int x);
+		int main() {
+			return sq(2) + twice(3) + external(4);
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn array() {
+    fn prototype() {
         let src: &str = "
-			int x[20];
-			int y[10][20];
-			int *p[10][20][30];
-			int *q;
-			int z;
-			sizeof(*y);
-			sizeof(x);
-			x - q;
-			x + 10;
-			y - &q;
-			**p - y;
-			&p - z;
-			****p;
-			*****&p;
+		char first(char *s, char c);
+		int sum(int n, ...);
+		int twice();
+		int twice(int x) { return x * 2; }
+		int main() {
+			char c = first(\"abc\", 300);
+			sum(2, c, 'a');
+			twice(c);
+			extern long big(void);
+			long v = big();
+			int any(), w = 2;
+			any(1, c);
+			return implicit(c);
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
-        let node_heads = parse_stmts(&mut token_ptr);
+        let node_heads = program(&mut token_ptr);
         let mut count: usize = 1;
         for node_ptr in node_heads {
-            println!("stmt{} {}", count, ">".to_string().repeat(REP));
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
             search_tree(&node_ptr);
             count += 1;
         }
     }
 
     #[test]
-    fn declare() {
+    fn integer_types() {
         let src: &str = "
-			int func(int x, int y) {
-				return x + y;
-			}
-			int calc(int a, int b, int c, int d, int e, int f) {
-				return a*b + c - d + e/f;
-			}
-			int main() {
-				int i, sum;
-				i = 0;
-				sum = 0;
-				for (; i < 10; i=i+1) {
-					sum = sum + i;
-				}
-				return func(i, sum);
-			}
+		unsigned long long int big;
+		short int half(short s, unsigned char c);
+		int main() {
+			unsigned u = -1;
+			long signed l = u;
+			char c = 1;
+			return (u > c) + (l >> 1) + ~c + sizeof(l) + half(c, c);
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -2631,22 +7651,21 @@ pub mod tests {
     }
 
     #[test]
-    fn no_return() {
+    fn redeclaration() {
         let src: &str = "
-			int func(int x, int y) {
-				return x + y;
-			}
-			int main() {
-				int i, sum, x, y, z;
-				i = 0;
-				sum = 0;
-				for (; i < 10; i=i+1) {
-					sum = sum + i;
-				}
-				func(x=1, (y=1, z=1));
-			}
+		int buf[];
+		int first(const int *p, int n);
+		int buf[8];
+		int first(const int *q, const int m) {
+			return *q + m;
+		}
+		int main() {
+			int *p = 0;
+			const int *cp = buf;
+			return first(buf, sizeof(buf)) + (cp - p);
+		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -2659,15 +7678,23 @@ pub mod tests {
     }
 
     #[test]
-    fn array_access() {
+    fn alignment() {
         let src: &str = "
+		_Alignas(16) char buf[3];
 		int main() {
-			int X[10][10][10];
-			X[0][0][0] = 10;
-			return ***X;
+			char c;
+			_Alignas(long) short s;
+			{
+				int x;
+				char y;
+			}
+			{
+				long z;
+			}
+			return _Alignof(int[4]) + _Alignof s + sizeof(_Alignof(buf));
 		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -2680,22 +7707,27 @@ pub mod tests {
     }
 
     #[test]
-    fn gvar() {
+    fn struct_union() {
         let src: &str = "
-		int x;
-		int X[0][0][0];
-		int func(int x, int y) {
-			return x + y;
-		}
-		int main() {
-			X;
-			int X[10][10][10];
-			X[0][0][0] = 10;
-			func(1, 3) + 1;
-			return ***X;
-		}
+			typedef struct point { int x, y; } Point;
+			typedef union { long l; char c[3]; } U;
+			struct list { struct list *next; int val; };
+			struct flex { int n; int data[]; };
+			int main() {
+				Point p, *pp = &p;
+				U u;
+				struct list l1, l2;
+				l1.next = &l2;
+				p.x = 1;
+				pp->y = 2;
+				u.l = 3;
+				struct { int a; union { int b; char c; }; } anon;
+				anon.b = 4;
+				l2 = l1;
+				return sizeof(Point) + sizeof(U) + sizeof(struct flex) + p.x + pp->y + u.c[0] + l1.next->val + anon.c;
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -2708,16 +7740,24 @@ pub mod tests {
     }
 
     #[test]
-    fn literal() {
+    fn gnu_extensions() {
         let src: &str = "
-		int main() {
-			char *c = \"aaaa\";
-			\"bbbb\";
-			*c = 60;
-			return 0;
-		}
+			# 1 \"header.h\" 1 3 4
+			typedef __builtin_va_list __gnuc_va_list;
+			extern int printf (const char *__restrict __format, ...) __attribute__ ((__nonnull__ (1)));
+			extern int renamed (void) __asm__ (\"\" \"real_name\") __attribute__ ((__nothrow__ , __leaf__));
+			__extension__ typedef long __quad_t;
+			static __inline __attribute__ ((__always_inline__)) int square (int x) { return x * x; }
+			void nothing(void) { return; }
+			int main() {
+				__quad_t q = __extension__ 3;
+				int *restrict p = (int *) 0;
+				__typeof__(p) p2 = p;
+				(void) p2;
+				return square((int) q) + renamed() + (char) 300;
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -2730,18 +7770,19 @@ pub mod tests {
     }
 
     #[test]
-    fn lvar_init() {
+    fn stmt_expr() {
         let src: &str = "
+			struct P { int x; int y; };
 			int main() {
-				int x = {4, 5};
-				int X[4][2][1] = {1, {2, 3}, x, 5, {6}, 7, 8, 9, };
-				char str[][2][2] = {{{{\"str\", }}}};
-				char str2[][2][3] = {\"s\", \"abcd\", \"pqrs\"};
-				char str3[] = {\"str\", };
-				// int str4[][2] = {\"str\"}; // invalid
+				int a = 3, b = 7;
+				int m = ({ typeof(a) _a = a; __typeof__(b) _b = b; int r; if (_a < _b) r = _b; else r = _a; r; });
+				struct P p = ({ struct P q; q.x = 1; q.y = 2; q; });
+				char *s = ({ char buf[] = \"str\"; buf; });
+				({ int unused = 0; });
+				return m + ({ p; }).y + ({ int z = ({ 2; }); z * z; });
 			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -2754,20 +7795,22 @@ pub mod tests {
     }
 
     #[test]
-    fn gvar_init() {
+    fn inline_asm() {
         let src: &str = "
-			int x = {4+10, 5};
-			int y = 10;
-			int *p = &x - &x + &y; // valid
-			int *q = &y + 10; // valid
-			// int *r = &x - &y; // invalid
-			int z[][2][10] = {{1, 4, 5, {10, x}}, 2, 3, 4, 2}; // valid
-			// int w[][2][10] = {{1, 4, 5, {10, xx}}, 2, 3, 4, 2}; // invalid
-			char c[10][1000] = {\"abcd\", \"str\", {\'c\'}}, d[] = \"compiler\";
-			int (*X)(int, int*(*)()) = 10;
-			int (*Y)[] = 10;
+			asm(\".globl f\\nf:\\n\\tret\");
+			int x __asm__(\"renamed_x\");
+			int main() {
+				int a = 1, b;
+				unsigned lo, hi;
+				asm volatile(\"rdtsc\" : \"=a\"(lo), \"=d\"(hi));
+				__asm__ __volatile__(\"movl %1, %0\" : \"=r\"(b) : \"r\"(a) : \"cc\", \"%rbx\");
+				asm(\"addl %[v], %[acc]\" : [acc] \"+r\"(b) : [v] \"i\"(2 * 3));
+				asm(\"incl %0\" : \"+m\"(x) :: \"memory\");
+				asm(\"nop\");
+				return b;
+			}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -2780,22 +7823,23 @@ pub mod tests {
     }
 
     #[test]
-    fn scope() {
+    fn attributes() {
         let src: &str = "
-		int main() {
-			int a = {0};
-			int i = 10;
-			for (int i = 0; i < 10; i++) {
-				int i = 10;
-				a++;
+			void exit(int) __attribute__((__noreturn__, __nothrow__));
+			_Noreturn void die(void) { exit(1); }
+			struct __attribute__((packed)) P { char c; int i; };
+			struct Q { char c; short s __attribute__((aligned(8))); } __attribute__((aligned(16)));
+			__attribute__((weak)) int wv = 1;
+			extern int undef __attribute__((weak));
+			int sec __attribute__((section(\".mydata\"))) = 2;
+			static int __attribute__((unused)) quiet(void) { return 0; }
+			static inline int __attribute__((used)) kept(void) { return 0; }
+			int pick(int x) {
+				if (x) return sizeof(struct P) + sizeof(struct Q);
+				die();
 			}
-			print_helper(i);
-			print_helper(a);
-
-			return 0;
-		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -2855,7 +7899,95 @@ pub mod tests {
 			return MEMO[N-1] = fib(N-1) + fib(N-2);
 		}
 		";
-        test_init(src);
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = program(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn thread_local() {
+        let src: &str = "
+			__thread int a;
+			_Thread_local long b = 5;
+			extern __thread int c;
+			static _Thread_local char buf[16];
+			int *f(void) {
+				static __thread int s = 3;
+				extern _Thread_local int d;
+				return &s + d;
+			}
+			int g(void) { return a + b + c + buf[1]; }
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = program(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn atomic() {
+        let src: &str = "
+			_Atomic long counter;
+			_Atomic(int *) head;
+			int *_Atomic tail;
+			int main() {
+				int x = 0, e = 0;
+				counter++;
+				counter += 2;
+				head = &x;
+				tail += 1;
+				__atomic_store_n(&x, 1, 5);
+				__atomic_compare_exchange_n(&x, &e, 2, 0, 5, 5);
+				__sync_synchronize();
+				x += __atomic_load_n(&head, 5)[0];
+				return __atomic_load_n(&x, 5) + __atomic_fetch_add(&x, 3, 5);
+			}
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr = tokenize(0);
+        let node_heads = program(&mut token_ptr);
+        let mut count: usize = 1;
+        for node_ptr in node_heads {
+            println!("declare{}{}", count, ">".to_string().repeat(REP));
+            search_tree(&node_ptr);
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn builtins() {
+        let src: &str = "
+			struct S { char c; long l; int a[4]; };
+			int tab[__builtin_offsetof(struct S, a[2])];
+			int f(int x) {
+				if (x) return 1;
+				__builtin_unreachable();
+			}
+			int main() {
+				char buf[8];
+				unsigned u = 40;
+				if (__builtin_expect(u > 3, 1))
+					__builtin_memcpy(buf, \"builtin\", 8);
+				if (!__builtin_constant_p(3 * 4))
+					__builtin_trap();
+				return __builtin_popcount(u) + __builtin_clz(u) + __builtin_ctzl(u) + __builtin_bswap32(u) + __builtin_bswap16(u);
+			}
+		";
+        let _lock = test_init(src);
 
         let mut token_ptr = tokenize(0);
         let node_heads = program(&mut token_ptr);
@@ -0,0 +1,181 @@
+// C11 6.3.1 に従った算術型の変換(整数拡張と通常の算術型変換)
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use crate::{
+    node::{Node, NodeRef, Nodekind},
+    typecell::{Type, TypeCell},
+};
+
+/// 整数変換の順位(C11 6.3.1.1): 大きさの異なる整数型の間では大きい方が高い
+fn rank(typ: &TypeCell) -> usize {
+    match typ.typ {
+        Type::Char => 1,
+        Type::Short => 2,
+        Type::Int => 3,
+        Type::Long => 4,
+        _ => panic!("cannot rank non-integer type: {}", typ),
+    }
+}
+
+/// 値として扱う場合は修飾子を外した型になる
+fn unqualified(typ: &TypeCell) -> TypeCell {
+    let mut typ = typ.clone();
+    typ.is_const = false;
+    typ.is_volatile = false;
+    typ.is_atomic = false;
+    typ
+}
+
+/// 整数拡張(C11 6.3.1.1): int より順位の低い整数型は int に変換する
+/// char, short の値は unsigned であっても全て int で表現できるので、 unsigned int になることはない
+pub fn integer_promotion(typ: &TypeCell) -> TypeCell {
+    if typ.is_integer() && rank(typ) < rank(&TypeCell::new(Type::Int)) {
+        TypeCell::new(Type::Int)
+    } else {
+        unqualified(typ)
+    }
+}
+
+/// 通常の算術型変換(C11 6.3.1.8): 二項演算子の両辺をこの型に揃えてから計算する
+/// ポインタが含まれる場合は(配列であっても)そのポインタの型に揃える
+pub fn usual_arith_conversion(left: &TypeCell, right: &TypeCell) -> TypeCell {
+    if let Some(typ) = &left.ptr_to {
+        return typ.borrow().make_ptr_to();
+    } else if let Some(typ) = &right.ptr_to {
+        return typ.borrow().make_ptr_to();
+    }
+
+    // 整数拡張後は int 以上の型しか残らないため、順位の高い方は大きさも大きく、低い方の値を全て表現できる
+    // 順位が等しい場合は unsigned の方に揃える
+    let left = integer_promotion(left);
+    let right = integer_promotion(right);
+    match rank(&left).cmp(&rank(&right)) {
+        Ordering::Greater => left,
+        Ordering::Less => right,
+        Ordering::Equal => {
+            if right.is_unsigned {
+                right
+            } else {
+                left
+            }
+        }
+    }
+}
+
+/// 式を型 typ に変換する Cast ノードを作る
+pub fn cast_to(expr: &NodeRef, typ: TypeCell) -> NodeRef {
+    let token = expr.borrow().token.clone();
+    Rc::new(RefCell::new(Node {
+        kind: Nodekind::Cast,
+        token,
+        typ: Some(typ),
+        left: Some(Rc::clone(expr)),
+        ..Default::default()
+    }))
+}
+
+/// 式を型 typ に変換する: 既に同じ整数型であればキャストは挟まない
+pub fn convert(expr: &NodeRef, typ: &TypeCell) -> NodeRef {
+    let from = expr.borrow().typ.clone().unwrap();
+    if from.is_integer() && from.typ == typ.typ && from.is_unsigned == typ.is_unsigned {
+        Rc::clone(expr)
+    } else {
+        cast_to(expr, typ.clone())
+    }
+}
+
+/// 式に整数拡張を適用する
+pub fn promote(expr: &NodeRef) -> NodeRef {
+    let typ = integer_promotion(expr.borrow().typ.as_ref().unwrap());
+    convert(expr, &typ)
+}
+
+/// 既定の実引数拡張(C11 6.5.2.2): 浮動小数点型はまだサポートしていないので、整数拡張のみを行う
+pub fn default_argument_promotion(expr: &NodeRef) -> NodeRef {
+    promote(expr)
+}
+
+/// 二項演算子の両辺に通常の算術型変換を適用し、変換後の型を返す
+pub fn arith_conversion(node: &mut Node) -> TypeCell {
+    let left = Rc::clone(node.left.as_ref().unwrap());
+    let right = Rc::clone(node.right.as_ref().unwrap());
+    let typ = usual_arith_conversion(
+        left.borrow().typ.as_ref().unwrap(),
+        right.borrow().typ.as_ref().unwrap(),
+    );
+    let _ = node.left.insert(convert(&left, &typ));
+    let _ = node.right.insert(convert(&right, &typ));
+    typ
+}
+
+/// シフト演算子は両辺を揃えず、それぞれに整数拡張を行う(C11 6.5.7): 結果は拡張後の左辺の型になる
+pub fn shift_conversion(node: &mut Node) -> TypeCell {
+    let left = promote(node.left.as_ref().unwrap());
+    let right = promote(node.right.as_ref().unwrap());
+    let typ = left.borrow().typ.clone().unwrap();
+    let _ = node.left.insert(left);
+    let _ = node.right.insert(right);
+    typ
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(typ: Type, is_unsigned: bool) -> TypeCell {
+        let mut cell = TypeCell::new(typ);
+        cell.is_unsigned = is_unsigned;
+        cell
+    }
+
+    #[test]
+    fn promotion_test() {
+        for typ in [Type::Char, Type::Short] {
+            for is_unsigned in [false, true] {
+                assert_eq!(
+                    format!("{}", integer_promotion(&integer(typ, is_unsigned))),
+                    "int"
+                );
+            }
+        }
+        assert_eq!(
+            format!("{}", integer_promotion(&integer(Type::Int, true))),
+            "unsigned int"
+        );
+
+        let mut c = integer(Type::Long, false);
+        c.is_const = true;
+        assert_eq!(format!("{}", integer_promotion(&c)), "long");
+    }
+
+    #[test]
+    fn conversion_test() {
+        let cases = [
+            ((Type::Char, false), (Type::Char, true), "int"),
+            ((Type::Short, true), (Type::Int, false), "int"),
+            ((Type::Int, false), (Type::Int, true), "unsigned int"),
+            ((Type::Int, true), (Type::Long, false), "long"),
+            ((Type::Long, true), (Type::Int, false), "unsigned long"),
+            ((Type::Long, false), (Type::Long, true), "unsigned long"),
+        ];
+        for ((l, l_unsigned), (r, r_unsigned), expected) in cases {
+            let (left, right) = (integer(l, l_unsigned), integer(r, r_unsigned));
+            assert_eq!(
+                format!("{}", usual_arith_conversion(&left, &right)),
+                expected
+            );
+            assert_eq!(
+                format!("{}", usual_arith_conversion(&right, &left)),
+                expected
+            );
+        }
+
+        let ptr = TypeCell::new(Type::Short).make_array_of(3);
+        let typ = usual_arith_conversion(&ptr, &integer(Type::Long, true));
+        assert_eq!(format!("{}", typ), "short*");
+        let typ = usual_arith_conversion(&TypeCell::new(Type::Int), &ptr);
+        assert_eq!(format!("{}", typ), "short*");
+    }
+}
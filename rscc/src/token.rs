@@ -3,7 +3,7 @@ use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 
-use crate::{exit_eprintln, utils::error_at};
+use crate::diagnostic::{abort, report, Diagnostic, Span};
 
 pub type TokenRef = Rc<RefCell<Token>>;
 
@@ -138,6 +138,17 @@ impl Token {
             } // Default を new で生成させない
         }
     }
+
+    /// トークンのソースコード上の範囲
+    pub fn span(&self) -> Span {
+        // token.line_offset は token.len 以上であるはずなので負になる可能性をチェックしない
+        Span::new(
+            self.file_num,
+            self.line_num,
+            self.line_offset - self.len,
+            self.len,
+        )
+    }
 }
 
 impl Display for Token {
@@ -175,39 +186,76 @@ pub fn token_ptr_exceed(token_ptr: &mut TokenRef) {
     let tmp_ptr = if let Some(ptr) = token_ptr.borrow().next.as_ref() {
         Rc::clone(ptr)
     } else {
-        exit_eprintln!(
-            "次のポインタを読めません。(現在のポインタのkind:{:?})",
-            token_ptr.borrow().kind
-        );
+        abort(Diagnostic::error(
+            "unexpected-eof",
+            format!(
+                "次のポインタを読めません。(現在のポインタのkind:{:?})",
+                token_ptr.borrow().kind
+            ),
+        ));
     };
     *token_ptr = tmp_ptr;
 }
 
 /// エラーメッセージ送出時に println! 等と同様の可変長引数を実現するためのマクロ
+/// 第一引数はエラーの分類(発生箇所ごとに決めた、安定した名前)
 #[macro_export]
 macro_rules! error_with_token {
-	($fmt: expr, $tok: expr) => (
+	($code: expr, $fmt: expr, $tok: expr) => (
 		use $crate::token::error_tok;
-		error_tok($fmt, $tok);
+		error_tok($code, $fmt, $tok);
 	);
 
-	($fmt: expr, $tok: expr, $($arg: tt)*) => (
+	($code: expr, $fmt: expr, $tok: expr, $($arg: tt)*) => (
 		use $crate::token::error_tok;
-		error_tok(format!($fmt, $($arg)*).as_str(), $tok);
+		error_tok($code, format!($fmt, $($arg)*).as_str(), $tok);
+	);
+}
+
+/// 以前の宣言などの位置を添えてエラーを送出するマクロ(引数は error_with_token! と同様)
+#[macro_export]
+macro_rules! error_with_prev {
+	($code: expr, $fmt: expr, $tok: expr, $prev: expr) => (
+		$crate::token::error_tok_with_prev($code, $fmt, $tok, $prev);
+	);
+
+	($code: expr, $fmt: expr, $tok: expr, $prev: expr, $($arg: tt)*) => (
+		$crate::token::error_tok_with_prev($code, format!($fmt, $($arg)*).as_str(), $tok, $prev);
+	);
+}
+
+/// 警告送出時に println! 等と同様の可変長引数を実現するためのマクロ
+/// 第一引数は警告の分類(gcc の -W オプションの名前に倣う)
+#[macro_export]
+macro_rules! warning_with_token {
+	($code: expr, $fmt: expr, $tok: expr) => (
+		$crate::token::warning_tok($code, $fmt, $tok);
+	);
+
+	($code: expr, $fmt: expr, $tok: expr, $($arg: tt)*) => (
+		$crate::token::warning_tok($code, format!($fmt, $($arg)*).as_str(), $tok);
 	);
 }
 
 /// エラー送出のためのラッパー
-pub fn error_tok(msg: &str, token: &Token) -> ! {
-    // token.line_offset は token.len 以上であるはずなので負になる可能性をチェックしない
-    error_at(
-        msg,
-        token.file_num,
-        token.line_num,
-        token.line_offset - token.len,
+pub fn error_tok(code: &'static str, msg: &str, token: &Token) -> ! {
+    abort(Diagnostic::error(code, msg).with_span(token.span()));
+}
+
+/// 以前の宣言などの位置 prev を添えてエラーを送出する
+pub fn error_tok_with_prev(code: &'static str, msg: &str, token: &Token, prev: &Token) -> ! {
+    abort(
+        Diagnostic::error(code, msg)
+            .with_span(token.span())
+            .with_label(prev.span(), "最初に現れた位置はここです。"),
     );
 }
 
+/// 警告送出のためのラッパー
+pub fn warning_tok(code: &'static str, msg: &str, token: &Token) {
+    report(Diagnostic::warning(code, msg).with_span(token.span()));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
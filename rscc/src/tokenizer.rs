@@ -1,17 +1,15 @@
 // トークナイザ
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::iter::FromIterator;
 use std::rc::Rc;
-use std::sync::Mutex;
 
 use once_cell::sync::Lazy;
 
 use crate::{
+    diagnostic::{abort, Diagnostic, Span},
     error_with_token,
-    globals::SRC,
+    globals::{Mutex, SRC},
     token::{token_ptr_exceed, Token, TokenRef, Tokenkind},
-    typecell::{Type, TypeCell},
     utils::{error_at, is_digit, strtol},
 };
 
@@ -20,7 +18,7 @@ pub fn tokenize(file_num: usize) -> TokenRef {
     // Rcを使って読み進める
     let mut token_ptr: TokenRef = Rc::new(RefCell::new(Token::new(Tokenkind::Head, "", 0, 0, 0)));
     let mut token_head_ptr: TokenRef = Rc::clone(&token_ptr);
-    let mut err_profile: (bool, usize, usize, &str) = (false, 0, 0, "");
+    let mut err_profile: (bool, usize, usize, &str, &str) = (false, 0, 0, "", "");
     // error_at を使うタイミングで SRC のロックが外れているようにスコープを調整
     {
         let code = &mut SRC.try_lock().unwrap()[file_num];
@@ -32,6 +30,11 @@ pub fn tokenize(file_num: usize) -> TokenRef {
             let string: Vec<char> = string.as_str().chars().collect::<Vec<char>>();
             let len: usize = string.len(); // Vec<char> にしてから len() を呼ぶことで、複数バイト文字も正しく1文字ずつ扱える
 
+            // プリプロセッサの出力に含まれる行マーカ(# 1 "file")などの '#' で始まる行は読み飛ばす
+            if !is_block_comment && string.iter().find(|c| !c.is_whitespace()) == Some(&'#') {
+                continue;
+            }
+
             while lookat < len {
                 // 余白をまとめて飛ばす。streamを最後まで読んだならbreakする。
                 match skipspace(&string, &mut lookat, len) {
@@ -88,7 +91,19 @@ pub fn tokenize(file_num: usize) -> TokenRef {
                 // 数字ならば、数字が終わるまでを読んでトークンを生成
                 c = string[lookat];
                 if is_digit(&c) {
+                    let num_offset = lookat;
                     let num = strtol(&string, &mut lookat);
+                    // 整数リテラルは今のところ int 型のみを扱う
+                    if num > i32::MAX as u64 {
+                        err_profile = (
+                            true,
+                            line_num,
+                            num_offset,
+                            "literal-out-of-range",
+                            "整数リテラルが int 型で表せる範囲を超えています。",
+                        );
+                        break;
+                    }
                     token_ptr.borrow_mut().next = Some(Rc::new(RefCell::new(Token::new(
                         Tokenkind::Num,
                         num.to_string(),
@@ -101,7 +116,7 @@ pub fn tokenize(file_num: usize) -> TokenRef {
                 }
 
                 // 英字とアンダーバーを先頭とする文字を識別子としてサポートする
-                if ('a'..='z').contains(&c) || ('A'..='Z').contains(&c) || c == '_' {
+                if c.is_ascii_alphabetic() || c == '_' {
                     let name = read_lvar(&string, &mut lookat);
 
                     // トークン列にIdentとして追加する必要がある
@@ -117,23 +132,21 @@ pub fn tokenize(file_num: usize) -> TokenRef {
                 }
 
                 // C ではソース上での文字列リテラルの改行は認められていないので、行ごとのループ内でリテラルを読む処理を完結させて良い
-                let line_offset = lookat; // 文字列の先頭を指すように　line_offset を押さえておく
+                let start = lookat; // 文字列・文字リテラルの先頭(引用符の位置)を押さえておく
                 match read_str_literal(&string, &mut lookat, len) {
                     Ok(literal) => {
                         if let Some(body) = literal {
-                            token_ptr.borrow_mut().next = Some(Rc::new(RefCell::new(Token::new(
-                                Tokenkind::String,
-                                body,
-                                file_num,
-                                line_num,
-                                line_offset,
-                            ))));
+                            let mut token =
+                                Token::new(Tokenkind::String, body, file_num, line_num, lookat);
+                            // エスケープを展開した長さではなく、引用符を含むソース上の長さを診断の範囲とする(文字リテラルも同様)
+                            token.len = lookat - start;
+                            token_ptr.borrow_mut().next = Some(Rc::new(RefCell::new(token)));
                             token_ptr_exceed(&mut token_ptr);
                             continue;
                         }
                     }
                     Err(msg) => {
-                        err_profile = (true, line_num, lookat, msg);
+                        err_profile = (true, line_num, lookat, "invalid-literal", msg);
                         break;
                     }
                 }
@@ -141,24 +154,32 @@ pub fn tokenize(file_num: usize) -> TokenRef {
                 match read_char_literal(&string, &mut lookat, len) {
                     Ok(encoded) => {
                         if let Some(val) = encoded {
-                            token_ptr.borrow_mut().next = Some(Rc::new(RefCell::new(Token::new(
+                            let mut token = Token::new(
                                 Tokenkind::Num,
                                 val.to_string(),
                                 file_num,
                                 line_num,
-                                line_offset,
-                            ))));
+                                lookat,
+                            );
+                            token.len = lookat - start;
+                            token_ptr.borrow_mut().next = Some(Rc::new(RefCell::new(token)));
                             token_ptr_exceed(&mut token_ptr);
                             continue;
                         }
                     }
                     Err(msg) => {
-                        err_profile = (true, line_num, lookat, msg);
+                        err_profile = (true, line_num, lookat, "invalid-literal", msg);
                         break;
                     }
                 }
 
-                err_profile = (true, line_num, lookat, "トークナイズできません");
+                err_profile = (
+                    true,
+                    line_num,
+                    lookat,
+                    "invalid-token",
+                    "トークナイズできません",
+                );
                 break;
             }
             if err_profile.0 {
@@ -168,7 +189,13 @@ pub fn tokenize(file_num: usize) -> TokenRef {
     }
 
     if err_profile.0 {
-        error_at(err_profile.3, file_num, err_profile.1, err_profile.2);
+        error_at(
+            err_profile.3,
+            err_profile.4,
+            file_num,
+            err_profile.1,
+            err_profile.2,
+        );
     }
 
     token_ptr.borrow_mut().next = Some(Rc::new(RefCell::new(Token::new(
@@ -184,33 +211,129 @@ pub fn tokenize(file_num: usize) -> TokenRef {
 }
 
 /* ------------------------------------------------- トークナイズ用関数 ------------------------------------------------- */
-static QUAD_KEYWORDS: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| Mutex::new(vec!["else", "char"]));
-
-static TRI_OPS: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| Mutex::new(vec!["<<=", ">>="]));
+// return は Tokenkind::Return として別で扱う
+static KEYWORDS: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| {
+    Mutex::new(vec![
+        "sizeof",
+        "while",
+        "else",
+        "char",
+        "short",
+        "for",
+        "int",
+        "long",
+        "signed",
+        "unsigned",
+        "if",
+        "do",
+        "break",
+        "continue",
+        "goto",
+        "switch",
+        "case",
+        "default",
+        "static",
+        "extern",
+        "const",
+        "volatile",
+        "inline",
+        "va_start",
+        "va_arg",
+        "va_end",
+        "va_copy",
+        "_Alignof",
+        "_Alignas",
+        "__builtin_alloca",
+        "_Static_assert",
+        "_Generic",
+        "_Noreturn",
+        "_Thread_local",
+        "__thread",
+        "__func__",
+        "void",
+        "float",
+        "double",
+        "typedef",
+        "struct",
+        "union",
+        "restrict",
+        "__restrict",
+        "__restrict__",
+        "__inline",
+        "__inline__",
+        "__extension__",
+        "__attribute__",
+        "__asm__",
+        "__asm",
+        "asm",
+        "__volatile__",
+        "__volatile",
+        "typeof",
+        "__typeof__",
+        "__typeof",
+        "__builtin_va_list",
+        "__builtin_va_start",
+        "__builtin_va_arg",
+        "__builtin_va_end",
+        "__builtin_va_copy",
+        "_Atomic",
+        "__atomic_load_n",
+        "__atomic_store_n",
+        "__atomic_fetch_add",
+        "__atomic_compare_exchange_n",
+        "__sync_synchronize",
+        "__builtin_expect",
+        "__builtin_unreachable",
+        "__builtin_trap",
+        "__builtin_popcount",
+        "__builtin_popcountl",
+        "__builtin_popcountll",
+        "__builtin_clz",
+        "__builtin_clzl",
+        "__builtin_clzll",
+        "__builtin_ctz",
+        "__builtin_ctzl",
+        "__builtin_ctzll",
+        "__builtin_bswap16",
+        "__builtin_bswap32",
+        "__builtin_bswap64",
+        "__builtin_offsetof",
+        "__builtin_memcpy",
+        "__builtin_constant_p",
+    ])
+});
 
-static TRI_KEYWORDS: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| Mutex::new(vec!["for", "int"]));
+static TRI_OPS: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| Mutex::new(vec!["<<=", ">>=", "..."]));
 
 static BI_OPS: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| {
     Mutex::new(vec![
         "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "++", "--", "+=", "-=", "*=", "/=", "%=",
-        "&=", "^=", "|=",
+        "&=", "^=", "|=", "->",
     ])
 });
 static UNI_RESERVED: Lazy<Mutex<Vec<char>>> = Lazy::new(|| {
     Mutex::new(vec![
         ';', ',', '(', ')', '{', '}', '[', ']', '+', '-', '*', '/', '%', '&', '|', '^', '!', '~',
-        '=', '<', '>',
+        '=', '<', '>', ':', '.',
     ])
 });
 
 static SPACES: Lazy<Mutex<Vec<char>>> = Lazy::new(|| Mutex::new(vec![' ', '\t', '\n']));
 
-// 現在は int のみサポート
-static TYPES: Lazy<Mutex<HashMap<String, Type>>> = Lazy::new(|| {
-    let mut map = HashMap::new();
-    let _ = map.insert(String::from("int"), Type::Int);
-    let _ = map.insert(String::from("char"), Type::Char);
-    Mutex::new(map)
+/// 型指定子: 組み合わせから型を決めるのはパーサ側で行う
+static TYPES: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| {
+    Mutex::new(vec![
+        "void",
+        "char",
+        "short",
+        "int",
+        "long",
+        "signed",
+        "unsigned",
+        "float",
+        "double",
+        "__builtin_va_list",
+    ])
 });
 
 // 空白を飛ばして読み進める
@@ -307,7 +430,7 @@ fn read(string: &[char], read: impl Into<String>, index: &mut usize, len: usize)
 
 // 識別子の一部として使用可能な文字であるかどうかを判別する
 fn canbe_ident_part(c: &char) -> bool {
-    ('a'..='z').contains(c) || ('A'..='Z').contains(c) || ('0'..='9').contains(c) || c == &'_'
+    c.is_ascii_alphanumeric() || c == &'_'
 }
 
 // 予約されたトークンの後に空白なしで連続して良い文字であるかどうかを判別する。
@@ -324,55 +447,32 @@ fn can_follow_reserved(string: &[char], index: usize) -> bool {
 
 // 予約されたトークンだった場合はSome(String)を返す
 fn is_reserved(string: &[char], index: &mut usize, len: usize) -> Option<String> {
-    // 先に複数文字の演算子かどうかチェックする(文字数の多い方から)
-    let lim = *index + 6;
-    if lim <= len {
-        let slice: String = String::from_iter(string[*index..lim].iter());
-        if slice == "sizeof" && can_follow_reserved(string, lim) {
-            *index = lim;
-            return Some(slice);
-        }
-    }
-
-    let lim = *index + 5;
-    if lim <= len {
-        let slice: String = String::from_iter(string[*index..lim].iter());
-        if slice == "while" && can_follow_reserved(string, lim) {
-            *index = lim;
-            return Some(slice);
-        }
-    }
-
-    let lim = *index + 4;
-    if lim <= len {
-        let slice: String = String::from_iter(string[*index..lim].iter());
-        if QUAD_KEYWORDS.try_lock().unwrap().contains(&slice.as_str())
+    // キーワードは後ろに識別子の一部となる文字が続かない場合のみ予約語とみなす
+    for keyword in KEYWORDS.try_lock().unwrap().iter() {
+        let lim = *index + keyword.len();
+        if lim <= len
+            && String::from_iter(string[*index..lim].iter()) == *keyword
             && can_follow_reserved(string, lim)
         {
             *index = lim;
-            return Some(slice);
+            return Some(keyword.to_string());
         }
     }
 
+    // 複数文字の演算子かどうかチェックする(文字数の多い方から)
     let lim = *index + 3;
     if lim <= len {
         let slice: String = String::from_iter(string[*index..lim].iter());
-        if TRI_OPS.try_lock().unwrap().contains(&slice.as_str())
-            || TRI_KEYWORDS.try_lock().unwrap().contains(&slice.as_str())
-                && can_follow_reserved(string, lim)
-        {
+        if TRI_OPS.try_lock().unwrap().contains(&slice.as_str()) {
             *index = lim;
             return Some(slice);
         }
     }
 
-    // 2文字演算子とif
     let lim = *index + 2;
     if lim <= len {
-        let slice: String = String::from_iter(string[*index..(*index + 2)].iter());
-        if BI_OPS.try_lock().unwrap().contains(&slice.as_str())
-            || (slice == "if" && can_follow_reserved(string, lim))
-        {
+        let slice: String = String::from_iter(string[*index..lim].iter());
+        if BI_OPS.try_lock().unwrap().contains(&slice.as_str()) {
             *index = lim;
             return Some(slice);
         }
@@ -448,11 +548,24 @@ pub fn consume(token_ptr: &mut TokenRef, op: &str) -> bool {
 #[inline]
 pub fn expect(token_ptr: &mut TokenRef, op: &str) {
     if !consume(token_ptr, op) {
-        error_with_token!(
-            "\"{}\"を期待した位置で予約されていないトークン\"{}\"が発見されました。",
-            &*token_ptr.borrow(),
-            op,
-            token_ptr.borrow().body.as_ref().unwrap()
+        // 期待したトークンをこの位置に挿入する修正候補を添える
+        let token = token_ptr.borrow();
+        let span = token.span();
+        let insert_at = Span {
+            len: 0,
+            ..span.clone()
+        };
+        abort(
+            Diagnostic::error(
+                "expected-token",
+                format!(
+                    "\"{}\"を期待した位置で予約されていないトークン\"{}\"が発見されました。",
+                    op,
+                    token.body.as_ref().unwrap()
+                ),
+            )
+            .with_span(span)
+            .with_fixit(insert_at, op),
         );
     }
 }
@@ -479,6 +592,7 @@ pub fn expect_number(token_ptr: &mut TokenRef) -> i32 {
         val
     } else {
         error_with_token!(
+            "expected-token",
             "数字であるべき位置で数字以外の文字\"{}\"が発見されました。",
             &*token_ptr.borrow(),
             token_ptr.borrow().body.as_ref().unwrap()
@@ -508,6 +622,7 @@ pub fn expect_ident(token_ptr: &mut TokenRef) -> String {
         body
     } else {
         error_with_token!(
+            "expected-token",
             "識別子を期待した位置で\"{}\"が発見されました。",
             &*token_ptr.borrow(),
             token_ptr.borrow().body.as_ref().unwrap()
@@ -521,33 +636,21 @@ pub fn is_type(token_ptr: &mut TokenRef) -> bool {
         && TYPES
             .try_lock()
             .unwrap()
-            .contains_key(token_ptr.borrow().body.as_ref().unwrap())
+            .contains(&token_ptr.borrow().body.as_ref().unwrap().as_str())
 }
 
+/// 型指定子であれば読み進め、その文字列を返す
 #[inline]
-pub fn consume_type(token_ptr: &mut TokenRef) -> Option<TypeCell> {
+pub fn consume_type_spec(token_ptr: &mut TokenRef) -> Option<String> {
     if is_type(token_ptr) {
-        let base: Type = *TYPES
-            .try_lock()
-            .unwrap()
-            .get(token_ptr.borrow().body.as_ref().unwrap())
-            .unwrap();
+        let spec = token_ptr.borrow().body.clone().unwrap();
         token_ptr_exceed(token_ptr);
-        Some(TypeCell::new(base))
+        Some(spec)
     } else {
         None
     }
 }
 
-#[inline]
-pub fn expect_type(token_ptr: &mut TokenRef) -> TypeCell {
-    if let Some(typ) = consume_type(token_ptr) {
-        typ
-    } else {
-        error_with_token!("型の指定が必要です。", &*token_ptr.borrow());
-    }
-}
-
 #[inline]
 pub fn is_kind(token_ptr: &mut TokenRef, kind: Tokenkind) -> bool {
     token_ptr.borrow().kind == kind
@@ -580,6 +683,7 @@ pub fn expect_literal(token_ptr: &mut TokenRef) -> String {
         literal
     } else {
         error_with_token!(
+            "expected-token",
             "文字列リテラルを期待した位置で予約されていないトークン\"{}\"が発見されました。",
             &*token_ptr.borrow(),
             token_ptr.borrow().body.as_ref().unwrap()
@@ -595,15 +699,7 @@ pub fn at_eof(token_ptr: &TokenRef) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::globals::{FILE_NAMES, SRC};
-
-    fn test_init(src: &str) {
-        let mut src_: Vec<String> = src.split('\n').map(|s| s.to_string() + "\n").collect();
-        FILE_NAMES.try_lock().unwrap().push("test".to_string());
-        let mut code = vec!["".to_string()];
-        code.append(&mut src_);
-        SRC.try_lock().unwrap().push(code);
-    }
+    use crate::globals::tests::test_init;
 
     #[test]
     fn lvar() {
@@ -617,7 +713,7 @@ mod tests {
 			local = (100 + 30 / 5 - 99) * (local > local);
 			LOCAL + local*local + (LOCAL + local_)* local_1 + oops;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -638,7 +734,7 @@ mod tests {
 			_return = 0;
 			return 11;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -661,7 +757,7 @@ mod tests {
 			return8 = 10;
 			return return8;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -681,7 +777,7 @@ mod tests {
 			{i = i + 1;}
 			return 10;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -701,7 +797,7 @@ mod tests {
 			z = &y;
 			return *&**z;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -724,7 +820,7 @@ mod tests {
 			p = !x;
 			return ~z;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -747,7 +843,7 @@ mod tests {
 			p = !x;
 			return ~z;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -778,7 +874,7 @@ mod tests {
 			++x;
 			--x;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -797,7 +893,7 @@ mod tests {
 			sizeof 1;
 			sizeof(x+y);
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -814,7 +910,7 @@ mod tests {
 			int x[20];
 			x[5] = 20;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -831,7 +927,7 @@ mod tests {
 			char c;
 			char c[10];
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -848,7 +944,7 @@ mod tests {
 			char *c =
 			\"This is a test of string literal\";
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -865,7 +961,7 @@ mod tests {
 			char c = \'a\';
 			int x = \'ああ\'
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
@@ -876,6 +972,24 @@ mod tests {
         println!("{}", token_ptr.borrow());
     }
 
+    #[test]
+    fn linemarker() {
+        let src: &str = "
+			# 1 \"test.c\"
+			int a;
+			  # 2 \"/usr/include/stdio.h\" 1 3 4
+			/* # 3 */ int b;
+		";
+        let _lock = test_init(src);
+
+        let mut token_ptr: TokenRef = tokenize(0);
+        while token_ptr.borrow().kind != Tokenkind::Eof {
+            println!("{}", token_ptr.borrow());
+            assert_ne!(token_ptr.borrow().body.as_deref(), Some("#"));
+            token_ptr_exceed(&mut token_ptr);
+        }
+    }
+
     #[test]
     fn comment() {
         let src: &str = "
@@ -887,7 +1001,7 @@ mod tests {
 			a = 100/*
 			*// 5;
 		";
-        test_init(src);
+        let _lock = test_init(src);
 
         let mut token_ptr: TokenRef = tokenize(0);
         while token_ptr.borrow().kind != Tokenkind::Eof {
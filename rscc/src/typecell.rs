@@ -8,11 +8,19 @@ pub type TypeCellRef = Rc<RefCell<TypeCell>>;
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Type {
     Invalid, // デフォルトや無名ノードに割り当てる
-    Int,
     Char,
+    Short,
+    Int,
+    Long,
     Ptr,
     Func,
     Array,
+    Void,
+    Float,
+    Double,
+    LDouble,
+    Struct,
+    Union,
 }
 
 impl Type {
@@ -21,10 +29,17 @@ impl Type {
         match self {
             Type::Invalid => panic!("cannot extract size of invalid type."),
             Type::Char => 1,
+            Type::Short => 2,
             Type::Int => 4,
-            Type::Ptr => 8,
+            Type::Long | Type::Ptr => 8,
             Type::Array => panic!("cannot infer size of array from only itself"),
             Type::Func => panic!("access to the size of function should not be implemented yet"),
+            // GNU 拡張と同様に、 void へのポインタ演算では大きさを1とみなす
+            Type::Void => 1,
+            Type::Float => 4,
+            Type::Double => 8,
+            Type::LDouble => 16,
+            Type::Struct | Type::Union => panic!("size of struct depends on its members"),
         }
     }
 }
@@ -34,39 +49,144 @@ impl Display for Type {
         let s: &str = match self {
             Type::Invalid => "invalid",
             Type::Char => "char",
+            Type::Short => "short",
             Type::Int => "int",
+            Type::Long => "long",
             Type::Ptr => "pointer",
             Type::Array => "array",
             Type::Func => "function",
+            Type::Void => "void",
+            Type::Float => "float",
+            Type::Double => "double",
+            Type::LDouble => "long double",
+            Type::Struct => "struct",
+            Type::Union => "union",
         };
         write!(f, "{}", s)
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum RawType {
-    I8 = 0,
-    I32 = 1,
-    U64 = 2,
-}
-
 #[derive(Clone, Debug, Eq)] // PartialEq は別で実装
 pub struct TypeCell {
     pub typ: Type,
-    // ポインタの情報はいくつ繋がっているか及び終端の型で管理 (chains は int *...*p; の時の * の数 + 配列の次元)
-    // ポインタと配列を透過的に扱うために、配列の場合も ptr_to を持つ: ただし、ポインタの場合は ptr_end は Type::Ptr にならないが、配列の場合はあり得る
+    // 型は再帰的に表現する: ポインタは指す先の型を、配列は要素の型を ptr_to に持つ
+    // ポインタと配列を透過的に扱うために、配列の場合も ptr_to を持つ
     // 配列の場合は、加えて array_size を持つ
     pub ptr_to: Option<TypeCellRef>,
-    pub ptr_end: Option<Type>,
-    pub chains: usize,
     pub array_size: Option<usize>,
 
     // self.typ == Type::Func
     pub ret_typ: Option<TypeCellRef>,
     pub arg_typs: Option<Vec<TypeCellRef>>,
     pub is_abstract: bool,
+    pub is_variadic: bool,
+    // () で宣言された関数はプロトタイプを持たず、仮引数の個数や型は分からない
+    pub is_unprototyped: bool,
 
     pub is_unsigned: bool,
+
+    // 型修飾子: 配列の場合は要素の型が持つ
+    pub is_const: bool,
+    // volatile な値へのロード・ストアは、最適化を行う場合にも省略してはならない
+    pub is_volatile: bool,
+    // _Atomic な値へのストアや複合代入は、他のスレッドから不可分に見える命令で行う
+    pub is_atomic: bool,
+
+    // _Alignas で指定された、この型で宣言されたオブジェクトのアラインメント(型の同一性には影響しない)
+    pub align: Option<usize>,
+
+    // 可変長配列の場合、実行時に計算したバイト数を保持する隠しローカル変数の rbp からのオフセット(array_size は None になる)
+    pub vla_size: Option<usize>,
+
+    // 構造体・共用体のメンバ情報: 同じタグの型は同じ実体を共有し、前方宣言の後で定義された場合も同じ実体が完全型になる
+    pub struct_info: Option<StructInfoRef>,
+}
+
+pub type StructInfoRef = Rc<RefCell<StructInfo>>;
+
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name: String,
+    pub typ: TypeCell,
+    pub offset: usize,
+}
+
+// Debug, PartialEq は別で実装(メンバが自身へのポインタを持つ場合に再帰しないようにするため)
+pub struct StructInfo {
+    pub tag: Option<String>,
+    pub members: Vec<Member>,
+    pub bytes: usize,
+    pub align: usize,
+    pub is_complete: bool,
+}
+
+impl StructInfo {
+    // メンバの配置を決めて完全型にする: 構造体は宣言順にアラインメントを考慮して並べ、共用体は全てオフセット0に置く
+    // 名前のない構造体・共用体のメンバは、そのメンバを外側のメンバとして展開する
+    // is_packed であれば各メンバのアラインメントを1として詰めて並べ、 min_align があれば全体のアラインメントをそれ以上にする
+    pub fn complete(
+        &mut self,
+        members: Vec<(Option<String>, TypeCell)>,
+        is_union: bool,
+        is_packed: bool,
+        min_align: Option<usize>,
+    ) {
+        let mut end = 0;
+        let mut align = min_align.unwrap_or(1);
+        for (name, typ) in members {
+            let member_align = if is_packed { 1 } else { typ.align() };
+            let offset = if is_union {
+                0
+            } else {
+                align_to(end, member_align)
+            };
+            // フレキシブル配列メンバは大きさを持たない
+            let bytes = if typ.is_flex_array() { 0 } else { typ.bytes() };
+            end = end.max(offset + bytes);
+            align = align.max(member_align);
+            if let Some(name) = name {
+                self.members.push(Member { name, typ, offset });
+            } else {
+                let inner = typ.struct_info.as_ref().unwrap().borrow();
+                for member in &inner.members {
+                    self.members.push(Member {
+                        offset: offset + member.offset,
+                        ..member.clone()
+                    });
+                }
+            }
+        }
+        self.bytes = align_to(end, align);
+        self.align = align;
+        self.is_complete = true;
+    }
+}
+
+impl fmt::Debug for StructInfo {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "StructInfo {{ tag: {:?}, members: {}, bytes: {}, is_complete: {} }}",
+            self.tag,
+            self.members.len(),
+            self.bytes,
+            self.is_complete
+        )
+    }
+}
+
+// 構造体の型は実体が同じ場合のみ一致する
+impl PartialEq for StructInfo {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for StructInfo {}
+
+#[inline]
+fn align_to(n: usize, align: usize) -> usize {
+    n.div_ceil(align) * align
 }
 
 impl TypeCell {
@@ -79,6 +199,84 @@ impl TypeCell {
         }
     }
 
+    // size_t (sizeof の結果の型) は unsigned long
+    #[inline]
+    pub fn new_size_t() -> Self {
+        TypeCell {
+            is_unsigned: true,
+            ..TypeCell::new(Type::Long)
+        }
+    }
+
+    // System V ABI の va_list は {unsigned int gp_offset, fp_offset; void *overflow_arg_area, *reg_save_area} の要素数1の配列
+    // メンバには直接アクセスしないので、同じ大きさの char の配列として扱う(関数に渡す際にはポインタとなる点も同じ)
+    #[inline]
+    pub fn new_va_list() -> Self {
+        TypeCell::new(Type::Char).make_array_of(24)
+    }
+
+    // 前方宣言のみの場合は不完全型となる
+    #[inline]
+    pub fn new_struct(tag: Option<String>, is_union: bool) -> Self {
+        let info = StructInfo {
+            tag,
+            members: vec![],
+            bytes: 0,
+            align: 1,
+            is_complete: false,
+        };
+        TypeCell {
+            typ: if is_union { Type::Union } else { Type::Struct },
+            struct_info: Some(Rc::new(RefCell::new(info))),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn is_struct(&self) -> bool {
+        self.is_one_of(&[Type::Struct, Type::Union])
+    }
+
+    // 配列と構造体・共用体は値をレジスタに載せず、アドレスで扱う
+    #[inline]
+    pub fn is_aggregate(&self) -> bool {
+        self.is_array() || self.is_struct()
+    }
+
+    #[inline]
+    pub fn is_void(&self) -> bool {
+        self.typ == Type::Void
+    }
+
+    // スカラ型(条件式や算術・比較演算の対象となる型)かどうか
+    #[inline]
+    pub fn is_scalar(&self) -> bool {
+        self.is_integer() || self.is_pointer_like()
+    }
+
+    // 大きさの分からない型: void と定義の無い構造体・共用体、要素数の分からない配列(及びそれらの配列)
+    pub fn is_incomplete(&self) -> bool {
+        match self.typ {
+            Type::Void => true,
+            Type::Struct | Type::Union => !self.struct_info.as_ref().unwrap().borrow().is_complete,
+            Type::Array => {
+                self.is_flex_array() || self.ptr_to.as_ref().unwrap().borrow().is_incomplete()
+            }
+            _ => false,
+        }
+    }
+
+    // 構造体・共用体のメンバを名前で探す
+    pub fn member(&self, name: &str) -> Option<Member> {
+        self.struct_info
+            .as_ref()?
+            .borrow()
+            .members
+            .iter()
+            .find(|m| m.name == name)
+            .cloned()
+    }
+
     #[inline]
     pub fn is_array(&self) -> bool {
         self.typ == Type::Array
@@ -94,6 +292,18 @@ impl TypeCell {
         self.typ == Type::Ptr
     }
 
+    // 配列も(& と sizeof 以外では)ポインタとして扱われるので true になる
+    #[inline]
+    pub fn is_pointer_like(&self) -> bool {
+        self.is_one_of(&[Type::Ptr, Type::Array])
+    }
+
+    // 整数型(算術演算の対象となる型)かどうか
+    #[inline]
+    pub fn is_integer(&self) -> bool {
+        self.is_one_of(&[Type::Char, Type::Short, Type::Int, Type::Long])
+    }
+
     #[inline]
     pub fn is_one_of(&self, types: &[Type]) -> bool {
         types.contains(&self.typ)
@@ -102,37 +312,21 @@ impl TypeCell {
     #[inline]
     pub fn make_ptr_to(&self) -> Self {
         let ptr_to = Some(Rc::new(RefCell::new(self.clone())));
-        let ptr_end = Some(if let Some(end) = self.ptr_end {
-            end
-        } else {
-            self.typ
-        });
-        let chains = self.chains + 1;
         TypeCell {
             typ: Type::Ptr,
             ptr_to,
-            ptr_end,
-            chains,
+            is_unsigned: true,
             ..Default::default()
         }
     }
 
     // 配列は & と sizeof 以外に対してはポインタとして扱う
-    // なので、ポインタと同じく ptr_end と chains も持たせておく(chains = dim(array) + chains(element))
     #[inline]
     pub fn make_array_of(&self, size: usize) -> Self {
         let array_of = Some(Rc::new(RefCell::new(self.clone())));
-        let ptr_end = if self.typ == Type::Array {
-            self.ptr_end
-        } else {
-            Some(self.typ)
-        };
-        let chains = self.chains + 1;
         TypeCell {
             typ: Type::Array,
             ptr_to: array_of,
-            ptr_end,
-            chains,
             array_size: Some(size),
             ..Default::default()
         }
@@ -141,25 +335,36 @@ impl TypeCell {
     #[inline]
     pub fn make_flex_array_of(&self) -> Self {
         let array_of = Some(Rc::new(RefCell::new(self.clone())));
-        let ptr_end = if self.typ == Type::Array {
-            self.ptr_end
-        } else {
-            Some(self.typ)
-        };
-        let chains = self.chains + 1;
         TypeCell {
             typ: Type::Array,
             ptr_to: array_of,
-            ptr_end,
-            chains,
             array_size: None,
             ..Default::default()
         }
     }
 
+    // 要素数の大きさが実行時に決まる配列: サイズは offset の位置のローカル変数に格納される
+    #[inline]
+    pub fn make_vla_of(&self, offset: usize) -> Self {
+        TypeCell {
+            vla_size: Some(offset),
+            ..self.make_flex_array_of()
+        }
+    }
+
     #[inline]
     pub fn is_flex_array(&self) -> bool {
-        self.typ == Type::Array && self.array_size.is_none()
+        self.typ == Type::Array && self.array_size.is_none() && self.vla_size.is_none()
+    }
+
+    #[inline]
+    pub fn is_vla(&self) -> bool {
+        self.vla_size.is_some()
+    }
+
+    // 自身または(多次元配列の)要素が可変長配列であり、大きさが実行時にしか分からないかどうか
+    pub fn has_vla(&self) -> bool {
+        self.is_vla() || (self.is_array() && self.ptr_to.as_ref().unwrap().borrow().has_vla())
     }
 
     // 配列の次元と最小要素の型情報を取得
@@ -182,12 +387,213 @@ impl TypeCell {
         }
     }
 
+    // 浮動小数点型(ベクタレジスタで受け渡しされる型)かどうか: 型としては宣言できるが、値を扱うことはまだできない
+    #[inline]
+    pub fn is_floating(&self) -> bool {
+        self.is_one_of(&[Type::Float, Type::Double, Type::LDouble])
+    }
+
+    // 配列の場合は要素の型の修飾子を見る
+    #[inline]
+    pub fn is_const_object(&self) -> bool {
+        if self.is_array() {
+            self.ptr_to.as_ref().unwrap().borrow().is_const_object()
+        } else {
+            self.is_const
+        }
+    }
+
+    #[inline]
+    pub fn is_volatile_object(&self) -> bool {
+        if self.is_array() {
+            self.ptr_to.as_ref().unwrap().borrow().is_volatile_object()
+        } else {
+            self.is_volatile
+        }
+    }
+
+    // other の持つ修飾子を全て持っているかどうか(ポインタの変換で指す先の修飾子が失われないかのチェックに使う)
+    #[inline]
+    pub fn has_qualifiers_of(&self, other: &Self) -> bool {
+        (self.is_const_object() || !other.is_const_object())
+            && (self.is_volatile_object() || !other.is_volatile_object())
+    }
+
+    // 値として扱う場合の大きさ(配列はポインタに変換され、構造体もアドレスで扱うので8バイト)
+    #[inline]
+    pub fn value_bytes(&self) -> usize {
+        if self.is_aggregate() {
+            8
+        } else {
+            self.bytes()
+        }
+    }
+
+    // 修飾子を外した型(値として読み出した後は _Atomic でもなくなる)
+    #[inline]
+    pub fn unqualified(&self) -> Self {
+        TypeCell {
+            is_const: false,
+            is_volatile: false,
+            is_atomic: false,
+            ..self.clone()
+        }
+    }
+
+    // 仮引数の型の調整(C11 6.7.6.3): 配列型の仮引数は要素へのポインタとして扱われ、修飾子は型の比較に影響しない
+    #[inline]
+    pub fn param_adjusted(&self) -> Self {
+        if self.is_array() {
+            self.make_deref().unwrap().make_ptr_to()
+        } else {
+            self.unqualified()
+        }
+    }
+
+    // 全く同じ型かどうか: 修飾子、符号の有無、配列の要素数、関数の仮引数まで全て一致する必要がある
+    pub fn is_same(&self, other: &Self) -> bool {
+        self.typ == other.typ
+            && (!self.is_integer() || self.is_unsigned == other.is_unsigned)
+            && self.is_const == other.is_const
+            && self.is_volatile == other.is_volatile
+            && self.is_atomic == other.is_atomic
+            && self.array_size == other.array_size
+            && self.is_variadic == other.is_variadic
+            && self.is_unprototyped == other.is_unprototyped
+            && same_ref(&self.ptr_to, &other.ptr_to, |a, b| a.is_same(b))
+            && same_ref(&self.ret_typ, &other.ret_typ, |a, b| a.is_same(b))
+            && same_args(&self.arg_typs, &other.arg_typs, |a, b| a.is_same(b))
+            && self.struct_info == other.struct_info
+    }
+
+    // 互換性のある型かどうか(C11 6.2.7): 同じ実体を指す複数の宣言や、ポインタの指す先の型はこの意味で一致している必要がある
+    // 配列の要素数は一方が不明であれば良く、関数の仮引数は調整後の型で比較する
+    // 一方の関数のみがプロトタイプを持つ場合は、その仮引数が既定の実引数拡張で変わらない型であれば良い(C11 6.7.6.3p15)
+    pub fn is_compatible(&self, other: &Self) -> bool {
+        if self.typ != other.typ
+            || self.is_const != other.is_const
+            || self.is_volatile != other.is_volatile
+            || self.is_atomic != other.is_atomic
+        {
+            return false;
+        }
+        match self.typ {
+            Type::Ptr => same_ref(&self.ptr_to, &other.ptr_to, |a, b| a.is_compatible(b)),
+            Type::Array => {
+                (self.array_size.is_none()
+                    || other.array_size.is_none()
+                    || self.array_size == other.array_size)
+                    && same_ref(&self.ptr_to, &other.ptr_to, |a, b| a.is_compatible(b))
+            }
+            Type::Func => {
+                same_ref(&self.ret_typ, &other.ret_typ, |a, b| a.is_compatible(b))
+                    && match (self.is_unprototyped, other.is_unprototyped) {
+                        (true, true) => true,
+                        (true, false) => other.has_promoted_params(),
+                        (false, true) => self.has_promoted_params(),
+                        (false, false) => {
+                            self.is_variadic == other.is_variadic
+                                && same_args(&self.arg_typs, &other.arg_typs, |a, b| {
+                                    a.param_adjusted().is_compatible(&b.param_adjusted())
+                                })
+                        }
+                    }
+            }
+            Type::Struct | Type::Union => self.struct_info == other.struct_info,
+            _ => self.is_unsigned == other.is_unsigned,
+        }
+    }
+
+    // 可変長引数を取らず、全ての仮引数が既定の実引数拡張で変わらない型(char, short, float 以外)であるかどうか
+    fn has_promoted_params(&self) -> bool {
+        !self.is_variadic
+            && self.arg_typs.as_ref().unwrap().iter().all(|arg| {
+                let arg = arg.borrow().param_adjusted();
+                !arg.is_one_of(&[Type::Char, Type::Short, Type::Float])
+            })
+    }
+
+    // 互換性のある2つの型の合成型(C11 6.2.7): 配列の要素数は分かっている方を採用する
+    // 一方の関数のみがプロトタイプを持つ場合は、そちらの仮引数を採用する
+    pub fn composite(&self, other: &Self) -> Self {
+        let compose = |a: &Option<TypeCellRef>, b: &Option<TypeCellRef>| {
+            a.as_ref().map(|a| {
+                let b = b.as_ref().unwrap().borrow();
+                Rc::new(RefCell::new(a.borrow().composite(&b)))
+            })
+        };
+        let arg_typs = if self.is_unprototyped {
+            other.arg_typs.clone()
+        } else if other.is_unprototyped {
+            self.arg_typs.clone()
+        } else {
+            self.arg_typs.as_ref().map(|args| {
+                args.iter()
+                    .zip(other.arg_typs.as_ref().unwrap().iter())
+                    .map(|(a, b)| Rc::new(RefCell::new(a.borrow().composite(&b.borrow()))))
+                    .collect()
+            })
+        };
+        TypeCell {
+            ptr_to: compose(&self.ptr_to, &other.ptr_to),
+            ret_typ: compose(&self.ret_typ, &other.ret_typ),
+            arg_typs,
+            is_variadic: self.is_variadic || other.is_variadic,
+            is_unprototyped: self.is_unprototyped && other.is_unprototyped,
+            array_size: self.array_size.or(other.array_size),
+            vla_size: if self.array_size.or(other.array_size).is_some() {
+                None
+            } else {
+                self.vla_size.or(other.vla_size)
+            },
+            align: self.align.max(other.align),
+            ..self.clone()
+        }
+    }
+
+    // from 型の値をこの型の左辺値に代入できるかどうか(C11 6.5.16.1)
+    // 空ポインタ定数(値が0の整数定数式)はどのポインタにも代入できるが、式を見る必要があるので呼び出し側で扱う
+    pub fn is_assignable_from(&self, from: &Self) -> bool {
+        if self.is_integer() {
+            from.is_integer()
+        } else if self.is_struct() {
+            self.unqualified().is_compatible(&from.unqualified())
+        } else if self.is_pointer() && from.is_pointer_like() {
+            // void へのポインタは関数以外を指すポインタと相互に変換できる
+            // _Atomic は修飾子を外しても残る違いなので、指す先の型の一方のみが _Atomic であれば互換性はない
+            let to_deref = self.make_deref().unwrap();
+            let from_deref = from.make_deref().unwrap();
+            let is_void_ptr = (to_deref.is_void() && from_deref.typ != Type::Func)
+                || (from_deref.is_void() && to_deref.typ != Type::Func);
+            (is_void_ptr
+                || (to_deref.is_atomic == from_deref.is_atomic
+                    && to_deref
+                        .unqualified()
+                        .is_compatible(&from_deref.unqualified())))
+                && to_deref.has_qualifiers_of(&from_deref)
+        } else {
+            false
+        }
+    }
+
+    fn qualifier_string(&self) -> String {
+        let atomic = if self.is_atomic { "_Atomic " } else { "" };
+        let qualifiers = match (self.is_const, self.is_volatile) {
+            (true, true) => "const volatile ",
+            (true, false) => "const ",
+            (false, true) => "volatile ",
+            (false, false) => "",
+        };
+        format!("{}{}", qualifiers, atomic)
+    }
+
+    // 多次元配列の最小要素の型
     #[inline]
     pub fn get_base_cell(&self) -> Self {
-        if let Some(_typ) = self.ptr_end {
-            Self::new(_typ)
+        if self.is_array() {
+            self.array_dim().1
         } else {
-            panic!("cannot extract base type from non-pointer.");
+            panic!("cannot extract base type from non-array.");
         }
     }
 
@@ -203,12 +609,6 @@ impl TypeCell {
         }
     }
 
-    #[inline]
-    pub fn flatten_size(&self) -> usize {
-        let (dim, _) = self.array_dim();
-        dim.iter().product::<usize>()
-    }
-
     #[inline]
     pub fn is_char_1d_array(&self) -> bool {
         self.typ == Type::Array && self.make_deref().unwrap().typ == Type::Char
@@ -216,19 +616,30 @@ impl TypeCell {
 
     pub fn bytes(&self) -> usize {
         match self.typ {
+            Type::Array if self.has_vla() => {
+                panic!("cannot infer size of variable length array at compile time")
+            }
+            Type::Array if self.is_incomplete() => panic!("cannot infer size of incomplete array"),
             Type::Array => {
                 let (dim, typ) = self.array_dim();
-                typ.typ.bytes() * dim.iter().product::<usize>()
+                typ.bytes() * dim.iter().product::<usize>()
             }
+            Type::Struct | Type::Union => self.struct_info.as_ref().unwrap().borrow().bytes,
             _ => self.typ.bytes(),
         }
     }
 
-    pub fn get_last_level_array(&self) -> Option<TypeCell> {
-        let (dim, typ) = self.array_dim();
-        dim.last().map(|&d| {
-            typ.make_array_of(d)
-        })
+    // アラインメント: _Alignas で指定されていればその値、そうでなければ型の自然なアラインメント(配列は要素のもの)
+    pub fn align(&self) -> usize {
+        if let Some(align) = self.align {
+            align
+        } else if self.is_array() {
+            self.ptr_to.as_ref().unwrap().borrow().align()
+        } else if self.is_struct() {
+            self.struct_info.as_ref().unwrap().borrow().align
+        } else {
+            self.typ.bytes()
+        }
     }
 
     fn get_type_string(&self, s: impl Into<String>) -> String {
@@ -237,13 +648,15 @@ impl TypeCell {
             let string = if self.typ == Type::Array {
                 if let Some(size) = self.array_size {
                     format!("{}[{}]", s, size)
+                } else if self.is_vla() {
+                    format!("{}[*]", s)
                 } else {
                     format!("{}[]", s)
                 }
             } else if deref.borrow().is_array() {
-                format!("({}*)", s)
+                format!("({}*{})", self.qualifier_string(), s)
             } else {
-                format!("*{}", s)
+                format!("*{}{}", self.qualifier_string(), s)
             };
             (*deref).borrow().get_type_string(string)
         } else if self.typ == Type::Func {
@@ -256,9 +669,27 @@ impl TypeCell {
                     format!("{}, {}", args_str, arg.borrow())
                 };
             }
+            if self.is_variadic {
+                args_str = if args_str.is_empty() {
+                    String::from("...")
+                } else {
+                    format!("{}, ...", args_str)
+                };
+            }
             format!("{} ({}func)({})", ret_typ, s, args_str)
         } else {
-            format!("{}{}", self.typ, s)
+            let sign = if self.is_unsigned && self.is_integer() {
+                "unsigned "
+            } else {
+                ""
+            };
+            let name = if let Some(info) = &self.struct_info {
+                let tag = info.borrow().tag.clone();
+                format!("{} {}", self.typ, tag.as_deref().unwrap_or("<anonymous>"))
+            } else {
+                format!("{}", self.typ)
+            };
+            format!("{}{}{}{}", self.qualifier_string(), sign, name, s)
         }
     }
 }
@@ -268,62 +699,62 @@ impl Default for TypeCell {
         TypeCell {
             typ: Type::Invalid,
             ptr_to: None,
-            ptr_end: None,
-            chains: 0,
             array_size: None,
             arg_typs: None,
             ret_typ: None,
             is_abstract: false,
+            is_variadic: false,
+            is_unprototyped: false,
             is_unsigned: false,
+            is_const: false,
+            is_volatile: false,
+            is_atomic: false,
+            align: None,
+            vla_size: None,
+            struct_info: None,
         }
     }
 }
 
 impl Display for TypeCell {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}", self.get_type_string(""))
+        write!(f, "{}", self.get_type_string("").trim_end())
     }
 }
 
 impl PartialEq for TypeCell {
-    // ポインタが連なっている個数と、最終的に指されている型が両方同じ時にイコールとみなす
-    // これは、配列とポインタを暗黙的に等価とみなすことにもなる
     fn eq(&self, other: &Self) -> bool {
-        if let Some(typ) = &self.ptr_end {
-            if let Some(other_typ) = &self.ptr_end {
-                // この時点で両方ポインタなので typ のチェックは飛ばす
-                self.chains == other.chains && typ == other_typ
-            } else {
-                false
-            }
-        } else {
-            self.typ == other.typ
-                && self.ret_typ == other.ret_typ
-                && self.arg_typs == other.arg_typs
-        }
+        self.is_same(other)
     }
 }
 
-// 計算時、代入時などに暗黙のキャストを行うための処理
-pub fn get_common_type(left_typ: &TypeCell, right_typ: &TypeCell) -> TypeCell {
-    // 現在はポインタ、 int, char しかサポートされていない
-    // 右側"のみ"がポインタになることはない(そのようなノード生成が起きる前にエラーになる)ことに注意
-    if let Some(_typ) = &left_typ.ptr_to {
-        _typ.borrow().make_ptr_to()
-    } else if let Some(_typ) = &right_typ.ptr_to {
-        _typ.borrow().make_ptr_to()
-    } else {
-        // int 以下のサイズの数は全て int にキャストされる
-        TypeCell::new(Type::Int)
+// 両方が持つ場合はその中身を比較し、両方が持たない場合は一致とみなす
+fn same_ref(
+    a: &Option<TypeCellRef>,
+    b: &Option<TypeCellRef>,
+    eq: impl Fn(&TypeCell, &TypeCell) -> bool,
+) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => eq(&a.borrow(), &b.borrow()),
+        (None, None) => true,
+        _ => false,
     }
 }
 
-pub fn get_raw_type(typ: Type) -> RawType {
-    match typ {
-        Type::Invalid => panic!("cannot extract raw type from {}.", typ),
-        Type::Char => RawType::I8,
-        Type::Int => RawType::I32,
-        _ => RawType::U64,
+fn same_args(
+    a: &Option<Vec<TypeCellRef>>,
+    b: &Option<Vec<TypeCellRef>>,
+    eq: impl Fn(&TypeCell, &TypeCell) -> bool,
+) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| eq(&a.borrow(), &b.borrow()))
+        }
+        (None, None) => true,
+        _ => false,
     }
 }
 
@@ -336,33 +767,208 @@ mod tests {
 
     #[test]
     fn eq_test() {
-        let mut t1 = TypeCell::new(Type::Int);
-        let mut t2 = TypeCell::new(Type::Int);
-        assert_eq!(t1, t2);
+        let int = TypeCell::new(Type::Int);
+        assert_eq!(int, TypeCell::new(Type::Int));
+        assert_ne!(int, int.make_ptr_to());
+        assert_ne!(int.make_ptr_to(), int.make_ptr_to().make_ptr_to());
+        assert_eq!(
+            int.make_ptr_to().make_ptr_to(),
+            int.make_ptr_to().make_ptr_to()
+        );
+
+        // int ** と int *[3] や、 char * と int * は異なる型
+        assert_ne!(
+            int.make_ptr_to().make_ptr_to(),
+            int.make_ptr_to().make_array_of(3)
+        );
+        assert_ne!(int.make_ptr_to(), TypeCell::new(Type::Char).make_ptr_to());
+
+        let mut uint = TypeCell::new(Type::Int);
+        uint.is_unsigned = true;
+        assert_ne!(int.make_ptr_to(), uint.make_ptr_to());
+    }
 
-        t1 = TypeCell {
-            typ: Type::Ptr,
-            ptr_end: Some(Type::Int),
-            chains: 1,
-            ..Default::default()
-        };
-        assert_ne!(t1, t2);
+    #[test]
+    fn compatible_test() {
+        let int = TypeCell::new(Type::Int);
+        let arr = int.make_array_of(3);
+        let flex = int.make_flex_array_of();
+        assert!(arr.is_compatible(&flex) && !arr.is_same(&flex));
+        assert!(!arr.is_compatible(&int.make_array_of(4)));
+        assert!(!arr.is_compatible(&int.make_ptr_to()));
+        assert_eq!(flex.composite(&arr), arr);
+
+        // 関数の仮引数は調整後の型で比べる
+        let rc = |t: TypeCell| Rc::new(RefCell::new(t));
+        let mut cint = TypeCell::new(Type::Int);
+        cint.is_const = true;
+        let f1 = int.make_func(vec![rc(arr.clone()), rc(cint)]);
+        let f2 = int.make_func(vec![rc(int.make_ptr_to()), rc(int.clone())]);
+        assert!(f1.is_compatible(&f2) && !f1.is_same(&f2));
+        let f3 = int.make_func(vec![
+            rc(TypeCell::new(Type::Char).make_ptr_to()),
+            rc(int.clone()),
+        ]);
+        assert!(!f1.is_compatible(&f3));
+        let mut f4 = f2.clone();
+        f4.is_variadic = true;
+        assert!(!f2.is_compatible(&f4));
+
+        // プロトタイプのない関数とは、仮引数が既定の実引数拡張で変わらない場合のみ互換性がある
+        let mut f5 = int.make_func(vec![]);
+        f5.is_unprototyped = true;
+        assert!(f5.is_compatible(&f2) && f2.is_compatible(&f5));
+        assert!(!f5.is_compatible(&f4));
+        let f6 = int.make_func(vec![rc(TypeCell::new(Type::Char))]);
+        assert!(!f5.is_compatible(&f6));
+        assert_eq!(f5.composite(&f2), f2);
+        assert_eq!(f2.composite(&f5), f2);
+    }
 
-        t2 = TypeCell {
-            typ: Type::Ptr,
-            ptr_end: Some(Type::Int),
-            chains: 2,
-            ..Default::default()
-        };
-        assert_ne!(t1, t2);
+    #[test]
+    fn assignable_test() {
+        let int = TypeCell::new(Type::Int);
+        let mut cint = TypeCell::new(Type::Int);
+        cint.is_const = true;
+        assert!(TypeCell::new(Type::Char).is_assignable_from(&TypeCell::new(Type::Long)));
+        assert!(int.make_ptr_to().is_assignable_from(&int.make_array_of(3)));
+        assert!(cint.make_ptr_to().is_assignable_from(&int.make_ptr_to()));
+        assert!(!int.make_ptr_to().is_assignable_from(&cint.make_ptr_to()));
+        assert!(!int.make_ptr_to().is_assignable_from(&int));
+        assert!(!int.is_assignable_from(&int.make_ptr_to()));
+        assert!(!int
+            .make_ptr_to()
+            .make_ptr_to()
+            .is_assignable_from(&int.make_ptr_to().make_array_of(3).make_ptr_to()));
+        assert!(!int
+            .make_array_of(3)
+            .is_assignable_from(&int.make_array_of(3)));
+    }
 
-        t1 = TypeCell {
-            typ: Type::Ptr,
-            ptr_end: Some(Type::Int),
-            chains: 2,
-            ..Default::default()
-        };
+    #[test]
+    fn align_test() {
+        for (typ, align) in [
+            (Type::Char, 1),
+            (Type::Short, 2),
+            (Type::Int, 4),
+            (Type::Long, 8),
+        ] {
+            let cell = TypeCell::new(typ);
+            assert_eq!(cell.align(), align);
+            assert_eq!(cell.make_array_of(5).make_array_of(3).align(), align);
+            assert_eq!(cell.make_ptr_to().align(), 8);
+        }
+
+        // _Alignas によるアラインメントは型の同一性には影響しない
+        let mut aligned = TypeCell::new(Type::Char);
+        let _ = aligned.align.insert(16);
+        assert_eq!(aligned.align(), 16);
+        assert_eq!(aligned.bytes(), 1);
+        assert!(aligned.is_same(&TypeCell::new(Type::Char)));
+    }
 
-        assert_eq!(t1, t2);
+    #[test]
+    fn incomplete_array_test() {
+        // 要素数の分からない配列は、それを要素とする配列やそれへのポインタを含めて不完全型として扱う
+        let flex = TypeCell::new(Type::Int).make_flex_array_of();
+        assert!(flex.is_incomplete());
+        assert!(flex.make_array_of(2).is_incomplete());
+        assert!(!flex.make_ptr_to().is_incomplete());
+        assert!(!TypeCell::new(Type::Int).make_array_of(0).is_incomplete());
+    }
+
+    #[test]
+    fn struct_test() {
+        // struct { char c; int i; char d; } は int のアラインメントに合わせて 12 バイト
+        let members = vec![
+            (Some("c".to_string()), TypeCell::new(Type::Char)),
+            (Some("i".to_string()), TypeCell::new(Type::Int)),
+            (Some("d".to_string()), TypeCell::new(Type::Char)),
+        ];
+        let s = TypeCell::new_struct(Some("S".to_string()), false);
+        assert!(s.is_incomplete());
+        s.struct_info
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .complete(members.clone(), false, false, None);
+        assert!(!s.is_incomplete());
+        assert_eq!((s.bytes(), s.align()), (12, 4));
+        assert_eq!(s.member("i").unwrap().offset, 4);
+        assert_eq!(s.member("d").unwrap().offset, 8);
+        assert_eq!(format!("{}", s.make_ptr_to()), "struct S*");
+
+        // packed であれば詰めて配置され、 aligned の指定があれば全体のアラインメントが強まる
+        let packed = TypeCell::new_struct(None, false);
+        packed.struct_info.as_ref().unwrap().borrow_mut().complete(
+            members.clone(),
+            false,
+            true,
+            None,
+        );
+        assert_eq!((packed.bytes(), packed.align()), (6, 1));
+        assert_eq!(packed.member("d").unwrap().offset, 5);
+        let aligned = TypeCell::new_struct(None, false);
+        aligned
+            .struct_info
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .complete(members, false, true, Some(8));
+        assert_eq!((aligned.bytes(), aligned.align()), (8, 8));
+
+        // 共用体は最大のメンバの大きさになり、名前のないメンバは展開される
+        let u = TypeCell::new_struct(None, true);
+        let members = vec![
+            (Some("l".to_string()), TypeCell::new(Type::Long)),
+            (None, s.clone()),
+        ];
+        u.struct_info
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .complete(members, true, false, None);
+        assert_eq!((u.bytes(), u.align()), (12 + 4, 8));
+        assert_eq!(u.member("d").unwrap().offset, 8);
+
+        // タグが同じでも別の実体であれば異なる型
+        let other = TypeCell::new_struct(Some("S".to_string()), false);
+        assert!(s.is_compatible(&s.clone()) && !s.is_compatible(&other));
+        assert!(s.is_assignable_from(&s));
+
+        // void * は他のオブジェクトへのポインタと相互に代入できる
+        let void_ptr = TypeCell::new(Type::Void).make_ptr_to();
+        assert!(void_ptr.is_assignable_from(&s.make_ptr_to()));
+        assert!(s.make_ptr_to().is_assignable_from(&void_ptr));
+    }
+
+    #[test]
+    fn qualifier_test() {
+        let mut c = TypeCell::new(Type::Char);
+        c.is_const = true;
+        let mut p = c.make_ptr_to();
+        p.is_const = true;
+        assert_eq!(format!("{}", p), "const char*const");
+        assert!(p.make_deref().unwrap().is_const);
+
+        let arr = c.make_array_of(3);
+        assert!(arr.is_const_object());
+        assert!(!TypeCell::new(Type::Char).has_qualifiers_of(&arr));
+        assert!(arr.has_qualifiers_of(&TypeCell::new(Type::Char)));
+
+        let mut v = TypeCell::new(Type::Int);
+        v.is_volatile = true;
+        assert_eq!(format!("{}", v.make_ptr_to()), "volatile int*");
+
+        // _Atomic int と int は修飾子を外して比べても互換性がない
+        let mut a = TypeCell::new(Type::Int);
+        a.is_atomic = true;
+        assert_eq!(format!("{}", a), "_Atomic int");
+        assert!(!a.is_compatible(&TypeCell::new(Type::Int)));
+        assert!(a.unqualified().is_same(&TypeCell::new(Type::Int)));
+        assert!(!a
+            .make_ptr_to()
+            .is_assignable_from(&TypeCell::new(Type::Int).make_ptr_to()));
+        assert!(a.make_ptr_to().is_assignable_from(&a.make_ptr_to()));
     }
 }
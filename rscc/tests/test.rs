@@ -7,6 +7,9 @@ use rscc::compile_src;
 const SRC: &str = "tests/utils/test.c";
 const ASM: &str = "tests/tmp.s";
 const RUN: &str = "tests/utils/run.sh";
+const PRINTF_SRC: &str = "tests/utils/printf.c";
+const PRINTF_PRE: &str = "tests/tmp_printf.i";
+const PRINTF_ASM: &str = "tests/tmp_printf.s";
 
 macro_rules! cprintln {
 	($fmt:expr, $color:expr) => {
@@ -24,7 +27,7 @@ pub fn rscc_test() {
     assert!(asm.is_ok());
     cprintln!("compile succeeded!", 36);
 
-    assert!(output_asm(asm.unwrap()).is_ok());
+    assert!(output_asm(asm.unwrap(), ASM).is_ok());
     cprintln!("assembly successfully created!", 36);
 
     let status = exec_asm();
@@ -34,8 +37,40 @@ pub fn rscc_test() {
     cprintln!("test succeeded!", 36);
 }
 
-fn output_asm(asm: String) -> io::Result<()> {
-    let mut f = File::create(ASM)?;
+// compile_src はコンパイラの状態をグローバル変数で共有するため、同じプロセスのテストから並行には呼べない
+// そのため gcc -E で前処理したソースを、ビルドされた rscc の実行ファイルでコンパイルする
+#[test]
+pub fn printf_test() {
+    let status = Command::new("gcc")
+        .args(["-E", "-o", PRINTF_PRE, PRINTF_SRC])
+        .status();
+    assert!(status.is_ok());
+    assert!(status.unwrap().success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rscc"))
+        .arg(PRINTF_PRE)
+        .output();
+    assert!(output.is_ok());
+    let output = output.unwrap();
+    assert!(output.status.success());
+    assert!(remove_file(PRINTF_PRE).is_ok());
+    cprintln!("compile succeeded!", 36);
+
+    assert!(output_asm(String::from_utf8(output.stdout).unwrap(), PRINTF_ASM).is_ok());
+    cprintln!("assembly successfully created!", 36);
+
+    // helper.c はリンクしない
+    let output = Command::new(RUN).args([PRINTF_ASM, ""]).output();
+    assert!(output.is_ok());
+    let stdout = String::from_utf8(output.unwrap().stdout).unwrap();
+    println!("{}", stdout);
+    assert_eq!(stdout, "bob: 95\nlength: 5\nOK\n0\n");
+    assert!(remove_file(PRINTF_ASM).is_ok());
+    cprintln!("test succeeded!", 36);
+}
+
+fn output_asm(asm: String, path: &str) -> io::Result<()> {
+    let mut f = File::create(path)?;
     f.write_all(asm.as_bytes())?;
     Ok(())
 }
@@ -0,0 +1,30 @@
+// helper.c を使わずに、標準ライブラリのヘッダを include して直接呼び出すテスト
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef struct {
+    char *name;
+    int score;
+} Entry;
+
+int main() {
+    Entry *entries = malloc(sizeof(Entry) * 2);
+    entries[0].name = "alice";
+    entries[0].score = 80;
+    entries[1].name = "bob";
+    entries[1].score = 95;
+
+    char buf[32];
+    strcpy(buf, entries[1].name);
+    printf("%s: %d\n", buf, entries[1].score);
+    fprintf(stdout, "length: %d\n", (int) strlen(entries[0].name));
+
+    int ok = strcmp(buf, "bob") == 0 && entries[0].score + entries[1].score == 175;
+    free(entries);
+    if (!ok) {
+        return 1;
+    }
+    puts("OK");
+    return 0;
+}
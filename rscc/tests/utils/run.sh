@@ -1,8 +1,22 @@
-#!/bin/zsh
+#!/bin/sh
 
 MOUNT_PATH=$(pwd)
 ASM_SRC=$1
-LINK_SRC="tests/utils/helper.c"
+# 第2引数でリンクするソースを指定できる(空文字列であれば何もリンクしない)
+LINK_SRC=${2-"tests/utils/helper.c"}
+# 並列に実行されるテスト同士で実行ファイルが衝突しないよう、アセンブリのファイル名を使う
+BIN="/tmp/$(basename ${ASM_SRC} .s)"
+CMD="gcc -o ${BIN} ${MOUNT_PATH}/${ASM_SRC} ${LINK_SRC:+${MOUNT_PATH}/${LINK_SRC}};
+    ${BIN};
+    echo \$?;
+    rm ${BIN};
+    "
+
+# x86_64 の Linux 上ではそのまま実行できる
+if [ "$(uname -s)" = "Linux" ] && [ "$(uname -m)" = "x86_64" ]; then
+    /bin/sh -c "$CMD"
+    exit
+fi
 
 # c_exec はイメージ名
 docker run --rm \
@@ -10,8 +24,4 @@ docker run --rm \
     --platform linux/amd64 \
 	c_exec \
     /bin/bash -c \
-    "gcc -o /tmp/tmp ${MOUNT_PATH}/${ASM_SRC} ${MOUNT_PATH}/${LINK_SRC};
-    /tmp/tmp;
-    echo \$?;
-    rm /tmp/tmp;
-    "
+    "$CMD"